@@ -0,0 +1,373 @@
+//! Translating the [IR](crate::ir) to C.
+//!
+//! The output is a single C file containing the [runtime](RUNTIME) and the
+//! program. Each value is a `pv`: a tagged machine word holding either an
+//! integer, a boolean, or a pointer to a heap object. Each function becomes a
+//! C function taking a pointer to its captured values and its argument.
+//! Expressions are flattened into a sequence of statements, so the order of
+//! evaluation is explicit.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use pion_core::prim::Prim;
+use pion_symbol::Symbol;
+
+use crate::ir::{self, FunctionId, LocalId};
+
+/// The C source of the runtime, which is included in every program.
+pub const RUNTIME: &str = include_str!("c/runtime.c");
+
+/// Translate `program` to a C file that evaluates it, printing the value of
+/// each [`ir::Stmt::Print`] on its own line.
+pub fn emit_program(program: &ir::Program) -> String {
+    let mut labels = Labels::default();
+    let mut functions = Vec::with_capacity(program.functions.len());
+    for (id, function) in program.functions.iter().enumerate() {
+        let mut emitter = Emitter::new(&mut labels);
+        emitter.declare_locals(1..function.locals);
+        emitter.tail(&function.body);
+        functions.push((FunctionId::try_from(id).unwrap(), emitter.out));
+    }
+
+    let mut main = Emitter::new(&mut labels);
+    main.declare_locals(0..program.main.locals);
+    for stmt in &program.main.stmts {
+        match stmt {
+            ir::Stmt::Global(global, expr) => {
+                let value = main.expr(expr);
+                main.line(format_args!("pion_global_{global} = {value};"));
+            }
+            ir::Stmt::Print(expr) => {
+                let value = main.expr(expr);
+                main.line(format_args!("pion_println({value});"));
+            }
+        }
+    }
+    main.line(format_args!("return 0;"));
+    let main = main.out;
+
+    let mut out = String::from(RUNTIME);
+    out.push_str("\n/* The program. */\n\n");
+    labels.emit(&mut out);
+
+    for (id, global) in program.globals.iter().enumerate() {
+        match global.name {
+            Some(name) => writeln!(out, "static pv pion_global_{id}; /* {name} */"),
+            None => writeln!(out, "static pv pion_global_{id};"),
+        }
+        .unwrap();
+    }
+    if !program.globals.is_empty() {
+        out.push('\n');
+    }
+
+    for (id, _) in &functions {
+        writeln!(out, "static pv pion_fn_{id}(const pv *captures, pv l_0);").unwrap();
+    }
+    if !functions.is_empty() {
+        out.push('\n');
+    }
+
+    for (id, body) in &functions {
+        writeln!(out, "static pv pion_fn_{id}(const pv *captures, pv l_0) {{").unwrap();
+        out.push_str("    (void)captures;\n    (void)l_0;\n");
+        out.push_str(body);
+        out.push_str("}\n\n");
+    }
+
+    out.push_str("int main(void) {\n");
+    out.push_str(&main);
+    out.push_str("}\n");
+    out
+}
+
+/// The record labels and shapes used by the program. Each label is a
+/// distinct static string, so labels can be compared by address.
+#[derive(Default)]
+struct Labels {
+    ids: HashMap<Symbol, usize>,
+    shapes: HashMap<Vec<Symbol>, usize>,
+    out: String,
+}
+
+impl Labels {
+    fn label(&mut self, label: Symbol) -> usize {
+        if let Some(id) = self.ids.get(&label) {
+            return *id;
+        }
+        let id = self.ids.len();
+        self.ids.insert(label, id);
+        let text = label.as_str().escape_default();
+        writeln!(
+            self.out,
+            "static const char pion_label_{id}[] = \"{text}\";"
+        )
+        .unwrap();
+        id
+    }
+
+    fn shape(&mut self, labels: Vec<Symbol>) -> usize {
+        if let Some(id) = self.shapes.get(&labels) {
+            return *id;
+        }
+        let id = self.shapes.len();
+        let is_tuple = i32::from(Symbol::are_tuple_field_names(labels.iter().copied()));
+        let len = labels.len();
+        let names = match labels.is_empty() {
+            true => String::from("NULL"),
+            false => {
+                let names: Vec<_> = (labels.iter())
+                    .map(|label| format!("pion_label_{}", self.label(*label)))
+                    .collect();
+                writeln!(
+                    self.out,
+                    "static const char *const pion_shape_{id}_labels[] = {{{}}};",
+                    names.join(", ")
+                )
+                .unwrap();
+                format!("pion_shape_{id}_labels")
+            }
+        };
+        writeln!(
+            self.out,
+            "static const struct pion_shape pion_shape_{id} = {{{len}, {is_tuple}, {names}}};"
+        )
+        .unwrap();
+        self.shapes.insert(labels, id);
+        id
+    }
+
+    fn emit(&self, out: &mut String) {
+        if !self.out.is_empty() {
+            out.push_str(&self.out);
+            out.push('\n');
+        }
+    }
+}
+
+/// Emits the statements of a function body.
+struct Emitter<'labels> {
+    labels: &'labels mut Labels,
+    out: String,
+    indent: usize,
+    temps: u32,
+}
+
+impl<'labels> Emitter<'labels> {
+    fn new(labels: &'labels mut Labels) -> Self {
+        Self {
+            labels,
+            out: String::new(),
+            indent: 1,
+            temps: 0,
+        }
+    }
+
+    fn line(&mut self, line: std::fmt::Arguments) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+        writeln!(self.out, "{line}").unwrap();
+    }
+
+    fn declare_locals(&mut self, locals: std::ops::Range<LocalId>) {
+        if !locals.is_empty() {
+            let locals: Vec<_> = locals.map(|local| format!("l_{local}")).collect();
+            self.line(format_args!("pv {};", locals.join(", ")));
+        }
+    }
+
+    fn fresh_temp(&mut self) -> String {
+        let temp = format!("t_{}", self.temps);
+        self.temps += 1;
+        temp
+    }
+
+    /// Bind `value` to a fresh temporary, and return the temporary.
+    fn bind(&mut self, value: std::fmt::Arguments) -> String {
+        let temp = self.fresh_temp();
+        self.line(format_args!("pv {temp} = {value};"));
+        temp
+    }
+
+    /// Emit statements that compute `expr`, and return a C expression for its
+    /// value that has no side effects.
+    fn expr(&mut self, expr: &ir::Expr) -> String {
+        match expr {
+            ir::Expr::Int(int) => format!("pion_int({int}u)"),
+            ir::Expr::Bool(bool) => format!("pion_bool({})", u8::from(*bool)),
+            ir::Expr::Erased => String::from("PION_ERASED"),
+            ir::Expr::Local(local) => format!("l_{local}"),
+            ir::Expr::Capture(index) => format!("captures[{index}]"),
+            ir::Expr::Global(global) => format!("pion_global_{global}"),
+            ir::Expr::Let(local, rhs, body) => {
+                let rhs = self.expr(rhs);
+                self.line(format_args!("l_{local} = {rhs};"));
+                self.expr(body)
+            }
+            ir::Expr::Closure { function, captures } => {
+                let len = captures.len();
+                let captures = self.array(captures);
+                self.bind(format_args!(
+                    "pion_closure(pion_fn_{function}, {len}, {captures})"
+                ))
+            }
+            ir::Expr::Apply(fun, arg) => {
+                let (fun, arg) = (self.expr(fun), self.expr(arg));
+                self.bind(format_args!("pion_apply({fun}, {arg})"))
+            }
+            ir::Expr::Prim(prim, args) => {
+                let args: Vec<_> = args.iter().map(|arg| self.expr(arg)).collect();
+                let value = prim_app(*prim, &args);
+                self.bind(format_args!("{value}"))
+            }
+            ir::Expr::Fix(fun) => {
+                let fun = self.expr(fun);
+                self.bind(format_args!("pion_fix({fun})"))
+            }
+            ir::Expr::List(elems) => {
+                let len = elems.len();
+                let elems = self.array(elems);
+                self.bind(format_args!("pion_list({len}, {elems})"))
+            }
+            ir::Expr::Record(fields) => {
+                let values = self.array(fields.iter().map(|(_, expr)| expr));
+                let labels = fields.iter().map(|(label, _)| *label).collect();
+                let shape = self.labels.shape(labels);
+                self.bind(format_args!("pion_record(&pion_shape_{shape}, {values})"))
+            }
+            ir::Expr::Proj(scrut, label) => {
+                let scrut = self.expr(scrut);
+                let label = self.labels.label(*label);
+                self.bind(format_args!("pion_proj({scrut}, pion_label_{label})"))
+            }
+            ir::Expr::If(..) | ir::Expr::Switch { .. } => {
+                let temp = self.fresh_temp();
+                self.line(format_args!("pv {temp};"));
+                self.branches(expr, &mut |this, expr| {
+                    let value = this.expr(expr);
+                    this.line(format_args!("{temp} = {value};"));
+                });
+                temp
+            }
+        }
+    }
+
+    /// Emit statements that return the value of `expr` from the function.
+    fn tail(&mut self, expr: &ir::Expr) {
+        match expr {
+            ir::Expr::Let(local, rhs, body) => {
+                let rhs = self.expr(rhs);
+                self.line(format_args!("l_{local} = {rhs};"));
+                self.tail(body);
+            }
+            // Emitted as a C tail call, so that C compilers can optimize it to
+            // a jump.
+            ir::Expr::Apply(fun, arg) => {
+                let (fun, arg) = (self.expr(fun), self.expr(arg));
+                self.line(format_args!("return pion_apply({fun}, {arg});"));
+            }
+            ir::Expr::If(..) | ir::Expr::Switch { .. } => {
+                self.branches(expr, &mut |this, expr| this.tail(expr));
+            }
+            _ => {
+                let value = self.expr(expr);
+                self.line(format_args!("return {value};"));
+            }
+        }
+    }
+
+    /// Emit an `if` or `switch` statement for `expr`, calling `branch` to emit
+    /// each branch.
+    fn branches(&mut self, expr: &ir::Expr, branch: &mut dyn FnMut(&mut Self, &ir::Expr)) {
+        match expr {
+            ir::Expr::If(cond, then, r#else) => {
+                let cond = self.expr(cond);
+                self.line(format_args!("if (pion_get_bool({cond})) {{"));
+                self.indented(|this| branch(this, then));
+                self.line(format_args!("}} else {{"));
+                self.indented(|this| branch(this, r#else));
+                self.line(format_args!("}}"));
+            }
+            ir::Expr::Switch {
+                scrut,
+                cases,
+                default,
+            } => {
+                let scrut = self.expr(scrut);
+                self.line(format_args!("switch (pion_get_int({scrut})) {{"));
+                for (int, expr) in cases {
+                    self.line(format_args!("case {int}u: {{"));
+                    self.indented(|this| {
+                        branch(this, expr);
+                        this.line(format_args!("break;"));
+                    });
+                    self.line(format_args!("}}"));
+                }
+                self.line(format_args!("default: {{"));
+                self.indented(|this| {
+                    branch(this, default);
+                    this.line(format_args!("break;"));
+                });
+                self.line(format_args!("}}"));
+                self.line(format_args!("}}"));
+            }
+            _ => unreachable!("not a branching expression: {expr:?}"),
+        }
+    }
+
+    fn indented(&mut self, f: impl FnOnce(&mut Self)) {
+        self.indent += 1;
+        f(self);
+        self.indent -= 1;
+    }
+
+    /// A C array of the values of `exprs`, or `NULL` if it is empty.
+    fn array<'a>(&mut self, exprs: impl IntoIterator<Item = &'a ir::Expr>) -> String {
+        let values: Vec<_> = exprs.into_iter().map(|expr| self.expr(expr)).collect();
+        if values.is_empty() {
+            return String::from("NULL");
+        }
+        format!("(const pv[]){{{}}}", values.join(", "))
+    }
+}
+
+/// A C expression for `prim` applied to `args`.
+fn prim_app(prim: Prim, args: &[String]) -> String {
+    let int_op = |op| {
+        format!(
+            "pion_int(pion_get_int({}) {op} pion_get_int({}))",
+            args[0], args[1]
+        )
+    };
+    let cmp_op = |op| {
+        format!(
+            "pion_bool(pion_get_int({}) {op} pion_get_int({}))",
+            args[0], args[1]
+        )
+    };
+    match prim {
+        Prim::len => format!("pion_len({})", args[0]),
+        Prim::push => format!("pion_push({}, {})", args[0], args[1]),
+        Prim::append => format!("pion_append({}, {})", args[0], args[1]),
+        Prim::bool_rec => format!("pion_get_bool({}) ? {} : {}", args[0], args[1], args[2]),
+        Prim::add => int_op("+"),
+        Prim::sub => int_op("-"),
+        Prim::mul => int_op("*"),
+        Prim::eq => cmp_op("=="),
+        Prim::ne => cmp_op("!="),
+        Prim::gt => cmp_op(">"),
+        Prim::lt => cmp_op("<"),
+        Prim::gte => cmp_op(">="),
+        Prim::lte => cmp_op("<="),
+        Prim::Type
+        | Prim::Int
+        | Prim::Bool
+        | Prim::List
+        | Prim::Eq
+        | Prim::fix
+        | Prim::refl
+        | Prim::subst => unreachable!("`{}` is lowered specially", prim.name()),
+    }
+}
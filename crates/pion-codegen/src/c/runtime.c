@@ -0,0 +1,195 @@
+/* The runtime for pion programs compiled to C. Everything is `static inline`,
+ * so that compilers do not warn about the parts a program does not use. */
+
+#include <inttypes.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* A value is either:
+ * - an integer `n`, represented as `n << 2 | 1`
+ * - a boolean `b`, represented as `b << 2 | 3`
+ * - a pointer to a heap object (closure, list or record)
+ * - `0`, for values with no runtime content (types and proofs)
+ */
+typedef uintptr_t pv;
+
+#define PION_ERASED ((pv)0)
+
+static inline pv pion_int(uint32_t n) { return ((pv)n << 2) | 1; }
+static inline uint32_t pion_get_int(pv v) { return (uint32_t)(v >> 2); }
+static inline pv pion_bool(int b) { return ((pv)(b != 0) << 2) | 3; }
+static inline int pion_get_bool(pv v) { return (int)(v >> 2); }
+
+enum pion_tag { PION_CLOSURE, PION_LIST, PION_RECORD };
+
+typedef pv (*pion_code)(const pv *captures, pv arg);
+
+struct pion_closure {
+    enum pion_tag tag;
+    pion_code code;
+    pv captures[];
+};
+
+struct pion_list {
+    enum pion_tag tag;
+    uint32_t len;
+    pv elems[];
+};
+
+/* The labels of a record. Labels are compared by address. */
+struct pion_shape {
+    uint32_t len;
+    int is_tuple;
+    const char *const *labels;
+};
+
+struct pion_record {
+    enum pion_tag tag;
+    const struct pion_shape *shape;
+    pv fields[];
+};
+
+/* Objects are never freed. */
+static inline void *pion_alloc(size_t size) {
+    void *ptr = malloc(size);
+    if (ptr == NULL) {
+        fputs("pion: out of memory\n", stderr);
+        exit(1);
+    }
+    return ptr;
+}
+
+static inline pv pion_closure(pion_code code, uint32_t len, const pv *captures) {
+    struct pion_closure *closure = pion_alloc(sizeof(*closure) + len * sizeof(pv));
+    closure->tag = PION_CLOSURE;
+    closure->code = code;
+    if (len != 0) memcpy(closure->captures, captures, len * sizeof(pv));
+    return (pv)closure;
+}
+
+static inline pv pion_apply(pv fun, pv arg) {
+    /* Applying a type former gives a type */
+    if (fun == PION_ERASED) return PION_ERASED;
+    struct pion_closure *closure = (struct pion_closure *)fun;
+    return closure->code(closure->captures, arg);
+}
+
+/* `fix f x = f (fix f) x`. Captures `f`, and the closure itself. */
+static inline pv pion_fix_code(const pv *captures, pv x) {
+    return pion_apply(pion_apply(captures[0], captures[1]), x);
+}
+
+static inline pv pion_fix(pv f) {
+    struct pion_closure *closure = pion_alloc(sizeof(*closure) + 2 * sizeof(pv));
+    closure->tag = PION_CLOSURE;
+    closure->code = pion_fix_code;
+    closure->captures[0] = f;
+    closure->captures[1] = (pv)closure;
+    return (pv)closure;
+}
+
+static inline struct pion_list *pion_list_alloc(uint32_t len) {
+    struct pion_list *list = pion_alloc(sizeof(*list) + len * sizeof(pv));
+    list->tag = PION_LIST;
+    list->len = len;
+    return list;
+}
+
+static inline pv pion_list(uint32_t len, const pv *elems) {
+    struct pion_list *list = pion_list_alloc(len);
+    if (len != 0) memcpy(list->elems, elems, len * sizeof(pv));
+    return (pv)list;
+}
+
+static inline pv pion_len(pv list) { return pion_int(((struct pion_list *)list)->len); }
+
+static inline pv pion_push(pv list, pv elem) {
+    struct pion_list *old = (struct pion_list *)list;
+    struct pion_list *new = pion_list_alloc(old->len + 1);
+    memcpy(new->elems, old->elems, old->len * sizeof(pv));
+    new->elems[old->len] = elem;
+    return (pv)new;
+}
+
+static inline pv pion_append(pv lhs, pv rhs) {
+    struct pion_list *l = (struct pion_list *)lhs;
+    struct pion_list *r = (struct pion_list *)rhs;
+    struct pion_list *new = pion_list_alloc(l->len + r->len);
+    memcpy(new->elems, l->elems, l->len * sizeof(pv));
+    memcpy(new->elems + l->len, r->elems, r->len * sizeof(pv));
+    return (pv)new;
+}
+
+static inline pv pion_record(const struct pion_shape *shape, const pv *fields) {
+    struct pion_record *record = pion_alloc(sizeof(*record) + shape->len * sizeof(pv));
+    record->tag = PION_RECORD;
+    record->shape = shape;
+    if (shape->len != 0) memcpy(record->fields, fields, shape->len * sizeof(pv));
+    return (pv)record;
+}
+
+static inline pv pion_proj(pv value, const char *label) {
+    /* Projecting from a type gives a type */
+    if (value == PION_ERASED) return PION_ERASED;
+    struct pion_record *record = (struct pion_record *)value;
+    for (uint32_t i = 0; i < record->shape->len; i++) {
+        if (record->shape->labels[i] == label) return record->fields[i];
+    }
+    fprintf(stderr, "pion: record has no field `%s`\n", label);
+    exit(1);
+}
+
+/* Print a value in the same syntax as `#eval`. */
+static inline void pion_print(pv value) {
+    if (value == PION_ERASED) {
+        fputs("<erased>", stdout);
+    } else if ((value & 3) == 1) {
+        printf("%" PRIu32, pion_get_int(value));
+    } else if ((value & 3) == 3) {
+        fputs(pion_get_bool(value) ? "true" : "false", stdout);
+    } else {
+        switch (*(enum pion_tag *)value) {
+        case PION_CLOSURE:
+            fputs("<function>", stdout);
+            break;
+        case PION_LIST: {
+            struct pion_list *list = (struct pion_list *)value;
+            putchar('[');
+            for (uint32_t i = 0; i < list->len; i++) {
+                if (i != 0) fputs(", ", stdout);
+                pion_print(list->elems[i]);
+            }
+            putchar(']');
+            break;
+        }
+        case PION_RECORD: {
+            struct pion_record *record = (struct pion_record *)value;
+            const struct pion_shape *shape = record->shape;
+            if (shape->is_tuple) {
+                putchar('(');
+                for (uint32_t i = 0; i < shape->len; i++) {
+                    if (i != 0) fputs(", ", stdout);
+                    pion_print(record->fields[i]);
+                }
+                fputs(shape->len == 1 ? ",)" : ")", stdout);
+            } else {
+                putchar('{');
+                for (uint32_t i = 0; i < shape->len; i++) {
+                    fputs(i == 0 ? " " : ", ", stdout);
+                    printf("%s = ", shape->labels[i]);
+                    pion_print(record->fields[i]);
+                }
+                fputs(" }", stdout);
+            }
+            break;
+        }
+        }
+    }
+}
+
+static inline void pion_println(pv value) {
+    pion_print(value);
+    putchar('\n');
+}
@@ -0,0 +1,112 @@
+//! A first-order intermediate representation for code generation.
+//!
+//! Compared to core, types and proofs have been erased, variables
+//! have been resolved to where their values are stored, and functions have
+//! been closure converted: each lambda is a top-level [`Function`] which takes
+//! an environment of captured values and a single argument.
+
+use pion_core::prim::Prim;
+use pion_symbol::Symbol;
+
+/// An index into [`Program::functions`].
+pub type FunctionId = u32;
+
+/// An index into [`Program::globals`].
+pub type GlobalId = u32;
+
+/// A local variable of a function (or of the program's entry point). Each
+/// function's parameter is local `0`.
+pub type LocalId = u32;
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    /// The top-level bindings of the program.
+    pub globals: Vec<Global>,
+    /// The program's entry point.
+    pub main: Main,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// The number of values captured by closures of the function.
+    pub captures: u32,
+    /// The number of local variables of the function, including its parameter.
+    pub locals: u32,
+    pub body: Expr,
+}
+
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: Option<Symbol>,
+    /// The type of the global, if it is a scalar or a function between
+    /// scalars.
+    pub signature: Option<Signature>,
+}
+
+/// A type whose values can be passed to and from code outside the program.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scalar {
+    Int,
+    Bool,
+}
+
+/// The type of a function taking `params` (one at a time) and returning
+/// `result`, or of a constant if there are no parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// `None` for irrelevant parameters, which are passed erased values.
+    pub params: Vec<Option<Scalar>>,
+    pub result: Scalar,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Main {
+    /// The number of local variables of the entry point.
+    pub locals: u32,
+    pub stmts: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    /// Initialize a global.
+    Global(GlobalId, Expr),
+    /// Display the value of an expression.
+    Print(Expr),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Int(u32),
+    Bool(bool),
+    /// A value with no runtime content: a type, or a proof.
+    Erased,
+
+    Local(LocalId),
+    /// A value captured by the current function.
+    Capture(u32),
+    Global(GlobalId),
+
+    Let(LocalId, Box<Self>, Box<Self>),
+
+    Closure {
+        function: FunctionId,
+        captures: Vec<Self>,
+    },
+    Apply(Box<Self>, Box<Self>),
+    /// A primitive function applied to all of its relevant arguments.
+    Prim(Prim, Vec<Self>),
+    /// `fix f`.
+    Fix(Box<Self>),
+
+    List(Vec<Self>),
+    Record(Vec<(Symbol, Self)>),
+    Proj(Box<Self>, Symbol),
+
+    If(Box<Self>, Box<Self>, Box<Self>),
+    Switch {
+        scrut: Box<Self>,
+        cases: Vec<(u32, Self)>,
+        default: Box<Self>,
+    },
+}
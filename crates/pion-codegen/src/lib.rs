@@ -0,0 +1,47 @@
+//! Compiling elaborated programs ahead of time.
+//!
+//! Core is first [lowered](lower) to a first-order [IR](ir), with types and
+//! proofs erased and functions closure converted, which is then
+//! translated to a target language: [C](c) or [WebAssembly](wasm).
+
+use std::fmt;
+
+pub mod c;
+pub mod ir;
+pub mod lower;
+pub mod wasm;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The program contains an error.
+    IllTyped,
+    /// The program contains an unsolved metavariable.
+    UnsolvedMeta,
+    /// A type or proof is taken apart at runtime.
+    IrrelevantUse,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IllTyped => write!(f, "cannot compile an ill-typed program"),
+            Self::UnsolvedMeta => write!(f, "cannot compile a program with unsolved holes"),
+            Self::IrrelevantUse => write!(
+                f,
+                "cannot compile a program that uses a type or proof at runtime"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<pion_erase::Error> for Error {
+    fn from(error: pion_erase::Error) -> Self {
+        match error {
+            pion_erase::Error::IllTyped => Self::IllTyped,
+            pion_erase::Error::UnsolvedMeta => Self::UnsolvedMeta,
+            pion_erase::Error::IrrelevantUse => Self::IrrelevantUse,
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Lowering elaborated core to the [IR](crate::ir).
+//!
+//! Core is first [erased](pion_erase), so types and proofs become
+//! [`ir::Expr::Erased`]. Irrelevant parameters are kept, and passed erased
+//! arguments.
+
+use pion_core::prim::Prim;
+use pion_core::syntax::{Expr, Item};
+use pion_erase::erase::relevance;
+use pion_erase::{syntax as erased, Eraser, Relevance};
+use pion_util::numeric_conversions::TruncateFrom;
+
+use crate::ir::{self, FunctionId, GlobalId, LocalId};
+use crate::Error;
+
+pub fn lower_program(items: &[Item]) -> Result<ir::Program, Error> {
+    let mut eraser = Eraser::new();
+    let mut lowerer = Lowerer::default();
+    let mut cx = FunctionCx::default();
+    let mut vars = Vec::new();
+    let mut stmts = Vec::new();
+
+    for item in items {
+        match item {
+            Item::Let(binding) => {
+                let relevance = relevance(&binding.r#type);
+                let rhs = match relevance {
+                    Relevance::Relevant => eraser.erase(&binding.rhs)?,
+                    Relevance::Irrelevant => erased::Expr::Erased,
+                };
+                eraser.push_local(relevance);
+                let rhs = lowerer.lower(&mut cx, &mut vars, &rhs)?;
+                let global = GlobalId::truncate_from(lowerer.globals.len());
+                lowerer.globals.push(ir::Global {
+                    name: binding.name,
+                    signature: signature(&binding.r#type),
+                });
+                stmts.push(ir::Stmt::Global(global, rhs));
+                vars.push(Var::Global(global));
+            }
+            Item::Eval(expr) => {
+                let expr = lowerer.lower(&mut cx, &mut vars, &eraser.erase(expr)?)?;
+                stmts.push(ir::Stmt::Print(expr));
+            }
+        }
+    }
+
+    Ok(ir::Program {
+        functions: lowerer.functions,
+        globals: lowerer.globals,
+        main: ir::Main {
+            locals: cx.locals,
+            stmts,
+        },
+    })
+}
+
+/// Where the value of a variable is stored.
+#[derive(Debug, Copy, Clone)]
+enum Var {
+    Global(GlobalId),
+    Local(LocalId),
+    Capture(u32),
+    /// The variable is not captured by the current function.
+    NotCaptured,
+}
+
+#[derive(Default)]
+struct Lowerer {
+    functions: Vec<ir::Function>,
+    globals: Vec<ir::Global>,
+}
+
+/// The function being lowered.
+#[derive(Default)]
+struct FunctionCx {
+    locals: u32,
+}
+
+impl FunctionCx {
+    fn fresh_local(&mut self) -> LocalId {
+        let local = self.locals;
+        self.locals += 1;
+        local
+    }
+}
+
+impl Lowerer {
+    fn push_function(&mut self, function: ir::Function) -> FunctionId {
+        let id = FunctionId::truncate_from(self.functions.len());
+        self.functions.push(function);
+        id
+    }
+
+    /// `vars` maps each variable in scope (by level) to where its value is
+    /// stored.
+    fn lower(
+        &mut self,
+        cx: &mut FunctionCx,
+        vars: &mut Vec<Var>,
+        expr: &erased::Expr,
+    ) -> Result<ir::Expr, Error> {
+        let expr = match expr {
+            erased::Expr::Erased => ir::Expr::Erased,
+            erased::Expr::Int(int) => ir::Expr::Int(*int),
+            erased::Expr::Bool(bool) => ir::Expr::Bool(*bool),
+            erased::Expr::Var(var) => {
+                let level = vars.len() - usize::from(*var) - 1;
+                match vars[level] {
+                    Var::Global(global) => ir::Expr::Global(global),
+                    Var::Local(local) => ir::Expr::Local(local),
+                    Var::Capture(index) => ir::Expr::Capture(index),
+                    Var::NotCaptured => unreachable!("free variables are captured"),
+                }
+            }
+            erased::Expr::Let(rhs, body) => {
+                let rhs = self.lower(cx, vars, rhs)?;
+                let local = cx.fresh_local();
+                vars.push(Var::Local(local));
+                let body = self.lower(cx, vars, body);
+                vars.pop();
+                ir::Expr::Let(local, Box::new(rhs), Box::new(body?))
+            }
+            erased::Expr::Fun(_, body) => {
+                let levels: Vec<_> = (expr.free_vars().into_iter())
+                    .map(|var| vars.len() - var - 1)
+                    .filter(|level| matches!(vars[*level], Var::Local(_) | Var::Capture(_)))
+                    .collect();
+
+                let mut inner_vars: Vec<_> = (vars.iter())
+                    .map(|var| match var {
+                        Var::Global(_) => *var,
+                        Var::Local(_) | Var::Capture(_) | Var::NotCaptured => Var::NotCaptured,
+                    })
+                    .collect();
+                let mut captures = Vec::with_capacity(levels.len());
+                for (index, level) in levels.iter().enumerate() {
+                    captures.push(match vars[*level] {
+                        Var::Local(local) => ir::Expr::Local(local),
+                        Var::Capture(index) => ir::Expr::Capture(index),
+                        _ => unreachable!(),
+                    });
+                    inner_vars[*level] = Var::Capture(u32::truncate_from(index));
+                }
+
+                let mut inner_cx = FunctionCx::default();
+                inner_vars.push(Var::Local(inner_cx.fresh_local()));
+                let body = self.lower(&mut inner_cx, &mut inner_vars, body)?;
+                let function = self.push_function(ir::Function {
+                    captures: u32::truncate_from(captures.len()),
+                    locals: inner_cx.locals,
+                    body,
+                });
+                ir::Expr::Closure { function, captures }
+            }
+            erased::Expr::App(fun, arg) => ir::Expr::Apply(
+                Box::new(self.lower(cx, vars, fun)?),
+                Box::new(self.lower(cx, vars, arg)?),
+            ),
+            erased::Expr::Prim(prim, args) => {
+                let mut args: Vec<_> = (args.iter())
+                    .map(|arg| self.lower(cx, vars, arg))
+                    .collect::<Result<_, Error>>()?;
+                match prim {
+                    Prim::fix => ir::Expr::Fix(Box::new(args.remove(0))),
+                    // `subst px` is `px`.
+                    Prim::subst => args.remove(0),
+                    _ => ir::Expr::Prim(*prim, args),
+                }
+            }
+            erased::Expr::List(elems) => ir::Expr::List(
+                (elems.iter())
+                    .map(|elem| self.lower(cx, vars, elem))
+                    .collect::<Result<_, Error>>()?,
+            ),
+            erased::Expr::Record(fields) => ir::Expr::Record(
+                (fields.iter())
+                    .map(|(label, expr)| Ok((*label, self.lower(cx, vars, expr)?)))
+                    .collect::<Result<_, Error>>()?,
+            ),
+            erased::Expr::Proj(scrut, label) => {
+                ir::Expr::Proj(Box::new(self.lower(cx, vars, scrut)?), *label)
+            }
+            erased::Expr::If(cond, then, r#else) => ir::Expr::If(
+                Box::new(self.lower(cx, vars, cond)?),
+                Box::new(self.lower(cx, vars, then)?),
+                Box::new(self.lower(cx, vars, r#else)?),
+            ),
+            erased::Expr::Switch {
+                scrut,
+                cases,
+                default,
+            } => ir::Expr::Switch {
+                scrut: Box::new(self.lower(cx, vars, scrut)?),
+                cases: (cases.iter())
+                    .map(|(int, expr)| Ok((*int, self.lower(cx, vars, expr)?)))
+                    .collect::<Result<_, Error>>()?,
+                default: Box::new(self.lower(cx, vars, default)?),
+            },
+        };
+        Ok(expr)
+    }
+}
+
+/// The signature of values of type `r#type`, if it is a scalar or a function
+/// between scalars (and irrelevant parameters).
+fn signature(mut r#type: &Expr) -> Option<ir::Signature> {
+    let mut params = Vec::new();
+    while let Expr::FunType { param, body } = r#type {
+        params.push(match relevance(param.r#type) {
+            Relevance::Relevant => Some(scalar(param.r#type)?),
+            Relevance::Irrelevant => None,
+        });
+        r#type = body;
+    }
+    let result = scalar(r#type)?;
+    return Some(ir::Signature { params, result });
+
+    fn scalar(r#type: &Expr) -> Option<ir::Scalar> {
+        match r#type {
+            Expr::Prim(Prim::Int) => Some(ir::Scalar::Int),
+            Expr::Prim(Prim::Bool) => Some(ir::Scalar::Bool),
+            _ => None,
+        }
+    }
+}
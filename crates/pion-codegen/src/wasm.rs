@@ -0,0 +1,824 @@
+//! Translating the [IR](crate::ir) to a WebAssembly module.
+//!
+//! Values are represented as in the [C backend](crate::c), as `i64`s: an
+//! integer `n` is `n << 2 | 1`, a boolean `b` is `b << 2 | 3`, erased values
+//! are `0`, and anything else is the address of an object in linear memory.
+//! Objects are allocated by bumping a pointer, and are never freed. Each
+//! object has an 8 byte header followed by 8 byte fields:
+//! - a closure's header is the index of its code in the function table, and its
+//!   fields are its captured values.
+//! - a list's header is its length, and its fields are its elements.
+//! - a record's header is the address of its shape, and its fields are its
+//!   values. A shape is a length followed by the ids of the record's labels.
+//!
+//! Top-level bindings whose types are scalars, or functions between scalars,
+//! are exported as functions taking and returning `i32`s. Tail calls use the
+//! WebAssembly tail call extension, so they run in constant space.
+
+use std::collections::HashMap;
+
+use pion_core::prim::Prim;
+use pion_symbol::Symbol;
+use pion_util::numeric_conversions::TruncateFrom;
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, ElementSection, Elements, ExportKind, ExportSection,
+    Function, FunctionSection, GlobalSection, GlobalType, Instruction, MemArg, MemorySection,
+    MemoryType, Module, RefType, StartSection, TableSection, TableType, TypeSection, ValType,
+};
+
+use crate::ir::{self, Scalar};
+
+/// `(env: i32, arg: i64) -> i64`: the code of a closure.
+const CLOSURE_TYPE: u32 = 0;
+/// `(size: i32) -> i32`
+const ALLOC_TYPE: u32 = 1;
+/// `(i64, i64) -> i64`
+const BINARY_TYPE: u32 = 2;
+/// `(i64) -> i64`
+const UNARY_TYPE: u32 = 3;
+/// `(record: i64, label: i32) -> i64`
+const PROJ_TYPE: u32 = 4;
+/// `() -> ()`
+const START_TYPE: u32 = 5;
+const RUNTIME_TYPES: u32 = 6;
+
+const ALLOC: u32 = 0;
+const APPLY: u32 = 1;
+const FIX: u32 = 2;
+const FIX_CODE: u32 = 3;
+const LEN: u32 = 4;
+const PUSH: u32 = 5;
+const APPEND: u32 = 6;
+const PROJ: u32 = 7;
+const RUNTIME_FUNCTIONS: u32 = 8;
+
+/// The global holding the address of the next free byte of memory.
+const HEAP: u32 = 0;
+
+/// The address of the first shape. Address `0` is never used, since it
+/// represents erased values.
+const DATA_START: u32 = 8;
+
+const PAGE_SIZE: u32 = 65536;
+
+/// Translate `program` to a WebAssembly module. The program's globals are
+/// initialized by the module's start function. [`ir::Stmt::Print`]
+/// statements are ignored, since WebAssembly has no way to print.
+pub fn emit_module(program: &ir::Program) -> Vec<u8> {
+    let function_count = u32::truncate_from(program.functions.len());
+    let mut data = Data::default();
+
+    // The function table holds each IR function, followed by `FIX_CODE`.
+    let fix_table_index = function_count;
+    let mut code = CodeSection::new();
+    for function in runtime_functions(fix_table_index) {
+        code.function(&function);
+    }
+    for function in &program.functions {
+        let mut emitter = Emitter::new(&mut data, 2, 1);
+        emitter.declare_locals(function.locals - 1, ValType::I64);
+        emitter.expr(&function.body, true);
+        code.function(&emitter.finish());
+    }
+
+    let mut start = Emitter::new(&mut data, 0, 0);
+    start.declare_locals(program.main.locals, ValType::I64);
+    for stmt in &program.main.stmts {
+        match stmt {
+            ir::Stmt::Global(global, expr) => {
+                start.expr(expr, false);
+                start.instr(Instruction::GlobalSet(1 + global));
+            }
+            ir::Stmt::Print(_) => {}
+        }
+    }
+    code.function(&start.finish());
+    let start_index = RUNTIME_FUNCTIONS + function_count;
+
+    let mut types = TypeSection::new();
+    types.function([ValType::I32, ValType::I64], [ValType::I64]);
+    types.function([ValType::I32], [ValType::I32]);
+    types.function([ValType::I64, ValType::I64], [ValType::I64]);
+    types.function([ValType::I64], [ValType::I64]);
+    types.function([ValType::I64, ValType::I32], [ValType::I64]);
+    types.function([], []);
+
+    let mut functions = FunctionSection::new();
+    functions.function(ALLOC_TYPE);
+    functions.function(BINARY_TYPE);
+    functions.function(UNARY_TYPE);
+    functions.function(CLOSURE_TYPE);
+    functions.function(UNARY_TYPE);
+    functions.function(BINARY_TYPE);
+    functions.function(BINARY_TYPE);
+    functions.function(PROJ_TYPE);
+    for _ in &program.functions {
+        functions.function(CLOSURE_TYPE);
+    }
+    functions.function(START_TYPE);
+
+    // Later bindings shadow earlier bindings with the same name.
+    let mut exported = HashMap::new();
+    for (global, binding) in program.globals.iter().enumerate().rev() {
+        if let (Some(name), Some(signature)) = (binding.name, &binding.signature) {
+            exported.entry(name).or_insert((global, signature));
+        }
+    }
+    let mut exported: Vec<_> = exported.into_iter().collect();
+    exported.sort_by_key(|(_, (global, _))| *global);
+
+    let mut exports = ExportSection::new();
+    for (index, (name, (global, signature))) in exported.iter().enumerate() {
+        let index = u32::truncate_from(index);
+        let params: Vec<_> = signature
+            .params
+            .iter()
+            .flatten()
+            .map(|_| ValType::I32)
+            .collect();
+        types.function(params, [ValType::I32]);
+        functions.function(RUNTIME_TYPES + index);
+        code.function(&export_wrapper(u32::truncate_from(*global), signature));
+        exports.export(name.as_str(), ExportKind::Func, start_index + 1 + index);
+    }
+
+    let mut table = TableSection::new();
+    let table_len = function_count + 1;
+    table.table(TableType {
+        element_type: RefType::FUNCREF,
+        minimum: table_len,
+        maximum: Some(table_len),
+    });
+    let mut elements = ElementSection::new();
+    let table_functions: Vec<_> = (0..function_count)
+        .map(|function| RUNTIME_FUNCTIONS + function)
+        .chain([FIX_CODE])
+        .collect();
+    elements.active(
+        None,
+        &ConstExpr::i32_const(0),
+        Elements::Functions(&table_functions),
+    );
+
+    let heap_start = (DATA_START + u32::truncate_from(data.bytes.len())).next_multiple_of(8);
+    let mut memory = MemorySection::new();
+    memory.memory(MemoryType {
+        minimum: u64::from(heap_start.div_ceil(PAGE_SIZE)),
+        maximum: None,
+        memory64: false,
+        shared: false,
+    });
+
+    let mut globals = GlobalSection::new();
+    let heap_type = GlobalType {
+        val_type: ValType::I32,
+        mutable: true,
+    };
+    globals.global(heap_type, &ConstExpr::i32_const(i32_bits(heap_start)));
+    for _ in &program.globals {
+        let global_type = GlobalType {
+            val_type: ValType::I64,
+            mutable: true,
+        };
+        globals.global(global_type, &ConstExpr::i64_const(0));
+    }
+
+    let mut data_section = wasm_encoder::DataSection::new();
+    let data_start = ConstExpr::i32_const(i32_bits(DATA_START));
+    data_section.active(0, &data_start, data.bytes.iter().copied());
+
+    let mut module = Module::new();
+    module.section(&types);
+    module.section(&functions);
+    module.section(&table);
+    module.section(&memory);
+    module.section(&globals);
+    module.section(&exports);
+    module.section(&StartSection {
+        function_index: start_index,
+    });
+    module.section(&elements);
+    module.section(&code);
+    module.section(&data_section);
+    module.finish()
+}
+
+/// The shapes of the records in the program, and the ids of their labels.
+#[derive(Default)]
+struct Data {
+    bytes: Vec<u8>,
+    labels: HashMap<Symbol, u32>,
+    shapes: HashMap<Vec<Symbol>, u32>,
+}
+
+impl Data {
+    fn label(&mut self, label: Symbol) -> u32 {
+        let id = u32::truncate_from(self.labels.len());
+        *self.labels.entry(label).or_insert(id)
+    }
+
+    /// The address of the shape of records with `labels`.
+    fn shape(&mut self, labels: Vec<Symbol>) -> u32 {
+        if let Some(address) = self.shapes.get(&labels) {
+            return *address;
+        }
+        let address = DATA_START + u32::truncate_from(self.bytes.len());
+        let len = u32::truncate_from(labels.len());
+        self.bytes.extend(len.to_le_bytes());
+        for label in &labels {
+            let id = self.label(*label);
+            self.bytes.extend(id.to_le_bytes());
+        }
+        self.shapes.insert(labels, address);
+        address
+    }
+}
+
+/// The `i32` with the same bits as `value`. WebAssembly integers have no
+/// sign, but `wasm_encoder` takes signed constants.
+const fn i32_bits(value: u32) -> i32 { i32::from_ne_bytes(value.to_ne_bytes()) }
+
+const fn i32_const(value: u32) -> Instruction<'static> { Instruction::I32Const(i32_bits(value)) }
+
+/// An `i64` constant with the same bits as `value`.
+const fn i64_const(value: u64) -> Instruction<'static> {
+    Instruction::I64Const(i64::from_ne_bytes(value.to_ne_bytes()))
+}
+
+/// The access of the 4 byte value at `offset` from an address.
+fn mem32(offset: u32) -> MemArg {
+    MemArg {
+        offset: u64::from(offset),
+        align: 2,
+        memory_index: 0,
+    }
+}
+
+/// The access of the 8 byte value at `offset` from an address.
+fn mem64(offset: u32) -> MemArg {
+    MemArg {
+        offset: u64::from(offset),
+        align: 3,
+        memory_index: 0,
+    }
+}
+
+/// The offset of the field at `index` in an object.
+fn field_offset(index: usize) -> u32 { 8 + 8 * u32::truncate_from(index) }
+
+/// Convert an `i32` on the stack to a value of type `scalar`.
+fn box_scalar(scalar: Scalar) -> [Instruction<'static>; 5] {
+    let tag = match scalar {
+        Scalar::Int => 1,
+        Scalar::Bool => 3,
+    };
+    [
+        Instruction::I64ExtendI32U,
+        Instruction::I64Const(2),
+        Instruction::I64Shl,
+        Instruction::I64Const(tag),
+        Instruction::I64Or,
+    ]
+}
+
+/// Convert a scalar value on the stack to an `i32`.
+const fn unbox_scalar() -> [Instruction<'static>; 3] {
+    [
+        Instruction::I64Const(2),
+        Instruction::I64ShrU,
+        Instruction::I32WrapI64,
+    ]
+}
+
+/// Emits the body of a function.
+struct Emitter<'data> {
+    data: &'data mut Data,
+    /// The number of parameters of the function.
+    params: u32,
+    /// The wasm local holding IR local `0`.
+    local_base: u32,
+    locals: Vec<ValType>,
+    instrs: Vec<Instruction<'static>>,
+}
+
+impl<'data> Emitter<'data> {
+    fn new(data: &'data mut Data, params: u32, local_base: u32) -> Self {
+        Self {
+            data,
+            params,
+            local_base,
+            locals: Vec::new(),
+            instrs: Vec::new(),
+        }
+    }
+
+    fn declare_locals(&mut self, count: u32, r#type: ValType) {
+        for _ in 0..count {
+            self.fresh_local(r#type);
+        }
+    }
+
+    fn fresh_local(&mut self, r#type: ValType) -> u32 {
+        let local = self.params + u32::truncate_from(self.locals.len());
+        self.locals.push(r#type);
+        local
+    }
+
+    fn instr(&mut self, instr: Instruction<'static>) { self.instrs.push(instr); }
+
+    fn instrs(&mut self, instrs: impl IntoIterator<Item = Instruction<'static>>) {
+        self.instrs.extend(instrs);
+    }
+
+    fn finish(mut self) -> Function {
+        self.instr(Instruction::End);
+        let mut function = Function::new_with_locals_types(self.locals);
+        for instr in &self.instrs {
+            function.instruction(instr);
+        }
+        function
+    }
+
+    /// Emit code that pushes the value of `expr` onto the stack (or returns
+    /// it from the function, if `tail` is set).
+    fn expr(&mut self, expr: &ir::Expr, tail: bool) {
+        match expr {
+            ir::Expr::Let(local, rhs, body) => {
+                self.expr(rhs, false);
+                self.instr(Instruction::LocalSet(self.local_base + local));
+                self.expr(body, tail);
+                return;
+            }
+            ir::Expr::Apply(fun, arg) => {
+                self.expr(fun, false);
+                self.expr(arg, false);
+                match tail {
+                    false => self.instr(Instruction::Call(APPLY)),
+                    true => self.instr(Instruction::ReturnCall(APPLY)),
+                }
+                return;
+            }
+            ir::Expr::If(cond, then, r#else) => {
+                self.expr(cond, false);
+                self.instrs(unbox_scalar());
+                self.instr(Instruction::If(BlockType::Result(ValType::I64)));
+                self.expr(then, tail);
+                self.instr(Instruction::Else);
+                self.expr(r#else, tail);
+                self.instr(Instruction::End);
+                return;
+            }
+            ir::Expr::Switch {
+                scrut,
+                cases,
+                default,
+            } => {
+                self.expr(scrut, false);
+                self.instrs(unbox_scalar());
+                let scrut = self.fresh_local(ValType::I32);
+                self.instr(Instruction::LocalSet(scrut));
+                for (int, expr) in cases {
+                    self.instrs([
+                        Instruction::LocalGet(scrut),
+                        i32_const(*int),
+                        Instruction::I32Eq,
+                        Instruction::If(BlockType::Result(ValType::I64)),
+                    ]);
+                    self.expr(expr, tail);
+                    self.instr(Instruction::Else);
+                }
+                self.expr(default, tail);
+                for _ in cases {
+                    self.instr(Instruction::End);
+                }
+                return;
+            }
+
+            ir::Expr::Int(int) => self.instr(i64_const(u64::from(*int) << 2 | 1)),
+            ir::Expr::Bool(bool) => self.instr(i64_const(u64::from(*bool) << 2 | 3)),
+            ir::Expr::Erased => self.instr(Instruction::I64Const(0)),
+            ir::Expr::Local(local) => self.instr(Instruction::LocalGet(self.local_base + local)),
+            ir::Expr::Capture(index) => self.instrs([
+                Instruction::LocalGet(0),
+                Instruction::I64Load(mem64(field_offset(usize::try_from(*index).unwrap()))),
+            ]),
+            ir::Expr::Global(global) => self.instr(Instruction::GlobalGet(1 + global)),
+            ir::Expr::Closure { function, captures } => {
+                self.object(*function, captures.iter());
+            }
+            ir::Expr::Prim(prim, args) => {
+                for arg in args {
+                    self.expr(arg, false);
+                }
+                self.prim_app(*prim);
+            }
+            ir::Expr::Fix(fun) => {
+                self.expr(fun, false);
+                self.instr(Instruction::Call(FIX));
+            }
+            ir::Expr::List(elems) => {
+                self.object(u32::truncate_from(elems.len()), elems.iter());
+            }
+            ir::Expr::Record(fields) => {
+                let shape = self
+                    .data
+                    .shape(fields.iter().map(|(label, _)| *label).collect());
+                self.object(shape, fields.iter().map(|(_, expr)| expr));
+            }
+            ir::Expr::Proj(scrut, label) => {
+                self.expr(scrut, false);
+                let label = self.data.label(*label);
+                self.instrs([i32_const(label), Instruction::Call(PROJ)]);
+            }
+        }
+
+        if tail {
+            self.instr(Instruction::Return);
+        }
+    }
+
+    /// Emit code that allocates an object with `header`, and fields holding
+    /// the values of `fields`.
+    fn object<'a>(&mut self, header: u32, fields: impl ExactSizeIterator<Item = &'a ir::Expr>) {
+        let object = self.fresh_local(ValType::I32);
+        self.instrs([
+            i32_const(field_offset(fields.len())),
+            Instruction::Call(ALLOC),
+            Instruction::LocalTee(object),
+            i32_const(header),
+            Instruction::I32Store(mem32(0)),
+        ]);
+        for (index, field) in fields.enumerate() {
+            self.instr(Instruction::LocalGet(object));
+            self.expr(field, false);
+            self.instr(Instruction::I64Store(mem64(field_offset(index))));
+        }
+        self.instrs([Instruction::LocalGet(object), Instruction::I64ExtendI32U]);
+    }
+
+    /// Instructions that apply the binary integer operation `op` to the two
+    /// integers on the stack.
+    fn int_op(&mut self, op: Instruction<'static>) -> Vec<Instruction<'static>> {
+        let rhs = self.fresh_local(ValType::I32);
+        let mut instrs = Vec::from(unbox_scalar());
+        instrs.push(Instruction::LocalSet(rhs));
+        instrs.extend(unbox_scalar());
+        instrs.extend([Instruction::LocalGet(rhs), op]);
+        instrs
+    }
+
+    /// Emit code that applies `prim` to the arguments on the stack.
+    fn prim_app(&mut self, prim: Prim) {
+        let (instrs, result) = match prim {
+            Prim::len => (vec![Instruction::Call(LEN)], None),
+            Prim::push => (vec![Instruction::Call(PUSH)], None),
+            Prim::append => (vec![Instruction::Call(APPEND)], None),
+            Prim::bool_rec => {
+                // `select` takes the condition last.
+                let then = self.fresh_local(ValType::I64);
+                let r#else = self.fresh_local(ValType::I64);
+                let mut instrs = vec![Instruction::LocalSet(r#else), Instruction::LocalSet(then)];
+                instrs.extend(unbox_scalar());
+                let cond = self.fresh_local(ValType::I32);
+                instrs.extend([
+                    Instruction::LocalSet(cond),
+                    Instruction::LocalGet(then),
+                    Instruction::LocalGet(r#else),
+                    Instruction::LocalGet(cond),
+                    Instruction::Select,
+                ]);
+                (instrs, None)
+            }
+            Prim::add => (self.int_op(Instruction::I32Add), Some(Scalar::Int)),
+            Prim::sub => (self.int_op(Instruction::I32Sub), Some(Scalar::Int)),
+            Prim::mul => (self.int_op(Instruction::I32Mul), Some(Scalar::Int)),
+            Prim::eq => (self.int_op(Instruction::I32Eq), Some(Scalar::Bool)),
+            Prim::ne => (self.int_op(Instruction::I32Ne), Some(Scalar::Bool)),
+            Prim::gt => (self.int_op(Instruction::I32GtU), Some(Scalar::Bool)),
+            Prim::lt => (self.int_op(Instruction::I32LtU), Some(Scalar::Bool)),
+            Prim::gte => (self.int_op(Instruction::I32GeU), Some(Scalar::Bool)),
+            Prim::lte => (self.int_op(Instruction::I32LeU), Some(Scalar::Bool)),
+            Prim::Type
+            | Prim::Int
+            | Prim::Bool
+            | Prim::List
+            | Prim::Eq
+            | Prim::fix
+            | Prim::refl
+            | Prim::subst => unreachable!("`{}` is lowered specially", prim.name()),
+        };
+        self.instrs(instrs);
+        if let Some(result) = result {
+            self.instrs(box_scalar(result));
+        }
+    }
+}
+
+/// A function that calls the value of `global` with `i32` arguments (and
+/// erased values for its irrelevant parameters), and returns an `i32`.
+fn export_wrapper(global: u32, signature: &ir::Signature) -> Function {
+    let mut function = Function::new([]);
+    function.instruction(&Instruction::GlobalGet(1 + global));
+    let mut local = 0;
+    for param in &signature.params {
+        match param {
+            None => {
+                function.instruction(&Instruction::I64Const(0));
+            }
+            Some(scalar) => {
+                function.instruction(&Instruction::LocalGet(local));
+                for instr in box_scalar(*scalar) {
+                    function.instruction(&instr);
+                }
+                local += 1;
+            }
+        }
+        function.instruction(&Instruction::Call(APPLY));
+    }
+    for instr in unbox_scalar() {
+        function.instruction(&instr);
+    }
+    function.instruction(&Instruction::End);
+    function
+}
+
+/// The functions of the runtime, in the order of their indices.
+fn runtime_functions(fix_table_index: u32) -> Vec<Function> {
+    use Instruction as I;
+
+    fn function(locals: &[ValType], instrs: &[Instruction]) -> Function {
+        let mut function = Function::new_with_locals_types(locals.iter().copied());
+        for instr in instrs {
+            function.instruction(instr);
+        }
+        function.instruction(&I::End);
+        function
+    }
+
+    // The address of the end of memory.
+    let memory_end = [I::MemorySize(0), I::I32Const(16), I::I32Shl];
+    let copy = I::MemoryCopy {
+        src_mem: 0,
+        dst_mem: 0,
+    };
+
+    // (size) -> address
+    let alloc = function(
+        &[ValType::I32],
+        &[
+            [I::GlobalGet(HEAP), I::LocalSet(1)].as_slice(),
+            &[
+                I::GlobalGet(HEAP),
+                I::LocalGet(0),
+                I::I32Add,
+                I::GlobalSet(HEAP),
+            ],
+            &[I::GlobalGet(HEAP)],
+            &memory_end,
+            &[I::I32GtU, I::If(BlockType::Empty), I::GlobalGet(HEAP)],
+            &memory_end,
+            &[
+                I::I32Sub,
+                I::I32Const(65535),
+                I::I32Add,
+                I::I32Const(16),
+                I::I32ShrU,
+            ],
+            &[I::MemoryGrow(0), I::I32Const(-1), I::I32Eq],
+            &[I::If(BlockType::Empty), I::Unreachable, I::End],
+            &[I::End, I::LocalGet(1)],
+        ]
+        .concat(),
+    );
+
+    // (fun, arg) -> result. Applying an erased value (a type former) gives an
+    // erased value.
+    let apply = function(
+        &[ValType::I32],
+        &[
+            I::LocalGet(0),
+            I::I64Eqz,
+            I::If(BlockType::Empty),
+            I::I64Const(0),
+            I::Return,
+            I::End,
+            I::LocalGet(0),
+            I::I32WrapI64,
+            I::LocalTee(2),
+            I::LocalGet(1),
+            I::LocalGet(2),
+            I::I32Load(mem32(0)),
+            I::ReturnCallIndirect {
+                ty: CLOSURE_TYPE,
+                table: 0,
+            },
+        ],
+    );
+
+    // (f) -> a closure of `FIX_CODE`, capturing `f` and itself.
+    let fix = function(
+        &[ValType::I32],
+        &[
+            I::I32Const(24),
+            I::Call(ALLOC),
+            I::LocalTee(1),
+            i32_const(fix_table_index),
+            I::I32Store(mem32(0)),
+            I::LocalGet(1),
+            I::LocalGet(0),
+            I::I64Store(mem64(8)),
+            I::LocalGet(1),
+            I::LocalGet(1),
+            I::I64ExtendI32U,
+            I::I64Store(mem64(16)),
+            I::LocalGet(1),
+            I::I64ExtendI32U,
+        ],
+    );
+
+    // `fix f x = f (fix f) x`
+    let fix_code = function(
+        &[],
+        &[
+            I::LocalGet(0),
+            I::I64Load(mem64(8)),
+            I::LocalGet(0),
+            I::I64Load(mem64(16)),
+            I::Call(APPLY),
+            I::LocalGet(1),
+            I::ReturnCall(APPLY),
+        ],
+    );
+
+    let len = function(
+        &[],
+        &[
+            [I::LocalGet(0), I::I32WrapI64, I::I32Load(mem32(0))].as_slice(),
+            &box_scalar(Scalar::Int),
+        ]
+        .concat(),
+    );
+
+    // (list, elem) -> list. Locals: the list's address, its length, and the
+    // new list's address.
+    let push = function(
+        &[ValType::I32, ValType::I32, ValType::I32],
+        &[
+            I::LocalGet(0),
+            I::I32WrapI64,
+            I::LocalTee(2),
+            I::I32Load(mem32(0)),
+            I::LocalSet(3),
+            // Allocate and set the length.
+            I::LocalGet(3),
+            I::I32Const(3),
+            I::I32Shl,
+            I::I32Const(16),
+            I::I32Add,
+            I::Call(ALLOC),
+            I::LocalTee(4),
+            I::LocalGet(3),
+            I::I32Const(1),
+            I::I32Add,
+            I::I32Store(mem32(0)),
+            // Copy the elements.
+            I::LocalGet(4),
+            I::I32Const(8),
+            I::I32Add,
+            I::LocalGet(2),
+            I::I32Const(8),
+            I::I32Add,
+            I::LocalGet(3),
+            I::I32Const(3),
+            I::I32Shl,
+            copy.clone(),
+            // Store the new element.
+            I::LocalGet(4),
+            I::LocalGet(3),
+            I::I32Const(3),
+            I::I32Shl,
+            I::I32Add,
+            I::LocalGet(1),
+            I::I64Store(mem64(8)),
+            I::LocalGet(4),
+            I::I64ExtendI32U,
+        ],
+    );
+
+    // (lhs, rhs) -> list. Locals: the addresses and lengths of the lists, and
+    // the new list's address.
+    let i32s = [ValType::I32; 5];
+    let append = function(
+        &i32s,
+        &[
+            I::LocalGet(0),
+            I::I32WrapI64,
+            I::LocalTee(2),
+            I::I32Load(mem32(0)),
+            I::LocalSet(4),
+            I::LocalGet(1),
+            I::I32WrapI64,
+            I::LocalTee(3),
+            I::I32Load(mem32(0)),
+            I::LocalSet(5),
+            // Allocate and set the length.
+            I::LocalGet(4),
+            I::LocalGet(5),
+            I::I32Add,
+            I::I32Const(3),
+            I::I32Shl,
+            I::I32Const(8),
+            I::I32Add,
+            I::Call(ALLOC),
+            I::LocalTee(6),
+            I::LocalGet(4),
+            I::LocalGet(5),
+            I::I32Add,
+            I::I32Store(mem32(0)),
+            // Copy the elements of `lhs`.
+            I::LocalGet(6),
+            I::I32Const(8),
+            I::I32Add,
+            I::LocalGet(2),
+            I::I32Const(8),
+            I::I32Add,
+            I::LocalGet(4),
+            I::I32Const(3),
+            I::I32Shl,
+            copy.clone(),
+            // Copy the elements of `rhs`.
+            I::LocalGet(6),
+            I::I32Const(8),
+            I::I32Add,
+            I::LocalGet(4),
+            I::I32Const(3),
+            I::I32Shl,
+            I::I32Add,
+            I::LocalGet(3),
+            I::I32Const(8),
+            I::I32Add,
+            I::LocalGet(5),
+            I::I32Const(3),
+            I::I32Shl,
+            copy,
+            I::LocalGet(6),
+            I::I64ExtendI32U,
+        ],
+    );
+
+    // (record, label) -> value. Projecting from an erased value (a type)
+    // gives an erased value. Locals: the record's address, its shape's
+    // address, and the index of the field being compared.
+    let proj = function(
+        &[ValType::I32, ValType::I32, ValType::I32],
+        &[
+            I::LocalGet(0),
+            I::I64Eqz,
+            I::If(BlockType::Empty),
+            I::I64Const(0),
+            I::Return,
+            I::End,
+            I::LocalGet(0),
+            I::I32WrapI64,
+            I::LocalTee(2),
+            I::I32Load(mem32(0)),
+            I::LocalSet(3),
+            I::Loop(BlockType::Empty),
+            // Trap if no field has the label.
+            I::LocalGet(4),
+            I::LocalGet(3),
+            I::I32Load(mem32(0)),
+            I::I32GeU,
+            I::If(BlockType::Empty),
+            I::Unreachable,
+            I::End,
+            // Return the field if it has the label.
+            I::LocalGet(3),
+            I::LocalGet(4),
+            I::I32Const(2),
+            I::I32Shl,
+            I::I32Add,
+            I::I32Load(mem32(4)),
+            I::LocalGet(1),
+            I::I32Eq,
+            I::If(BlockType::Empty),
+            I::LocalGet(2),
+            I::LocalGet(4),
+            I::I32Const(3),
+            I::I32Shl,
+            I::I32Add,
+            I::I64Load(mem64(8)),
+            I::Return,
+            I::End,
+            I::LocalGet(4),
+            I::I32Const(1),
+            I::I32Add,
+            I::LocalSet(4),
+            I::Br(0),
+            I::End,
+            I::Unreachable,
+        ],
+    );
+
+    vec![alloc, apply, fix, fix_code, len, push, append, proj]
+}
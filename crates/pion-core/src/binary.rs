@@ -0,0 +1,510 @@
+//! A compact binary format for elaborated items, so that the results of
+//! elaboration can be saved and loaded again without re-elaborating.
+//!
+//! An encoded file is:
+//!
+//! ```text
+//! file    ::= "PIONCORE" version symbols items
+//! version ::= uint                          must equal `VERSION`
+//! symbols ::= uint (uint byte*)*            count, then length-prefixed UTF-8
+//! items   ::= uint item*
+//! ```
+//!
+//! All integers are unsigned LEB128. Names, labels and primitives are stored
+//! as indices into the symbol table, so a file does not depend on the order
+//! that symbols were interned in, and loading it re-interns them. Items and
+//! expressions are written in prefix order, each starting with a tag byte.
+//!
+//! The format is only meant to be read by the same version of pion that wrote
+//! it: [`VERSION`] must be incremented whenever it changes.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use pion_symbol::Symbol;
+use pion_util::collect_in::CollectIn;
+use pion_util::numeric_conversions::{TruncateFrom, ZeroExtendFrom};
+
+use crate::env::{AbsoluteVar, RelativeVar};
+use crate::prim::Prim;
+use crate::syntax::{Expr, FunArg, FunParam, Item, LetBinding, Lit, Plicity};
+
+const MAGIC: &[u8] = b"PIONCORE";
+
+/// The version of the format written by [`encode_items`].
+pub const VERSION: u32 = 1;
+
+mod tag {
+    pub const LET_ITEM: u8 = 0;
+    pub const EVAL_ITEM: u8 = 1;
+
+    pub const ERROR: u8 = 0;
+    pub const INT: u8 = 1;
+    pub const FALSE: u8 = 2;
+    pub const TRUE: u8 = 3;
+    pub const PRIM: u8 = 4;
+    pub const LOCAL_VAR: u8 = 5;
+    pub const META_VAR: u8 = 6;
+    pub const LET: u8 = 7;
+    pub const FUN_TYPE: u8 = 8;
+    pub const FUN_LIT: u8 = 9;
+    pub const FUN_APP: u8 = 10;
+    pub const LIST_LIT: u8 = 11;
+    pub const RECORD_TYPE: u8 = 12;
+    pub const RECORD_LIT: u8 = 13;
+    pub const RECORD_PROJ: u8 = 14;
+    pub const MATCH_BOOL: u8 = 15;
+    pub const MATCH_INT: u8 = 16;
+
+    pub const EXPLICIT: u8 = 0;
+    pub const IMPLICIT: u8 = 1;
+    pub const INSTANCE: u8 = 2;
+}
+
+pub fn encode_items(items: &[Item]) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+    encoder.uint(items.len());
+    for item in items {
+        encoder.item(item);
+    }
+
+    let mut bytes = MAGIC.to_vec();
+    write_uint(&mut bytes, u64::from(VERSION));
+    write_uint(&mut bytes, u64::zext_from(encoder.symbols.len()));
+    for symbol in &encoder.symbols {
+        let text = symbol.as_str();
+        write_uint(&mut bytes, u64::zext_from(text.len()));
+        bytes.extend_from_slice(text.as_bytes());
+    }
+    bytes.extend_from_slice(&encoder.bytes);
+    bytes
+}
+
+/// Decode items written by [`encode_items`], allocating them in `bump`.
+pub fn decode_items<'core>(
+    bump: &'core bumpalo::Bump,
+    bytes: &[u8],
+) -> Result<Vec<Item<'core>>, DecodeError> {
+    let mut decoder = Decoder {
+        bump,
+        bytes,
+        offset: 0,
+        symbols: Vec::new(),
+    };
+
+    if !bytes.starts_with(MAGIC) {
+        return Err(decoder.error("not a pion core file"));
+    }
+    decoder.offset = MAGIC.len();
+
+    let version = decoder.uint()?;
+    if version != u64::from(VERSION) {
+        let message = format!("unsupported version {version}, expected {VERSION}");
+        return Err(error_at(MAGIC.len(), message));
+    }
+
+    let len = decoder.len()?;
+    for _ in 0..len {
+        let len = decoder.len()?;
+        let start = decoder.offset;
+        let text = std::str::from_utf8(decoder.take(len)?)
+            .map_err(|_| error_at(start, String::from("symbol is not valid UTF-8")))?;
+        decoder.symbols.push(Symbol::intern(text));
+    }
+
+    let len = decoder.len()?;
+    let mut items = Vec::with_capacity(len);
+    for _ in 0..len {
+        items.push(decoder.item()?);
+    }
+    if decoder.offset != bytes.len() {
+        return Err(decoder.error("trailing bytes after last item"));
+    }
+    Ok(items)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    /// The byte offset of the error in the input.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+    symbols: Vec<Symbol>,
+    symbol_indices: HashMap<Symbol, usize>,
+}
+
+impl Encoder {
+    fn uint(&mut self, value: usize) { write_uint(&mut self.bytes, u64::zext_from(value)); }
+
+    fn symbol_index(&mut self, symbol: Symbol) -> usize {
+        *self.symbol_indices.entry(symbol).or_insert_with(|| {
+            self.symbols.push(symbol);
+            self.symbols.len() - 1
+        })
+    }
+
+    fn symbol(&mut self, symbol: Symbol) {
+        let index = self.symbol_index(symbol);
+        self.uint(index);
+    }
+
+    /// `0` for `None`, otherwise one more than the symbol's index.
+    fn name(&mut self, name: Option<Symbol>) {
+        let index = name.map_or(0, |name| self.symbol_index(name) + 1);
+        self.uint(index);
+    }
+
+    fn plicity(&mut self, plicity: Plicity) {
+        self.bytes.push(match plicity {
+            Plicity::Explicit => tag::EXPLICIT,
+            Plicity::Implicit => tag::IMPLICIT,
+            Plicity::Instance => tag::INSTANCE,
+        });
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Let(binding) => {
+                self.bytes.push(tag::LET_ITEM);
+                self.name(binding.name);
+                self.expr(&binding.r#type);
+                self.expr(&binding.rhs);
+            }
+            Item::Eval(expr) => {
+                self.bytes.push(tag::EVAL_ITEM);
+                self.expr(expr);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Error => self.bytes.push(tag::ERROR),
+            Expr::Lit(Lit::Int(int)) => {
+                self.bytes.push(tag::INT);
+                write_uint(&mut self.bytes, u64::from(*int));
+            }
+            Expr::Lit(Lit::Bool(false)) => self.bytes.push(tag::FALSE),
+            Expr::Lit(Lit::Bool(true)) => self.bytes.push(tag::TRUE),
+            Expr::Prim(prim) => {
+                self.bytes.push(tag::PRIM);
+                self.symbol(Symbol::intern(prim.name()));
+            }
+            Expr::LocalVar(var) => {
+                self.bytes.push(tag::LOCAL_VAR);
+                self.uint(usize::from(*var));
+            }
+            Expr::MetaVar(var) => {
+                self.bytes.push(tag::META_VAR);
+                self.uint(usize::from(*var));
+            }
+            Expr::Let { binding, body } => {
+                self.bytes.push(tag::LET);
+                self.name(binding.name);
+                self.expr(binding.r#type);
+                self.expr(binding.rhs);
+                self.expr(body);
+            }
+            Expr::FunType { param, body } | Expr::FunLit { param, body } => {
+                self.bytes.push(match expr {
+                    Expr::FunType { .. } => tag::FUN_TYPE,
+                    _ => tag::FUN_LIT,
+                });
+                self.plicity(param.plicity);
+                self.name(param.name);
+                self.expr(param.r#type);
+                self.expr(body);
+            }
+            Expr::FunApp { fun, arg } => {
+                self.bytes.push(tag::FUN_APP);
+                self.expr(fun);
+                self.plicity(arg.plicity);
+                self.expr(arg.expr);
+            }
+            Expr::ListLit(elems) => {
+                self.bytes.push(tag::LIST_LIT);
+                self.uint(elems.len());
+                for elem in *elems {
+                    self.expr(elem);
+                }
+            }
+            Expr::RecordType(fields) | Expr::RecordLit(fields) => {
+                self.bytes.push(match expr {
+                    Expr::RecordType(..) => tag::RECORD_TYPE,
+                    _ => tag::RECORD_LIT,
+                });
+                self.uint(fields.len());
+                for (label, expr) in *fields {
+                    self.symbol(*label);
+                    self.expr(expr);
+                }
+            }
+            Expr::RecordProj(scrut, label) => {
+                self.bytes.push(tag::RECORD_PROJ);
+                self.expr(scrut);
+                self.symbol(*label);
+            }
+            Expr::MatchBool { cond, then, r#else } => {
+                self.bytes.push(tag::MATCH_BOOL);
+                self.expr(cond);
+                self.expr(then);
+                self.expr(r#else);
+            }
+            Expr::MatchInt {
+                scrut,
+                cases,
+                default,
+            } => {
+                self.bytes.push(tag::MATCH_INT);
+                self.expr(scrut);
+                self.uint(cases.len());
+                for (int, expr) in *cases {
+                    write_uint(&mut self.bytes, u64::from(*int));
+                    self.expr(expr);
+                }
+                self.expr(default);
+            }
+        }
+    }
+}
+
+struct Decoder<'core, 'bytes> {
+    bump: &'core bumpalo::Bump,
+    bytes: &'bytes [u8],
+    offset: usize,
+    symbols: Vec<Symbol>,
+}
+
+impl<'core, 'bytes> Decoder<'core, 'bytes> {
+    fn take(&mut self, len: usize) -> Result<&'bytes [u8], DecodeError> {
+        let end = self.offset.checked_add(len);
+        match end.and_then(|end| self.bytes.get(self.offset..end)) {
+            None => Err(self.error("unexpected end of input")),
+            Some(bytes) => {
+                self.offset += len;
+                Ok(bytes)
+            }
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, DecodeError> { Ok(self.take(1)?[0]) }
+
+    fn uint(&mut self) -> Result<u64, DecodeError> {
+        let rest = &self.bytes[self.offset..];
+        match read_uint(rest) {
+            None if rest.iter().all(|byte| byte & 0x80 != 0) => Err(error_at(
+                self.bytes.len(),
+                String::from("unexpected end of input"),
+            )),
+            None => Err(self.error("integer is too large")),
+            Some((value, len)) => {
+                self.offset += len;
+                Ok(value)
+            }
+        }
+    }
+
+    /// An integer that must fit in a `usize`.
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        let start = self.offset;
+        let value = self.uint()?;
+        usize::try_from(value).map_err(|_| error_at(start, format!("{value} is too large")))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        let start = self.offset;
+        let value = self.uint()?;
+        u32::try_from(value).map_err(|_| error_at(start, format!("{value} is too large")))
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, DecodeError> {
+        let start = self.offset;
+        let index = self.len()?;
+        (self.symbols.get(index).copied())
+            .ok_or_else(|| error_at(start, format!("symbol {index} is out of range")))
+    }
+
+    fn name(&mut self) -> Result<Option<Symbol>, DecodeError> {
+        let start = self.offset;
+        match self.len()? {
+            0 => Ok(None),
+            index => match self.symbols.get(index - 1) {
+                Some(symbol) => Ok(Some(*symbol)),
+                None => Err(error_at(
+                    start,
+                    format!("symbol {} is out of range", index - 1),
+                )),
+            },
+        }
+    }
+
+    fn plicity(&mut self) -> Result<Plicity, DecodeError> {
+        let start = self.offset;
+        match self.byte()? {
+            tag::EXPLICIT => Ok(Plicity::Explicit),
+            tag::IMPLICIT => Ok(Plicity::Implicit),
+            tag::INSTANCE => Ok(Plicity::Instance),
+            byte => Err(error_at(start, format!("invalid plicity {byte}"))),
+        }
+    }
+
+    fn item(&mut self) -> Result<Item<'core>, DecodeError> {
+        let start = self.offset;
+        match self.byte()? {
+            tag::LET_ITEM => {
+                let name = self.name()?;
+                let r#type = self.expr()?;
+                let rhs = self.expr()?;
+                Ok(Item::Let(LetBinding::new(name, r#type, rhs)))
+            }
+            tag::EVAL_ITEM => Ok(Item::Eval(self.expr()?)),
+            byte => Err(error_at(start, format!("invalid item tag {byte}"))),
+        }
+    }
+
+    fn expr_ref(&mut self) -> Result<&'core Expr<'core>, DecodeError> {
+        let expr = self.expr()?;
+        Ok(self.bump.alloc(expr))
+    }
+
+    fn param(&mut self) -> Result<FunParam<&'core Expr<'core>>, DecodeError> {
+        let plicity = self.plicity()?;
+        let name = self.name()?;
+        let r#type = self.expr_ref()?;
+        Ok(FunParam::new(plicity, name, r#type))
+    }
+
+    fn fields(&mut self) -> Result<&'core [(Symbol, Expr<'core>)], DecodeError> {
+        let len = self.len()?;
+        let mut fields = Vec::new();
+        for _ in 0..len {
+            let label = self.symbol()?;
+            fields.push((label, self.expr()?));
+        }
+        Ok(fields.into_iter().collect_in(self.bump))
+    }
+
+    fn expr(&mut self) -> Result<Expr<'core>, DecodeError> {
+        let start = self.offset;
+        match self.byte()? {
+            tag::ERROR => Ok(Expr::Error),
+            tag::INT => Ok(Expr::Lit(Lit::Int(self.u32()?))),
+            tag::FALSE => Ok(Expr::FALSE),
+            tag::TRUE => Ok(Expr::TRUE),
+            tag::PRIM => {
+                let symbol = self.symbol()?;
+                match Prim::from_symbol(symbol) {
+                    Some(prim) => Ok(Expr::Prim(prim)),
+                    None => Err(error_at(start, format!("unknown primitive `{symbol}`"))),
+                }
+            }
+            tag::LOCAL_VAR => Ok(Expr::LocalVar(RelativeVar::new(self.len()?))),
+            tag::META_VAR => Ok(Expr::MetaVar(AbsoluteVar::from(self.len()?))),
+            tag::LET => {
+                let name = self.name()?;
+                let r#type = self.expr_ref()?;
+                let rhs = self.expr_ref()?;
+                let body = self.expr_ref()?;
+                let binding = LetBinding::new(name, r#type, rhs);
+                Ok(Expr::Let { binding, body })
+            }
+            tag::FUN_TYPE => {
+                let param = self.param()?;
+                let body = self.expr_ref()?;
+                Ok(Expr::FunType { param, body })
+            }
+            tag::FUN_LIT => {
+                let param = self.param()?;
+                let body = self.expr_ref()?;
+                Ok(Expr::FunLit { param, body })
+            }
+            tag::FUN_APP => {
+                let fun = self.expr_ref()?;
+                let plicity = self.plicity()?;
+                let arg = self.expr_ref()?;
+                let arg = FunArg::new(plicity, arg);
+                Ok(Expr::FunApp { fun, arg })
+            }
+            tag::LIST_LIT => {
+                let len = self.len()?;
+                let mut elems = Vec::new();
+                for _ in 0..len {
+                    elems.push(self.expr()?);
+                }
+                Ok(Expr::ListLit(elems.into_iter().collect_in(self.bump)))
+            }
+            tag::RECORD_TYPE => Ok(Expr::RecordType(self.fields()?)),
+            tag::RECORD_LIT => Ok(Expr::RecordLit(self.fields()?)),
+            tag::RECORD_PROJ => {
+                let scrut = self.expr_ref()?;
+                let label = self.symbol()?;
+                Ok(Expr::RecordProj(scrut, label))
+            }
+            tag::MATCH_BOOL => {
+                let cond = self.expr_ref()?;
+                let then = self.expr_ref()?;
+                let r#else = self.expr_ref()?;
+                Ok(Expr::MatchBool { cond, then, r#else })
+            }
+            tag::MATCH_INT => {
+                let scrut = self.expr_ref()?;
+                let len = self.len()?;
+                let mut cases = Vec::new();
+                for _ in 0..len {
+                    let int = self.u32()?;
+                    cases.push((int, self.expr()?));
+                }
+                let cases = cases.into_iter().collect_in(self.bump);
+                let default = self.expr_ref()?;
+                Ok(Expr::MatchInt {
+                    scrut,
+                    cases,
+                    default,
+                })
+            }
+            byte => Err(error_at(start, format!("invalid expression tag {byte}"))),
+        }
+    }
+
+    fn error(&self, message: &str) -> DecodeError { error_at(self.offset, message.to_owned()) }
+}
+
+const fn error_at(offset: usize, message: String) -> DecodeError { DecodeError { offset, message } }
+
+fn write_uint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(u8::truncate_from(value) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(u8::truncate_from(value));
+}
+
+/// Read an unsigned LEB128 integer from the start of `bytes`, returning it
+/// and the number of bytes it took up.
+fn read_uint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0_u64;
+    for (index, byte) in bytes.iter().enumerate() {
+        let shift = index.checked_mul(7).filter(|shift| *shift < 64)?;
+        let bits = u64::from(byte & 0x7f);
+        if (bits << shift) >> shift != bits {
+            return None;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Some((value, index + 1));
+        }
+    }
+    None
+}
@@ -1,5 +1,9 @@
+pub mod binary;
 pub mod env;
+pub mod optimize;
 pub mod prim;
+pub mod reduce;
 pub mod semantics;
 pub mod syntax;
+pub mod text;
 pub mod unelab;
@@ -0,0 +1,518 @@
+//! Optimizing core expressions.
+//!
+//! The elaborator produces many administrative `let`s (for example when
+//! destructuring patterns), and applications of known functions. The
+//! optimizer removes them with a few local rewrites, repeated until nothing
+//! changes:
+//!
+//! * beta reduction: `(fun x => body) arg` becomes `let x = arg; body`
+//! * dead-let elimination: `let x = rhs; body` becomes `body` if `body` does
+//!   not refer to `x`
+//! * inlining: `let x = rhs; body` becomes `body[x := rhs]` if `rhs` is small,
+//!   or is only used once
+//! * constant folding of primitive functions applied to literals, with the same
+//!   semantics as the normalizer
+//! * case-of-known-literal: `match` on a literal, and projection from a record
+//!   literal, select the branch or field.
+//!
+//! Rewrites only ever discard or move expressions that are total: an
+//! application of a function other than a primitive could loop forever, so is
+//! never discarded or moved.
+
+use pion_symbol::Symbol;
+use pion_util::collect_in::CollectIn;
+
+use crate::env::{EnvLen, RelativeVar, SharedEnv};
+use crate::prim::Prim;
+use crate::semantics::{EvalEnv, EvalOpts, MetaValues, QuoteEnv, Value};
+use crate::syntax::{Expr, FunArg, LetBinding, Lit};
+
+/// How much to optimize.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OptLevel {
+    /// Do not optimize.
+    None,
+    /// Beta reduction, constant folding, case-of-known-literal and dead-let
+    /// elimination.
+    Simplify,
+    /// Everything in [`Self::Simplify`], and inlining.
+    #[default]
+    Inline,
+}
+
+/// The maximum number of times the rewrites are applied to the whole
+/// expression.
+const MAX_ROUNDS: usize = 8;
+
+pub fn optimize<'core>(
+    bump: &'core bumpalo::Bump,
+    level: OptLevel,
+    expr: &Expr<'core>,
+) -> Expr<'core> {
+    let mut optimizer = Optimizer {
+        bump,
+        level,
+        changed: false,
+    };
+    let mut expr = *expr;
+    if level == OptLevel::None {
+        return expr;
+    }
+    for _ in 0..MAX_ROUNDS {
+        optimizer.changed = false;
+        expr = optimizer.expr(&expr);
+        if !optimizer.changed {
+            break;
+        }
+    }
+    expr
+}
+
+struct Optimizer<'core> {
+    bump: &'core bumpalo::Bump,
+    level: OptLevel,
+    /// Whether any rewrite was applied in the current round.
+    changed: bool,
+}
+
+impl<'core> Optimizer<'core> {
+    fn expr(&mut self, expr: &Expr<'core>) -> Expr<'core> {
+        match *expr {
+            // Types are left alone: they are erased before running anyway.
+            Expr::Error
+            | Expr::Lit(_)
+            | Expr::Prim(_)
+            | Expr::LocalVar(_)
+            | Expr::MetaVar(_)
+            | Expr::FunType { .. }
+            | Expr::RecordType(_) => *expr,
+
+            Expr::Let { binding, body } => {
+                let rhs = self.expr(binding.rhs);
+                let body = self.expr(body);
+                self.simplify_let(binding.name, binding.r#type, rhs, body)
+            }
+            Expr::FunLit { param, body } => Expr::FunLit {
+                param,
+                body: self.bump.alloc(self.expr(body)),
+            },
+            Expr::FunApp { fun, arg } => {
+                let fun = self.expr(fun);
+                let arg_expr = self.expr(arg.expr);
+                if let Expr::FunLit { param, body } = fun {
+                    self.changed = true;
+                    return self.simplify_let(param.name, param.r#type, arg_expr, *body);
+                }
+                let (fun, arg_expr) = self.bump.alloc((fun, arg_expr));
+                let expr = Expr::FunApp {
+                    fun,
+                    arg: FunArg::new(arg.plicity, arg_expr),
+                };
+                self.fold_prim_app(expr)
+            }
+
+            Expr::ListLit(elems) => {
+                let bump = self.bump;
+                Expr::ListLit(elems.iter().map(|elem| self.expr(elem)).collect_in(bump))
+            }
+            Expr::RecordLit(fields) => {
+                let bump = self.bump;
+                Expr::RecordLit(
+                    (fields.iter())
+                        .map(|(label, expr)| (*label, self.expr(expr)))
+                        .collect_in(bump),
+                )
+            }
+            Expr::RecordProj(scrut, label) => {
+                let scrut = self.expr(scrut);
+                match scrut {
+                    Expr::RecordLit(fields) if fields.iter().all(|(_, expr)| is_total(expr)) => {
+                        self.changed = true;
+                        field(fields, label)
+                    }
+                    _ => Expr::RecordProj(self.bump.alloc(scrut), label),
+                }
+            }
+
+            Expr::MatchBool { cond, then, r#else } => match self.expr(cond) {
+                Expr::Lit(Lit::Bool(cond)) => {
+                    self.changed = true;
+                    self.expr(if cond { then } else { r#else })
+                }
+                cond => {
+                    let then = self.expr(then);
+                    let r#else = self.expr(r#else);
+                    let (cond, then, r#else) = self.bump.alloc((cond, then, r#else));
+                    Expr::MatchBool { cond, then, r#else }
+                }
+            },
+            Expr::MatchInt {
+                scrut,
+                cases,
+                default,
+            } => match self.expr(scrut) {
+                Expr::Lit(Lit::Int(int)) => {
+                    self.changed = true;
+                    let case = cases.iter().find(|(case, _)| *case == int);
+                    self.expr(case.map_or(default, |(_, expr)| expr))
+                }
+                scrut => {
+                    let bump = self.bump;
+                    let cases = (cases.iter())
+                        .map(|(int, expr)| (*int, self.expr(expr)))
+                        .collect_in(bump);
+                    let default = self.expr(default);
+                    let (scrut, cases, default) = self.bump.alloc((scrut, cases, default));
+                    Expr::MatchInt {
+                        scrut,
+                        cases,
+                        default,
+                    }
+                }
+            },
+        }
+    }
+
+    /// Simplify `let name : r#type = rhs; body`, where `rhs` and `body` have
+    /// already been optimized.
+    fn simplify_let(
+        &mut self,
+        name: Option<Symbol>,
+        r#type: &'core Expr<'core>,
+        rhs: Expr<'core>,
+        body: Expr<'core>,
+    ) -> Expr<'core> {
+        let uses = Uses::of(&body);
+        if uses.count == 0 && is_total(&rhs) {
+            self.changed = true;
+            return instantiate(self.bump, &body, &Expr::Error);
+        }
+
+        let inline = matches!(rhs, Expr::Lit(_) | Expr::Prim(_) | Expr::LocalVar(_))
+            || (uses.count == 1
+                && is_total(&rhs)
+                && (!uses.under_fun || matches!(rhs, Expr::FunLit { .. })));
+        if self.level >= OptLevel::Inline && inline {
+            self.changed = true;
+            return instantiate(self.bump, &body, &rhs);
+        }
+
+        let (rhs, body) = self.bump.alloc((rhs, body));
+        Expr::Let {
+            binding: LetBinding::new(name, r#type, &*rhs),
+            body,
+        }
+    }
+
+    /// Fold an application of a primitive function to literals, by
+    /// evaluating it.
+    fn fold_prim_app(&mut self, expr: Expr<'core>) -> Expr<'core> {
+        let mut args = Vec::new();
+        let mut head = &expr;
+        while let Expr::FunApp { fun, arg } = head {
+            args.push(arg.expr);
+            head = fun;
+        }
+        args.reverse();
+
+        match (head, args.as_slice()) {
+            (
+                Expr::Prim(Prim::bool_rec),
+                [_, Expr::Lit(Lit::Bool(cond)), then, r#else, rest @ ..],
+            ) => {
+                self.changed = true;
+                let branch = if *cond { then } else { r#else };
+                return (rest.iter()).fold(**branch, |fun, arg| {
+                    let (fun, arg) = self.bump.alloc((fun, **arg));
+                    Expr::FunApp {
+                        fun,
+                        arg: FunArg::explicit(arg),
+                    }
+                });
+            }
+            // `fix` is unfolded by applying it, which might not terminate.
+            (Expr::Prim(Prim::fix), _) => return expr,
+            (Expr::Prim(_), _) if Uses::is_closed(&expr) => {}
+            _ => return expr,
+        }
+
+        let metas = <&MetaValues>::from(&[][..]);
+        let opts = EvalOpts {
+            unfold_fix: false,
+            fuel: None,
+        };
+        let value = EvalEnv::new(self.bump, opts, &mut SharedEnv::new(), metas).eval(&expr);
+        if !is_data(&value) {
+            return expr;
+        }
+        self.changed = true;
+        QuoteEnv::new(self.bump, EnvLen::new(), metas).quote(&value)
+    }
+}
+
+fn field<'core>(fields: &[(Symbol, Expr<'core>)], label: Symbol) -> Expr<'core> {
+    match fields.iter().find(|(field, _)| *field == label) {
+        Some((_, expr)) => *expr,
+        None => Expr::Error,
+    }
+}
+
+/// Whether `value` is a literal, or a list or record of literals.
+pub(crate) fn is_data(value: &Value) -> bool {
+    match value {
+        Value::Lit(_) => true,
+        Value::List(elems) => elems.iter().all(is_data),
+        Value::RecordLit(fields) => fields.iter().all(|(_, value)| is_data(value)),
+        _ => false,
+    }
+}
+
+/// Whether evaluating `expr` is guaranteed to terminate.
+fn is_total(expr: &Expr) -> bool {
+    match expr {
+        Expr::Error
+        | Expr::Lit(_)
+        | Expr::Prim(_)
+        | Expr::LocalVar(_)
+        | Expr::MetaVar(_)
+        | Expr::FunType { .. }
+        | Expr::FunLit { .. }
+        | Expr::RecordType(_) => true,
+        Expr::Let { binding, body } => is_total(binding.rhs) && is_total(body),
+        Expr::FunApp { .. } => {
+            let mut args = 0;
+            let mut head = expr;
+            while let Expr::FunApp { fun, arg } = head {
+                if !is_total(arg.expr) {
+                    return false;
+                }
+                args += 1;
+                head = fun;
+            }
+            match head {
+                // `fix @A @B f x` unfolds `f`.
+                Expr::Prim(Prim::fix) => args < Prim::fix.arity(),
+                // Extra arguments are applied to the primitive's result, which
+                // may be any function (e.g. the branch chosen by `bool_rec`).
+                Expr::Prim(prim) => args <= prim.arity(),
+                _ => false,
+            }
+        }
+        Expr::ListLit(elems) => elems.iter().all(is_total),
+        Expr::RecordLit(fields) => fields.iter().all(|(_, expr)| is_total(expr)),
+        Expr::RecordProj(scrut, _) => is_total(scrut),
+        Expr::MatchBool { cond, then, r#else } => {
+            is_total(cond) && is_total(then) && is_total(r#else)
+        }
+        Expr::MatchInt {
+            scrut,
+            cases,
+            default,
+        } => is_total(scrut) && cases.iter().all(|(_, expr)| is_total(expr)) && is_total(default),
+    }
+}
+
+/// The occurrences of the variable bound by the innermost binder.
+#[derive(Debug, Copy, Clone, Default)]
+pub(crate) struct Uses {
+    count: usize,
+    /// Whether any occurrence is inside a function literal.
+    under_fun: bool,
+}
+
+impl Uses {
+    fn of(expr: &Expr) -> Self {
+        let mut uses = Self::default();
+        visit_free_vars(expr, 0, false, &mut |var, under_fun| {
+            if var == Some(0) {
+                uses.count += 1;
+                uses.under_fun |= under_fun;
+            }
+        });
+        uses
+    }
+
+    /// Whether `expr` refers to no local variables, and to no metavariables
+    /// (which cannot be evaluated without their solutions).
+    pub(crate) fn is_closed(expr: &Expr) -> bool {
+        let mut closed = true;
+        visit_free_vars(expr, 0, false, &mut |_, _| closed = false);
+        closed
+    }
+}
+
+/// Call `f` for each occurrence of a variable that is free in `expr`, with
+/// the variable's index outside of `expr` (or `None` for a metavariable), and
+/// whether the occurrence is inside a function literal.
+fn visit_free_vars(
+    expr: &Expr,
+    binders: usize,
+    under_fun: bool,
+    f: &mut impl FnMut(Option<usize>, bool),
+) {
+    match expr {
+        Expr::LocalVar(var) => {
+            if let Some(var) = usize::from(*var).checked_sub(binders) {
+                f(Some(var), under_fun);
+            }
+        }
+        Expr::MetaVar(_) => f(None, under_fun),
+        Expr::Error | Expr::Lit(_) | Expr::Prim(_) => {}
+        Expr::Let { binding, body } => {
+            visit_free_vars(binding.r#type, binders, under_fun, f);
+            visit_free_vars(binding.rhs, binders, under_fun, f);
+            visit_free_vars(body, binders + 1, under_fun, f);
+        }
+        Expr::FunType { param, body } => {
+            visit_free_vars(param.r#type, binders, under_fun, f);
+            visit_free_vars(body, binders + 1, under_fun, f);
+        }
+        Expr::FunLit { param, body } => {
+            visit_free_vars(param.r#type, binders, under_fun, f);
+            visit_free_vars(body, binders + 1, true, f);
+        }
+        Expr::FunApp { fun, arg } => {
+            visit_free_vars(fun, binders, under_fun, f);
+            visit_free_vars(arg.expr, binders, under_fun, f);
+        }
+        Expr::ListLit(elems) => {
+            for elem in *elems {
+                visit_free_vars(elem, binders, under_fun, f);
+            }
+        }
+        Expr::RecordType(fields) => {
+            for (index, (_, r#type)) in fields.iter().enumerate() {
+                visit_free_vars(r#type, binders + index, under_fun, f);
+            }
+        }
+        Expr::RecordLit(fields) => {
+            for (_, expr) in *fields {
+                visit_free_vars(expr, binders, under_fun, f);
+            }
+        }
+        Expr::RecordProj(scrut, _) => visit_free_vars(scrut, binders, under_fun, f),
+        Expr::MatchBool { cond, then, r#else } => {
+            visit_free_vars(cond, binders, under_fun, f);
+            visit_free_vars(then, binders, under_fun, f);
+            visit_free_vars(r#else, binders, under_fun, f);
+        }
+        Expr::MatchInt {
+            scrut,
+            cases,
+            default,
+        } => {
+            visit_free_vars(scrut, binders, under_fun, f);
+            for (_, expr) in *cases {
+                visit_free_vars(expr, binders, under_fun, f);
+            }
+            visit_free_vars(default, binders, under_fun, f);
+        }
+    }
+}
+
+/// Substitute `arg` for the variable bound by the innermost binder of
+/// `body`, and remove the binder.
+pub(crate) fn instantiate<'core>(
+    bump: &'core bumpalo::Bump,
+    body: &Expr<'core>,
+    arg: &Expr<'core>,
+) -> Expr<'core> {
+    return recur(bump, body, arg, 0);
+
+    fn recur<'core>(
+        bump: &'core bumpalo::Bump,
+        expr: &Expr<'core>,
+        arg: &Expr<'core>,
+        binders: usize,
+    ) -> Expr<'core> {
+        match expr {
+            Expr::LocalVar(var) => match usize::from(*var).checked_sub(binders) {
+                None => *expr,
+                Some(0) => arg.shift(bump, EnvLen::from(binders)),
+                Some(_) => Expr::LocalVar(RelativeVar::new(usize::from(*var) - 1)),
+            },
+            Expr::Error | Expr::Lit(_) | Expr::Prim(_) | Expr::MetaVar(_) => *expr,
+            Expr::Let { binding, body } => {
+                let r#type = recur(bump, binding.r#type, arg, binders);
+                let rhs = recur(bump, binding.rhs, arg, binders);
+                let body = recur(bump, body, arg, binders + 1);
+                let (r#type, rhs, body) = bump.alloc((r#type, rhs, body));
+                Expr::Let {
+                    binding: LetBinding::new(binding.name, &*r#type, &*rhs),
+                    body,
+                }
+            }
+            Expr::FunType { param, body } => {
+                let r#type = recur(bump, param.r#type, arg, binders);
+                let body = recur(bump, body, arg, binders + 1);
+                let (r#type, body) = bump.alloc((r#type, body));
+                Expr::FunType {
+                    param: crate::syntax::FunParam::new(param.plicity, param.name, &*r#type),
+                    body,
+                }
+            }
+            Expr::FunLit { param, body } => {
+                let r#type = recur(bump, param.r#type, arg, binders);
+                let body = recur(bump, body, arg, binders + 1);
+                let (r#type, body) = bump.alloc((r#type, body));
+                Expr::FunLit {
+                    param: crate::syntax::FunParam::new(param.plicity, param.name, &*r#type),
+                    body,
+                }
+            }
+            Expr::FunApp { fun, arg: fun_arg } => {
+                let fun = recur(bump, fun, arg, binders);
+                let arg_expr = recur(bump, fun_arg.expr, arg, binders);
+                let (fun, arg_expr) = bump.alloc((fun, arg_expr));
+                Expr::FunApp {
+                    fun,
+                    arg: FunArg::new(fun_arg.plicity, arg_expr),
+                }
+            }
+            Expr::ListLit(elems) => Expr::ListLit(
+                (elems.iter())
+                    .map(|elem| recur(bump, elem, arg, binders))
+                    .collect_in(bump),
+            ),
+            Expr::RecordType(fields) => Expr::RecordType(
+                (fields.iter().enumerate())
+                    .map(|(index, (label, r#type))| {
+                        (*label, recur(bump, r#type, arg, binders + index))
+                    })
+                    .collect_in(bump),
+            ),
+            Expr::RecordLit(fields) => Expr::RecordLit(
+                (fields.iter())
+                    .map(|(label, expr)| (*label, recur(bump, expr, arg, binders)))
+                    .collect_in(bump),
+            ),
+            Expr::RecordProj(scrut, label) => {
+                Expr::RecordProj(bump.alloc(recur(bump, scrut, arg, binders)), *label)
+            }
+            Expr::MatchBool { cond, then, r#else } => {
+                let cond = recur(bump, cond, arg, binders);
+                let then = recur(bump, then, arg, binders);
+                let r#else = recur(bump, r#else, arg, binders);
+                let (cond, then, r#else) = bump.alloc((cond, then, r#else));
+                Expr::MatchBool { cond, then, r#else }
+            }
+            Expr::MatchInt {
+                scrut,
+                cases,
+                default,
+            } => {
+                let scrut = recur(bump, scrut, arg, binders);
+                let cases = (cases.iter())
+                    .map(|(int, expr)| (*int, recur(bump, expr, arg, binders)))
+                    .collect_in(bump);
+                let default = recur(bump, default, arg, binders);
+                let (scrut, cases, default) = bump.alloc((scrut, cases, default));
+                Expr::MatchInt {
+                    scrut,
+                    cases,
+                    default,
+                }
+            }
+        }
+    }
+}
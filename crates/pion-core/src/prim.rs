@@ -13,6 +13,8 @@ macro_rules! prims {
         }
 
         impl Prim {
+            pub const ALL: &'static [Self] = &[$(Self::$prim,)*];
+
             pub const fn name(self) -> &'static str {
                 match self {
                     $(Self::$prim => stringify!($prim),)*
@@ -41,6 +43,29 @@ prims! {
 }
 
 impl Prim {
+    /// The number of parameters of the primitive's type, counting implicit
+    /// ones.
+    pub const fn arity(self) -> usize {
+        match self {
+            Self::Type | Self::Int | Self::Bool => 0,
+            Self::List => 1,
+            Self::len
+            | Self::refl
+            | Self::add
+            | Self::sub
+            | Self::mul
+            | Self::eq
+            | Self::ne
+            | Self::gt
+            | Self::lt
+            | Self::gte
+            | Self::lte => 2,
+            Self::push | Self::append | Self::Eq => 3,
+            Self::fix | Self::bool_rec => 4,
+            Self::subst => 6,
+        }
+    }
+
     pub const fn r#type(self) -> Type<'static> {
         use Plicity::{Explicit, Implicit};
 
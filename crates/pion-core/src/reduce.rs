@@ -0,0 +1,508 @@
+//! Step-by-step reduction of core expressions.
+//!
+//! The normalizer in [`crate::semantics`] evaluates expressions to values and
+//! reads them back, so the intermediate expressions are never built. The
+//! reducer instead rewrites an expression one redex at a time, contracting
+//! the leftmost outermost redex first, so that every intermediate expression
+//! can be displayed. This is much slower than normalization, and is only used
+//! by the `#reduce` and `#trace` commands.
+//!
+//! The types annotating `let` bindings and function parameters are not
+//! reduced, but function and record types are, like any other expression.
+
+use std::fmt;
+
+use pion_symbol::Symbol;
+
+use crate::env::{EnvLen, RelativeVar, SharedEnv, SliceEnv};
+use crate::optimize::{instantiate, is_data, Uses};
+use crate::prim::Prim;
+use crate::semantics::{grow_stack, EvalEnv, EvalOpts, MetaValues, QuoteEnv};
+use crate::syntax::{Expr, FunArg, FunParam, LetBinding, Lit};
+
+/// Which redexes to contract.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReduceOpts {
+    /// Stop at weak head normal form, instead of also reducing inside
+    /// arguments and under binders.
+    pub whnf: bool,
+    /// Replace `let`-bound variables by their definitions.
+    pub unfold_lets: bool,
+    /// Unfold applications of `fix`.
+    pub unfold_fix: bool,
+}
+
+impl Default for ReduceOpts {
+    fn default() -> Self {
+        Self {
+            whnf: false,
+            unfold_lets: true,
+            unfold_fix: true,
+        }
+    }
+}
+
+/// A kind of reduction step.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// `(fun x => body) arg` becomes `body[x := arg]`.
+    Beta,
+    /// `let x = rhs; body` becomes `body[x := rhs]`, or a `let`-bound
+    /// variable becomes its definition.
+    Let,
+    /// An application of a primitive function, including the unfolding of
+    /// `fix`.
+    Prim(Prim),
+    /// A `match` on a boolean literal selects a branch.
+    MatchBool,
+    /// A `match` on an integer literal selects a case.
+    MatchInt,
+    /// A projection from a record literal selects a field.
+    RecordProj(Symbol),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Beta => write!(f, "beta"),
+            Self::Let => write!(f, "let"),
+            Self::Prim(prim) => write!(f, "prim `{}`", prim.name()),
+            Self::MatchBool => write!(f, "match bool"),
+            Self::MatchInt => write!(f, "match int"),
+            Self::RecordProj(label) => write!(f, "projection `.{label}`"),
+        }
+    }
+}
+
+/// The kind of step taken and the reduced value, if a step was taken.
+type Reduced<T> = Option<(Step, T)>;
+
+pub struct Reducer<'core, 'env> {
+    bump: &'core bumpalo::Bump,
+    opts: ReduceOpts,
+    /// The definitions of the local variables in scope, or `None` for
+    /// variables without one, such as function parameters.
+    defs: &'env SliceEnv<Option<Expr<'core>>>,
+}
+
+impl<'core, 'env> Reducer<'core, 'env> {
+    pub const fn new(
+        bump: &'core bumpalo::Bump,
+        opts: ReduceOpts,
+        defs: &'env SliceEnv<Option<Expr<'core>>>,
+    ) -> Self {
+        Self { bump, opts, defs }
+    }
+
+    /// Contract the next redex of `expr`, returning the kind of step taken
+    /// and the resulting expression, or `None` if `expr` is already normal.
+    pub fn step(&self, expr: &Expr<'core>) -> Reduced<Expr<'core>> { self.step_at(expr, 0) }
+
+    /// Like [`Self::step`], where `expr` is under `binders` binders that are
+    /// not in `defs`.
+    fn step_at(&self, expr: &Expr<'core>, binders: usize) -> Reduced<Expr<'core>> {
+        if let Some(reduced) = self.step_head(expr, binders) {
+            return Some(reduced);
+        }
+        if self.opts.whnf {
+            return None;
+        }
+        grow_stack(|| self.step_inside(expr, binders))
+    }
+
+    /// Contract the redex at the head of `expr`, if it is not in weak head
+    /// normal form.
+    fn step_head(&self, expr: &Expr<'core>, binders: usize) -> Reduced<Expr<'core>> {
+        grow_stack(|| self.step_head_inner(expr, binders))
+    }
+
+    fn step_head_inner(&self, expr: &Expr<'core>, binders: usize) -> Reduced<Expr<'core>> {
+        match *expr {
+            Expr::LocalVar(var) if self.opts.unfold_lets => {
+                let def_var = RelativeVar::new(usize::from(var).checked_sub(binders)?);
+                let def = (*self.defs.get_relative(def_var)?)?;
+                let def = def.shift(self.bump, EnvLen::from(usize::from(var) + 1));
+                Some((Step::Let, def))
+            }
+            Expr::Let { binding, body } if self.opts.unfold_lets => {
+                Some((Step::Let, instantiate(self.bump, body, binding.rhs)))
+            }
+            Expr::FunApp { .. } => {
+                let (head, args) = spine(expr);
+                match *head {
+                    Expr::FunLit { body, .. } => {
+                        let body = instantiate(self.bump, body, args[0].expr);
+                        Some((Step::Beta, self.apply(body, &args[1..])))
+                    }
+                    Expr::Prim(prim) => self.step_prim_app(expr, prim, &args, binders),
+                    _ => {
+                        let (step, head) = self.step_head(head, binders)?;
+                        Some((step, self.apply(head, &args)))
+                    }
+                }
+            }
+            Expr::MatchBool { cond, then, r#else } => match cond {
+                Expr::Lit(Lit::Bool(cond)) => {
+                    Some((Step::MatchBool, if *cond { *then } else { *r#else }))
+                }
+                _ => {
+                    let (step, cond) = self.step_head(cond, binders)?;
+                    let cond = self.bump.alloc(cond);
+                    Some((step, Expr::MatchBool { cond, then, r#else }))
+                }
+            },
+            Expr::MatchInt {
+                scrut,
+                cases,
+                default,
+            } => match scrut {
+                Expr::Lit(Lit::Int(int)) => {
+                    let case = cases.iter().find(|(case, _)| case == int);
+                    Some((Step::MatchInt, case.map_or(*default, |(_, expr)| *expr)))
+                }
+                _ => {
+                    let (step, scrut) = self.step_head(scrut, binders)?;
+                    let scrut = self.bump.alloc(scrut);
+                    let expr = Expr::MatchInt {
+                        scrut,
+                        cases,
+                        default,
+                    };
+                    Some((step, expr))
+                }
+            },
+            Expr::RecordProj(scrut, label) => match scrut {
+                Expr::RecordLit(fields) => {
+                    let field = fields.iter().find(|(field, _)| *field == label);
+                    let expr = field.map_or(Expr::Error, |(_, expr)| *expr);
+                    Some((Step::RecordProj(label), expr))
+                }
+                _ => {
+                    let (step, scrut) = self.step_head(scrut, binders)?;
+                    Some((step, Expr::RecordProj(self.bump.alloc(scrut), label)))
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Contract the redex at the head of `expr`, an application of `prim` to
+    /// `args`, or reduce the argument it is waiting on.
+    fn step_prim_app(
+        &self,
+        expr: &Expr<'core>,
+        prim: Prim,
+        args: &[FunArg<&'core Expr<'core>>],
+        binders: usize,
+    ) -> Reduced<Expr<'core>> {
+        let head = Expr::Prim(prim);
+        match (prim, args) {
+            // fix @A @B f x = f (fix @A @B f) x
+            (Prim::fix, [a, b, f, x, rest @ ..]) if self.opts.unfold_fix => {
+                let fix = self.bump.alloc(self.apply(head, &[*a, *b, *f]));
+                let args: Vec<_> = [FunArg::explicit(&*fix), *x]
+                    .into_iter()
+                    .chain(rest.iter().copied())
+                    .collect();
+                Some((Step::Prim(prim), self.apply(*f.expr, &args)))
+            }
+            (Prim::bool_rec, [p, cond, then, r#else, rest @ ..]) => match cond.expr {
+                Expr::Lit(Lit::Bool(cond)) => {
+                    let branch = if *cond { then } else { r#else };
+                    Some((Step::Prim(prim), self.apply(*branch.expr, rest)))
+                }
+                _ => {
+                    let (step, cond_expr) = self.step_head(cond.expr, binders)?;
+                    let cond = FunArg::new(cond.plicity, &*self.bump.alloc(cond_expr));
+                    let args: Vec<_> = [*p, cond, *then, *r#else]
+                        .into_iter()
+                        .chain(rest.iter().copied())
+                        .collect();
+                    Some((step, self.apply(head, &args)))
+                }
+            },
+            // subst @A @p @a @b (refl @A a) px = px
+            (Prim::subst, [a, p, x, y, proof, px, rest @ ..]) => {
+                let mut head_expr = proof.expr;
+                while let Expr::FunApp { fun, .. } = head_expr {
+                    head_expr = fun;
+                }
+                match head_expr {
+                    Expr::Prim(Prim::refl) => Some((Step::Prim(prim), self.apply(*px.expr, rest))),
+                    _ => {
+                        let (step, proof_expr) = self.step_head(proof.expr, binders)?;
+                        let proof = FunArg::new(proof.plicity, &*self.bump.alloc(proof_expr));
+                        let args: Vec<_> = [*a, *p, *x, *y, proof, *px]
+                            .into_iter()
+                            .chain(rest.iter().copied())
+                            .collect();
+                        Some((step, self.apply(head, &args)))
+                    }
+                }
+            }
+            (
+                Prim::len
+                | Prim::push
+                | Prim::append
+                | Prim::add
+                | Prim::sub
+                | Prim::mul
+                | Prim::eq
+                | Prim::ne
+                | Prim::gt
+                | Prim::lt
+                | Prim::gte
+                | Prim::lte,
+                _,
+            ) => {
+                let reduced = step_first(self.bump, args, |arg| {
+                    if !arg.plicity.is_explicit() {
+                        return None;
+                    }
+                    let (step, expr) = self.step_data(arg.expr, binders)?;
+                    Some((step, FunArg::new(arg.plicity, &*self.bump.alloc(expr))))
+                });
+                match reduced {
+                    Some((step, args)) => Some((step, self.apply(head, args))),
+                    None => self.fold_prim_app(expr, prim),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Reduce an argument of a primitive function towards a literal, or a
+    /// list or record of literals.
+    fn step_data(&self, expr: &Expr<'core>, binders: usize) -> Reduced<Expr<'core>> {
+        if let Some(reduced) = self.step_head(expr, binders) {
+            return Some(reduced);
+        }
+        match expr {
+            Expr::ListLit(elems) => {
+                let (step, elems) =
+                    step_first(self.bump, elems, |elem| self.step_data(elem, binders))?;
+                Some((step, Expr::ListLit(elems)))
+            }
+            Expr::RecordLit(fields) => {
+                let (step, fields) = step_first(self.bump, fields, |(label, expr)| {
+                    let (step, expr) = self.step_data(expr, binders)?;
+                    Some((step, (*label, expr)))
+                })?;
+                Some((step, Expr::RecordLit(fields)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Apply `prim` to its arguments in `expr`, if they are all closed data.
+    fn fold_prim_app(&self, expr: &Expr<'core>, prim: Prim) -> Reduced<Expr<'core>> {
+        if !Uses::is_closed(expr) {
+            return None;
+        }
+
+        let metas = <&MetaValues>::from(&[][..]);
+        let opts = EvalOpts {
+            unfold_fix: false,
+            fuel: None,
+        };
+        let value = EvalEnv::new(self.bump, opts, &mut SharedEnv::new(), metas).eval(expr);
+        if !is_data(&value) {
+            return None;
+        }
+        let expr = QuoteEnv::new(self.bump, EnvLen::new(), metas).quote(&value);
+        Some((Step::Prim(prim), expr))
+    }
+
+    /// Contract the leftmost redex inside `expr`, whose head is already in
+    /// weak head normal form.
+    fn step_inside(&self, expr: &Expr<'core>, binders: usize) -> Reduced<Expr<'core>> {
+        let bump = self.bump;
+        match *expr {
+            Expr::Error | Expr::Lit(_) | Expr::Prim(_) | Expr::LocalVar(_) | Expr::MetaVar(_) => {
+                None
+            }
+            Expr::Let { binding, body } => {
+                if let Some((step, rhs)) = self.step_at(binding.rhs, binders) {
+                    let binding = LetBinding::new(binding.name, binding.r#type, &*bump.alloc(rhs));
+                    return Some((step, Expr::Let { binding, body }));
+                }
+                let (step, body) = self.step_at(body, binders + 1)?;
+                Some((
+                    step,
+                    Expr::Let {
+                        binding,
+                        body: bump.alloc(body),
+                    },
+                ))
+            }
+            Expr::FunType { param, body } => {
+                if let Some((step, r#type)) = self.step_at(param.r#type, binders) {
+                    let param = FunParam::new(param.plicity, param.name, &*bump.alloc(r#type));
+                    return Some((step, Expr::FunType { param, body }));
+                }
+                let (step, body) = self.step_at(body, binders + 1)?;
+                Some((
+                    step,
+                    Expr::FunType {
+                        param,
+                        body: bump.alloc(body),
+                    },
+                ))
+            }
+            Expr::FunLit { param, body } => {
+                let (step, body) = self.step_at(body, binders + 1)?;
+                Some((
+                    step,
+                    Expr::FunLit {
+                        param,
+                        body: bump.alloc(body),
+                    },
+                ))
+            }
+            Expr::FunApp { .. } => {
+                let (head, args) = spine(expr);
+                if let Some((step, head)) = self.step_at(head, binders) {
+                    return Some((step, self.apply(head, &args)));
+                }
+                let (step, args) = step_first(bump, &args, |arg| {
+                    let (step, expr) = self.step_at(arg.expr, binders)?;
+                    Some((step, FunArg::new(arg.plicity, &*bump.alloc(expr))))
+                })?;
+                Some((step, self.apply(*head, args)))
+            }
+            Expr::ListLit(elems) => {
+                let (step, elems) = step_first(bump, elems, |elem| self.step_at(elem, binders))?;
+                Some((step, Expr::ListLit(elems)))
+            }
+            Expr::RecordType(fields) => {
+                let mut index = 0;
+                let (step, fields) = step_first(bump, fields, |(label, r#type)| {
+                    let reduced = self.step_at(r#type, binders + index);
+                    index += 1;
+                    let (step, r#type) = reduced?;
+                    Some((step, (*label, r#type)))
+                })?;
+                Some((step, Expr::RecordType(fields)))
+            }
+            Expr::RecordLit(fields) => {
+                let (step, fields) = step_first(bump, fields, |(label, expr)| {
+                    let (step, expr) = self.step_at(expr, binders)?;
+                    Some((step, (*label, expr)))
+                })?;
+                Some((step, Expr::RecordLit(fields)))
+            }
+            Expr::RecordProj(scrut, label) => {
+                let (step, scrut) = self.step_at(scrut, binders)?;
+                Some((step, Expr::RecordProj(bump.alloc(scrut), label)))
+            }
+            Expr::MatchBool { cond, then, r#else } => {
+                let exprs = [*cond, *then, *r#else];
+                let (step, exprs) = step_first(bump, &exprs, |expr| self.step_at(expr, binders))?;
+                let [cond, then, r#else] = exprs else {
+                    unreachable!()
+                };
+                Some((step, Expr::MatchBool { cond, then, r#else }))
+            }
+            Expr::MatchInt {
+                scrut,
+                cases,
+                default,
+            } => {
+                if let Some((step, scrut)) = self.step_at(scrut, binders) {
+                    let scrut = bump.alloc(scrut);
+                    let expr = Expr::MatchInt {
+                        scrut,
+                        cases,
+                        default,
+                    };
+                    return Some((step, expr));
+                }
+                if let Some((step, cases)) = step_first(bump, cases, |(int, expr)| {
+                    let (step, expr) = self.step_at(expr, binders)?;
+                    Some((step, (*int, expr)))
+                }) {
+                    let expr = Expr::MatchInt {
+                        scrut,
+                        cases,
+                        default,
+                    };
+                    return Some((step, expr));
+                }
+                let (step, default) = self.step_at(default, binders)?;
+                let default = bump.alloc(default);
+                let expr = Expr::MatchInt {
+                    scrut,
+                    cases,
+                    default,
+                };
+                Some((step, expr))
+            }
+        }
+    }
+
+    /// Apply `head` to `args`.
+    fn apply(&self, head: Expr<'core>, args: &[FunArg<&'core Expr<'core>>]) -> Expr<'core> {
+        args.iter().fold(head, |fun, arg| Expr::FunApp {
+            fun: self.bump.alloc(fun),
+            arg: *arg,
+        })
+    }
+}
+
+/// The head of an application, and its arguments in order.
+fn spine<'a, 'core>(
+    mut expr: &'a Expr<'core>,
+) -> (&'a Expr<'core>, Vec<FunArg<&'core Expr<'core>>>) {
+    let mut args = Vec::new();
+    while let Expr::FunApp { fun, arg } = expr {
+        args.push(*arg);
+        expr = fun;
+    }
+    args.reverse();
+    (expr, args)
+}
+
+/// Reduce the first of `items` that `step_item` can reduce, returning a copy
+/// of `items` with it replaced.
+fn step_first<'core, T: Copy>(
+    bump: &'core bumpalo::Bump,
+    items: &[T],
+    mut step_item: impl FnMut(&T) -> Option<(Step, T)>,
+) -> Reduced<&'core [T]> {
+    items.iter().enumerate().find_map(|(index, item)| {
+        let (step, item) = step_item(item)?;
+        let items = bump.alloc_slice_copy(items);
+        items[index] = item;
+        Some((step, &*items))
+    })
+}
+
+/// Whether `expr` is nested more than `max_depth` levels deep. Descends at
+/// most `max_depth` levels.
+pub fn exceeds_depth(expr: &Expr, max_depth: u32) -> bool {
+    let Some(max_depth) = max_depth.checked_sub(1) else {
+        return true;
+    };
+    let exceeds = |expr: &Expr| grow_stack(|| exceeds_depth(expr, max_depth));
+    match expr {
+        Expr::Error | Expr::Lit(_) | Expr::Prim(_) | Expr::LocalVar(_) | Expr::MetaVar(_) => false,
+        Expr::Let { binding, body } => {
+            exceeds(binding.r#type) || exceeds(binding.rhs) || exceeds(body)
+        }
+        Expr::FunType { param, body } | Expr::FunLit { param, body } => {
+            exceeds(param.r#type) || exceeds(body)
+        }
+        Expr::FunApp { fun, arg } => exceeds(fun) || exceeds(arg.expr),
+        Expr::ListLit(elems) => elems.iter().any(exceeds),
+        Expr::RecordType(fields) | Expr::RecordLit(fields) => {
+            fields.iter().any(|(_, expr)| exceeds(expr))
+        }
+        Expr::RecordProj(scrut, _) => exceeds(scrut),
+        Expr::MatchBool { cond, then, r#else } => exceeds(cond) || exceeds(then) || exceeds(r#else),
+        Expr::MatchInt {
+            scrut,
+            cases,
+            default,
+        } => exceeds(scrut) || cases.iter().any(|(_, expr)| exceeds(expr)) || exceeds(default),
+    }
+}
@@ -1,3 +1,5 @@
+use std::cell::Cell;
+
 use ecow::EcoVec;
 use either::Either::{self, Left, Right};
 use pion_symbol::Symbol;
@@ -157,14 +159,182 @@ impl<'core> Telescope<'core> {
 
 pub type LocalValues<'core> = SharedEnv<Value<'core>>;
 pub type MetaValues<'core> = SliceEnv<Option<Value<'core>>>;
+pub type MetaExprs<'core> = SliceEnv<Option<MetaExpr<'core>>>;
+
+/// An expression that a solved metavariable should be zonked to where it was
+/// inserted, instead of quoting its value. Unlike its value, the expression
+/// may refer to let-bound locals, like the instance that solved an instance
+/// argument.
+#[derive(Debug, Clone)]
+pub struct MetaExpr<'core> {
+    /// The number of locals in scope where the metavariable was inserted.
+    pub local_len: EnvLen,
+    /// The metavariable applied to the local variables, as it was inserted.
+    pub occurrence: Expr<'core>,
+    pub expr: Expr<'core>,
+}
 
 #[derive(Debug, Copy, Clone)]
-pub struct EvalOpts {
+pub struct EvalOpts<'fuel> {
     pub unfold_fix: bool,
+    /// The resources evaluation may use. Evaluation is unlimited if this is
+    /// `None`.
+    pub fuel: Option<&'fuel Fuel>,
+}
+
+impl Default for EvalOpts<'_> {
+    fn default() -> Self {
+        Self {
+            unfold_fix: true,
+            fuel: None,
+        }
+    }
+}
+
+/// Limits on the work done by evaluation, so that diverging or very
+/// expensive computations fail instead of hanging or exhausting memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EvalLimits {
+    /// The maximum number of evaluation steps.
+    pub max_steps: u64,
+    /// The maximum nesting depth of evaluation. The native stack is grown on
+    /// demand (see [`grow_stack`]), so this bounds the memory used by deep
+    /// recursion rather than the size of the stack.
+    pub max_depth: u32,
+    /// The maximum number of bytes allocated in the arena.
+    pub max_arena_bytes: usize,
+}
+
+/// Run `f`, which recurses on the native stack, on a freshly allocated stack
+/// segment if the current one is nearly exhausted.
+pub fn grow_stack<R>(f: impl FnOnce() -> R) -> R {
+    // Debug builds use kilobytes of stack per level of recursion, so leave
+    // room for helpers like `Expr::shift` that recurse without growing it.
+    const RED_ZONE: usize = 1024 * 1024;
+    const SEGMENT_SIZE: usize = 8 * 1024 * 1024;
+    stacker::maybe_grow(RED_ZONE, SEGMENT_SIZE, f)
+}
+
+impl Default for EvalLimits {
+    fn default() -> Self {
+        Self {
+            max_steps: 10_000_000,
+            max_depth: 100_000,
+            max_arena_bytes: 1 << 30,
+        }
+    }
+}
+
+/// A limit that evaluation has exceeded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Steps(u64),
+    Depth(u32),
+    ArenaBytes(usize),
 }
 
-impl Default for EvalOpts {
-    fn default() -> Self { Self { unfold_fix: true } }
+/// The resources used by evaluation since the last [`Fuel::reset`], shared by
+/// every environment it is passed to in [`EvalOpts`].
+///
+/// Once a limit has been exceeded, evaluation returns [`Value::Error`]
+/// immediately until the fuel is reset.
+#[derive(Debug)]
+pub struct Fuel {
+    limits: EvalLimits,
+    steps: Cell<u64>,
+    depth: Cell<u32>,
+    arena_start: Cell<usize>,
+    exceeded: Cell<Option<LimitExceeded>>,
+    reported: Cell<bool>,
+}
+
+impl Fuel {
+    /// How many steps are taken between checks of the arena size.
+    const ARENA_CHECK_INTERVAL: u64 = 1024;
+
+    pub const fn new(limits: EvalLimits) -> Self {
+        Self {
+            limits,
+            steps: Cell::new(0),
+            depth: Cell::new(0),
+            arena_start: Cell::new(0),
+            exceeded: Cell::new(None),
+            reported: Cell::new(false),
+        }
+    }
+
+    pub const fn limits(&self) -> EvalLimits { self.limits }
+
+    /// Refill the fuel, counting allocations in `bump` from now on.
+    pub fn reset(&self, bump: &bumpalo::Bump) {
+        self.steps.set(0);
+        self.depth.set(0);
+        self.arena_start.set(bump.allocated_bytes());
+        self.exceeded.set(None);
+        self.reported.set(false);
+    }
+
+    /// The limit that has been exceeded since the last reset, if any.
+    pub fn exceeded(&self) -> Option<LimitExceeded> { self.exceeded.get() }
+
+    /// The limit that has been exceeded since the last reset, if it has not
+    /// already been returned by this method.
+    pub fn take_unreported(&self) -> Option<LimitExceeded> {
+        let exceeded = self.exceeded.get()?;
+        match self.reported.replace(true) {
+            true => None,
+            false => Some(exceeded),
+        }
+    }
+
+    /// Record that evaluation by another backend, such as a virtual machine,
+    /// exceeded `limit`.
+    pub fn set_exceeded(&self, limit: LimitExceeded) { self.exceeded.set(Some(limit)); }
+
+    /// Use up `steps` steps, returning whether evaluation may continue.
+    pub fn consume(&self, bump: &bumpalo::Bump, steps: u64) -> bool {
+        if self.exceeded.get().is_some() {
+            return false;
+        }
+
+        let before = self.steps.get();
+        let after = before.saturating_add(steps);
+        self.steps.set(after);
+        if after > self.limits.max_steps {
+            return self.exceed(LimitExceeded::Steps(self.limits.max_steps));
+        }
+
+        if before / Self::ARENA_CHECK_INTERVAL != after / Self::ARENA_CHECK_INTERVAL {
+            let used = bump
+                .allocated_bytes()
+                .saturating_sub(self.arena_start.get());
+            if used > self.limits.max_arena_bytes {
+                return self.exceed(LimitExceeded::ArenaBytes(self.limits.max_arena_bytes));
+            }
+        }
+        true
+    }
+
+    /// Take a step one level deeper, returning whether evaluation may
+    /// continue. If it may, [`Self::exit`] must be called afterwards.
+    fn enter(&self, bump: &bumpalo::Bump) -> bool {
+        if !self.consume(bump, 1) {
+            return false;
+        }
+        let depth = self.depth.get() + 1;
+        if depth > self.limits.max_depth {
+            return self.exceed(LimitExceeded::Depth(self.limits.max_depth));
+        }
+        self.depth.set(depth);
+        true
+    }
+
+    fn exit(&self) { self.depth.set(self.depth.get() - 1); }
+
+    fn exceed(&self, limit: LimitExceeded) -> bool {
+        self.exceeded.set(Some(limit));
+        false
+    }
 }
 
 pub fn normalize<'core>(
@@ -179,14 +349,14 @@ pub fn normalize<'core>(
 
 pub struct ElimEnv<'core, 'env> {
     bump: &'core bumpalo::Bump,
-    opts: EvalOpts,
+    opts: EvalOpts<'env>,
     meta_values: &'env MetaValues<'core>,
 }
 
 impl<'core, 'env> ElimEnv<'core, 'env> {
     pub const fn new(
         bump: &'core bumpalo::Bump,
-        opts: EvalOpts,
+        opts: EvalOpts<'env>,
         meta_values: &'env MetaValues<'core>,
     ) -> Self {
         Self {
@@ -200,6 +370,13 @@ impl<'core, 'env> ElimEnv<'core, 'env> {
         EvalEnv::new(self.bump, self.opts, local_values, self.meta_values)
     }
 
+    /// Use up `steps` steps of fuel, returning whether evaluation may
+    /// continue.
+    fn consume(&self, steps: usize) -> bool {
+        let steps = u64::try_from(steps).unwrap_or(u64::MAX);
+        (self.opts.fuel).is_none_or(|fuel| fuel.consume(self.bump, steps))
+    }
+
     pub fn fun_app(&self, fun: Value<'core>, arg: FunArg<Value<'core>>) -> Value<'core> {
         match fun {
             Value::Error => Value::Error,
@@ -326,6 +503,7 @@ fn prim_app2<'core>(
         Prim::append => append,
 
         Prim::bool_rec => bool_rec,
+        Prim::subst => subst,
 
         Prim::add => add,
         Prim::sub => sub,
@@ -416,7 +594,7 @@ fn prim_app2<'core>(
         Ok(Value::List(list))
     }
 
-    fn append<'core>(_: &ElimEnv<'core, '_>, mut spine: Spine<'core>) -> PrimAppResult<'core> {
+    fn append<'core>(env: &ElimEnv<'core, '_>, mut spine: Spine<'core>) -> PrimAppResult<'core> {
         match spine.as_ref() {
             args![_, Value::List(_lhs), Value::List(_rhs)] => {}
             _ => return Err(spine),
@@ -436,6 +614,11 @@ fn prim_app2<'core>(
             unreachable!()
         };
 
+        // Appending copies every element of `rhs`, so charge for each of them
+        if !env.consume(rhs.len()) {
+            return Ok(Value::Error);
+        }
+
         lhs.extend_from_slice(rhs.as_ref());
         Ok(Value::List(lhs))
     }
@@ -448,6 +631,14 @@ fn prim_app2<'core>(
         }
     }
 
+    // subst @A @p @a @b (refl @A a) px = px
+    fn subst<'core>(_: &ElimEnv<'core, '_>, spine: Spine<'core>) -> PrimAppResult<'core> {
+        match spine.as_ref() {
+            args![_, _, _, _, Value::Neutral(Head::Prim(Prim::refl), _), px] => Ok(px.clone()),
+            _ => Err(spine),
+        }
+    }
+
     fn add<'core>(_: &ElimEnv<'core, '_>, spine: Spine<'core>) -> PrimAppResult<'core> {
         match spine.as_ref() {
             args![Value::Lit(Lit::Int(lhs)), Value::Lit(Lit::Int(rhs))] => {
@@ -532,7 +723,7 @@ fn prim_app2<'core>(
 
 pub struct EvalEnv<'core, 'env> {
     bump: &'core bumpalo::Bump,
-    opts: EvalOpts,
+    opts: EvalOpts<'env>,
     local_values: &'env mut LocalValues<'core>,
     meta_values: &'env MetaValues<'core>,
 }
@@ -540,7 +731,7 @@ pub struct EvalEnv<'core, 'env> {
 impl<'core, 'env> EvalEnv<'core, 'env> {
     pub fn new(
         bump: &'core bumpalo::Bump,
-        opts: EvalOpts,
+        opts: EvalOpts<'env>,
         local_values: &'env mut LocalValues<'core>,
         meta_values: &'env MetaValues<'core>,
     ) -> Self {
@@ -562,6 +753,18 @@ impl<'core, 'env> EvalEnv<'core, 'env> {
     }
 
     pub fn eval(&mut self, expr: &Expr<'core>) -> Value<'core> {
+        let Some(fuel) = self.opts.fuel else {
+            return grow_stack(|| self.eval_unlimited(expr));
+        };
+        if !fuel.enter(self.bump) {
+            return Value::Error;
+        }
+        let value = grow_stack(|| self.eval_unlimited(expr));
+        fuel.exit();
+        value
+    }
+
+    fn eval_unlimited(&mut self, expr: &Expr<'core>) -> Value<'core> {
         match expr {
             Expr::Error => Value::Error,
             Expr::Lit(lit) => Value::Lit(*lit),
@@ -661,12 +864,18 @@ impl<'core, 'env> QuoteEnv<'core, 'env> {
     }
 
     const fn elim_env(&self) -> ElimEnv<'core, 'env> {
-        let opts = EvalOpts { unfold_fix: false };
+        let opts = EvalOpts {
+            unfold_fix: false,
+            fuel: None,
+        };
         ElimEnv::new(self.bump, opts, self.meta_values)
     }
 
     fn eval_env(&self, local_values: &'env mut LocalValues<'core>) -> EvalEnv<'core, 'env> {
-        let opts = EvalOpts { unfold_fix: false };
+        let opts = EvalOpts {
+            unfold_fix: false,
+            fuel: None,
+        };
         EvalEnv::new(self.bump, opts, local_values, self.meta_values)
     }
 
@@ -679,6 +888,10 @@ impl<'core, 'env> QuoteEnv<'core, 'env> {
     }
 
     pub fn quote(&mut self, value: &Value<'core>) -> Expr<'core> {
+        grow_stack(|| self.quote_value(value))
+    }
+
+    fn quote_value(&mut self, value: &Value<'core>) -> Expr<'core> {
         let value = self.elim_env().update_metas(value);
         match value {
             Value::Error => Expr::Error,
@@ -809,6 +1022,7 @@ pub struct ZonkEnv<'core, 'env> {
     bump: &'core bumpalo::Bump,
     local_values: &'env mut LocalValues<'core>,
     meta_values: &'env MetaValues<'core>,
+    meta_exprs: &'env MetaExprs<'core>,
 }
 
 impl<'core, 'env> ZonkEnv<'core, 'env> {
@@ -821,9 +1035,18 @@ impl<'core, 'env> ZonkEnv<'core, 'env> {
             bump,
             local_values,
             meta_values,
+            meta_exprs: <&MetaExprs>::from(&[][..]),
         }
     }
 
+    /// Zonk the occurrences of metavariables that have a [`MetaExpr`] to
+    /// their expressions.
+    #[must_use]
+    pub const fn with_meta_exprs(mut self, meta_exprs: &'env MetaExprs<'core>) -> Self {
+        self.meta_exprs = meta_exprs;
+        self
+    }
+
     fn elim_env(&self) -> ElimEnv<'core, 'env> {
         ElimEnv::new(self.bump, EvalOpts::default(), self.meta_values)
     }
@@ -832,6 +1055,22 @@ impl<'core, 'env> ZonkEnv<'core, 'env> {
         QuoteEnv::new(self.bump, self.local_values.len(), self.meta_values)
     }
 
+    /// The [`MetaExpr`] of `expr`, if it is an occurrence of a metavariable
+    /// that has one.
+    fn meta_expr(&self, expr: &Expr<'core>) -> Option<Expr<'core>> {
+        let mut head = expr;
+        while let Expr::FunApp { fun, .. } = head {
+            head = fun;
+        }
+        let Expr::MetaVar(var) = head else {
+            return None;
+        };
+        let meta_expr = self.meta_exprs.get_absolute(*var)?.as_ref()?;
+        (meta_expr.local_len == self.local_values.len()
+            && same_occurrence(expr, &meta_expr.occurrence))
+        .then_some(meta_expr.expr)
+    }
+
     pub fn zonk(&mut self, expr: &Expr<'core>) -> Expr<'core> {
         match expr {
             Expr::Error => Expr::Error,
@@ -870,12 +1109,15 @@ impl<'core, 'env> ZonkEnv<'core, 'env> {
             | Expr::FunApp { .. }
             | Expr::MatchBool { .. }
             | Expr::MatchInt { .. }
-            | Expr::RecordProj(..) => match self.zonk_meta_var_spines(expr) {
-                Left(expr) => expr,
-                Right(value) => {
-                    let expr = self.quote_env().quote(&value);
-                    self.zonk(&expr)
-                }
+            | Expr::RecordProj(..) => match self.meta_expr(expr) {
+                Some(expr) => self.zonk(&expr),
+                None => match self.zonk_meta_var_spines(expr) {
+                    Left(expr) => expr,
+                    Right(value) => {
+                        let expr = self.quote_env().quote(&value);
+                        self.zonk(&expr)
+                    }
+                },
             },
             Expr::RecordType(type_fields) => {
                 let local_len = self.local_values.len();
@@ -992,3 +1234,25 @@ impl<'core, 'env> ZonkEnv<'core, 'env> {
         }
     }
 }
+
+/// Whether `expr` and `occurrence` apply the same metavariable to the same
+/// local variables.
+fn same_occurrence(expr: &Expr, occurrence: &Expr) -> bool {
+    match (expr, occurrence) {
+        (Expr::MetaVar(var1), Expr::MetaVar(var2)) => var1 == var2,
+        (
+            Expr::FunApp {
+                fun: fun1,
+                arg: arg1,
+            },
+            Expr::FunApp {
+                fun: fun2,
+                arg: arg2,
+            },
+        ) => {
+            matches!((arg1.expr, arg2.expr), (Expr::LocalVar(var1), Expr::LocalVar(var2)) if var1 == var2)
+                && same_occurrence(fun1, fun2)
+        }
+        _ => false,
+    }
+}
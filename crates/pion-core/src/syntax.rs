@@ -107,6 +107,44 @@ impl<'core> Expr<'core> {
         }
     }
 
+    pub fn references_meta_vars(&self) -> bool {
+        match self {
+            Expr::MetaVar(..) => true,
+            Expr::Error | Expr::Lit(..) | Expr::Prim(..) | Expr::LocalVar(..) => false,
+            Expr::Let { binding, body, .. } => {
+                binding.r#type.references_meta_vars()
+                    || binding.rhs.references_meta_vars()
+                    || body.references_meta_vars()
+            }
+            Expr::FunType { param, body } | Expr::FunLit { param, body } => {
+                param.r#type.references_meta_vars() || body.references_meta_vars()
+            }
+            Expr::FunApp { fun, arg } => {
+                fun.references_meta_vars() || arg.expr.references_meta_vars()
+            }
+            Expr::RecordType(fields) | Expr::RecordLit(fields) => {
+                fields.iter().any(|(_, expr)| expr.references_meta_vars())
+            }
+            Expr::RecordProj(scrut, _) => scrut.references_meta_vars(),
+            Expr::ListLit(elems) => elems.iter().any(Expr::references_meta_vars),
+
+            Expr::MatchBool { cond, then, r#else } => {
+                cond.references_meta_vars()
+                    || then.references_meta_vars()
+                    || r#else.references_meta_vars()
+            }
+            Expr::MatchInt {
+                scrut,
+                cases,
+                default,
+            } => {
+                scrut.references_meta_vars()
+                    || cases.iter().any(|(_, expr)| expr.references_meta_vars())
+                    || default.references_meta_vars()
+            }
+        }
+    }
+
     pub fn shift(&self, bump: &'core bumpalo::Bump, amount: EnvLen) -> Self {
         return recur(self, bump, RelativeVar::default(), amount);
 
@@ -242,6 +280,15 @@ impl<'core> Expr<'core> {
     }
 }
 
+/// A top-level item of an elaborated file.
+#[derive(Debug, Copy, Clone)]
+pub enum Item<'core> {
+    Let(LetBinding<Expr<'core>, Expr<'core>>),
+    /// An expression whose value is displayed: the argument of an `#eval`
+    /// command, or the result expression of the file.
+    Eval(Expr<'core>),
+}
+
 pub type RecordFields<'core, Field> = &'core [(Symbol, Field)];
 
 pub fn record_keys_equal<L, R>(lhs: RecordFields<L>, rhs: RecordFields<R>) -> bool {
@@ -252,15 +299,18 @@ pub fn record_keys_equal<L, R>(lhs: RecordFields<L>, rhs: RecordFields<R>) -> bo
 pub enum Plicity {
     Implicit,
     Explicit,
+    Instance,
 }
 
 impl Plicity {
     pub const fn is_implicit(&self) -> bool { matches!(self, Self::Implicit) }
     pub const fn is_explicit(&self) -> bool { matches!(self, Self::Explicit) }
+    pub const fn is_instance(&self) -> bool { matches!(self, Self::Instance) }
     pub const fn description(&self) -> &'static str {
         match self {
             Self::Implicit => "implicit",
             Self::Explicit => "explicit",
+            Self::Instance => "instance",
         }
     }
 }
@@ -270,6 +320,7 @@ impl From<pion_surface::syntax::Plicity> for Plicity {
         match value {
             pion_surface::syntax::Plicity::Implicit => Self::Implicit,
             pion_surface::syntax::Plicity::Explicit => Self::Explicit,
+            pion_surface::syntax::Plicity::Instance => Self::Instance,
         }
     }
 }
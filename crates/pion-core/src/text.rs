@@ -0,0 +1,567 @@
+//! A textual format for core expressions.
+//!
+//! Unlike the output of the [unelaborator](crate::unelab), which is surface
+//! syntax meant for people, this format is a direct transcription of
+//! [`Expr`]: [`parse_expr`] and [`print_expr`] round-trip exactly, so it can
+//! be used to write tests of core-level passes (evaluation, optimization)
+//! without going through elaboration, and to dump the elaborator's output.
+//!
+//! The format is a kind of S-expression:
+//!
+//! ```text
+//! expr  ::= "#error"
+//!         | int | "true" | "false"
+//!         | prim                                  eg `Int`, `add`, `fix`
+//!         | "$" int                               local variable (de Bruijn index)
+//!         | "?" int                               metavariable (de Bruijn level)
+//!         | "(" "let" name expr expr expr ")"     type, right hand side, body
+//!         | "(" "forall" param expr ")"
+//!         | "(" "fun" param expr ")"
+//!         | "(" expr arg+ ")"                     application
+//!         | "[" expr* "]"                         list literal
+//!         | "(" "record-type" field* ")"
+//!         | "(" "record" field* ")"
+//!         | "(" "proj" expr label ")"
+//!         | "(" "if" expr expr expr ")"           match on a `Bool`
+//!         | "(" "match-int" expr case* "(" "_" expr ")" ")"
+//! param ::= "(" plicity name expr ")"
+//! arg   ::= plicity expr
+//! plicity ::= "" | "@" | "@@"
+//! field ::= "(" label expr ")"
+//! case  ::= "(" int expr ")"
+//! name  ::= label | "_"
+//! item  ::= "(" "def" name expr expr ")" | "(" "eval" expr ")"
+//! ```
+//!
+//! `@` marks implicit parameters and arguments, and `@@` instance ones. `(f a
+//! b)` is `((f a) b)`. Labels are any other sequence of characters except
+//! whitespace, `(`, `)`, `[`, `]` and `@`. Line comments start with `//`.
+
+use std::fmt;
+
+use pion_printer::{BumpDocAllocator, DocAllocator as _, DocBuilder, INDENT};
+use pion_symbol::Symbol;
+use pion_util::collect_in::CollectIn;
+
+use crate::env::{AbsoluteVar, RelativeVar};
+use crate::prim::Prim;
+use crate::syntax::{Expr, FunArg, FunParam, Item, LetBinding, Lit, Plicity};
+
+/// The width that [`print_expr`] and [`print_items`] try to fit lines into.
+const WIDTH: usize = 80;
+
+pub fn print_expr(bump: &bumpalo::Bump, expr: &Expr) -> String {
+    let printer = Printer::new(bump);
+    printer.expr(expr).pretty(WIDTH).to_string()
+}
+
+/// Print `items`, one per line.
+pub fn print_items(bump: &bumpalo::Bump, items: &[Item]) -> String {
+    let printer = Printer::new(bump);
+    let mut text = String::new();
+    for item in items {
+        text.push_str(&printer.item(item).pretty(WIDTH).to_string());
+        text.push('\n');
+    }
+    text
+}
+
+pub fn parse_expr<'core>(
+    bump: &'core bumpalo::Bump,
+    text: &str,
+) -> Result<Expr<'core>, ParseError> {
+    let mut parser = Parser::new(bump, text);
+    let expr = parser.expr()?;
+    parser.expect_end()?;
+    Ok(expr)
+}
+
+pub fn parse_items<'core>(
+    bump: &'core bumpalo::Bump,
+    text: &str,
+) -> Result<Vec<Item<'core>>, ParseError> {
+    let mut parser = Parser::new(bump, text);
+    let mut items = Vec::new();
+    while parser.peek().is_some() {
+        items.push(parser.item()?);
+    }
+    Ok(items)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset of the error in the text.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+struct Printer<'bump> {
+    alloc: BumpDocAllocator<'bump>,
+}
+
+impl<'bump> Printer<'bump> {
+    fn new(bump: &'bump bumpalo::Bump) -> Self {
+        Self {
+            alloc: BumpDocAllocator::new(bump),
+        }
+    }
+
+    fn item(&'bump self, item: &Item) -> DocBuilder<'bump> {
+        match item {
+            Item::Let(binding) => self.list(
+                self.alloc.text("def ").append(self.name(binding.name)),
+                [self.expr(&binding.r#type), self.expr(&binding.rhs)],
+            ),
+            Item::Eval(expr) => self.list("eval", [self.expr(expr)]),
+        }
+    }
+
+    fn expr(&'bump self, expr: &Expr) -> DocBuilder<'bump> {
+        match expr {
+            Expr::Error => self.alloc.text("#error"),
+            Expr::Lit(Lit::Int(int)) => self.alloc.text(int.to_string()),
+            Expr::Lit(Lit::Bool(true)) => self.alloc.text("true"),
+            Expr::Lit(Lit::Bool(false)) => self.alloc.text("false"),
+            Expr::Prim(prim) => self.alloc.text(prim.name()),
+            Expr::LocalVar(var) => self.alloc.text(format!("${}", usize::from(*var))),
+            Expr::MetaVar(var) => self.alloc.text(format!("?{}", usize::from(*var))),
+            Expr::Let { binding, body } => self.list(
+                self.alloc.text("let ").append(self.name(binding.name)),
+                [
+                    self.expr(binding.r#type),
+                    self.expr(binding.rhs),
+                    self.expr(body),
+                ],
+            ),
+            Expr::FunType { param, body } => self.list(
+                self.alloc.text("forall ").append(self.param(param)),
+                [self.expr(body)],
+            ),
+            Expr::FunLit { param, body } => self.list(
+                self.alloc.text("fun ").append(self.param(param)),
+                [self.expr(body)],
+            ),
+            Expr::FunApp { .. } => {
+                let mut args = Vec::new();
+                let mut head = expr;
+                while let Expr::FunApp { fun, arg } = head {
+                    args.push(self.arg(arg));
+                    head = fun;
+                }
+                args.reverse();
+                self.list(self.expr(head), args)
+            }
+            Expr::ListLit(elems) => {
+                let elems = elems.iter().map(|elem| self.expr(elem));
+                let elems = self.alloc.intersperse(elems, self.alloc.line());
+                (self.alloc.text("["))
+                    .append(elems.nest(INDENT))
+                    .append("]")
+                    .group()
+            }
+            Expr::RecordType(fields) => self.list("record-type", self.fields(fields)),
+            Expr::RecordLit(fields) => self.list("record", self.fields(fields)),
+            Expr::RecordProj(scrut, label) => {
+                self.list("proj", [self.expr(scrut), self.alloc.text(label.as_str())])
+            }
+            Expr::MatchBool { cond, then, r#else } => {
+                self.list("if", [self.expr(cond), self.expr(then), self.expr(r#else)])
+            }
+            Expr::MatchInt {
+                scrut,
+                cases,
+                default,
+            } => {
+                let cases = cases.iter().map(|(int, expr)| {
+                    self.list(self.alloc.text(int.to_string()), [self.expr(expr)])
+                });
+                let default = self.list("_", [self.expr(default)]);
+                let args = std::iter::once(self.expr(scrut))
+                    .chain(cases)
+                    .chain([default]);
+                self.list("match-int", args)
+            }
+        }
+    }
+
+    fn param(&'bump self, param: &FunParam<&Expr>) -> DocBuilder<'bump> {
+        let name = self.plicity(param.plicity).append(self.name(param.name));
+        self.list(name, [self.expr(param.r#type)])
+    }
+
+    fn arg(&'bump self, arg: &FunArg<&Expr>) -> DocBuilder<'bump> {
+        self.plicity(arg.plicity).append(self.expr(arg.expr))
+    }
+
+    fn fields(&'bump self, fields: &[(Symbol, Expr)]) -> Vec<DocBuilder<'bump>> {
+        (fields.iter())
+            .map(|(label, expr)| self.list(self.alloc.text(label.as_str()), [self.expr(expr)]))
+            .collect()
+    }
+
+    fn plicity(&'bump self, plicity: Plicity) -> DocBuilder<'bump> {
+        match plicity {
+            Plicity::Implicit => self.alloc.text("@"),
+            Plicity::Instance => self.alloc.text("@@"),
+            Plicity::Explicit => self.alloc.nil(),
+        }
+    }
+
+    fn name(&'bump self, name: Option<Symbol>) -> DocBuilder<'bump> {
+        match name {
+            None => self.alloc.text("_"),
+            Some(name) => self.alloc.text(name.as_str()),
+        }
+    }
+
+    /// `(head arg ...)`, with each argument on its own line if it does not
+    /// fit on one.
+    fn list(
+        &'bump self,
+        head: impl pretty::Pretty<'bump, BumpDocAllocator<'bump>>,
+        args: impl IntoIterator<Item = DocBuilder<'bump>>,
+    ) -> DocBuilder<'bump> {
+        let args = args.into_iter().map(|arg| self.alloc.line().append(arg));
+        (self.alloc.text("("))
+            .append(head)
+            .append(self.alloc.concat(args).nest(INDENT))
+            .append(")")
+            .group()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Token<'text> {
+    LParen,
+    RParen,
+    LSquare,
+    RSquare,
+    At,
+    Atom(&'text str),
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LParen => write!(f, "`(`"),
+            Self::RParen => write!(f, "`)`"),
+            Self::LSquare => write!(f, "`[`"),
+            Self::RSquare => write!(f, "`]`"),
+            Self::At => write!(f, "`@`"),
+            Self::Atom(atom) => write!(f, "`{atom}`"),
+        }
+    }
+}
+
+struct Parser<'core, 'text> {
+    bump: &'core bumpalo::Bump,
+    text: &'text str,
+    offset: usize,
+}
+
+impl<'core, 'text> Parser<'core, 'text> {
+    const fn new(bump: &'core bumpalo::Bump, text: &'text str) -> Self {
+        Self {
+            bump,
+            text,
+            offset: 0,
+        }
+    }
+
+    /// Skip whitespace and comments, and return the next token and its end
+    /// offset, without consuming it.
+    fn peek(&mut self) -> Option<(Token<'text>, usize)> {
+        loop {
+            let rest = &self.text[self.offset..];
+            let trimmed = rest.trim_start();
+            self.offset += rest.len() - trimmed.len();
+            if !trimmed.starts_with("//") {
+                break;
+            }
+            self.offset += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+
+        let rest = &self.text[self.offset..];
+        let token = match rest.chars().next()? {
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LSquare,
+            ']' => Token::RSquare,
+            '@' => Token::At,
+            _ => {
+                let len = rest.find(is_delimiter).unwrap_or(rest.len());
+                return Some((Token::Atom(&rest[..len]), self.offset + len));
+            }
+        };
+        Some((token, self.offset + 1))
+    }
+
+    /// Skip whitespace and comments, and return the start offset of the next
+    /// token.
+    fn start(&mut self) -> usize {
+        self.peek();
+        self.offset
+    }
+
+    fn next(&mut self) -> Result<Token<'text>, ParseError> {
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some((token, end)) => {
+                self.offset = end;
+                Ok(token)
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        let start = self.start();
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(error_at(
+                start,
+                format!("expected {expected}, found {token}"),
+            )),
+        }
+    }
+
+    fn expect_end(&mut self) -> Result<(), ParseError> {
+        match self.peek() {
+            None => Ok(()),
+            Some((token, _)) => Err(self.error(format!("expected end of input, found {token}"))),
+        }
+    }
+
+    fn atom(&mut self) -> Result<&'text str, ParseError> {
+        let start = self.start();
+        match self.next()? {
+            Token::Atom(atom) => Ok(atom),
+            token => Err(error_at(start, format!("expected a name, found {token}"))),
+        }
+    }
+
+    fn name(&mut self) -> Result<Option<Symbol>, ParseError> {
+        match self.atom()? {
+            "_" => Ok(None),
+            name => Ok(Some(Symbol::intern(name))),
+        }
+    }
+
+    fn plicity(&mut self) -> Plicity {
+        match self.peek() {
+            Some((Token::At, end)) => {
+                self.offset = end;
+                match self.peek() {
+                    Some((Token::At, end)) => {
+                        self.offset = end;
+                        Plicity::Instance
+                    }
+                    _ => Plicity::Implicit,
+                }
+            }
+            _ => Plicity::Explicit,
+        }
+    }
+
+    fn item(&mut self) -> Result<Item<'core>, ParseError> {
+        self.expect(Token::LParen)?;
+        let start = self.start();
+        let item = match self.atom()? {
+            "def" => {
+                let name = self.name()?;
+                let r#type = self.expr()?;
+                let rhs = self.expr()?;
+                Item::Let(LetBinding::new(name, r#type, rhs))
+            }
+            "eval" => Item::Eval(self.expr()?),
+            atom => {
+                let message = format!("expected `def` or `eval`, found `{atom}`");
+                return Err(error_at(start, message));
+            }
+        };
+        self.expect(Token::RParen)?;
+        Ok(item)
+    }
+
+    fn expr(&mut self) -> Result<Expr<'core>, ParseError> {
+        let start = self.start();
+        match self.next()? {
+            Token::Atom(atom) => Self::atom_expr(start, atom),
+            Token::LSquare => {
+                let mut elems = Vec::new();
+                while !matches!(self.peek(), Some((Token::RSquare, _))) {
+                    elems.push(self.expr()?);
+                }
+                self.expect(Token::RSquare)?;
+                Ok(Expr::ListLit(self.bump.alloc_slice_copy(&elems)))
+            }
+            Token::LParen => {
+                let expr = self.list_expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            token => Err(error_at(
+                start,
+                format!("expected an expression, found {token}"),
+            )),
+        }
+    }
+
+    fn atom_expr(start: usize, atom: &str) -> Result<Expr<'core>, ParseError> {
+        let index = |digits: &str| {
+            (digits.parse::<usize>())
+                .map_err(|_| error_at(start, format!("invalid variable `{atom}`")))
+        };
+
+        match atom {
+            "#error" => Ok(Expr::Error),
+            "true" => Ok(Expr::TRUE),
+            "false" => Ok(Expr::FALSE),
+            _ if atom.starts_with(|c: char| c.is_ascii_digit()) => match atom.parse() {
+                Ok(int) => Ok(Expr::Lit(Lit::Int(int))),
+                Err(_) => Err(error_at(start, format!("invalid integer `{atom}`"))),
+            },
+            _ if atom.starts_with('$') => Ok(Expr::LocalVar(RelativeVar::new(index(&atom[1..])?))),
+            _ if atom.starts_with('?') => Ok(Expr::MetaVar(AbsoluteVar::from(index(&atom[1..])?))),
+            _ => match Prim::ALL.iter().find(|prim| prim.name() == atom) {
+                Some(prim) => Ok(Expr::Prim(*prim)),
+                None => Err(error_at(start, format!("unknown primitive `{atom}`"))),
+            },
+        }
+    }
+
+    /// The contents of a parenthesized expression.
+    fn list_expr(&mut self) -> Result<Expr<'core>, ParseError> {
+        let keyword = match self.peek() {
+            Some((Token::Atom(atom), end)) if is_keyword(atom) => {
+                self.offset = end;
+                atom
+            }
+            _ => return self.fun_app(),
+        };
+
+        match keyword {
+            "let" => {
+                let name = self.name()?;
+                let r#type = self.expr()?;
+                let rhs = self.expr()?;
+                let body = self.expr()?;
+                let (r#type, rhs, body) = self.bump.alloc((r#type, rhs, body));
+                Ok(Expr::Let {
+                    binding: LetBinding::new(name, &*r#type, &*rhs),
+                    body,
+                })
+            }
+            "forall" => {
+                let param = self.param()?;
+                let body = self.bump.alloc(self.expr()?);
+                Ok(Expr::FunType { param, body })
+            }
+            "fun" => {
+                let param = self.param()?;
+                let body = self.bump.alloc(self.expr()?);
+                Ok(Expr::FunLit { param, body })
+            }
+            "record-type" => Ok(Expr::RecordType(self.fields()?)),
+            "record" => Ok(Expr::RecordLit(self.fields()?)),
+            "proj" => {
+                let scrut = self.bump.alloc(self.expr()?);
+                let label = Symbol::intern(self.atom()?);
+                Ok(Expr::RecordProj(scrut, label))
+            }
+            "if" => {
+                let cond = self.expr()?;
+                let then = self.expr()?;
+                let r#else = self.expr()?;
+                let (cond, then, r#else) = self.bump.alloc((cond, then, r#else));
+                Ok(Expr::MatchBool { cond, then, r#else })
+            }
+            "match-int" => {
+                let scrut = self.expr()?;
+                let mut cases = Vec::new();
+                loop {
+                    self.expect(Token::LParen)?;
+                    let start = self.start();
+                    let pat = self.atom()?;
+                    let expr = self.expr()?;
+                    self.expect(Token::RParen)?;
+                    if pat == "_" {
+                        let cases = cases.into_iter().collect_in(self.bump);
+                        let (scrut, default) = self.bump.alloc((scrut, expr));
+                        return Ok(Expr::MatchInt {
+                            scrut,
+                            cases,
+                            default,
+                        });
+                    }
+                    match pat.parse() {
+                        Ok(int) => cases.push((int, expr)),
+                        Err(_) => return Err(error_at(start, format!("invalid integer `{pat}`"))),
+                    }
+                }
+            }
+            _ => unreachable!("not a keyword: {keyword}"),
+        }
+    }
+
+    fn fun_app(&mut self) -> Result<Expr<'core>, ParseError> {
+        let mut expr = self.expr()?;
+        let mut args = 0;
+        while !matches!(self.peek(), Some((Token::RParen, _)) | None) {
+            let plicity = self.plicity();
+            let (fun, arg) = self.bump.alloc((expr, self.expr()?));
+            expr = Expr::FunApp {
+                fun,
+                arg: FunArg::new(plicity, arg),
+            };
+            args += 1;
+        }
+        match args {
+            0 => Err(self.error("expected a function argument")),
+            _ => Ok(expr),
+        }
+    }
+
+    fn param(&mut self) -> Result<FunParam<&'core Expr<'core>>, ParseError> {
+        self.expect(Token::LParen)?;
+        let plicity = self.plicity();
+        let name = self.name()?;
+        let r#type = self.bump.alloc(self.expr()?);
+        self.expect(Token::RParen)?;
+        Ok(FunParam::new(plicity, name, r#type))
+    }
+
+    fn fields(&mut self) -> Result<&'core [(Symbol, Expr<'core>)], ParseError> {
+        let mut fields = Vec::new();
+        while !matches!(self.peek(), Some((Token::RParen, _)) | None) {
+            self.expect(Token::LParen)?;
+            let label = Symbol::intern(self.atom()?);
+            let expr = self.expr()?;
+            self.expect(Token::RParen)?;
+            fields.push((label, expr));
+        }
+        Ok(self.bump.alloc_slice_copy(&fields))
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        error_at(self.offset, message.into())
+    }
+}
+
+const fn error_at(offset: usize, message: String) -> ParseError { ParseError { offset, message } }
+
+fn is_delimiter(c: char) -> bool { c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | '@') }
+
+fn is_keyword(atom: &str) -> bool {
+    matches!(
+        atom,
+        "let" | "forall" | "fun" | "record-type" | "record" | "proj" | "if" | "match-int"
+    )
+}
@@ -93,7 +93,7 @@ impl<'bump> Unelaborator<'bump> {
         let pat = self.name(name);
         let r#type = self.expr_prec(names, r#type, Prec::MAX);
         let rhs = self.expr_prec(names, rhs, Prec::MAX);
-        self.alloc.let_stmt(false, pat, Some(r#type), rhs)
+        self.alloc.let_stmt(false, false, pat, Some(r#type), rhs)
     }
 
     pub fn expr_prec(
@@ -287,6 +287,16 @@ impl<'bump> Unelaborator<'bump> {
 impl<'bump> Unelaborator<'bump> {
     fn fun_arg(&'bump self, names: &mut NameEnv, arg: &FunArg<&Expr>) -> DocBuilder<'bump> {
         let expr = self.expr_prec(names, arg.expr, Prec::Atom);
+        // `@{x = e}` would be read back as an argument for the parameter `x`
+        let expr = match arg.expr {
+            Expr::RecordLit(fields)
+                if arg.plicity.is_implicit()
+                    && !Symbol::are_tuple_field_names(fields.iter().map(|(n, _)| *n)) =>
+            {
+                self.alloc.paren(expr)
+            }
+            _ => expr,
+        };
         self.alloc.fun_arg(arg.plicity, expr)
     }
 
@@ -326,6 +336,7 @@ impl<'a, D: DocAllocator<'a>> Pretty<'a, D> for Plicity {
     fn pretty(self, allocator: &'a D) -> pretty::DocBuilder<'a, D, ()> {
         match self {
             Self::Implicit => allocator.text("@"),
+            Self::Instance => allocator.text("@@"),
             Self::Explicit => allocator.nil(),
         }
     }
@@ -0,0 +1,64 @@
+//! Tests of the binary core format that do not need elaboration.
+
+use pion_core::binary::{decode_items, encode_items, VERSION};
+use pion_core::text::{parse_items, print_items};
+
+const ITEMS: &str = "\
+(def id (forall (@A Type) (forall (_ $0) $1)) (fun (@A Type) (fun (x $0) $0)))
+(def p (record-type (fst Int) (snd Bool)) (record (fst 4294967295) (snd true)))
+(eval (match-int (proj $0 fst) (0 [?0]) (1 []) (_ (if false #error [1 2]))))
+(eval (let x Int 1 (let _ Bool true $1)))
+";
+
+#[test]
+fn items_round_trip() {
+    let bump = bumpalo::Bump::new();
+    let items = parse_items(&bump, ITEMS).unwrap();
+    let bytes = encode_items(&items);
+    let decoded = decode_items(&bump, &bytes).unwrap();
+    assert_eq!(print_items(&bump, &decoded), ITEMS);
+}
+
+#[test]
+fn symbols_are_stored_once() {
+    let bump = bumpalo::Bump::new();
+    let items = parse_items(&bump, "(eval (record (xyzzy 1) (b (proj $0 xyzzy))))").unwrap();
+    let bytes = encode_items(&items);
+    let count = bytes.windows(5).filter(|window| window == b"xyzzy").count();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn decode_errors() {
+    let bump = bumpalo::Bump::new();
+    let items = parse_items(&bump, ITEMS).unwrap();
+    let bytes = encode_items(&items);
+    let error = |bytes: &[u8]| decode_items(&bump, bytes).unwrap_err().to_string();
+
+    assert_eq!(error(b"not core"), "not a pion core file at byte 0");
+
+    let mut other_version = bytes.clone();
+    other_version[8] = u8::try_from(VERSION + 1).unwrap();
+    assert_eq!(
+        error(&other_version),
+        format!(
+            "unsupported version {}, expected {VERSION} at byte 8",
+            VERSION + 1
+        )
+    );
+
+    let truncated = &bytes[..bytes.len() - 1];
+    assert!(error(truncated).starts_with("unexpected end of input"));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        error(&trailing),
+        format!("trailing bytes after last item at byte {}", bytes.len())
+    );
+
+    // Every strict prefix is rejected, rather than decoded or panicking.
+    for len in 0..bytes.len() {
+        assert!(decode_items(&bump, &bytes[..len]).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Golden tests of core-level passes, written in the textual core format.
+
+use pion_core::optimize::{optimize, OptLevel};
+use pion_core::semantics::{self, LocalValues, MetaValues};
+use pion_core::text::{parse_expr, parse_items, print_expr, ParseError};
+
+#[track_caller]
+fn round_trip(text: &str) {
+    let bump = bumpalo::Bump::new();
+    let expr = parse_expr(&bump, text).unwrap();
+    assert_eq!(print_expr(&bump, &expr), text);
+}
+
+#[track_caller]
+fn normalize(text: &str, expected: &str) {
+    let bump = bumpalo::Bump::new();
+    let expr = parse_expr(&bump, text).unwrap();
+    let metas = <&MetaValues>::from(&[][..]);
+    let expr = semantics::normalize(&bump, &mut LocalValues::default(), metas, &expr);
+    assert_eq!(print_expr(&bump, &expr), expected);
+}
+
+#[track_caller]
+fn optimized(level: OptLevel, text: &str, expected: &str) {
+    let bump = bumpalo::Bump::new();
+    let expr = parse_expr(&bump, text).unwrap();
+    let expr = optimize(&bump, level, &expr);
+    assert_eq!(print_expr(&bump, &expr), expected);
+}
+
+#[track_caller]
+fn parse_error(text: &str) -> ParseError {
+    let bump = bumpalo::Bump::new();
+    parse_expr(&bump, text).unwrap_err()
+}
+
+#[test]
+fn round_trips() {
+    round_trip("#error");
+    round_trip("(add 1 2)");
+    round_trip("(fun (@A Type) (fun (x $0) $0))");
+    round_trip("(forall (_ Int) (List Bool))");
+    round_trip("(let x Int 1 (let _ Bool true $1))");
+    round_trip("(?0 $1 @$0)");
+    round_trip("[1 2 3]");
+    round_trip("[]");
+    round_trip("(record-type (fst Int) (snd $0))");
+    round_trip("(record)");
+    round_trip("(proj (record (x 1) (y false)) y)");
+    round_trip("(if true 1 0)");
+    round_trip("(match-int 3 (0 false) (3 true) (_ false))");
+    round_trip("(match-int 3 (_ false))");
+    round_trip("(fix @Int @Int (fun (f (forall (_ Int) Int)) (fun (n Int) ($1 $0))))");
+}
+
+#[test]
+fn long_terms_are_broken_across_lines() {
+    round_trip(
+        "(fun (xs (List Int))
+    (let n
+        Int
+        (len @Int $0)
+        (match-int $0 (0 (append @Int $1 $1)) (1 (push @Int $1 100)) (_ $1))))",
+    );
+}
+
+#[test]
+fn comments_and_whitespace_are_ignored() {
+    let bump = bumpalo::Bump::new();
+    let expr = parse_expr(&bump, "// the answer\n( add\t40 // forty\n 2 )").unwrap();
+    assert_eq!(print_expr(&bump, &expr), "(add 40 2)");
+}
+
+#[test]
+fn items() {
+    let bump = bumpalo::Bump::new();
+    let text = "(def x Int 1)\n(eval (add $0 1))\n";
+    let items = parse_items(&bump, text).unwrap();
+    assert_eq!(pion_core::text::print_items(&bump, &items), text);
+}
+
+#[test]
+fn parse_errors() {
+    let error = parse_error("(add 1 two)");
+    assert_eq!(error.to_string(), "unknown primitive `two` at byte 7");
+
+    let error = parse_error("(add 1");
+    assert_eq!(error.to_string(), "unexpected end of input at byte 6");
+
+    let error = parse_error("(fun x $0)");
+    assert_eq!(error.to_string(), "expected `(`, found `x` at byte 5");
+
+    let error = parse_error("($0)");
+    assert_eq!(error.to_string(), "expected a function argument at byte 3");
+
+    let error = parse_error("1 2");
+    assert_eq!(
+        error.to_string(),
+        "expected end of input, found `2` at byte 2"
+    );
+
+    let error = parse_error("(match-int $0 (zero 1) (_ 2))");
+    assert_eq!(error.to_string(), "invalid integer `zero` at byte 15");
+}
+
+#[test]
+fn normalizes() {
+    normalize("((fun (x Int) (add $0 1)) 41)", "42");
+    normalize(
+        "(fun (x Int) (add $0 (mul 2 3)))",
+        "(fun (x Int) (add $0 6))",
+    );
+    normalize(
+        "(fun (b Bool) (if $0 1 (sub 3 1)))",
+        "(fun (b Bool) (if $0 1 2))",
+    );
+    normalize("(match-int (add 1 1) (0 false) (2 true) (_ false))", "true");
+    normalize("(match-int 7 (0 false) (_ true))", "true");
+    normalize("(proj (record (x 1) (y (len @Int [1 2]))) y)", "2");
+    normalize("(let x Int 5 (push @Int [1] $0))", "[1 5]");
+    normalize("(bool_rec @(fun (_ Bool) Type) false Int Bool)", "Bool");
+}
+
+#[test]
+fn optimizes() {
+    use OptLevel::{Inline, None, Simplify};
+
+    // Inlining enables constant folding.
+    optimized(Inline, "(let x Int 1 (add $0 $0))", "2");
+    optimized(
+        Simplify,
+        "(let x Int 1 (add $0 $0))",
+        "(let x Int 1 (add $0 $0))",
+    );
+    optimized(None, "((fun (x Int) $0) 1)", "((fun (x Int) $0) 1)");
+
+    // Beta reduction, then dead-let elimination.
+    optimized(Simplify, "((fun (x Int) 2) 1)", "2");
+
+    // Case-of-known-literal.
+    optimized(Simplify, "(fun (x Int) (if true $0 0))", "(fun (x Int) $0)");
+    optimized(
+        Simplify,
+        "(fun (x Int) (match-int 2 (1 0) (2 $0) (_ 1)))",
+        "(fun (x Int) $0)",
+    );
+
+    // Applications of unknown functions might not terminate, so are kept.
+    optimized(
+        Inline,
+        "(fun (f (forall (_ Int) Int)) (let x Int ($0 1) 2))",
+        "(fun (f (forall (_ Int) Int)) (let x Int ($0 1) 2))",
+    );
+
+    // Bindings used twice are not inlined, unless they are trivial.
+    optimized(
+        Inline,
+        "(fun (n Int) (let x Int (add $0 1) (mul $0 $0)))",
+        "(fun (n Int) (let x Int (add $0 1) (mul $0 $0)))",
+    );
+    optimized(
+        Inline,
+        "(fun (n Int) (let x Int $0 (mul $0 $0)))",
+        "(fun (n Int) (mul $0 $0))",
+    );
+}
@@ -0,0 +1,98 @@
+//! Saving the results of checking a file to disk, so that an unchanged file
+//! does not need to be elaborated again.
+//!
+//! Entries are keyed by a hash of the file's text and of the options it was
+//! checked with. Only files that checked without any diagnostics or
+//! assertions are saved, so an entry records everything that checking
+//! reported: the output of each command, and the elaborated items in the
+//! format of [`pion_core::binary`].
+//!
+//! The cache is best-effort: entries that cannot be read or were written by
+//! another version of pion are ignored, and failures to write entries are not
+//! reported.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+
+use pion_core::binary;
+use pion_core::syntax::Item as CoreItem;
+use pion_util::numeric_conversions::ZeroExtendFrom;
+
+/// A directory of saved check results.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+/// The saved result of checking a file.
+pub struct Entry<'db> {
+    pub outputs: Vec<String>,
+    pub items: Vec<CoreItem<'db>>,
+}
+
+impl Cache {
+    /// A cache stored in `dir`, which is created when the first entry is saved.
+    pub fn new(dir: impl Into<PathBuf>) -> Self { Self { dir: dir.into() } }
+
+    /// The key of the entry for checking `text` with `options`.
+    pub fn key(text: &str, options: &impl std::fmt::Debug) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        binary::VERSION.hash(&mut hasher);
+        format!("{options:?}").hash(&mut hasher);
+        text.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn path(&self, key: u64) -> PathBuf { self.dir.join(format!("{key:016x}.pionc")) }
+
+    pub fn load<'db>(&self, bump: &'db bumpalo::Bump, key: u64) -> Option<Entry<'db>> {
+        let bytes = std::fs::read(self.path(key)).ok()?;
+        let mut reader = Reader(&bytes);
+
+        let len = reader.len()?;
+        let mut outputs = Vec::new();
+        for _ in 0..len {
+            let len = reader.len()?;
+            outputs.push(String::from_utf8(reader.take(len)?.to_vec()).ok()?);
+        }
+        let items = binary::decode_items(bump, reader.0).ok()?;
+        Some(Entry { outputs, items })
+    }
+
+    pub fn store(&self, key: u64, entry: &Entry) {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&u64::zext_from(entry.outputs.len()).to_le_bytes());
+        for output in &entry.outputs {
+            bytes.extend_from_slice(&u64::zext_from(output.len()).to_le_bytes());
+            bytes.extend_from_slice(output.as_bytes());
+        }
+        bytes.extend_from_slice(&binary::encode_items(&entry.items));
+
+        // Write to a temporary file and rename it into place, so that another
+        // process never sees a partially written entry.
+        let path = self.path(key);
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        let _ = std::fs::create_dir_all(&self.dir)
+            .and_then(|()| std::fs::write(&temp_path, bytes))
+            .and_then(|()| std::fs::rename(&temp_path, &path))
+            .inspect_err(|_| {
+                let _ = std::fs::remove_file(&temp_path);
+            });
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (bytes, rest) = self.0.split_at_checked(len)?;
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        let bytes = self.take(8)?.try_into().ok()?;
+        usize::try_from(u64::from_le_bytes(bytes)).ok()
+    }
+}
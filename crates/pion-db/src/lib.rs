@@ -0,0 +1,569 @@
+//! Incremental, demand-driven checking of a single source file.
+//!
+//! A [`Database`] holds the source text of a file and answers queries about it
+//! (its concrete syntax tree, its surface syntax, and the result of checking
+//! it), memoizing their results so that an edit only recomputes what it
+//! affects:
+//! - the syntax trees are recomputed only when the text changes
+//! - each top-level statement is re-elaborated only when its own text changes,
+//!   or when a binding it refers to changes
+//!
+//! Metavariables are shared between top-level statements, so a statement can
+//! solve a metavariable left unsolved by an earlier one (for example, the type
+//! of the parameter of `let f = fun x => x;` is solved by `let y : Int = f
+//! 1;`). Results are only reused for statements elaborated when every
+//! metavariable so far has been solved, and that leave none unsolved: all
+//! statements from one that leaves a metavariable unsolved up to the one
+//! that solves it are re-elaborated whenever the file is checked.
+//!
+//! The results of checking a file can also be saved to a [`Cache`], so that
+//! checking the same text again later does not need to elaborate it at all.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+
+use pion_core::optimize::{optimize, OptLevel};
+use pion_core::semantics::EvalLimits;
+use pion_core::syntax::{Expr, Item as CoreItem, LetBinding};
+use pion_diagnostic::{Diagnostic, DiagnosticHandler, Suggestion};
+use pion_elab::command::{CommandHandler, EvalBackend};
+use pion_elab::env::MetaEnv;
+use pion_elab::{Elaborator, TopLevelItem};
+use pion_parser::cst;
+use pion_surface::syntax::{self as surface, File, Located};
+use pion_symbol::Symbol;
+
+mod cache;
+mod names;
+
+pub use cache::Cache;
+
+/// Something reported while checking a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Diagnostic(Diagnostic<usize>, Vec<Suggestion>),
+    /// The output of a command, eg `#check`.
+    Output(String),
+    /// Whether an assertion command, eg `#assert_eq`, passed.
+    Assertion(bool),
+}
+
+impl Event {
+    /// Pass the event on to the appropriate handler.
+    pub fn report(
+        self,
+        diagnostic_handler: &mut dyn DiagnosticHandler,
+        command_handler: &mut dyn CommandHandler,
+    ) {
+        match self {
+            Self::Diagnostic(diagnostic, suggestions) => {
+                diagnostic_handler.handle_diagnostic_with_suggestions(diagnostic, suggestions);
+            }
+            Self::Output(text) => command_handler.display_to_user(text),
+            Self::Assertion(passed) => command_handler.assertion_result(passed),
+        }
+    }
+
+    fn map_ranges(mut self, f: impl Fn(usize) -> usize) -> Self {
+        let map_range = |range: &mut Range<usize>| *range = f(range.start)..f(range.end);
+        if let Self::Diagnostic(diagnostic, suggestions) = &mut self {
+            for label in &mut diagnostic.labels {
+                map_range(&mut label.range);
+            }
+            for suggestion in suggestions {
+                map_range(&mut suggestion.range);
+            }
+        }
+        self
+    }
+}
+
+/// Records events reported by the elaborator.
+struct Recorder<'a>(&'a RefCell<Vec<Event>>);
+
+impl DiagnosticHandler for Recorder<'_> {
+    fn handle_diagnostic(&mut self, diagnostic: Diagnostic<usize>) {
+        self.handle_diagnostic_with_suggestions(diagnostic, Vec::new());
+    }
+
+    fn handle_diagnostic_with_suggestions(
+        &mut self,
+        diagnostic: Diagnostic<usize>,
+        suggestions: Vec<Suggestion>,
+    ) {
+        self.0
+            .borrow_mut()
+            .push(Event::Diagnostic(diagnostic, suggestions));
+    }
+}
+
+impl CommandHandler for Recorder<'_> {
+    fn display_to_user(&mut self, text: String) { self.0.borrow_mut().push(Event::Output(text)); }
+
+    fn assertion_result(&mut self, passed: bool) {
+        self.0.borrow_mut().push(Event::Assertion(passed));
+    }
+}
+
+/// How much work the last call to [`Database::check`] did.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CheckStats {
+    /// The number of top-level items that were elaborated.
+    pub elaborated: usize,
+    /// The number of top-level items whose results were reused from a
+    /// previous revision.
+    pub reused: usize,
+}
+
+type Fingerprint = u64;
+
+/// A top-level statement, or the result expression of the file.
+#[derive(Copy, Clone)]
+enum Item<'surface> {
+    Stmt(&'surface Located<surface::Stmt<'surface>>),
+    Expr(&'surface Located<surface::Expr<'surface>>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum ItemKind {
+    Stmt,
+    Expr,
+}
+
+/// Everything that the elaboration of a top-level item depends on. Two items
+/// with the same key elaborate to the same result.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ItemKey {
+    kind: ItemKind,
+    /// The source text of the item. Ranges in the item's result are relative
+    /// to the start of the item, so moving an item does not invalidate it.
+    text: String,
+    /// The index of the item among the items with the same range: a `let`
+    /// statement that could not be parsed is replaced by one binding for each
+    /// variable of its pattern, all with the range of the whole statement.
+    part: usize,
+    /// The number of bindings in scope.
+    scope_len: usize,
+    /// The number of metavariables created by earlier items, which
+    /// determines the names of the item's own metavariables.
+    meta_len: usize,
+    /// The names referred to by the item, and the level and fingerprint of
+    /// the binding each resolves to (if any).
+    deps: Vec<(Symbol, Option<(usize, Fingerprint)>)>,
+    /// The names in scope, if any name referred to by the item is unbound
+    /// (they are used to suggest corrections).
+    scope_names: Option<Vec<Option<Symbol>>>,
+    /// The level and fingerprint of each instance in scope, since instance
+    /// search can use them without the item referring to them.
+    instances: Vec<(usize, Fingerprint)>,
+}
+
+impl ItemKey {
+    fn new(
+        text: &str,
+        item: Item,
+        part: usize,
+        scope: &[(Option<Symbol>, Fingerprint)],
+        instances: &[(usize, Fingerprint)],
+        meta_len: usize,
+    ) -> Self {
+        let (kind, range, names) = match item {
+            Item::Stmt(stmt) => (ItemKind::Stmt, stmt.range, names::stmt_names(&stmt.data)),
+            Item::Expr(expr) => (ItemKind::Expr, expr.range, names::expr_names(&expr.data)),
+        };
+
+        let deps: Vec<_> = (names.into_iter())
+            .map(|name| {
+                let binding = (scope.iter().enumerate().rev())
+                    .find(|(_, (binding_name, _))| *binding_name == Some(name))
+                    .map(|(level, (_, fingerprint))| (level, *fingerprint));
+                (name, binding)
+            })
+            .collect();
+        let scope_names = match deps.iter().any(|(_, binding)| binding.is_none()) {
+            true => Some(scope.iter().map(|(name, _)| *name).collect()),
+            false => None,
+        };
+
+        Self {
+            kind,
+            text: text[range].to_owned(),
+            part,
+            scope_len: scope.len(),
+            meta_len,
+            deps,
+            scope_names,
+            instances: instances.to_vec(),
+        }
+    }
+}
+
+/// The memoized result of elaborating a top-level item.
+#[derive(Clone)]
+struct ItemResult<'db> {
+    item: TopLevelItem<'db>,
+    /// Events reported while elaborating the item, with ranges relative to the
+    /// start of the item.
+    events: Vec<Event>,
+    /// The metavariables created while elaborating the item, all of which
+    /// were solved.
+    metas: MetaEnv<'db>,
+    /// Changes whenever the bindings, or the bindings they depend on, change.
+    fingerprint: Fingerprint,
+}
+
+/// The parse of the current text.
+#[derive(Clone)]
+struct Parse<'db> {
+    cst: cst::Node<'db>,
+    events: Vec<Event>,
+}
+
+/// Memoized queries over the text of a single file.
+///
+/// All results are allocated in `bump`, and are not freed until `bump` is:
+/// long-running clients should start again with a fresh database (and arena)
+/// every so often.
+pub struct Database<'db> {
+    bump: &'db bumpalo::Bump,
+    file_id: usize,
+    text: String,
+    eval_backend: EvalBackend,
+    opt_level: OptLevel,
+    eval_limits: EvalLimits,
+    eval_commands: bool,
+    run: bool,
+
+    parse: Option<Parse<'db>>,
+    surface: Option<File<'db>>,
+    items: HashMap<ItemKey, ItemResult<'db>>,
+    stats: CheckStats,
+
+    cache: Option<Cache>,
+    /// The elaborated items of the current text, once it has been checked or
+    /// they have been loaded from the cache.
+    cached_items: Option<Vec<CoreItem<'db>>>,
+}
+
+impl<'db> Database<'db> {
+    pub fn new(bump: &'db bumpalo::Bump, file_id: usize, text: String) -> Self {
+        Self {
+            bump,
+            file_id,
+            text,
+            eval_backend: EvalBackend::default(),
+            opt_level: OptLevel::default(),
+            eval_limits: EvalLimits::default(),
+            eval_commands: true,
+            run: false,
+
+            parse: None,
+            surface: None,
+            items: HashMap::new(),
+            stats: CheckStats::default(),
+
+            cache: None,
+            cached_items: None,
+        }
+    }
+
+    pub const fn file_id(&self) -> usize { self.file_id }
+
+    pub fn text(&self) -> &str { &self.text }
+
+    /// Replace the text of the file.
+    pub fn set_text(&mut self, text: String) {
+        if text != self.text {
+            self.text = text;
+            self.parse = None;
+            self.surface = None;
+            self.cached_items = None;
+        }
+    }
+
+    /// Set how `#eval` commands evaluate expressions.
+    pub fn set_eval_backend(&mut self, eval_backend: EvalBackend) {
+        if eval_backend != self.eval_backend {
+            self.eval_backend = eval_backend;
+            self.items.clear();
+            self.cached_items = None;
+        }
+    }
+
+    /// Set how expressions are optimized before they are run or compiled.
+    pub fn set_opt_level(&mut self, opt_level: OptLevel) {
+        if opt_level != self.opt_level {
+            self.opt_level = opt_level;
+            self.items.clear();
+            self.cached_items = None;
+        }
+    }
+
+    /// Set the limits on evaluation while elaborating each top-level item.
+    pub fn set_eval_limits(&mut self, eval_limits: EvalLimits) {
+        if eval_limits != self.eval_limits {
+            self.eval_limits = eval_limits;
+            self.items.clear();
+            self.cached_items = None;
+        }
+    }
+
+    /// Set whether `#eval` commands evaluate and display their arguments, or
+    /// are only elaborated, for [`Self::core_items`] to compile.
+    pub fn set_eval_commands(&mut self, eval_commands: bool) {
+        if eval_commands != self.eval_commands {
+            self.eval_commands = eval_commands;
+            self.items.clear();
+            self.cached_items = None;
+        }
+    }
+
+    /// Set whether checking the file also displays the value of its result
+    /// expression.
+    pub fn set_run(&mut self, run: bool) {
+        if run != self.run {
+            self.run = run;
+            self.items.clear();
+            self.cached_items = None;
+        }
+    }
+
+    /// Load and save the results of [`Self::check`] in `cache`.
+    pub fn set_cache(&mut self, cache: Option<Cache>) { self.cache = cache; }
+
+    /// The work done by the last call to [`Self::check`].
+    pub const fn stats(&self) -> CheckStats { self.stats }
+
+    fn parse(&mut self) -> &Parse<'db> {
+        self.parse.get_or_insert_with(|| {
+            let events = RefCell::new(Vec::new());
+            let cst = pion_parser::parse_file_cst(
+                self.bump,
+                &mut Recorder(&events),
+                self.file_id,
+                &self.text,
+            );
+            let events = events.into_inner();
+            Parse { cst, events }
+        })
+    }
+
+    /// The concrete syntax tree of the file.
+    pub fn cst(&mut self) -> cst::Node<'db> { self.parse().cst }
+
+    /// The surface syntax of the file.
+    pub fn surface(&mut self) -> File<'db> {
+        if let Some(file) = self.surface {
+            return file;
+        }
+        let cst = self.cst();
+        let file = pion_parser::lower_file(self.bump, &self.text, cst);
+        self.surface = Some(file);
+        file
+    }
+
+    /// Check the file, returning everything reported in source order.
+    ///
+    /// Only top-level items whose text or dependencies have changed since
+    /// the last call are re-elaborated; the results of other items are
+    /// reused. If a cache is set and has an entry for the current text,
+    /// nothing is elaborated at all.
+    pub fn check(&mut self) -> Vec<Event> {
+        let Some(cache) = self.cache.clone() else {
+            return self.check_uncached();
+        };
+
+        let options = (
+            self.eval_backend,
+            self.opt_level,
+            self.eval_limits,
+            self.eval_commands,
+            self.run,
+        );
+        let key = Cache::key(&self.text, &options);
+        if let Some(entry) = cache.load(self.bump, key) {
+            self.stats = CheckStats::default();
+            self.cached_items = Some(entry.items);
+            return entry.outputs.into_iter().map(Event::Output).collect();
+        }
+
+        // Entries only record outputs, so files with assertions are not saved.
+        let events = self.check_uncached();
+        let outputs: Option<Vec<_>> = (events.iter())
+            .map(|event| match event {
+                Event::Output(text) => Some(text.clone()),
+                Event::Diagnostic(..) | Event::Assertion(_) => None,
+            })
+            .collect();
+        if let Some(outputs) = outputs {
+            let items = self.elaborated_items();
+            cache.store(key, &cache::Entry { outputs, items });
+        }
+        events
+    }
+
+    fn check_uncached(&mut self) -> Vec<Event> {
+        let file = self.surface();
+        let mut events = self.parse().events.clone();
+
+        let stmt_items = file.contents.stmts.iter().map(Item::Stmt);
+        let expr_item = file.contents.result_expr.map(Item::Expr);
+
+        let mut old_items = std::mem::take(&mut self.items);
+        let mut stats = CheckStats::default();
+
+        let recorded = RefCell::new(Vec::new());
+        let (mut diagnostic_handler, mut command_handler) =
+            (Recorder(&recorded), Recorder(&recorded));
+        let mut elaborator = Elaborator::new(
+            self.bump,
+            &self.text,
+            self.file_id,
+            &mut diagnostic_handler,
+            &mut command_handler,
+        );
+        elaborator.set_eval_backend(self.eval_backend);
+        elaborator.set_opt_level(self.opt_level);
+        elaborator.set_eval_limits(self.eval_limits);
+        elaborator.set_eval_commands(self.eval_commands);
+
+        let mut scope = Vec::new();
+        let mut instances = Vec::new();
+        let mut core_items = Vec::new();
+        let mut all_settled = true;
+        let mut prev_range = None;
+        let mut part = 0;
+        for item in stmt_items.chain(expr_item) {
+            let range = match item {
+                Item::Stmt(stmt) => stmt.range,
+                Item::Expr(expr) => expr.range,
+            };
+            let start = usize::from(range.start());
+            part = match prev_range == Some(range) {
+                true => part + 1,
+                false => 0,
+            };
+            prev_range = Some(range);
+            let meta_len = elaborator.meta_len();
+            let key = ItemKey::new(&self.text, item, part, &scope, &instances, meta_len);
+
+            // Only items elaborated while no metavariables are unsolved are
+            // memoized, so there is nothing to reuse otherwise.
+            let settled = elaborator.is_settled();
+            let reused = match settled {
+                true => self
+                    .items
+                    .get(&key)
+                    .or_else(|| old_items.get(&key))
+                    .cloned(),
+                false => None,
+            };
+            let result = match reused {
+                Some(result) => {
+                    stats.reused += 1;
+                    elaborator.push_metas(&result.metas);
+                    result
+                }
+                None => {
+                    stats.elaborated += 1;
+                    let item = match item {
+                        Item::Stmt(stmt) => elaborator.elab_top_level_stmt(stmt),
+                        Item::Expr(expr) => match self.run {
+                            true => elaborator.run_top_level_expr(expr),
+                            false => elaborator.elab_top_level_expr(expr),
+                        },
+                    };
+                    let events = (recorded.take().into_iter())
+                        .map(|event| event.map_ranges(|pos| pos - start))
+                        .collect();
+                    let fingerprint = fingerprint(&item.bindings, &key);
+                    ItemResult {
+                        item,
+                        events,
+                        metas: elaborator.metas_since(meta_len),
+                        fingerprint,
+                    }
+                }
+            };
+
+            if let Item::Stmt(stmt) = item {
+                elaborator.push_top_level_bindings(&stmt.data, &result.item.bindings);
+                if let surface::Stmt::Let(_, binding) = stmt.data {
+                    if binding.instance {
+                        let levels = scope.len()..scope.len() + result.item.bindings.len();
+                        instances.extend(levels.map(|level| (level, result.fingerprint)));
+                    }
+                }
+            }
+            scope.extend(
+                (result.item.bindings.iter()).map(|binding| (binding.name, result.fingerprint)),
+            );
+            core_items.extend(result.item.core_items());
+            events.extend(
+                (result.events.iter().cloned()).map(|event| event.map_ranges(|pos| pos + start)),
+            );
+
+            // The results of items that leave metavariables unsolved can be
+            // changed by later items that solve them.
+            if settled && elaborator.is_settled() {
+                old_items.remove(&key);
+                self.items.insert(key, result);
+            } else {
+                all_settled = false;
+            }
+        }
+
+        elaborator.default_unsolved_metas();
+        events.extend(recorded.take());
+
+        // Items were zonked when they were elaborated, but metavariables left
+        // unsolved then may have been solved by later items since.
+        if !all_settled {
+            elaborator.zonk_top_level_items(&mut core_items);
+        }
+        self.cached_items = Some(core_items);
+
+        self.stats = stats;
+        events
+    }
+
+    /// The elaborated bindings, `#eval` arguments and result expression of the
+    /// file, in source order, optimized at the configured level. Should only
+    /// be called after [`Self::check`] has reported no errors.
+    pub fn core_items(&mut self) -> Vec<CoreItem<'db>> {
+        let (bump, opt_level) = (self.bump, self.opt_level);
+        (self.elaborated_items().into_iter())
+            .map(|item| match item {
+                CoreItem::Let(binding) => CoreItem::Let(LetBinding {
+                    rhs: optimize(bump, opt_level, &binding.rhs),
+                    ..binding
+                }),
+                CoreItem::Eval(expr) => CoreItem::Eval(optimize(bump, opt_level, &expr)),
+            })
+            .collect()
+    }
+
+    /// The items of [`Self::core_items`], exactly as produced by the
+    /// elaborator. Reuses the results of [`Self::check`], which is called
+    /// first if the current text has not been checked yet.
+    pub fn elaborated_items(&mut self) -> Vec<CoreItem<'db>> {
+        if self.cached_items.is_none() {
+            self.check();
+        }
+        self.cached_items.clone().unwrap_or_default()
+    }
+}
+
+/// The fingerprint of the bindings introduced by an item. Does not depend on
+/// the item's text, so that edits that do not change the meaning of an item
+/// (eg to comments or whitespace) do not invalidate the items that depend on
+/// it.
+fn fingerprint(bindings: &[LetBinding<Expr, Expr>], key: &ItemKey) -> Fingerprint {
+    let mut hasher = DefaultHasher::new();
+    format!("{bindings:?}").hash(&mut hasher);
+    key.scope_len.hash(&mut hasher);
+    key.deps.hash(&mut hasher);
+    hasher.finish()
+}
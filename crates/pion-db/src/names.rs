@@ -0,0 +1,150 @@
+//! The names referred to by surface syntax.
+//!
+//! Scoping is ignored, so the names collected are a superset of the free
+//! variables: this is fine for dependency tracking, where the only cost of a
+//! spurious dependency is an unnecessary re-elaboration. The names bound by
+//! patterns are collected too, since whether they shadow a binding in scope
+//! affects the diagnostics reported.
+
+use pion_surface::syntax::{Block, Command, Expr, Located, MatchGuard, Pat, Stmt};
+use pion_symbol::Symbol;
+
+/// The names referred to by `stmt`, in order of first occurrence.
+pub fn stmt_names(stmt: &Stmt) -> Vec<Symbol> {
+    let mut names = Vec::new();
+    stmt_names_into(stmt, &mut names);
+    names
+}
+
+/// The names referred to by `expr`, in order of first occurrence.
+pub fn expr_names(expr: &Expr) -> Vec<Symbol> {
+    let mut names = Vec::new();
+    expr_names_into(expr, &mut names);
+    names
+}
+
+fn push_name(name: Symbol, names: &mut Vec<Symbol>) {
+    if !names.contains(&name) {
+        names.push(name);
+    }
+}
+
+fn block_names_into(block: &Block, names: &mut Vec<Symbol>) {
+    for stmt in block.stmts {
+        stmt_names_into(&stmt.data, names);
+    }
+    if let Some(expr) = block.result_expr {
+        expr_names_into(&expr.data, names);
+    }
+}
+
+fn stmt_names_into(stmt: &Stmt, names: &mut Vec<Symbol>) {
+    match stmt {
+        Stmt::Error => {}
+        Stmt::Let(_, binding) => {
+            pat_names_into(&binding.pat.data, names);
+            if let Some(r#type) = binding.r#type {
+                expr_names_into(&r#type.data, names);
+            }
+            expr_names_into(&binding.rhs.data, names);
+        }
+        Stmt::Command(command) => match command.data {
+            Command::Check(expr)
+            | Command::Eval(expr)
+            | Command::Print(_, expr)
+            | Command::Reduce(_, expr)
+            | Command::Trace(_, expr) => {
+                expr_names_into(&expr.data, names);
+            }
+            Command::AssertEq(lhs, rhs) | Command::AssertType(lhs, rhs) => {
+                expr_names_into(&lhs.data, names);
+                expr_names_into(&rhs.data, names);
+            }
+            Command::Fail(_, block) => block_names_into(&block, names),
+            Command::Show(name) => push_name(name.data, names),
+        },
+    }
+}
+
+fn exprs_names_into(exprs: &[Located<Expr>], names: &mut Vec<Symbol>) {
+    for expr in exprs {
+        expr_names_into(&expr.data, names);
+    }
+}
+
+fn expr_names_into(expr: &Expr, names: &mut Vec<Symbol>) {
+    match expr {
+        Expr::Error | Expr::Lit(..) | Expr::Hole => {}
+        Expr::VarRef(name) => push_name(name.data, names),
+        Expr::Paren(expr) => expr_names_into(&expr.data, names),
+        Expr::Ann(expr, r#type) => {
+            expr_names_into(&expr.data, names);
+            expr_names_into(&r#type.data, names);
+        }
+        Expr::Do(block) => block_names_into(block, names),
+        Expr::If(cond, then, r#else) => {
+            expr_names_into(&cond.data, names);
+            expr_names_into(&then.data, names);
+            expr_names_into(&r#else.data, names);
+        }
+        Expr::Match(scrut, cases) => {
+            expr_names_into(&scrut.data, names);
+            for case in *cases {
+                pat_names_into(&case.pat.data, names);
+                if let Some(MatchGuard::If(cond)) = case.guard {
+                    expr_names_into(&cond.data, names);
+                }
+                expr_names_into(&case.expr.data, names);
+            }
+        }
+        Expr::FunArrow(_, lhs, rhs) => {
+            expr_names_into(&lhs.data, names);
+            expr_names_into(&rhs.data, names);
+        }
+        Expr::FunType(params, body) | Expr::FunLit(params, body) => {
+            for param in *params {
+                pat_names_into(&param.data.pat.data, names);
+                if let Some(r#type) = param.data.r#type {
+                    expr_names_into(&r#type.data, names);
+                }
+            }
+            expr_names_into(&body.data, names);
+        }
+        Expr::FunApp(fun, args) => {
+            expr_names_into(&fun.data, names);
+            for arg in *args {
+                expr_names_into(&arg.data.expr.data, names);
+            }
+        }
+        Expr::ListLit(exprs) | Expr::TupleLit(exprs) => exprs_names_into(exprs, names),
+        Expr::RecordType(fields) => {
+            for field in *fields {
+                expr_names_into(&field.data.r#type.data, names);
+            }
+        }
+        Expr::RecordLit(fields) => {
+            for field in *fields {
+                expr_names_into(&field.data.expr.data, names);
+            }
+        }
+        Expr::RecordProj(scrut, _) => expr_names_into(&scrut.data, names),
+    }
+}
+
+fn pat_names_into(pat: &Pat, names: &mut Vec<Symbol>) {
+    match pat {
+        Pat::Error | Pat::Underscore | Pat::Lit(_) => {}
+        Pat::Var(name) => push_name(name.data, names),
+        Pat::Paren(pat) => pat_names_into(&pat.data, names),
+        Pat::TupleLit(pats) | Pat::Or(pats) => {
+            for pat in *pats {
+                pat_names_into(&pat.data, names);
+            }
+        }
+        Pat::RecordLit(fields) => {
+            for field in *fields {
+                pat_names_into(&field.data.pat.data, names);
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Check that results loaded from a cache are the same as those they replace.
+
+use std::path::PathBuf;
+
+use pion_db::{Cache, CheckStats, Database, Event};
+
+/// A fresh, empty cache directory for the test called `name`.
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pion-cache-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn check(dir: &PathBuf, text: &str) -> (Vec<Event>, CheckStats, String) {
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, text.to_owned());
+    db.set_cache(Some(Cache::new(dir)));
+    let events = db.check();
+    let items = format!("{:?}", db.elaborated_items());
+    (events, db.stats(), items)
+}
+
+#[test]
+fn unchanged_text_is_not_elaborated() {
+    let dir = cache_dir("unchanged");
+    let text = "let id = fun (@A : Type) (x : A) => x;\n#eval id 1;\n#check id;\n";
+
+    let (events, stats, items) = check(&dir, text);
+    assert_eq!(stats.elaborated, 3);
+    assert_eq!(
+        events,
+        [
+            Event::Output(String::from("id 1 ⇝ 1")),
+            Event::Output(String::from("id : forall (@A : Type) -> A -> A")),
+        ]
+    );
+
+    assert_eq!(check(&dir, text), (events, CheckStats::default(), items));
+
+    let (_, stats, _) = check(&dir, "let id = fun (@A : Type) (x : A) => x;\n");
+    assert_eq!(stats.elaborated, 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn files_with_diagnostics_are_not_saved() {
+    let dir = cache_dir("diagnostics");
+    let text = "let x : Int = true;\n";
+
+    let (events, stats, _) = check(&dir, text);
+    assert!(matches!(events[..], [Event::Diagnostic(..)]));
+    assert_eq!(stats.elaborated, 1);
+
+    let (events, stats, _) = check(&dir, text);
+    assert!(matches!(events[..], [Event::Diagnostic(..)]));
+    assert_eq!(stats.elaborated, 1);
+    assert!(!dir.exists());
+}
+
+#[test]
+fn unreadable_entries_are_ignored() {
+    let dir = cache_dir("unreadable");
+    let text = "#eval add 1 2;\n";
+    let expected = check(&dir, text);
+
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        std::fs::write(&path, bytes).unwrap();
+    }
+
+    // The corrupt entry is elaborated again, and replaced.
+    assert_eq!(check(&dir, text), expected);
+    let (_, stats, _) = check(&dir, text);
+    assert_eq!(stats, CheckStats::default());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,42 @@
+//! Check that the elaborated core of the test data round-trips through the
+//! binary core format.
+
+use pion_core::binary::{decode_items, encode_items};
+use pion_db::Database;
+
+#[test]
+fn elaborated_test_data_round_trips() {
+    let test_data = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data");
+    let mut count = 0;
+
+    for entry in walkdir::WalkDir::new(test_data) {
+        let entry = entry.unwrap();
+        if entry.path().extension() != Some("pion".as_ref()) {
+            continue;
+        }
+
+        let text = std::fs::read_to_string(entry.path()).unwrap();
+        let bump = bumpalo::Bump::new();
+        let mut db = Database::new(&bump, 0, text);
+        db.check();
+        let items = db.elaborated_items();
+
+        // Decode into a fresh arena, as a later run would.
+        let bytes = encode_items(&items);
+        let decoded_bump = bumpalo::Bump::new();
+        let decoded = match decode_items(&decoded_bump, &bytes) {
+            Ok(decoded) => decoded,
+            Err(error) => panic!("{}: {error}", entry.path().display()),
+        };
+        assert_eq!(
+            format!("{decoded:?}"),
+            format!("{items:?}"),
+            "{}: decoded items differ",
+            entry.path().display(),
+        );
+        assert_eq!(encode_items(&decoded), bytes);
+        count += 1;
+    }
+
+    assert!(count > 0, "no test files found");
+}
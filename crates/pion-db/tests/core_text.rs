@@ -0,0 +1,40 @@
+//! Check that the elaborated core of the test data round-trips through the
+//! textual core format.
+
+use pion_core::text::{parse_items, print_items};
+use pion_db::Database;
+
+#[test]
+fn elaborated_test_data_round_trips() {
+    let test_data = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data");
+    let mut count = 0;
+
+    for entry in walkdir::WalkDir::new(test_data) {
+        let entry = entry.unwrap();
+        if entry.path().extension() != Some("pion".as_ref()) {
+            continue;
+        }
+
+        let text = std::fs::read_to_string(entry.path()).unwrap();
+        let bump = bumpalo::Bump::new();
+        let mut db = Database::new(&bump, 0, text);
+        db.check();
+        let items = db.elaborated_items();
+
+        let printed = print_items(&bump, &items);
+        let parsed = match parse_items(&bump, &printed) {
+            Ok(parsed) => parsed,
+            Err(error) => panic!("{}: {error}\n{printed}", entry.path().display()),
+        };
+        assert_eq!(
+            format!("{parsed:?}"),
+            format!("{items:?}"),
+            "{}: parsed items differ",
+            entry.path().display(),
+        );
+        assert_eq!(print_items(&bump, &parsed), printed);
+        count += 1;
+    }
+
+    assert!(count > 0, "no test files found");
+}
@@ -0,0 +1,89 @@
+//! Check that evaluating with the bytecode virtual machine gives the same
+//! results as the normalizer, at every optimization level.
+
+use pion_core::optimize::OptLevel;
+use pion_db::{Database, Event};
+use pion_elab::command::EvalBackend;
+
+fn check(text: &str, eval_backend: EvalBackend, opt_level: OptLevel) -> Vec<Event> {
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, text.to_owned());
+    db.set_eval_backend(eval_backend);
+    db.set_opt_level(opt_level);
+    db.check()
+}
+
+#[test]
+fn bytecode_matches_normalizer() {
+    let test_data = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data");
+    let mut count = 0;
+
+    for dir in ["elab", "compile"] {
+        for entry in walkdir::WalkDir::new(test_data.join(dir)) {
+            let entry = entry.unwrap();
+            if entry.path().extension() != Some("pion".as_ref()) {
+                continue;
+            }
+
+            let text = std::fs::read_to_string(entry.path()).unwrap();
+            for opt_level in [OptLevel::None, OptLevel::Simplify, OptLevel::Inline] {
+                assert_eq!(
+                    check(&text, EvalBackend::Bytecode, opt_level),
+                    check(&text, EvalBackend::Normalize, opt_level),
+                    "{}: bytecode and normalizer results differ at {opt_level:?}",
+                    entry.path().display(),
+                );
+            }
+            count += 1;
+        }
+    }
+
+    assert!(count > 0, "no test files found");
+}
+
+#[test]
+fn run_deep_recursion() {
+    let text = "
+let rec sum : Int -> Int -> Int = fun n acc => if eq n 0 then acc else sum (sub n 1) (add acc n);
+#eval sum 100000 0;
+sum 200000 0
+";
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, text.to_owned());
+    db.set_eval_backend(EvalBackend::Bytecode);
+    db.set_run(true);
+    assert_eq!(
+        db.check(),
+        [
+            Event::Output(String::from("sum 100000 0 ⇝ 705082704")),
+            Event::Output(String::from("2820230816")),
+        ]
+    );
+}
+
+#[test]
+fn eval_in_nested_blocks() {
+    let text = "
+let x = 1;
+let y = do {
+    let z = add x 1;
+    #eval add z x;
+    let w = do { let v = mul z 3; #eval [x, z, v]; v };
+    #eval add w z;
+    w
+};
+#eval add x y;
+";
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, text.to_owned());
+    db.set_eval_backend(EvalBackend::Bytecode);
+    assert_eq!(
+        db.check(),
+        [
+            Event::Output(String::from("add z x ⇝ 3")),
+            Event::Output(String::from("[x, z, v] ⇝ [1, 2, 6]")),
+            Event::Output(String::from("add w z ⇝ 8")),
+            Event::Output(String::from("add x y ⇝ 7")),
+        ]
+    );
+}
@@ -0,0 +1,119 @@
+//! Check that evaluation which does not terminate is stopped by the
+//! evaluation limits and reported, rather than hanging the checker.
+
+use pion_core::semantics::EvalLimits;
+use pion_db::{Database, Event};
+use pion_elab::command::EvalBackend;
+
+/// Check `text`, or run it with the bytecode virtual machine if `run` is set.
+fn check(text: &str, run: bool) -> Vec<String> {
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, text.to_owned());
+    db.set_eval_limits(EvalLimits {
+        max_steps: 10_000,
+        max_depth: 100,
+        max_arena_bytes: 1 << 20,
+    });
+    if run {
+        db.set_eval_backend(EvalBackend::Bytecode);
+        db.set_run(true);
+    }
+    (db.check().into_iter())
+        .map(|event| match event {
+            Event::Diagnostic(diagnostic, _) => {
+                format!(
+                    "{}: {}",
+                    diagnostic.code.unwrap_or_default(),
+                    diagnostic.labels[0].message
+                )
+            }
+            Event::Output(output) => output,
+            event @ Event::Assertion(_) => panic!("unexpected event: {event:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn diverging_eval() {
+    let text = "
+let rec loop : Int -> Int = fun n => loop n;
+#eval loop 0;
+#eval 1;
+";
+    assert_eq!(
+        check(text, false),
+        [
+            "E0019: evaluation nested more than 100 levels deep",
+            "1 ⇝ 1"
+        ]
+    );
+}
+
+#[test]
+fn deep_reduce() {
+    // The expression being reduced nests 200 levels deep. Every step recurses
+    // that deep, which in debug builds needs more than the test thread's
+    // stack, so run on a stack as big as the main thread's rather than grow it
+    // at every step.
+    let text = "
+let rec count : Int -> Int = fun n => if eq n 0 then 0 else add 1 (count (sub n 1));
+#reduce count 200, steps = 100000;
+";
+    let events = std::thread::Builder::new()
+        .stack_size(8 << 20)
+        .spawn(|| {
+            let bump = bumpalo::Bump::new();
+            let mut db = Database::new(&bump, 0, text.to_owned());
+            db.check()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(events, [Event::Output(String::from("count 200 ⇝ 200"))]);
+}
+
+#[test]
+fn diverging_type() {
+    let text = "
+let rec loop : Int -> Type = fun n => loop (add n 1);
+let x : loop 0 = 5;
+";
+    assert_eq!(
+        check(text, false),
+        ["E0019: evaluation nested more than 100 levels deep"]
+    );
+}
+
+#[test]
+fn exponential_eval() {
+    let text = "
+let rec fork : Int -> Int = fun n => if eq n 0 then 1 else add (fork (sub n 1)) (fork (sub n 1));
+#eval fork 20;
+";
+    assert_eq!(
+        check(text, false),
+        ["E0019: evaluation took more than 10000 steps"]
+    );
+}
+
+#[test]
+fn diverging_run() {
+    let text = "
+let rec loop : Int -> Int = fun n => loop (add n 1);
+let rec deep : Int -> Int = fun n => add (deep n) 1;
+let rec grow : List Int -> List Int = fun xs => grow (append xs xs);
+#eval loop 0;
+#eval deep 0;
+#eval grow [1];
+loop 0
+";
+    assert_eq!(
+        check(text, true),
+        [
+            "E0019: evaluation took more than 10000 steps",
+            "E0019: evaluation took more than 10000 steps",
+            "E0019: evaluation allocated more than 1048576 bytes",
+            "E0019: evaluation took more than 10000 steps",
+        ]
+    );
+}
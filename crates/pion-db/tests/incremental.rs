@@ -0,0 +1,313 @@
+//! Check that incremental checking gives the same results as checking from
+//! scratch, and that it only re-elaborates what it needs to.
+
+use std::cell::RefCell;
+
+use pion_db::{CheckStats, Database, Event};
+use pion_diagnostic::{codes, Diagnostic, DiagnosticHandler, Suggestion};
+use pion_elab::command::CommandHandler;
+use pion_elab::Elaborator;
+use pion_surface::syntax::Stmt;
+
+/// Records events, as the database does.
+struct Recorder<'a>(&'a RefCell<Vec<Event>>);
+
+impl DiagnosticHandler for Recorder<'_> {
+    fn handle_diagnostic(&mut self, diagnostic: Diagnostic<usize>) {
+        self.handle_diagnostic_with_suggestions(diagnostic, Vec::new());
+    }
+
+    fn handle_diagnostic_with_suggestions(
+        &mut self,
+        diagnostic: Diagnostic<usize>,
+        suggestions: Vec<Suggestion>,
+    ) {
+        let event = Event::Diagnostic(diagnostic, suggestions);
+        self.0.borrow_mut().push(event);
+    }
+}
+
+impl CommandHandler for Recorder<'_> {
+    fn display_to_user(&mut self, text: String) { self.0.borrow_mut().push(Event::Output(text)); }
+
+    fn assertion_result(&mut self, passed: bool) {
+        self.0.borrow_mut().push(Event::Assertion(passed));
+    }
+}
+
+/// Check `text` without the database, by elaborating the whole file as a
+/// single block.
+fn check_from_scratch(text: &str) -> Vec<Event> {
+    let bump = bumpalo::Bump::new();
+    let events = RefCell::new(Vec::new());
+    let file = pion_parser::parse_file(&bump, &mut Recorder(&events), 0, text);
+
+    let (mut diagnostic_handler, mut command_handler) = (Recorder(&events), Recorder(&events));
+    let mut elaborator = Elaborator::new(
+        &bump,
+        text,
+        0,
+        &mut diagnostic_handler,
+        &mut command_handler,
+    );
+    elaborator.synth_block(&file.contents);
+    elaborator.default_unsolved_metas();
+
+    // Unlike the bindings of a block, top-level bindings are not reported as
+    // unused.
+    let top_level_pats: Vec<_> = (file.contents.stmts.iter())
+        .filter_map(|stmt| match stmt.data {
+            Stmt::Let(_, binding) => Some(std::ops::Range::from(binding.pat.range)),
+            Stmt::Error | Stmt::Command(_) => None,
+        })
+        .collect();
+    let is_top_level_unused = |event: &Event| match event {
+        Event::Diagnostic(diagnostic, _) => {
+            diagnostic.code.as_deref() == Some(codes::UNUSED_BINDING.code)
+                && (top_level_pats.iter()).any(|pat| {
+                    let range = &diagnostic.labels[0].range;
+                    pat.start <= range.start && range.end <= pat.end
+                })
+        }
+        Event::Output(_) | Event::Assertion(_) => false,
+    };
+    let events = events.into_inner();
+    events
+        .into_iter()
+        .filter(|event| !is_top_level_unused(event))
+        .collect()
+}
+
+/// A small deterministic pseudo-random number generator (xorshift64), so
+/// that failures are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        usize::try_from(self.next() % u64::try_from(n).unwrap()).unwrap()
+    }
+
+    fn char_boundary(&mut self, text: &str) -> usize {
+        loop {
+            let pos = self.below(text.len() + 1);
+            if text.is_char_boundary(pos) {
+                return pos;
+            }
+        }
+    }
+
+    /// A random range of at most `max_len` bytes of `text`.
+    fn span(&mut self, text: &str, max_len: usize) -> std::ops::Range<usize> {
+        let start = self.char_boundary(text);
+        let mut end = (start + self.below(max_len + 1)).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        start..end
+    }
+}
+
+const SNIPPETS: &[&str] = &[
+    " ",
+    "\n",
+    "// comment\n",
+    ";",
+    "x",
+    "1",
+    "Int",
+    "true",
+    "(",
+    ")",
+    "{",
+    "}",
+    "_",
+    "let y = 5;\n",
+    "let x : Int = 1;\n",
+    "let f = fun x => x;\n",
+    "#check x;\n",
+    "#eval f 1;\n",
+];
+
+/// Delete, insert or duplicate a short random span of `text`.
+fn random_edit(rng: &mut Rng, text: &str) -> String {
+    let mut text = text.to_owned();
+    match rng.below(3) {
+        0 => {
+            let span = rng.span(&text, 16);
+            text.replace_range(span, "");
+        }
+        1 => {
+            let pos = rng.char_boundary(&text);
+            text.insert_str(pos, SNIPPETS[rng.below(SNIPPETS.len())]);
+        }
+        _ => {
+            let span = text[rng.span(&text, 64)].to_owned();
+            let pos = rng.char_boundary(&text);
+            text.insert_str(pos, &span);
+        }
+    }
+    text
+}
+
+#[test]
+fn random_edits() {
+    let test_data = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data");
+    let mut count = 0;
+
+    for entry in walkdir::WalkDir::new(test_data) {
+        let entry = entry.unwrap();
+        if entry.path().extension() != Some("pion".as_ref()) {
+            continue;
+        }
+
+        let mut text = std::fs::read_to_string(entry.path()).unwrap();
+        let bump = bumpalo::Bump::new();
+        let mut db = Database::new(&bump, 0, text.clone());
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d ^ u64::try_from(count).unwrap());
+
+        for _ in 0..20 {
+            assert_eq!(
+                db.check(),
+                check_from_scratch(&text),
+                "{}: incremental and from-scratch results differ for:\n{text}",
+                entry.path().display(),
+            );
+            text = random_edit(&mut rng, &text);
+            db.set_text(text.clone());
+        }
+        count += 1;
+    }
+
+    assert!(count > 0, "no test files found");
+}
+
+#[track_caller]
+fn assert_stats(db: &mut Database, text: &str, elaborated: usize, reused: usize) {
+    db.set_text(text.to_owned());
+    assert_eq!(db.check(), check_from_scratch(text));
+    assert_eq!(db.stats(), CheckStats { elaborated, reused });
+}
+
+#[test]
+fn reuse() {
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, String::new());
+
+    assert_stats(
+        &mut db,
+        "let a = 1;\nlet b = 2;\nlet c = a;\n#check c;",
+        4,
+        0,
+    );
+
+    // editing a binding re-elaborates it and its dependents
+    assert_stats(
+        &mut db,
+        "let a = true;\nlet b = 2;\nlet c = a;\n#check c;",
+        3,
+        1,
+    );
+
+    // editing an unrelated binding does not re-elaborate anything else
+    assert_stats(
+        &mut db,
+        "let a = true;\nlet b = 3;\nlet c = a;\n#check c;",
+        1,
+        3,
+    );
+
+    // edits that do not change a binding's meaning do not invalidate its
+    // dependents
+    assert_stats(
+        &mut db,
+        "let a = /* comment */ true;\nlet b = 3;\nlet c = a;\n#check c;",
+        1,
+        3,
+    );
+
+    // moving items does not invalidate them
+    assert_stats(
+        &mut db,
+        "\n\nlet a = /* comment */ true;\nlet b = 3;\nlet c = a;\n#check c;",
+        0,
+        4,
+    );
+}
+
+#[test]
+fn metas_solved_by_later_items() {
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, String::new());
+
+    let text = "let a = 1;\nlet f = fun x => x;\nlet y : Int = f 1;\n#check f;";
+    assert_stats(&mut db, text, 4, 0);
+    assert!(!db
+        .check()
+        .iter()
+        .any(|event| matches!(event, Event::Diagnostic(..))));
+
+    // `f` is re-elaborated, since the type of its parameter was solved by `y`
+    let text = "let a = 2;\nlet f = fun x => x;\nlet y : Int = f 1;\n#check f;";
+    assert_stats(&mut db, text, 3, 1);
+
+    // The type of `f` in its elaborated item is the solution found by `y`
+    let items = format!("{:?}", db.elaborated_items());
+    assert!(!items.contains("MetaVar"), "{items}");
+}
+
+#[test]
+fn unparsed_let_patterns() {
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, String::new());
+
+    // `let h  h;` binds both `h`s, in two items with the same text: the second
+    // `h` here elaborates exactly as the first `h` does below, but they
+    // report diagnostics at different offsets
+    assert_stats(&mut db, "let h  h;", 2, 0);
+    assert_stats(&mut db, "let h  ;\nlet h  h;", 3, 0);
+}
+
+#[test]
+fn elaborated_items_reuse() {
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, String::new());
+
+    let text = "let a = 1;\nlet f = fun (x : Int) => x;\nlet y = f a;\n#eval y;";
+    assert_stats(&mut db, text, 4, 0);
+
+    // Only the edited item is elaborated, by the check that the edit needs,
+    // and the items are the same as those of a fresh database
+    let text = "let a = 1;\nlet f = fun (x : Int) => x;\nlet y = f a;\n#eval f y;";
+    db.set_text(text.to_owned());
+    let items = db.elaborated_items();
+    assert_eq!(
+        db.stats(),
+        CheckStats {
+            elaborated: 1,
+            reused: 3
+        }
+    );
+    let expected = Database::new(&bump, 0, text.to_owned()).elaborated_items();
+    assert_eq!(format!("{items:?}"), format!("{expected:?}"));
+
+    // Once the text has been checked, nothing is elaborated again
+    assert_stats(&mut db, text, 0, 4);
+    assert_eq!(
+        format!("{:?}", db.elaborated_items()),
+        format!("{expected:?}")
+    );
+    assert_eq!(
+        db.stats(),
+        CheckStats {
+            elaborated: 0,
+            reused: 4
+        }
+    );
+}
@@ -0,0 +1,79 @@
+//! Stable diagnostic codes.
+//!
+//! Every diagnostic reported by the parser or elaborator carries one of these
+//! codes. Codes starting with `E` are errors, codes starting with `W` are
+//! [lints][crate::lints]. Once assigned, a code is never reused for a
+//! different diagnostic.
+//!
+//! Each code has a long-form explanation, found in `src/codes/<CODE>.md`,
+//! which can be printed with `pion explain <CODE>`. The first `pion` code
+//! block in each explanation is an example that reproduces the diagnostic.
+
+use std::fmt;
+
+/// A stable diagnostic code, eg `E0003`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Code {
+    /// The code itself, eg `E0003`.
+    pub code: &'static str,
+    /// A short description of the diagnostic.
+    pub title: &'static str,
+    /// A long-form explanation of the diagnostic, in Markdown.
+    pub explanation: &'static str,
+}
+
+impl Code {
+    /// Lookup a code by name.
+    pub fn from_code(code: &str) -> Option<Self> {
+        ALL_CODES.iter().copied().find(|c| c.code == code)
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.code.fmt(f) }
+}
+
+macro_rules! codes {
+    ($($code:ident $name:ident $title:literal,)*) => {
+        $(
+            pub const $name: Code = Code {
+                code: stringify!($code),
+                title: $title,
+                explanation: include_str!(concat!("codes/", stringify!($code), ".md")),
+            };
+        )*
+
+        /// All diagnostic codes, in ascending order.
+        pub const ALL_CODES: &[Code] = &[$($name),*];
+    };
+}
+
+codes! {
+    E0001 UNBOUND_LOCAL_VAR "unbound local variable",
+    E0002 INVALID_INTEGER_LITERAL "invalid integer literal",
+    E0003 UNABLE_TO_UNIFY "unable to unify",
+    E0004 UNSOLVED_META_VAR "unsolved metavariable",
+    E0005 DUPLICATE_RECORD_FIELD "duplicate record field",
+    E0006 FIELD_NOT_FOUND "record field not found",
+    E0007 RECORD_PROJ_NOT_RECORD "projection from non-record",
+    E0008 INEXHAUSTIVE_MATCH "inexhaustive match",
+    E0009 RECURSIVE_LET_NOT_FUNCTION "recursive binding is not a function",
+    E0010 FUN_APP_NOT_FUN "application of non-function",
+    E0011 FUN_APP_TOO_MANY_ARGS "too many arguments",
+    E0012 FUN_APP_PLICITY_MISMATCH "argument plicity mismatch",
+    E0013 UNEXPECTED_TOKEN "syntax error: unexpected token",
+    E0014 UNEXPECTED_EOF "syntax error: unexpected end of file",
+    E0015 UNKNOWN_PRINT_OPTION "unknown `#print` option",
+    E0016 UNKNOWN_IMPLICIT_PARAM "unknown implicit parameter",
+    E0017 INSTANCE_NOT_FOUND "no instance found",
+    E0018 AMBIGUOUS_INSTANCE "ambiguous instances",
+    E0019 EVAL_LIMIT_EXCEEDED "evaluation limit exceeded",
+    E0020 INVALID_REDUCE_OPTION "invalid `#reduce` option",
+    E0021 ASSERTION_FAILED "assertion failed",
+    E0022 INVALID_TOKEN "syntax error: invalid token",
+    E0023 RECURSIVE_LET_DEPENDENT "recursive function has a dependent return type",
+
+    W0001 UNREACHABLE_MATCH_CASE "unreachable match case",
+    W0002 UNUSED_BINDING "unused binding",
+    W0003 SHADOWED_BINDING "shadowed binding",
+}
@@ -1,7 +1,55 @@
-pub use codespan_reporting::diagnostic::{Diagnostic, Label};
+use std::ops::Range;
+
+pub use codespan_reporting::diagnostic::{Diagnostic, Label, Severity};
+
+pub mod codes;
+pub mod lints;
+
+/// A machine-applicable fix for a diagnostic: replace the text at `range` in
+/// `file_id` with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub file_id: usize,
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// Apply `suggestions` to `text`. Suggestions that overlap an earlier
+/// suggestion are skipped.
+pub fn apply_suggestions<'a>(
+    text: &str,
+    suggestions: impl IntoIterator<Item = &'a Suggestion>,
+) -> String {
+    let mut suggestions: Vec<_> = suggestions.into_iter().collect();
+    suggestions.sort_by_key(|suggestion| (suggestion.range.start, suggestion.range.end));
+
+    let mut output = String::with_capacity(text.len());
+    let mut pos = 0;
+    for suggestion in suggestions {
+        if suggestion.range.start < pos {
+            continue;
+        }
+        output.push_str(&text[pos..suggestion.range.start]);
+        output.push_str(&suggestion.replacement);
+        pos = suggestion.range.end;
+    }
+    output.push_str(&text[pos..]);
+    output
+}
 
 pub trait DiagnosticHandler {
     fn handle_diagnostic(&mut self, diagnostic: Diagnostic<usize>);
+
+    /// Report a diagnostic, along with suggested fixes. Handlers that cannot
+    /// apply fixes can ignore the suggestions.
+    fn handle_diagnostic_with_suggestions(
+        &mut self,
+        diagnostic: Diagnostic<usize>,
+        suggestions: Vec<Suggestion>,
+    ) {
+        let _ = suggestions;
+        self.handle_diagnostic(diagnostic);
+    }
 }
 
 impl<F> DiagnosticHandler for F
@@ -0,0 +1,66 @@
+//! Lints: diagnostics that are not hard errors, and whose severity can be
+//! configured by the user.
+
+use std::fmt;
+
+use crate::codes;
+
+/// A lint, identified by a stable diagnostic code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Lint {
+    /// The stable diagnostic code attached to diagnostics reported by this
+    /// lint, eg `W0001`.
+    pub code: &'static str,
+    /// A short description of the lint.
+    pub description: &'static str,
+    /// The level of the lint when not configured by the user.
+    pub default_level: LintLevel,
+}
+
+impl Lint {
+    /// Lookup a lint by its diagnostic code.
+    pub fn from_code(code: &str) -> Option<Self> {
+        ALL_LINTS.iter().copied().find(|lint| lint.code == code)
+    }
+}
+
+/// The level a lint is reported at.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Do not report the lint.
+    Allow,
+    /// Report the lint as a warning.
+    Warn,
+    /// Report the lint as an error.
+    Deny,
+}
+
+impl fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Allow => "allow".fmt(f),
+            Self::Warn => "warn".fmt(f),
+            Self::Deny => "deny".fmt(f),
+        }
+    }
+}
+
+pub const UNREACHABLE_MATCH_CASE: Lint = Lint {
+    code: codes::UNREACHABLE_MATCH_CASE.code,
+    description: codes::UNREACHABLE_MATCH_CASE.title,
+    default_level: LintLevel::Warn,
+};
+
+pub const UNUSED_BINDING: Lint = Lint {
+    code: codes::UNUSED_BINDING.code,
+    description: codes::UNUSED_BINDING.title,
+    default_level: LintLevel::Warn,
+};
+
+pub const SHADOWED_BINDING: Lint = Lint {
+    code: codes::SHADOWED_BINDING.code,
+    description: codes::SHADOWED_BINDING.title,
+    default_level: LintLevel::Warn,
+};
+
+pub const ALL_LINTS: &[Lint] = &[UNREACHABLE_MATCH_CASE, UNUSED_BINDING, SHADOWED_BINDING];
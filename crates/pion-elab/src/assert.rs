@@ -0,0 +1,131 @@
+//! The assertion commands, `#assert_eq`, `#assert_type` and `#fail`.
+//!
+//! An assertion passes if it holds and elaborating it reported no errors.
+//! Failed assertions are reported as diagnostics, and the result of every
+//! assertion is passed on to
+//! [`CommandHandler::assertion_result`](crate::command::CommandHandler::assertion_result),
+//! so that a test runner can count them.
+//!
+//! The block of a `#fail` command is elaborated with its diagnostics
+//! captured rather than reported. Postponed problems and metavariables that
+//! the block leaves unsolved are reported (and captured) at the end of the
+//! block, rather than at the end of the top-level item, since they are
+//! errors in the block.
+
+use pion_core::semantics::Value;
+use pion_core::syntax::Expr;
+use pion_diagnostic::Severity;
+use pion_surface::syntax::{self as surface, Located};
+use pion_symbol::Symbol;
+use pion_util::location::Location;
+use text_size::TextRange;
+
+use crate::{diagnostics, Elaborator};
+
+impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
+    pub(crate) fn elab_assert_eq(
+        &mut self,
+        lhs: &Located<surface::Expr<'surface>>,
+        rhs: &Located<surface::Expr<'surface>>,
+    ) {
+        let error_count = self.diagnostic_handler.error_count;
+        let (lhs_expr, r#type) = self.synth_expr(lhs);
+        let rhs_expr = self.check_expr(rhs, &r#type);
+        let lhs_value = self.eval_env().eval(&lhs_expr);
+        let rhs_value = self.eval_env().eval(&rhs_expr);
+
+        let range = TextRange::new(lhs.range.start(), rhs.range.end());
+        if self.unify(range, &lhs_value, &rhs_value).is_err() {
+            let lhs_nf = self.normal_form(&lhs_expr);
+            let rhs_nf = self.normal_form(&rhs_expr);
+            let lhs_loc = Location::new(self.file_id, lhs.range);
+            let rhs_loc = Location::new(self.file_id, rhs.range);
+            diagnostics::assert_eq_failed(self, &lhs_nf, &rhs_nf, lhs_loc, rhs_loc);
+        }
+        self.report_assertion(error_count);
+    }
+
+    pub(crate) fn elab_assert_type(
+        &mut self,
+        expr: &Located<surface::Expr<'surface>>,
+        r#type: &Located<surface::Expr<'surface>>,
+    ) {
+        let error_count = self.diagnostic_handler.error_count;
+        let expected_expr = self.check_expr_is_type(r#type);
+        let expected = self.eval_env().eval(&expected_expr);
+        let (_, found) = self.synth_expr(expr);
+
+        if self.unify(expr.range, &found, &expected).is_err() {
+            let found = self.quote_env().quote(&found);
+            let found = self.pretty(&found);
+            let expected = self.pretty(&expected_expr);
+            let expr_loc = Location::new(self.file_id, expr.range);
+            let type_loc = Location::new(self.file_id, r#type.range);
+            diagnostics::assert_type_failed(self, &found, &expected, expr_loc, type_loc);
+        }
+        self.report_assertion(error_count);
+    }
+
+    pub(crate) fn elab_fail(
+        &mut self,
+        range: TextRange,
+        code: Option<Located<Symbol>>,
+        block: &surface::Block<'surface>,
+    ) {
+        let error_count = self.diagnostic_handler.error_count;
+        let outer_captured = self.diagnostic_handler.captured.replace(Vec::new());
+        let outer_constraints = std::mem::take(&mut self.env.constraints);
+        let outer_instance_goals = std::mem::take(&mut self.env.instance_goals);
+        let meta_len = usize::from(self.env.metas.len());
+
+        self.synth_block(block);
+        self.report_unsolved_instance_goals();
+        self.report_stuck_constraints();
+        self.report_unsolved_metas_from(meta_len);
+        for value in self.env.metas.values.iter_mut().skip(meta_len) {
+            value.get_or_insert(Value::Error);
+        }
+
+        self.env.constraints = outer_constraints;
+        self.env.instance_goals = outer_instance_goals;
+        let captured = std::mem::replace(&mut self.diagnostic_handler.captured, outer_captured);
+        self.diagnostic_handler.error_count = error_count;
+
+        let errors: Vec<_> = (captured.unwrap_or_default().into_iter())
+            .filter(|diagnostic| diagnostic.severity >= Severity::Error)
+            .collect();
+        let loc = Location::new(self.file_id, range);
+        match code {
+            None if errors.is_empty() => diagnostics::fail_block_succeeded(self, loc),
+            None => {}
+            Some(code) => {
+                let mut codes: Vec<_> = (errors.iter())
+                    .filter_map(|diagnostic| diagnostic.code.as_deref())
+                    .collect();
+                codes.sort_unstable();
+                codes.dedup();
+                if !codes.contains(&code.data.as_str()) {
+                    let code_loc = Location::new(self.file_id, code.range);
+                    diagnostics::fail_code_not_reported(self, code.data, &codes, loc, code_loc);
+                }
+            }
+        }
+        self.report_assertion(error_count);
+    }
+
+    /// Pass on the result of an assertion, which passed if no errors have
+    /// been reported since there were `error_count` errors. Assertions inside
+    /// the block of a `#fail` command are not counted, since their failures
+    /// are expected.
+    fn report_assertion(&mut self, error_count: usize) {
+        if self.diagnostic_handler.captured.is_none() {
+            let passed = self.diagnostic_handler.error_count == error_count;
+            self.command_handler.assertion_result(passed);
+        }
+    }
+
+    fn normal_form(&mut self, expr: &Expr<'core>) -> String {
+        let expr = self.eval_env().normalize(expr);
+        self.pretty(&expr)
+    }
+}
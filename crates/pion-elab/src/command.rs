@@ -1,5 +1,10 @@
 pub trait CommandHandler {
     fn display_to_user(&mut self, text: String);
+
+    /// Report whether an assertion command (`#assert_eq`, `#assert_type` or
+    /// `#fail`) passed. A failed assertion is also reported as a diagnostic,
+    /// so handlers that only display diagnostics can ignore this.
+    fn assertion_result(&mut self, passed: bool) { let _ = passed; }
 }
 
 impl<F> CommandHandler for F
@@ -8,3 +13,15 @@ where
 {
     fn display_to_user(&mut self, text: String) { self(text) }
 }
+
+/// How `#eval` commands evaluate expressions.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum EvalBackend {
+    /// Normalize the expression with the type checker's evaluator.
+    #[default]
+    Normalize,
+    /// Compile the expression to bytecode and run it on a virtual machine.
+    /// Falls back to [`Self::Normalize`] if the expression is not closed, or if
+    /// its result is not first-order data.
+    Bytecode,
+}
@@ -0,0 +1,113 @@
+//! Postponing unification problems that cannot be solved yet.
+//!
+//! Pattern unification can only solve a metavariable applied to distinct
+//! local variables. A problem like `?F 0 =? Int` has no unique solution, but
+//! may become solvable (or even trivial) once `?F` has been solved by some
+//! other problem. Rather than failing, such problems are queued in
+//! [`ElabEnv::constraints`](crate::env::ElabEnv::constraints), retried
+//! whenever a metavariable is solved, and only reported if they are still
+//! stuck at the end of the top-level item.
+
+use pion_core::semantics::Type;
+use pion_util::location::Location;
+use text_size::TextRange;
+
+use crate::env::{Constraint, LocalEnv};
+use crate::unify::{UnifyCtx, UnifyError};
+use crate::{diagnostics, Elaborator};
+
+impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
+    /// Unify `from` with `to`, postponing any problems that cannot be solved
+    /// yet.
+    pub(crate) fn unify(
+        &mut self,
+        range: TextRange,
+        from: &Type<'core>,
+        to: &Type<'core>,
+    ) -> Result<(), UnifyError<'core>> {
+        let mut unify_ctx = self.unify_env();
+        let result = unify_ctx.unify(from, to);
+        let (postponed, solved) = unify_ctx.finish();
+        self.report_eval_limit(range);
+
+        // If unification failed, the error is reported instead.
+        if result.is_ok() && !postponed.is_empty() {
+            let names: Vec<_> = self.env.locals.names.iter().copied().collect();
+            self.env
+                .constraints
+                .extend(postponed.into_iter().map(|problem| Constraint {
+                    range,
+                    names: names.clone(),
+                    found: from.clone(),
+                    expected: to.clone(),
+                    problem,
+                }));
+        }
+        if solved {
+            self.retry_constraints();
+            self.solve_instance_goals();
+        }
+        result
+    }
+
+    /// Retry the postponed problems, until no more metavariables are solved.
+    pub(crate) fn retry_constraints(&mut self) {
+        loop {
+            let mut solved_any = false;
+            for constraint in std::mem::take(&mut self.env.constraints) {
+                let problem = &constraint.problem;
+                let mut unify_ctx = UnifyCtx::new(
+                    self.bump,
+                    &mut self.env.renaming,
+                    problem.local_len,
+                    &mut self.env.metas.values,
+                    &self.fuel,
+                );
+                let result = unify_ctx.unify(&problem.left, &problem.right);
+                let (postponed, solved) = unify_ctx.finish();
+                solved_any |= solved;
+
+                match result {
+                    Ok(()) => {
+                        let constraints = postponed.into_iter().map(|problem| Constraint {
+                            problem,
+                            ..constraint.clone()
+                        });
+                        self.env.constraints.extend(constraints);
+                    }
+                    Err(error) => self.report_constraint(&constraint, &error),
+                }
+            }
+            if !solved_any {
+                break;
+            }
+        }
+    }
+
+    /// Report the postponed problems that are still stuck, and forget them.
+    pub(crate) fn report_stuck_constraints(&mut self) {
+        for constraint in std::mem::take(&mut self.env.constraints) {
+            let error = UnifyError::Spine(constraint.problem.error);
+            self.report_constraint(&constraint, &error);
+        }
+    }
+
+    fn report_constraint(&mut self, constraint: &Constraint<'core>, error: &UnifyError<'core>) {
+        // The problem may have arisen under binders that are no longer in
+        // scope, so report it in the environment it arose in. The whole types
+        // are reported, since the path to the problem within them is not
+        // known.
+        let error = error.clone().without_path();
+        let outer_locals = std::mem::take(&mut self.env.locals);
+        let mut locals = LocalEnv::default();
+        for name in &constraint.names {
+            locals.push_param(*name, Type::Error);
+        }
+        self.env.locals = locals;
+
+        let loc = Location::new(self.file_id, constraint.range);
+        let (found, expected) = (&constraint.found, &constraint.expected);
+        diagnostics::unable_to_unify(self, &error, found, expected, loc);
+        self.env.locals = outer_locals;
+    }
+}
@@ -1,16 +1,27 @@
-use pion_core::semantics::Type;
-use pion_diagnostic::{Diagnostic, Label};
+use pion_core::prim::Prim;
+use pion_core::semantics::{LimitExceeded, Type};
+use pion_diagnostic::{codes, Diagnostic, DiagnosticHandler as _, Label, Suggestion};
 use pion_symbol::Symbol;
+use pion_util::edit_distance;
 use pion_util::location::Location;
 
 use crate::env::MetaSource;
+use crate::instance::{SearchError, DEPTH_LIMIT};
+use crate::lint::BinderKind;
+use crate::reduce::{OptionValue, ReduceCommand, REDUCE_OPTIONS};
 use crate::Elaborator;
 
 pub fn unbound_local_var(elaborator: &mut Elaborator, name: Symbol, var_loc: Location) {
+    let locals = elaborator.env.locals.names.iter().rev().flatten();
+    let candidates =
+        (locals.map(|name| name.as_str())).chain(Prim::ALL.iter().map(|prim| prim.name()));
+    let suggestion = edit_distance::find_best_match(name.as_str(), candidates);
+
     let diagnostic = Diagnostic::error()
         .with_message(format!("Unbound local variable `{name}`"))
+        .with_code(codes::UNBOUND_LOCAL_VAR.code)
         .with_labels(vec![Label::primary(var_loc.file, var_loc.range)]);
-    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+    report_with_suggestion(elaborator, diagnostic, suggestion, var_loc);
 }
 
 pub fn invalid_integer_literal(
@@ -20,6 +31,7 @@ pub fn invalid_integer_literal(
 ) {
     let diagnostic = Diagnostic::error()
         .with_message(format!("Invalid integer literal: {error}"))
+        .with_code(codes::INVALID_INTEGER_LITERAL.code)
         .with_labels(vec![Label::primary(lit_loc.file, lit_loc.range)]);
     elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
 }
@@ -36,11 +48,13 @@ pub fn unsolved_meta_var(elaborator: &mut Elaborator, id: usize, source: MetaSou
             name: Some(name), ..
         } => format!("implicit argument `{name}`"),
         MetaSource::ImplicitArg { name: None, .. } => "implicit argument".to_string(),
+        MetaSource::InstanceArg { .. } => "instance argument".to_string(),
         MetaSource::ListElemType { .. } => "element type of empty list".to_string(),
         MetaSource::MatchResultType { .. } => "result type of match expression".to_string(),
     };
     let diagnostic = Diagnostic::error()
         .with_message(format!("Unsolved metavariable: ?{id}"))
+        .with_code(codes::UNSOLVED_META_VAR.code)
         .with_labels(vec![
             Label::primary(file, source.range()).with_message(format!("could not infer {message}"))
         ]);
@@ -55,6 +69,7 @@ pub fn duplicate_record_field(
 ) {
     let diagnostic = Diagnostic::error()
         .with_message(format!("Duplicate field `{name}`"))
+        .with_code(codes::DUPLICATE_RECORD_FIELD.code)
         .with_labels(vec![
             Label::primary(duplicate_loc.file, duplicate_loc.range),
             Label::secondary(first_loc.file, first_loc.range)
@@ -63,11 +78,20 @@ pub fn duplicate_record_field(
     elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
 }
 
-pub fn field_not_found(elaborator: &mut Elaborator, name: Symbol, field_loc: Location) {
+pub fn field_not_found(
+    elaborator: &mut Elaborator,
+    name: Symbol,
+    field_loc: Location,
+    fields: impl IntoIterator<Item = Symbol>,
+) {
+    let candidates = fields.into_iter().map(Symbol::as_str);
+    let suggestion = edit_distance::find_best_match(name.as_str(), candidates);
+
     let diagnostic = Diagnostic::error()
         .with_message(format!("Field `{name}` not found"))
+        .with_code(codes::FIELD_NOT_FOUND.code)
         .with_labels(vec![Label::primary(field_loc.file, field_loc.range)]);
-    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+    report_with_suggestion(elaborator, diagnostic, suggestion, field_loc);
 }
 
 pub fn record_proj_not_record<'core>(
@@ -80,13 +104,14 @@ pub fn record_proj_not_record<'core>(
 
     let diagnostic = Diagnostic::error()
         .with_message(format!("Expected record, found `{scrut_type}`"))
+        .with_code(codes::RECORD_PROJ_NOT_RECORD.code)
         .with_labels(vec![Label::primary(scrut_loc.file, scrut_loc.range)]);
     elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
 }
 
 pub fn unable_to_unify<'core>(
     elaborator: &mut Elaborator<'_, 'core, '_>,
-    error: crate::unify::UnifyError,
+    error: &crate::unify::UnifyError<'core>,
     from: &Type<'core>,
     to: &Type<'core>,
     loc: Location,
@@ -95,13 +120,76 @@ pub fn unable_to_unify<'core>(
     let to = elaborator.quote_env().quote(to);
     let found = elaborator.pretty(&from);
     let expected = elaborator.pretty(&to);
-    let diagnostic = error.to_diagnostic(loc.file, loc.range, &expected, &found);
+    let diagnostic =
+        error.to_diagnostic(loc.file, loc.range, &expected, &found, |binders, expr| {
+            elaborator.pretty_under(binders, expr)
+        });
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn eval_limit_exceeded(elaborator: &mut Elaborator, limit: LimitExceeded, loc: Location) {
+    let label = match limit {
+        LimitExceeded::Steps(steps) => format!("evaluation took more than {steps} steps"),
+        LimitExceeded::Depth(depth) => format!("evaluation nested more than {depth} levels deep"),
+        LimitExceeded::ArenaBytes(bytes) => format!("evaluation allocated more than {bytes} bytes"),
+    };
+    let diagnostic = Diagnostic::error()
+        .with_message("Evaluation limit exceeded")
+        .with_code(codes::EVAL_LIMIT_EXCEEDED.code)
+        .with_labels(vec![Label::primary(loc.file, loc.range).with_message(label)]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn bytecode_eval_failed(elaborator: &mut Elaborator, error: pion_vm::Error, loc: Location) {
+    let diagnostic = Diagnostic::bug()
+        .with_message("The bytecode virtual machine failed to evaluate an expression")
+        .with_labels(vec![
+            Label::primary(loc.file, loc.range).with_message(error.to_string())
+        ])
+        .with_notes(vec![String::from(
+            "note: this is a bug in pion: the value shown was computed by the normalizer instead",
+        )]);
     elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
 }
 
 pub fn unreachable_match_case(elaborator: &mut Elaborator, loc: Location) {
     let diagnostic = Diagnostic::warning()
         .with_message("Unreachable match case")
+        .with_code(codes::UNREACHABLE_MATCH_CASE.code)
+        .with_labels(vec![Label::primary(loc.file, loc.range)]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn unused_binding(elaborator: &mut Elaborator, kind: BinderKind, name: Symbol, loc: Location) {
+    let diagnostic = Diagnostic::warning()
+        .with_message(format!("Unused {} `{name}`", kind.description()))
+        .with_code(codes::UNUSED_BINDING.code)
+        .with_labels(vec![Label::primary(loc.file, loc.range)])
+        .with_notes(vec![format!(
+            "help: if this is intentional, prefix it with an underscore: `_{name}`"
+        )]);
+    let suggestion = Suggestion {
+        file_id: loc.file,
+        range: loc.range.into(),
+        replacement: format!("_{name}"),
+    };
+    (elaborator.diagnostic_handler)
+        .handle_diagnostic_with_suggestions(diagnostic, vec![suggestion]);
+}
+
+pub fn shadowed_binding(
+    elaborator: &mut Elaborator,
+    name: Symbol,
+    shadows_prim: bool,
+    loc: Location,
+) {
+    let shadowed = match shadows_prim {
+        true => "a primitive",
+        false => "an earlier binding",
+    };
+    let diagnostic = Diagnostic::warning()
+        .with_message(format!("`{name}` shadows {shadowed}"))
+        .with_code(codes::SHADOWED_BINDING.code)
         .with_labels(vec![Label::primary(loc.file, loc.range)]);
     elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
 }
@@ -109,6 +197,7 @@ pub fn unreachable_match_case(elaborator: &mut Elaborator, loc: Location) {
 pub fn inexhaustive_match(elaborator: &mut Elaborator, loc: Location) {
     let diagnostic = Diagnostic::error()
         .with_message("Inexhaustive match")
+        .with_code(codes::INEXHAUSTIVE_MATCH.code)
         .with_labels(vec![Label::primary(loc.file, loc.range)]);
     elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
 }
@@ -116,6 +205,15 @@ pub fn inexhaustive_match(elaborator: &mut Elaborator, loc: Location) {
 pub fn recursive_let_not_function(elaborator: &mut Elaborator, loc: Location) {
     let diagnostic = Diagnostic::error()
         .with_message("recursive bindings must be function literals")
+        .with_code(codes::RECURSIVE_LET_NOT_FUNCTION.code)
+        .with_labels(vec![Label::primary(loc.file, loc.range)]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn recursive_let_dependent(elaborator: &mut Elaborator, loc: Location) {
+    let diagnostic = Diagnostic::error()
+        .with_message("the return type of a recursive function must not depend on its parameter")
+        .with_code(codes::RECURSIVE_LET_DEPENDENT.code)
         .with_labels(vec![Label::primary(loc.file, loc.range)]);
     elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
 }
@@ -130,6 +228,7 @@ pub fn fun_app_not_fun<'core>(
 
     let diagnostic = Diagnostic::error()
         .with_message(format!("Expected function, found `{fun_type}`"))
+        .with_code(codes::FUN_APP_NOT_FUN.code)
         .with_labels(vec![Label::primary(fun_loc.file, fun_loc.range)]);
     elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
 }
@@ -149,6 +248,7 @@ pub fn fun_app_too_many_args<'core>(
 
     let diagnostic = Diagnostic::error()
         .with_message("Called function with too many arguments")
+        .with_code(codes::FUN_APP_TOO_MANY_ARGS.code)
         .with_labels(vec![
             Label::secondary(fun_loc.file, fun_loc.range),
             Label::primary(arg_loc.file, arg_loc.range),
@@ -181,6 +281,7 @@ pub fn fun_app_plicity_mismatch<'core>(
             given.description(),
             expected.description()
         ))
+        .with_code(codes::FUN_APP_PLICITY_MISMATCH.code)
         .with_labels(vec![
             Label::primary(arg_loc.file, arg_loc.range)
                 .with_message(format!("{} argument", given.description())),
@@ -190,6 +291,228 @@ pub fn fun_app_plicity_mismatch<'core>(
     elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
 }
 
+pub fn unknown_implicit_param<'core>(
+    elaborator: &mut Elaborator<'_, 'core, '_>,
+    name: Symbol,
+    fun_type: &Type<'core>,
+    name_loc: Location,
+    fun_loc: Location,
+    params: impl IntoIterator<Item = Symbol>,
+) {
+    let candidates = params.into_iter().map(Symbol::as_str);
+    let suggestion = edit_distance::find_best_match(name.as_str(), candidates);
+
+    let fun_type = elaborator.quote_env().quote(fun_type);
+    let fun_type = elaborator.pretty(&fun_type);
+
+    let diagnostic = Diagnostic::error()
+        .with_message(format!("Function has no implicit parameter named `{name}`"))
+        .with_code(codes::UNKNOWN_IMPLICIT_PARAM.code)
+        .with_labels(vec![
+            Label::primary(name_loc.file, name_loc.range),
+            Label::secondary(fun_loc.file, fun_loc.range)
+                .with_message(format!("function has type {fun_type}")),
+        ]);
+    report_with_suggestion(elaborator, diagnostic, suggestion, name_loc);
+}
+
+pub fn instance_not_found<'core>(
+    elaborator: &mut Elaborator<'_, 'core, '_>,
+    error: &SearchError,
+    r#type: &Type<'core>,
+    loc: Location,
+) {
+    let r#type = elaborator.quote_env().quote(r#type);
+    let r#type = elaborator.pretty(&r#type);
+
+    let diagnostic = match error {
+        SearchError::NotFound | SearchError::DepthLimit => Diagnostic::error()
+            .with_message(format!("No instance found for `{type}`"))
+            .with_code(codes::INSTANCE_NOT_FOUND.code),
+        SearchError::Ambiguous(_) => Diagnostic::error()
+            .with_message(format!("Ambiguous instances for `{type}`"))
+            .with_code(codes::AMBIGUOUS_INSTANCE.code),
+    };
+    let notes = match error {
+        SearchError::NotFound => Vec::new(),
+        SearchError::DepthLimit => vec![format!(
+            "help: the search for instances stopped after {DEPTH_LIMIT} nested instance arguments"
+        )],
+        SearchError::Ambiguous(names) => (names.iter())
+            .map(|name| match name {
+                Some(name) => format!("candidate: `{name}`"),
+                None => "candidate: an unnamed instance".to_owned(),
+            })
+            .collect(),
+    };
+    let diagnostic = diagnostic
+        .with_labels(vec![Label::primary(loc.file, loc.range)
+            .with_message(format!("instance argument of type `{type}`"))])
+        .with_notes(notes);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn unknown_print_option(elaborator: &mut Elaborator, option: Symbol, option_loc: Location) {
+    let suggestion = edit_distance::find_best_match(option.as_str(), PRINT_OPTIONS.iter().copied());
+
+    let diagnostic = Diagnostic::error()
+        .with_message(format!("Unknown `#print` option `{option}`"))
+        .with_code(codes::UNKNOWN_PRINT_OPTION.code)
+        .with_labels(vec![Label::primary(option_loc.file, option_loc.range)]);
+    report_with_suggestion(elaborator, diagnostic, suggestion, option_loc);
+}
+
+/// The options accepted by `#print`.
+const PRINT_OPTIONS: &[&str] = &["optimized"];
+
+pub fn unknown_reduce_option(
+    elaborator: &mut Elaborator,
+    command: ReduceCommand,
+    option: Symbol,
+    option_loc: Location,
+) {
+    let suggestion =
+        edit_distance::find_best_match(option.as_str(), REDUCE_OPTIONS.iter().copied());
+
+    let diagnostic = Diagnostic::error()
+        .with_message(format!("Unknown `{}` option `{option}`", command.keyword()))
+        .with_code(codes::INVALID_REDUCE_OPTION.code)
+        .with_labels(vec![Label::primary(option_loc.file, option_loc.range)]);
+    report_with_suggestion(elaborator, diagnostic, suggestion, option_loc);
+}
+
+pub fn invalid_reduce_option_value(
+    elaborator: &mut Elaborator,
+    option: Symbol,
+    expected: OptionValue,
+    value_loc: Location,
+) {
+    let diagnostic = Diagnostic::error()
+        .with_message(format!("Invalid value for option `{option}`"))
+        .with_code(codes::INVALID_REDUCE_OPTION.code)
+        .with_labels(vec![Label::primary(value_loc.file, value_loc.range)
+            .with_message(format!("expected {}", expected.description()))]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn reduce_limit_exceeded(
+    elaborator: &mut Elaborator,
+    command: ReduceCommand,
+    steps: u32,
+    loc: Location,
+) {
+    let diagnostic = Diagnostic::error()
+        .with_message("Evaluation limit exceeded")
+        .with_code(codes::EVAL_LIMIT_EXCEEDED.code)
+        .with_labels(vec![Label::primary(loc.file, loc.range)
+            .with_message(format!("reduction took more than {steps} steps"))])
+        .with_notes(vec![format!(
+            "help: to allow more steps, write `{} <expr>, steps = <n>`",
+            command.keyword()
+        )]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn reduce_depth_exceeded(elaborator: &mut Elaborator, depth: u32, loc: Location) {
+    let diagnostic = Diagnostic::error()
+        .with_message("Evaluation limit exceeded")
+        .with_code(codes::EVAL_LIMIT_EXCEEDED.code)
+        .with_labels(vec![Label::primary(loc.file, loc.range).with_message(
+            format!("reduction nested more than {depth} levels deep"),
+        )]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn assert_eq_failed(
+    elaborator: &mut Elaborator,
+    lhs: &str,
+    rhs: &str,
+    lhs_loc: Location,
+    rhs_loc: Location,
+) {
+    let diagnostic = Diagnostic::error()
+        .with_message("Assertion failed: expressions are not equal")
+        .with_code(codes::ASSERTION_FAILED.code)
+        .with_labels(vec![
+            Label::primary(lhs_loc.file, lhs_loc.range)
+                .with_message(format!("normalizes to `{lhs}`")),
+            Label::primary(rhs_loc.file, rhs_loc.range)
+                .with_message(format!("normalizes to `{rhs}`")),
+        ]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn assert_type_failed(
+    elaborator: &mut Elaborator,
+    found: &str,
+    expected: &str,
+    expr_loc: Location,
+    type_loc: Location,
+) {
+    let diagnostic = Diagnostic::error()
+        .with_message("Assertion failed: types are not equal")
+        .with_code(codes::ASSERTION_FAILED.code)
+        .with_labels(vec![
+            Label::primary(expr_loc.file, expr_loc.range)
+                .with_message(format!("has type `{found}`")),
+            Label::secondary(type_loc.file, type_loc.range)
+                .with_message(format!("expected type `{expected}`")),
+        ]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn fail_block_succeeded(elaborator: &mut Elaborator, loc: Location) {
+    let diagnostic = Diagnostic::error()
+        .with_message("Assertion failed: block did not report an error")
+        .with_code(codes::ASSERTION_FAILED.code)
+        .with_labels(vec![Label::primary(loc.file, loc.range)]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+pub fn fail_code_not_reported(
+    elaborator: &mut Elaborator,
+    code: Symbol,
+    reported: &[&str],
+    loc: Location,
+    code_loc: Location,
+) {
+    let label = match reported {
+        [] => String::from("the block reported no errors"),
+        _ => format!("the block reported {}", reported.join(", ")),
+    };
+    let diagnostic = Diagnostic::error()
+        .with_message(format!("Assertion failed: block did not report `{code}`"))
+        .with_code(codes::ASSERTION_FAILED.code)
+        .with_labels(vec![
+            Label::primary(code_loc.file, code_loc.range).with_message(label),
+            Label::secondary(loc.file, loc.range),
+        ]);
+    elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+}
+
+/// Report `diagnostic`, suggesting that the name at `loc` be replaced with
+/// `suggestion`.
+fn report_with_suggestion(
+    elaborator: &mut Elaborator,
+    diagnostic: Diagnostic<usize>,
+    suggestion: Option<&str>,
+    loc: Location,
+) {
+    let Some(suggestion) = suggestion else {
+        elaborator.diagnostic_handler.handle_diagnostic(diagnostic);
+        return;
+    };
+
+    let diagnostic = diagnostic.with_notes(vec![format!("help: did you mean `{suggestion}`?")]);
+    let suggestion = Suggestion {
+        file_id: loc.file,
+        range: loc.range.into(),
+        replacement: suggestion.to_owned(),
+    };
+    (elaborator.diagnostic_handler)
+        .handle_diagnostic_with_suggestions(diagnostic, vec![suggestion]);
+}
+
 const fn pluralize<'a>(count: usize, singular: &'a str, plural: &'a str) -> &'a str {
     if count == 1 {
         singular
@@ -1,22 +1,45 @@
-use pion_core::env::{EnvLen, RelativeVar, SharedEnv, UniqueEnv};
-use pion_core::semantics::{Type, Value};
+use pion_core::env::{AbsoluteVar, EnvLen, RelativeVar, SharedEnv, UniqueEnv};
+use pion_core::semantics::{MetaExpr, Type, Value};
 use pion_core::syntax::Expr;
 use pion_symbol::Symbol;
 use text_size::TextRange;
 
-use crate::unify::PartialRenaming;
+use crate::instance::InstanceGoal;
+use crate::unify::{PartialRenaming, Postponed};
 
 #[derive(Default)]
 pub struct ElabEnv<'core> {
     pub locals: LocalEnv<'core>,
     pub metas: MetaEnv<'core>,
     pub renaming: PartialRenaming,
+    /// Unification problems that have been postponed, to be retried whenever
+    /// a metavariable is solved.
+    pub constraints: Vec<Constraint<'core>>,
+    /// Instance arguments that have not been found yet.
+    pub instance_goals: Vec<InstanceGoal<'core>>,
 }
 
-#[derive(Default)]
+/// A postponed unification problem, and where it arose.
+#[derive(Debug, Clone)]
+pub struct Constraint<'core> {
+    pub range: TextRange,
+    /// The names of the local variables in scope where the problem arose.
+    pub names: Vec<Option<Symbol>>,
+    /// The types whose unification produced the problem.
+    pub found: Type<'core>,
+    pub expected: Type<'core>,
+    pub problem: Postponed<'core>,
+}
+
+#[derive(Default, Clone)]
 pub struct LocalEnv<'core> {
     pub names: UniqueEnv<Option<Symbol>>,
     pub infos: UniqueEnv<LocalInfo>,
+    /// Whether each local is an instance: a `let instance` binding or an
+    /// instance parameter.
+    pub instances: UniqueEnv<bool>,
+    /// Whether each local has been referred to by name.
+    pub used: UniqueEnv<bool>,
     pub exprs: UniqueEnv<Option<Expr<'core>>>,
     pub types: UniqueEnv<Type<'core>>,
     pub values: SharedEnv<Value<'core>>,
@@ -41,6 +64,8 @@ impl<'core> LocalEnv<'core> {
     ) {
         self.names.push(name);
         self.infos.push(info);
+        self.instances.push(false);
+        self.used.push(false);
         self.exprs.push(expr);
         self.types.push(r#type);
         self.values.push(value);
@@ -64,6 +89,8 @@ impl<'core> LocalEnv<'core> {
     pub fn pop(&mut self) {
         self.names.pop();
         self.infos.pop();
+        self.instances.pop();
+        self.used.pop();
         self.exprs.pop();
         self.types.pop();
         self.values.pop();
@@ -72,11 +99,23 @@ impl<'core> LocalEnv<'core> {
     pub fn truncate(&mut self, len: EnvLen) {
         self.names.truncate(len);
         self.infos.truncate(len);
+        self.instances.truncate(len);
+        self.used.truncate(len);
         self.exprs.truncate(len);
         self.types.truncate(len);
         self.values.truncate(len);
     }
 
+    /// Make the last `count` locals instances.
+    pub fn mark_instances(&mut self, count: usize) {
+        for instance in self.instances.iter_mut().rev().take(count) {
+            *instance = true;
+        }
+    }
+
+    /// Record that the local `var` has been referred to by name.
+    pub fn mark_used(&mut self, var: RelativeVar) { self.used.set_relative(var, true); }
+
     pub fn lookup(&self, sym: Symbol) -> Option<RelativeVar> {
         self.names
             .iter()
@@ -89,11 +128,14 @@ impl<'core> LocalEnv<'core> {
     pub fn next_var(&self) -> Value<'core> { Value::local_var(self.len().to_absolute()) }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct MetaEnv<'core> {
     pub sources: UniqueEnv<MetaSource>,
     pub types: UniqueEnv<Type<'core>>,
     pub values: UniqueEnv<Option<Value<'core>>>,
+    /// The expressions to zonk solved metavariables to, if they should not be
+    /// zonked to their values.
+    pub exprs: UniqueEnv<Option<MetaExpr<'core>>>,
 }
 
 impl<'core> MetaEnv<'core> {
@@ -103,8 +145,79 @@ impl<'core> MetaEnv<'core> {
         self.sources.push(source);
         self.types.push(r#type);
         self.values.push(None);
+        self.exprs.push(None);
     }
 
+    /// The metavariables after the first `start`.
+    pub fn since(&self, start: EnvLen) -> Self {
+        let start = usize::from(start);
+        let mut metas = Self::default();
+        for ((source, r#type, value), expr) in self.iter().zip(self.exprs.iter()).skip(start) {
+            metas.sources.push(source);
+            metas.types.push(r#type.clone());
+            metas.values.push(value.clone());
+            metas.exprs.push(expr.clone());
+        }
+        metas
+    }
+
+    /// Add the metavariables of `other` after those of `self`.
+    pub fn extend(&mut self, other: &Self) {
+        for ((source, r#type, value), expr) in other.iter().zip(other.exprs.iter()) {
+            self.sources.push(source);
+            self.types.push(r#type.clone());
+            self.values.push(value.clone());
+            self.exprs.push(expr.clone());
+        }
+    }
+
+    /// Remember which metavariables exist and are unsolved, so that the
+    /// changes made after this can be [undone][Self::undo].
+    pub fn snapshot(&self) -> MetaSnapshot {
+        let unsolved = (AbsoluteVar::iter().zip(self.values.iter()))
+            .filter(|(_, value)| value.is_none())
+            .map(|(var, _)| var)
+            .collect();
+        MetaSnapshot {
+            len: self.len(),
+            unsolved,
+        }
+    }
+
+    /// Remove the metavariables added since `snapshot`, and forget the
+    /// solutions found since then, returning them so that they can be
+    /// [redone][Self::redo].
+    pub fn undo(&mut self, snapshot: &MetaSnapshot) -> MetaChanges<'core> {
+        let added = self.since(snapshot.len);
+        self.sources.truncate(snapshot.len);
+        self.types.truncate(snapshot.len);
+        self.values.truncate(snapshot.len);
+        self.exprs.truncate(snapshot.len);
+
+        let mut solved = Vec::new();
+        for var in &snapshot.unsolved {
+            if let Some(value) = self.values.get_absolute(*var).unwrap().clone() {
+                let expr = self.exprs.get_absolute(*var).unwrap().clone();
+                solved.push((*var, value, expr));
+                self.values.set_absolute(*var, None);
+                self.exprs.set_absolute(*var, None);
+            }
+        }
+        MetaChanges { added, solved }
+    }
+
+    /// Make the changes returned by [`Self::undo`] again.
+    pub fn redo(&mut self, changes: MetaChanges<'core>) {
+        for (var, value, expr) in changes.solved {
+            self.values.set_absolute(var, Some(value));
+            self.exprs.set_absolute(var, expr);
+        }
+        self.extend(&changes.added);
+    }
+
+    /// Whether every metavariable has been solved.
+    pub fn all_solved(&self) -> bool { self.values.iter().all(Option::is_some) }
+
     pub fn iter(
         &self,
     ) -> impl Iterator<Item = (MetaSource, &Type<'core>, &Option<Value<'core>>)> + '_ {
@@ -116,6 +229,19 @@ impl<'core> MetaEnv<'core> {
     }
 }
 
+/// The metavariables that existed, and those that were unsolved, at some
+/// point during elaboration.
+pub struct MetaSnapshot {
+    len: EnvLen,
+    unsolved: Vec<AbsoluteVar>,
+}
+
+/// The changes to the metavariables since a [`MetaSnapshot`].
+pub struct MetaChanges<'core> {
+    added: MetaEnv<'core>,
+    solved: Vec<(AbsoluteVar, Value<'core>, Option<MetaExpr<'core>>)>,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum MetaSource {
     PatType {
@@ -132,6 +258,9 @@ pub enum MetaSource {
         range: TextRange,
         name: Option<Symbol>,
     },
+    InstanceArg {
+        range: TextRange,
+    },
     ListElemType {
         range: TextRange,
     },
@@ -147,6 +276,7 @@ impl MetaSource {
             | Self::HoleType { range, .. }
             | Self::HoleExpr { range, .. }
             | Self::ImplicitArg { range, .. }
+            | Self::InstanceArg { range, .. }
             | Self::ListElemType { range, .. }
             | Self::MatchResultType { range, .. } => *range,
         }
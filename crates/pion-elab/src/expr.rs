@@ -14,6 +14,7 @@ use text_size::TextRange;
 
 use super::{Elaborator, MetaSource};
 use crate::diagnostics;
+use crate::lint::BinderKind;
 
 impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
     pub fn synth_lit(
@@ -84,6 +85,7 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
             }
             surface::Expr::VarRef(Located { data: name, .. }) => {
                 if let Some(var) = self.env.locals.lookup(name) {
+                    self.env.locals.mark_used(var);
                     let r#type = self.env.locals.types.get_relative(var).unwrap().clone();
                     return (Expr::LocalVar(var), r#type);
                 }
@@ -104,9 +106,10 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                 (expr, r#type)
             }
             surface::Expr::Paren(expr) => self.synth_expr(expr),
-            surface::Expr::Ann(expr, r#type) => {
-                let r#type = self.check_expr_is_type(r#type);
+            surface::Expr::Ann(expr, surface_type) => {
+                let r#type = self.check_expr_is_type(surface_type);
                 let r#type = self.eval_env().eval(&r#type);
+                self.report_eval_limit(surface_type.range);
                 let expr = self.check_expr(expr, &r#type);
                 (expr, r#type)
             }
@@ -154,12 +157,32 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                         (expr, r#type) = self.insert_implicit_apps(surface_arg.range, expr, r#type);
                     }
 
+                    if let Some(name) = surface_arg.data.name {
+                        let params = self.implicit_param_names(&r#type);
+                        if params.contains(&name.data) {
+                            (expr, r#type) = self.insert_implicit_apps_until(
+                                surface_arg.range,
+                                name.data,
+                                expr,
+                                r#type,
+                            );
+                        } else if let Value::FunType { .. } = r#type {
+                            let name_loc = Location::new(self.file_id, name.range);
+                            let fun_loc = Location::new(self.file_id, fun.range);
+                            diagnostics::unknown_implicit_param(
+                                self, name.data, &fun_type, name_loc, fun_loc, params,
+                            );
+                            return (Expr::Error, Type::Error);
+                        }
+                    }
+
                     match r#type {
                         Value::FunType { param, body }
                             if param.plicity == surface_arg.data.plicity =>
                         {
                             let arg_expr = self.check_expr(surface_arg.data.expr, param.r#type);
                             let arg_value = self.eval_env().eval(&arg_expr);
+                            self.report_eval_limit(surface_arg.range);
                             let (fun_expr, arg_expr) = self.bump.alloc((expr, arg_expr));
                             expr = Expr::FunApp {
                                 fun: &*fun_expr,
@@ -311,7 +334,8 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                     Value::RecordType(mut telescope) => {
                         let Some(_) = telescope.fields.iter().find(|(n, _)| *n == name.data) else {
                             let loc = Location::new(self.file_id, name.range);
-                            diagnostics::field_not_found(self, name.data, loc);
+                            let fields = telescope.fields.iter().map(|(n, _)| *n);
+                            diagnostics::field_not_found(self, name.data, loc, fields);
                             return (Expr::Error, Type::Error);
                         };
 
@@ -342,7 +366,7 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
     }
 
     /// Wrap an expr in fresh implicit applications that correspond to implicit
-    /// parameters in the type provided.
+    /// and instance parameters in the type provided.
     fn insert_implicit_apps(
         &mut self,
         fun_range: TextRange,
@@ -352,19 +376,8 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
         loop {
             r#type = self.elim_env().update_metas(&r#type);
             match r#type {
-                Value::FunType { param, body } if param.plicity.is_implicit() => {
-                    let source = MetaSource::ImplicitArg {
-                        range: fun_range,
-                        name: param.name,
-                    };
-                    let arg_expr = self.push_unsolved_expr(source, param.r#type.clone());
-                    let arg_value = self.eval_env().eval(&arg_expr);
-
-                    let (fun, arg_expr) = self.bump.alloc((expr, arg_expr));
-
-                    let arg = FunArg::new(param.plicity, &*arg_expr);
-                    expr = Expr::FunApp { fun, arg };
-                    r#type = self.elim_env().apply_closure(body, arg_value);
+                Value::FunType { param, body } if !param.plicity.is_explicit() => {
+                    (expr, r#type) = self.insert_implicit_app(fun_range, expr, param, body);
                 }
                 _ => break,
             }
@@ -372,6 +385,80 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
         (expr, r#type)
     }
 
+    /// The names of the implicit parameters before the first explicit
+    /// parameter of `r#type`.
+    fn implicit_param_names(&self, r#type: &Type<'core>) -> Vec<Symbol> {
+        let mut names = Vec::new();
+        let mut r#type = self.elim_env().update_metas(r#type);
+        loop {
+            match r#type {
+                Value::FunType { param, body } if !param.plicity.is_explicit() => {
+                    if param.plicity.is_implicit() {
+                        names.extend(param.name);
+                    }
+                    r#type = self.elim_env().apply_closure(body, Value::Error);
+                    r#type = self.elim_env().update_metas(&r#type);
+                }
+                _ => return names,
+            }
+        }
+    }
+
+    /// Like [`Self::insert_implicit_apps`], but stop at the implicit parameter
+    /// called `name`.
+    fn insert_implicit_apps_until(
+        &mut self,
+        arg_range: TextRange,
+        name: Symbol,
+        mut expr: Expr<'core>,
+        mut r#type: Type<'core>,
+    ) -> (Expr<'core>, Type<'core>) {
+        loop {
+            r#type = self.elim_env().update_metas(&r#type);
+            match r#type {
+                Value::FunType { param, body }
+                    if param.plicity.is_instance()
+                        || (param.plicity.is_implicit() && param.name != Some(name)) =>
+                {
+                    (expr, r#type) = self.insert_implicit_app(arg_range, expr, param, body);
+                }
+                _ => break,
+            }
+        }
+        (expr, r#type)
+    }
+
+    /// Apply `expr` to a fresh metavariable for the implicit or instance
+    /// parameter `param`. Instance arguments are then solved by instance
+    /// search.
+    fn insert_implicit_app(
+        &mut self,
+        range: TextRange,
+        expr: Expr<'core>,
+        param: FunParam<&'core Value<'core>>,
+        body: Closure<'core>,
+    ) -> (Expr<'core>, Type<'core>) {
+        let source = match param.plicity {
+            Plicity::Instance => MetaSource::InstanceArg { range },
+            _ => MetaSource::ImplicitArg {
+                range,
+                name: param.name,
+            },
+        };
+        let arg_expr = self.push_unsolved_expr(source, param.r#type.clone());
+        if param.plicity.is_instance() {
+            self.push_instance_goal(range, arg_expr, param.r#type.clone());
+        }
+        let arg_value = self.eval_env().eval(&arg_expr);
+
+        let (fun, arg_expr) = self.bump.alloc((expr, arg_expr));
+
+        let arg = FunArg::new(param.plicity, &*arg_expr);
+        let expr = Expr::FunApp { fun, arg };
+        let r#type = self.elim_env().apply_closure(body, arg_value);
+        (expr, r#type)
+    }
+
     // FIXME: check patterns for exhaustiveness
     fn synth_fun_type(
         &mut self,
@@ -425,9 +512,13 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                     let bindings = self.destruct_pat(&pat, &var, &param_type, true);
 
                     self.env.locals.push_param(param.name, param_type.clone());
+                    if param.plicity.is_instance() {
+                        self.env.locals.mark_instances(1);
+                    }
                     self.push_let_bindings(&bindings);
                     let (body_expr, body_type) = self.synth_fun_lit(surface_params, surface_body);
                     let body_type = self.quote_env().quote(&body_type);
+                    self.lint_unused(BinderKind::Param, &surface_param.data.pat, local_len);
                     self.env.locals.truncate(local_len);
 
                     let body_expr = Expr::lets(self.bump, &bindings, body_expr);
@@ -463,7 +554,12 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
     ) -> Expr<'core> {
         let expected = self.elim_env().update_metas(expected);
         match surface_expr.data {
-            surface::Expr::Error => Expr::Error,
+            surface::Expr::Error => {
+                // Solve any metavariables in the expected type, so that they are not reported
+                // as unsolved.
+                let _ = self.unify_env().unify(&Type::Error, &expected);
+                Expr::Error
+            }
             surface::Expr::Hole => {
                 let range = surface_expr.range;
                 let expr = self.push_unsolved_expr(MetaSource::HoleExpr { range }, expected);
@@ -571,13 +667,14 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
         };
 
         match expected {
-            // If an implicit function is expected, try to generalize the
-            // function literal by wrapping it in an implicit function
+            // If an implicit or instance function is expected, try to
+            // generalize the function literal by wrapping it in an implicit or
+            // instance function
             Value::FunType {
                 param: expected_param,
                 body: expected_body,
             } if surface_param.data.plicity.is_explicit()
-                && expected_param.plicity.is_implicit() =>
+                && !expected_param.plicity.is_explicit() =>
             {
                 let r#type = self.quote_env().quote(expected_param.r#type);
 
@@ -585,6 +682,9 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                 self.env
                     .locals
                     .push_param(expected_param.name, expected_param.r#type.clone());
+                if expected_param.plicity.is_instance() {
+                    self.env.locals.mark_instances(1);
+                }
                 let expected = self.elim_env().apply_closure(expected_body.clone(), var);
                 let body = self.check_fun_lit(surface_params, surface_body, &expected);
                 self.env.locals.pop();
@@ -608,11 +708,15 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                     self.env
                         .locals
                         .push_param(param.name, expected_param.r#type.clone());
+                    if param.plicity.is_instance() {
+                        self.env.locals.mark_instances(1);
+                    }
                     self.push_let_bindings(&bindings);
                     let expected = self
                         .elim_env()
                         .apply_closure(expected_body.clone(), arg_value);
                     let body_expr = self.check_fun_lit(rest_params, surface_body, &expected);
+                    self.lint_unused(BinderKind::Param, &surface_param.data.pat, local_len);
                     self.env.locals.truncate(local_len);
 
                     Expr::lets(self.bump, &bindings, body_expr)
@@ -659,11 +763,11 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
             _ => (expr, from),
         };
 
-        match self.unify_env().unify(&from, to) {
+        match self.unify(range, &from, to) {
             Ok(()) => expr,
             Err(error) => {
                 let loc = Location::new(self.file_id, range);
-                diagnostics::unable_to_unify(self, error, &from, to, loc);
+                diagnostics::unable_to_unify(self, &error, &from, to, loc);
                 Expr::Error
             }
         }
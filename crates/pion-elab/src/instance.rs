@@ -0,0 +1,268 @@
+//! Instance arguments.
+//!
+//! An instance parameter, `(@@eq : Eq A)`, is filled in by searching the
+//! instances in scope: the bindings declared with `let instance`, and the
+//! instance parameters of enclosing functions. When a function is applied, a
+//! metavariable is inserted for each of its instance arguments, and an
+//! [`InstanceGoal`] is recorded for it. A goal is solved as soon as its type
+//! has no unsolved metavariables, and goals that are left at the end of the
+//! top-level item are solved with whatever is known by then, or reported.
+//!
+//! An instance may itself have implicit and instance parameters, like
+//! `forall (@A : Type) (@@eq : Eq A) -> Eq (List A)`. Its implicit parameters
+//! are solved by unifying its type with the goal, and its instance parameters
+//! by searching again, at most [`DEPTH_LIMIT`] levels deep.
+
+use pion_core::env::RelativeVar;
+use pion_core::semantics::{Head, MetaExpr, Type, Value};
+use pion_core::syntax::{Expr, FunArg};
+use pion_symbol::Symbol;
+use pion_util::location::Location;
+use text_size::TextRange;
+
+use crate::env::{LocalEnv, MetaSource};
+use crate::{diagnostics, Elaborator};
+
+/// How many instance parameters deep the search for an instance may go.
+pub const DEPTH_LIMIT: usize = 16;
+
+/// An instance argument that has not been found yet.
+#[derive(Clone)]
+pub struct InstanceGoal<'core> {
+    pub range: TextRange,
+    /// The local variables in scope where the argument was inserted.
+    pub locals: LocalEnv<'core>,
+    /// The metavariable inserted for the argument, applied to the local
+    /// variables.
+    pub expr: Expr<'core>,
+    pub r#type: Type<'core>,
+}
+
+pub enum SearchError {
+    NotFound,
+    Ambiguous(Vec<Option<Symbol>>),
+    DepthLimit,
+}
+
+impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
+    /// Record that `expr`, the metavariable inserted for an instance argument
+    /// of type `r#type`, should be solved by instance search.
+    pub(crate) fn push_instance_goal(
+        &mut self,
+        range: TextRange,
+        expr: Expr<'core>,
+        r#type: Type<'core>,
+    ) {
+        self.env.instance_goals.push(InstanceGoal {
+            range,
+            locals: self.env.locals.clone(),
+            expr,
+            r#type,
+        });
+        self.solve_instance_goals();
+    }
+
+    /// Solve the goals whose types have no unsolved metavariables, until no
+    /// more can be solved.
+    pub(crate) fn solve_instance_goals(&mut self) {
+        while self.solve_instance_goals_once(false) {
+            self.retry_constraints();
+        }
+    }
+
+    /// Solve the remaining goals with whatever is known, and report those that
+    /// cannot be solved.
+    pub(crate) fn report_unsolved_instance_goals(&mut self) {
+        while self.solve_instance_goals_once(true) {
+            self.retry_constraints();
+        }
+    }
+
+    /// Try to solve each goal once, returning whether any were solved. If
+    /// `last_try` is set, goals that cannot be solved are reported and
+    /// forgotten.
+    fn solve_instance_goals_once(&mut self, last_try: bool) -> bool {
+        let mut solved_any = false;
+        for goal in std::mem::take(&mut self.env.instance_goals) {
+            let outer_locals = std::mem::replace(&mut self.env.locals, goal.locals.clone());
+            let solved = self.solve_instance_goal(&goal, last_try);
+            self.env.locals = outer_locals;
+            match solved {
+                true => solved_any = true,
+                false => self.env.instance_goals.push(goal),
+            }
+        }
+        solved_any
+    }
+
+    /// Solve `goal`, in the environment it arose in, returning whether it no
+    /// longer needs to be solved.
+    fn solve_instance_goal(&mut self, goal: &InstanceGoal<'core>, last_try: bool) -> bool {
+        let arg = self.eval_env().eval(&goal.expr);
+        if !matches!(
+            self.elim_env().update_metas(&arg),
+            Value::Neutral(Head::MetaVar(_), _)
+        ) {
+            // Already solved by unification.
+            return true;
+        }
+
+        let r#type = self.elim_env().update_metas(&goal.r#type);
+        if !last_try {
+            let type_expr = self.quote_env().quote(&r#type);
+            if self.zonk_env().zonk(&type_expr).references_meta_vars() {
+                return false;
+            }
+        }
+
+        let solution = match r#type {
+            Value::Error => Ok(Expr::Error),
+            _ => self.search_instance(goal.range, &r#type, 0),
+        };
+        let solution = match solution {
+            Ok(solution) => solution,
+            Err(_) if !last_try => return false,
+            Err(error) => {
+                let loc = Location::new(self.file_id, goal.range);
+                diagnostics::instance_not_found(self, &error, &r#type, loc);
+                Expr::Error
+            }
+        };
+        self.solve_instance_arg(goal.expr, solution);
+        true
+    }
+
+    /// Solve `arg`, the metavariable inserted for an instance argument, with
+    /// the instance `solution`, returning whether they unify. The argument is
+    /// zonked to `solution` itself, so that it refers to the instance by name
+    /// instead of copying its definition.
+    fn solve_instance_arg(&mut self, arg: Expr<'core>, solution: Expr<'core>) -> bool {
+        let arg_value = self.eval_env().eval(&arg);
+        let solution_value = self.eval_env().eval(&solution);
+        if self.unify_env().unify(&solution_value, &arg_value).is_err() {
+            return false;
+        }
+
+        let mut head = &arg;
+        while let Expr::FunApp { fun, .. } = head {
+            head = fun;
+        }
+        if let Expr::MetaVar(var) = head {
+            let meta_expr = MetaExpr {
+                local_len: self.env.locals.len(),
+                occurrence: arg,
+                expr: solution,
+            };
+            self.env.metas.exprs.set_absolute(*var, Some(meta_expr));
+        }
+        true
+    }
+
+    /// Find the unique instance of `r#type` in scope.
+    fn search_instance(
+        &mut self,
+        range: TextRange,
+        r#type: &Type<'core>,
+        depth: usize,
+    ) -> Result<Expr<'core>, SearchError> {
+        if depth > DEPTH_LIMIT {
+            return Err(SearchError::DepthLimit);
+        }
+
+        let candidates: Vec<_> = (RelativeVar::iter())
+            .zip(self.env.locals.instances.iter().rev())
+            .filter(|(_, instance)| **instance)
+            .map(|(var, _)| var)
+            .collect();
+
+        let mut found = Vec::new();
+        let mut reached_depth_limit = false;
+        for var in candidates {
+            let snapshot = self.env.metas.snapshot();
+            match self.try_instance(range, var, r#type, depth) {
+                Ok(expr) => {
+                    let changes = self.env.metas.undo(&snapshot);
+                    found.push((var, expr, changes));
+                }
+                Err(error) => {
+                    reached_depth_limit |= matches!(error, SearchError::DepthLimit);
+                    self.env.metas.undo(&snapshot);
+                }
+            }
+        }
+
+        match found.len() {
+            0 if reached_depth_limit => Err(SearchError::DepthLimit),
+            0 => Err(SearchError::NotFound),
+            1 => {
+                let (_, expr, changes) = found.pop().unwrap();
+                self.env.metas.redo(changes);
+                Ok(expr)
+            }
+            _ => {
+                let names = (found.iter())
+                    .map(|(var, ..)| *self.env.locals.names.get_relative(*var).unwrap())
+                    .collect();
+                Err(SearchError::Ambiguous(names))
+            }
+        }
+    }
+
+    /// Check whether the instance `var` can be used as an instance of
+    /// `r#type`, returning it applied to its implicit and instance arguments.
+    fn try_instance(
+        &mut self,
+        range: TextRange,
+        var: RelativeVar,
+        r#type: &Type<'core>,
+        depth: usize,
+    ) -> Result<Expr<'core>, SearchError> {
+        let mut expr = Expr::LocalVar(var);
+        let mut instance_type = self.env.locals.types.get_relative(var).unwrap().clone();
+        let mut subgoals = Vec::new();
+        loop {
+            instance_type = self.elim_env().update_metas(&instance_type);
+            match instance_type {
+                Value::FunType { param, body } if !param.plicity.is_explicit() => {
+                    let source = match param.plicity.is_instance() {
+                        true => MetaSource::InstanceArg { range },
+                        false => MetaSource::ImplicitArg {
+                            range,
+                            name: param.name,
+                        },
+                    };
+                    let arg_expr = self.push_unsolved_expr(source, param.r#type.clone());
+                    if param.plicity.is_instance() {
+                        subgoals.push((arg_expr, param.r#type.clone()));
+                    }
+                    let arg_value = self.eval_env().eval(&arg_expr);
+
+                    let (fun, arg_expr) = self.bump.alloc((expr, arg_expr));
+                    let arg = FunArg::new(param.plicity, &*arg_expr);
+                    expr = Expr::FunApp { fun, arg };
+                    instance_type = self.elim_env().apply_closure(body, arg_value);
+                }
+                _ => break,
+            }
+        }
+
+        let mut unify_ctx = self.unify_env();
+        let result = unify_ctx.unify(&instance_type, r#type);
+        let (postponed, _) = unify_ctx.finish();
+        if result.is_err() || !postponed.is_empty() {
+            return Err(SearchError::NotFound);
+        }
+
+        for (arg_expr, arg_type) in subgoals {
+            let solution = match self.search_instance(range, &arg_type, depth + 1) {
+                Ok(solution) => solution,
+                Err(SearchError::DepthLimit) => return Err(SearchError::DepthLimit),
+                Err(_) => return Err(SearchError::NotFound),
+            };
+            if !self.solve_instance_arg(arg_expr, solution) {
+                return Err(SearchError::NotFound);
+            }
+        }
+        Ok(expr)
+    }
+}
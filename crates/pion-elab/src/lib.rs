@@ -1,20 +1,29 @@
 #![feature(allocator_api)]
 
-use command::CommandHandler;
+use command::{CommandHandler, EvalBackend};
 use env::{ElabEnv, LocalInfo, MetaSource};
 use pion_core::env::{AbsoluteVar, EnvLen};
-use pion_core::semantics::{self, EvalOpts, Type, Value};
+use pion_core::optimize::OptLevel;
+use pion_core::semantics::{self, EvalLimits, EvalOpts, Fuel, Type, Value};
 use pion_core::syntax::{Expr, FunArg, LetBinding, Plicity};
-use pion_diagnostic::DiagnosticHandler;
+use pion_diagnostic::{Diagnostic, DiagnosticHandler, Severity, Suggestion};
 use pion_printer::BumpDocAllocator;
+use pion_symbol::Symbol;
+use pion_util::location::Location;
 use text_size::TextRange;
 
+pub use self::stmt::TopLevelItem;
 use self::unify::UnifyCtx;
 
+mod assert;
+mod constraints;
 mod diagnostics;
 mod expr;
+mod instance;
+mod lint;
 mod r#match;
 mod pat;
+mod reduce;
 mod stmt;
 mod unify;
 
@@ -25,8 +34,17 @@ pub struct Elaborator<'handler, 'core, 'text> {
     bump: &'core bumpalo::Bump,
     text: &'text str,
     file_id: usize,
-    diagnostic_handler: &'handler mut dyn DiagnosticHandler,
+    diagnostic_handler: DiagnosticSink<'handler>,
     command_handler: &'handler mut dyn CommandHandler,
+    eval_backend: EvalBackend,
+    opt_level: OptLevel,
+    /// Whether `#eval` commands evaluate and display their arguments.
+    eval_commands: bool,
+    /// The top-level bindings, compiled for the bytecode virtual machine.
+    bytecode_lets: pion_vm::Lets,
+    /// The resources that evaluation may use while elaborating the current
+    /// top-level item.
+    fuel: Fuel,
 
     env: ElabEnv<'core>,
 }
@@ -43,16 +61,43 @@ impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
             bump,
             text,
             file_id,
-            diagnostic_handler,
+            diagnostic_handler: DiagnosticSink {
+                handler: diagnostic_handler,
+                error_count: 0,
+                captured: None,
+            },
             command_handler,
+            eval_backend: EvalBackend::default(),
+            opt_level: OptLevel::default(),
+            eval_commands: true,
+            bytecode_lets: pion_vm::Lets::default(),
+            fuel: Fuel::new(EvalLimits::default()),
 
             env: ElabEnv::default(),
         }
     }
 
-    pub fn report_unsolved_metas(&mut self) {
+    pub fn set_eval_backend(&mut self, eval_backend: EvalBackend) {
+        self.eval_backend = eval_backend;
+    }
+
+    /// Set how expressions are optimized before being run by the bytecode
+    /// virtual machine.
+    pub fn set_opt_level(&mut self, opt_level: OptLevel) { self.opt_level = opt_level; }
+
+    /// Set whether `#eval` commands evaluate and display their arguments, or
+    /// only elaborate them (when they are compiled, say).
+    pub fn set_eval_commands(&mut self, eval_commands: bool) { self.eval_commands = eval_commands; }
+
+    /// Set the limits on evaluation while elaborating each top-level item.
+    pub fn set_eval_limits(&mut self, limits: EvalLimits) { self.fuel = Fuel::new(limits); }
+
+    pub fn report_unsolved_metas(&mut self) { self.report_unsolved_metas_from(0); }
+
+    /// Report the unsolved metavariables created after the first `start`.
+    fn report_unsolved_metas_from(&mut self, start: usize) {
         let meta_env = std::mem::take(&mut self.env.metas);
-        for (id, (source, _, value)) in meta_env.iter().enumerate() {
+        for (id, (source, _, value)) in meta_env.iter().enumerate().skip(start) {
             if value.is_none() {
                 diagnostics::unsolved_meta_var(self, id, source, self.file_id);
             }
@@ -60,6 +105,16 @@ impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
         self.env.metas = meta_env;
     }
 
+    /// Report that evaluation has exceeded its limits, blaming the expression
+    /// at `range`, unless this has already been reported since the fuel was
+    /// reset.
+    fn report_eval_limit(&mut self, range: TextRange) {
+        if let Some(limit) = self.fuel.take_unreported() {
+            let loc = Location::new(self.file_id, range);
+            diagnostics::eval_limit_exceeded(self, limit, loc);
+        }
+    }
+
     fn push_unsolved_expr(&mut self, source: MetaSource, r#type: Type<'core>) -> Expr<'core> {
         let var = self.env.metas.len().to_absolute();
         self.env.metas.push(source, r#type);
@@ -86,13 +141,21 @@ impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
     }
 
     pub fn elim_env(&self) -> semantics::ElimEnv<'core, '_> {
-        semantics::ElimEnv::new(self.bump, EvalOpts::default(), &self.env.metas.values)
+        let opts = EvalOpts {
+            fuel: Some(&self.fuel),
+            ..EvalOpts::default()
+        };
+        semantics::ElimEnv::new(self.bump, opts, &self.env.metas.values)
     }
 
     pub fn eval_env(&mut self) -> semantics::EvalEnv<'core, '_> {
+        let opts = EvalOpts {
+            fuel: Some(&self.fuel),
+            ..EvalOpts::default()
+        };
         semantics::EvalEnv::new(
             self.bump,
-            EvalOpts::default(),
+            opts,
             &mut self.env.locals.values,
             &self.env.metas.values,
         )
@@ -108,6 +171,7 @@ impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
             &mut self.env.locals.values,
             &self.env.metas.values,
         )
+        .with_meta_exprs(&self.env.metas.exprs)
     }
 
     pub fn unify_env(&mut self) -> UnifyCtx<'core, '_> {
@@ -116,6 +180,7 @@ impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
             &mut self.env.renaming,
             self.env.locals.len(),
             &mut self.env.metas.values,
+            &self.fuel,
         )
     }
 
@@ -130,11 +195,54 @@ impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
         doc.pretty(usize::MAX).to_string()
     }
 
-    fn push_let_bindings(&mut self, bindings: &[LetBinding<Expr<'core>, Expr<'core>>]) {
+    /// Pretty-print `expr`, which is under local variables named `binders`
+    /// that are not in the environment.
+    pub fn pretty_under(&mut self, binders: &[Option<Symbol>], expr: &Expr<'core>) -> String {
+        let len = self.env.locals.len();
+        for name in binders {
+            self.env.locals.push_param(*name, Type::Error);
+        }
+        let text = self.pretty(expr);
+        self.env.locals.truncate(len);
+        text
+    }
+
+    pub fn push_let_bindings(&mut self, bindings: &[LetBinding<Expr<'core>, Expr<'core>>]) {
         for LetBinding { name, r#type, rhs } in bindings {
-            let value = self.eval_env().eval(rhs);
+            // Evaluate the type first: if evaluating the value runs out of fuel,
+            // the type is still known.
             let r#type = self.eval_env().eval(r#type);
+            let value = self.eval_env().eval(rhs);
             self.env.locals.push_let(*name, *rhs, r#type, value);
         }
     }
 }
+
+/// Passes diagnostics on to a [`DiagnosticHandler`], counting the errors.
+/// While elaborating the block of a `#fail` command, diagnostics are captured
+/// instead, so that the command can check them.
+struct DiagnosticSink<'handler> {
+    handler: &'handler mut dyn DiagnosticHandler,
+    error_count: usize,
+    captured: Option<Vec<Diagnostic<usize>>>,
+}
+
+impl DiagnosticHandler for DiagnosticSink<'_> {
+    fn handle_diagnostic(&mut self, diagnostic: Diagnostic<usize>) {
+        self.handle_diagnostic_with_suggestions(diagnostic, Vec::new());
+    }
+
+    fn handle_diagnostic_with_suggestions(
+        &mut self,
+        diagnostic: Diagnostic<usize>,
+        suggestions: Vec<Suggestion>,
+    ) {
+        if diagnostic.severity >= Severity::Error {
+            self.error_count += 1;
+        }
+        match &mut self.captured {
+            Some(captured) => captured.push(diagnostic),
+            None => (self.handler).handle_diagnostic_with_suggestions(diagnostic, suggestions),
+        }
+    }
+}
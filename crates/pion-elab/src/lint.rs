@@ -0,0 +1,124 @@
+//! Lints for unused and shadowing bindings.
+//!
+//! Every local records whether it has been referred to by name. When a local
+//! scope ends, the variables bound by the surface pattern that opened it are
+//! looked up among the locals of the scope, and the ones that were never
+//! used are reported. Variables whose names start with `_` are not reported,
+//! nor are instances, which are used by instance resolution rather than by
+//! name. Top-level bindings are not reported either, since they may be used
+//! by later items.
+
+use pion_core::env::{AbsoluteVar, EnvLen};
+use pion_core::prim::Prim;
+use pion_surface::syntax::{self as surface, Located};
+use pion_symbol::Symbol;
+use pion_util::location::Location;
+
+use crate::{diagnostics, Elaborator};
+
+/// What introduced a binding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinderKind {
+    Let,
+    Param,
+    PatVar,
+}
+
+impl BinderKind {
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Let => "let binding",
+            Self::Param => "parameter",
+            Self::PatVar => "pattern variable",
+        }
+    }
+}
+
+impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
+    /// Report `name` if it shadows a local variable or a primitive.
+    pub(crate) fn lint_shadowing(&mut self, name: Located<Symbol>) {
+        if is_ignored(name.data) {
+            return;
+        }
+        let shadows_prim = match self.env.locals.lookup(name.data) {
+            Some(_) => false,
+            None if Prim::from_symbol(name.data).is_some() => true,
+            None => return,
+        };
+        let loc = Location::new(self.file_id, name.range);
+        diagnostics::shadowed_binding(self, name.data, shadows_prim, loc);
+    }
+
+    /// Report the variables bound by `pat` that have not been used since
+    /// `scope_start`. `kind` describes `pat` when it is a single variable.
+    pub(crate) fn lint_unused(
+        &mut self,
+        kind: BinderKind,
+        pat: &Located<surface::Pat>,
+        scope_start: EnvLen,
+    ) {
+        // Evaluation that has run out of fuel can cut elaboration short, before
+        // the uses of a binding are seen.
+        if self.fuel.exceeded().is_some() {
+            return;
+        }
+
+        let kind = match strip_parens(pat).data {
+            surface::Pat::Var(_) => kind,
+            _ => BinderKind::PatVar,
+        };
+
+        let mut names = Vec::new();
+        pat_vars(pat, &mut names);
+        for (index, name) in names.iter().enumerate() {
+            let is_repeated = names[..index].iter().any(|other| other.data == name.data);
+            if is_ignored(name.data) || is_repeated {
+                continue;
+            }
+
+            let locals = &self.env.locals;
+            let var = (usize::from(scope_start)..usize::from(locals.len()))
+                .rev()
+                .map(AbsoluteVar::from)
+                .find(|var| locals.names.get_absolute(*var) == Some(&Some(name.data)));
+            let Some(var) = var else { continue };
+            let is_used = locals.used.get_absolute(var) == Some(&true);
+            let is_instance = locals.instances.get_absolute(var) == Some(&true);
+            if is_used || is_instance {
+                continue;
+            }
+
+            let loc = Location::new(self.file_id, name.range);
+            diagnostics::unused_binding(self, kind, name.data, loc);
+        }
+    }
+}
+
+/// Names starting with `_` are deliberately unused.
+fn is_ignored(name: Symbol) -> bool { name.as_str().starts_with('_') }
+
+fn strip_parens<'a, 'surface>(
+    pat: &'a Located<surface::Pat<'surface>>,
+) -> &'a Located<surface::Pat<'surface>> {
+    match &pat.data {
+        surface::Pat::Paren(pat) => strip_parens(pat),
+        _ => pat,
+    }
+}
+
+/// The variables bound by `pat`, in order.
+fn pat_vars(pat: &Located<surface::Pat>, names: &mut Vec<Located<Symbol>>) {
+    match &pat.data {
+        surface::Pat::Error | surface::Pat::Underscore | surface::Pat::Lit(_) => {}
+        surface::Pat::Var(name) => names.push(*name),
+        surface::Pat::Paren(pat) => pat_vars(pat, names),
+        surface::Pat::TupleLit(pats) | surface::Pat::Or(pats) => {
+            pats.iter().for_each(|pat| pat_vars(pat, names));
+        }
+        surface::Pat::RecordLit(fields) => {
+            fields
+                .iter()
+                .for_each(|field| pat_vars(&field.data.pat, names));
+        }
+    }
+}
@@ -4,6 +4,7 @@ use pion_util::location::Location;
 
 use super::{Elaborator, EnvLen, Expr, TextRange, Type};
 use crate::diagnostics;
+use crate::lint::BinderKind;
 
 mod compile;
 mod constructors;
@@ -35,8 +36,15 @@ impl<'handler, 'core, 'text> Elaborator<'handler, 'core, 'text> {
             let pat = self.check_pat(&surface_case.pat, &scrut_type);
             let bindings = self.destruct_pat(&pat, &scrut_expr, &scrut_type, false);
             self.push_let_bindings(&bindings);
+            // FIXME: guards are not compiled yet. They are still elaborated, so
+            // that errors in them are reported and the variables they use are
+            // not linted as unused.
+            if let Some(surface::MatchGuard::If(cond)) = &surface_case.guard {
+                self.check_expr(cond, &Type::BOOL);
+            }
             let expr = self.check_expr(&surface_case.expr, expected);
             let expr = Expr::lets(self.bump, &bindings, expr);
+            self.lint_unused(BinderKind::PatVar, &surface_case.pat, len);
             self.env.locals.truncate(len);
 
             matrix.push_row(PatRow::new(&[(pat, scrut_expr)], index));
@@ -55,6 +55,7 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
             Some(surface_ann) => {
                 let ann_expr = self.check_expr_is_type(surface_ann);
                 let ann_value = self.eval_env().eval(&ann_expr);
+                self.report_eval_limit(surface_ann.range);
                 let name = self.check_pat(surface_pat, &ann_value);
                 (name, ann_value)
             }
@@ -72,6 +73,7 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
             Some(surface_ann) => {
                 let type_expr = self.check_expr_is_type(surface_ann);
                 let type_value = self.eval_env().eval(&type_expr);
+                self.report_eval_limit(surface_ann.range);
                 let pat = self.check_pat(surface_pat, &type_value);
                 self.convert_pat(surface_pat.range, pat, &type_value, expected);
                 pat
@@ -94,6 +96,7 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                 (Pat::Underscore, r#type)
             }
             surface::Pat::Var(Located { range, data: name }) => {
+                self.lint_shadowing(Located::new(range, name));
                 let source = MetaSource::PatType {
                     range,
                     name: Some(name),
@@ -175,16 +178,28 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
         expected: &Type<'core>,
     ) -> Pat<'core> {
         match surface_pat.data {
-            surface::Pat::Error => Pat::Error,
+            surface::Pat::Error => {
+                // Solve any metavariables in the expected type, so that they are not reported
+                // as unsolved.
+                let _ = self.unify_env().unify(&Type::Error, expected);
+                Pat::Error
+            }
             surface::Pat::Underscore => Pat::Underscore,
-            surface::Pat::Var(Located { data: name, .. }) => Pat::Ident(name),
+            surface::Pat::Var(name) => {
+                self.lint_shadowing(name);
+                Pat::Ident(name.data)
+            }
             surface::Pat::Paren(pat) => self.check_pat(pat, expected),
             surface::Pat::TupleLit(surface_fields) => {
                 let Type::RecordType(telescope) = &expected else {
                     return self.synth_and_convert_pat(surface_pat, expected);
                 };
 
-                if !Symbol::are_tuple_field_names(telescope.fields.iter().map(|(n, _)| *n)) {
+                // Patterns with the wrong number of fields are reported as a
+                // type mismatch.
+                if surface_fields.len() != telescope.fields.len()
+                    || !Symbol::are_tuple_field_names(telescope.fields.iter().map(|(n, _)| *n))
+                {
                     return self.synth_and_convert_pat(surface_pat, expected);
                 }
 
@@ -256,11 +271,11 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
         from: &Type<'core>,
         to: &Type<'core>,
     ) -> Pat<'core> {
-        match self.unify_env().unify(from, to) {
+        match self.unify(range, from, to) {
             Ok(()) => pat,
             Err(error) => {
                 let loc = Location::new(self.file_id, range);
-                diagnostics::unable_to_unify(self, error, from, to, loc);
+                diagnostics::unable_to_unify(self, &error, from, to, loc);
                 Pat::Error
             }
         }
@@ -292,8 +307,17 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                 }
                 Pat::RecordLit(pat_fields) => {
                     let r#type = ctx.elim_env().update_metas(r#type);
-                    let Type::RecordType(mut telescope) = r#type else {
-                        unreachable!("expected record type, got {type:?}")
+                    let mut telescope = match r#type {
+                        Type::RecordType(telescope) => telescope,
+                        // The pattern did not match its expected type: this has already been
+                        // reported, so bind its variables to errors.
+                        Type::Error => {
+                            for (_, pat) in *pat_fields {
+                                recur(ctx, pat, &Expr::Error, &Type::Error, bindings, false);
+                            }
+                            return;
+                        }
+                        _ => unreachable!("expected record type, got {type:?}"),
                     };
                     for (pat_name, pat) in *pat_fields {
                         let (telescope_name, r#type, update_telescope) =
@@ -0,0 +1,192 @@
+//! The `#reduce` and `#trace` commands.
+//!
+//! Both reduce an expression step by step with
+//! [`pion_core::reduce::Reducer`], which contracts one redex at a time, so
+//! that they can stop after a limited number of steps. `#reduce` displays
+//! the final expression, and `#trace` displays every intermediate expression
+//! along with the kind of step that produced it.
+
+use pion_core::reduce::{exceeds_depth, ReduceOpts, Reducer, Step};
+use pion_core::syntax::{Expr, Lit};
+use pion_printer::{docs, BumpDocAllocator, DocAllocator as _};
+use pion_surface::syntax::{self as surface, Located, ReduceOption};
+use pion_util::location::Location;
+
+use crate::{diagnostics, Elaborator};
+
+/// The options accepted by `#reduce` and `#trace`.
+pub const REDUCE_OPTIONS: &[&str] = &["whnf", "unfold_lets", "unfold_fix", "steps"];
+
+/// The default maximum number of steps taken by `#reduce`.
+const REDUCE_STEPS: u32 = 10_000;
+
+/// The default maximum number of steps displayed by `#trace`.
+const TRACE_STEPS: u32 = 100;
+
+/// Which of the two commands is being elaborated.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReduceCommand {
+    Reduce,
+    Trace,
+}
+
+impl ReduceCommand {
+    pub const fn keyword(self) -> &'static str {
+        match self {
+            Self::Reduce => "#reduce",
+            Self::Trace => "#trace",
+        }
+    }
+}
+
+/// The kind of value expected by an option.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OptionValue {
+    Bool,
+    Int,
+}
+
+impl OptionValue {
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Bool => "a boolean",
+            Self::Int => "an integer",
+        }
+    }
+}
+
+impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
+    pub(crate) fn elab_reduce_command(
+        &mut self,
+        command: ReduceCommand,
+        options: &[ReduceOption],
+        surface_expr: &Located<surface::Expr<'surface>>,
+    ) {
+        let Some((opts, max_steps)) = self.reduce_opts(command, options) else {
+            return;
+        };
+
+        let (expr, _) = self.synth_expr(surface_expr);
+        let mut expr = self.zonk_env().zonk(&expr);
+
+        let reducer = Reducer::new(self.bump, opts, &self.env.locals.exprs);
+        let mut steps: Vec<(Step, Expr<'core>)> = Vec::new();
+        // Expressions are limited to the depth that evaluation may nest.
+        let max_depth = self.fuel.limits().max_depth;
+        let mut count = 0;
+        let mut stopped = false;
+        while let Some((step, next)) = reducer.step(&expr) {
+            if count == max_steps {
+                stopped = true;
+                break;
+            }
+            count += 1;
+            expr = next;
+            if exceeds_depth(&expr, max_depth) {
+                let loc = Location::new(self.file_id, surface_expr.range);
+                diagnostics::reduce_depth_exceeded(self, max_depth, loc);
+                return;
+            }
+            if command == ReduceCommand::Trace {
+                steps.push((step, expr));
+            }
+        }
+
+        let alloc = BumpDocAllocator::new(self.bump);
+        let unelaborator =
+            pion_core::unelab::Unelaborator::new(alloc, pion_core::unelab::Config::default());
+        let names = &mut self.env.locals.names;
+        match command {
+            ReduceCommand::Reduce => {
+                if stopped {
+                    let loc = Location::new(self.file_id, surface_expr.range);
+                    diagnostics::reduce_limit_exceeded(self, command, max_steps, loc);
+                    return;
+                }
+
+                let surface_printer = pion_surface::printer::Printer::new(alloc, self.text);
+                let surface_expr_doc = surface_printer.expr(&surface_expr.data);
+                let expr_doc = unelaborator.expr(names, &expr);
+                let doc = docs![
+                    &alloc,
+                    surface_expr_doc,
+                    alloc.line(),
+                    "⇝",
+                    alloc.line(),
+                    expr_doc
+                ]
+                .group();
+                self.command_handler
+                    .display_to_user(doc.pretty(80).to_string());
+            }
+            ReduceCommand::Trace => {
+                let surface_printer = pion_surface::printer::Printer::new(alloc, self.text);
+                let mut lines = vec![surface_printer
+                    .expr(&surface_expr.data)
+                    .pretty(80)
+                    .to_string()];
+                for (step, expr) in &steps {
+                    let expr_doc = unelaborator.expr(names, expr);
+                    let doc = docs![&alloc, "⇝ ", expr_doc.nest(2), format!("  // {step}")];
+                    lines.push(doc.pretty(80).to_string());
+                }
+                if stopped {
+                    lines.push(format!("// stopped after {max_steps} steps"));
+                }
+                self.command_handler.display_to_user(lines.join("\n"));
+            }
+        }
+    }
+
+    /// The reduction options and step limit given by `options`, or `None` if
+    /// any of them are invalid.
+    fn reduce_opts(
+        &mut self,
+        command: ReduceCommand,
+        options: &[ReduceOption],
+    ) -> Option<(ReduceOpts, u32)> {
+        let mut opts = ReduceOpts::default();
+        let mut max_steps = match command {
+            ReduceCommand::Reduce => REDUCE_STEPS,
+            ReduceCommand::Trace => TRACE_STEPS,
+        };
+        let mut valid = true;
+
+        for option in options {
+            let expected = match option.name.data.as_str() {
+                "whnf" | "unfold_lets" | "unfold_fix" => OptionValue::Bool,
+                "steps" => OptionValue::Int,
+                _ => {
+                    let loc = Location::new(self.file_id, option.name.range);
+                    diagnostics::unknown_reduce_option(self, command, option.name.data, loc);
+                    valid = false;
+                    continue;
+                }
+            };
+
+            // An option without a value is a flag that is turned on.
+            let value = match option.value {
+                None => Ok(Lit::Bool(true)),
+                Some(value) => self.synth_lit(&value).0,
+            };
+            let Ok(value) = value else {
+                valid = false;
+                continue;
+            };
+            match (option.name.data.as_str(), value) {
+                ("whnf", Lit::Bool(value)) => opts.whnf = value,
+                ("unfold_lets", Lit::Bool(value)) => opts.unfold_lets = value,
+                ("unfold_fix", Lit::Bool(value)) => opts.unfold_fix = value,
+                ("steps", Lit::Int(value)) => max_steps = value,
+                _ => {
+                    let range = option.value.map_or(option.name.range, |value| value.range);
+                    let loc = Location::new(self.file_id, range);
+                    diagnostics::invalid_reduce_option_value(self, option.name.data, expected, loc);
+                    valid = false;
+                }
+            }
+        }
+
+        valid.then_some((opts, max_steps))
+    }
+}
@@ -1,18 +1,62 @@
 use pion_core::env::{EnvLen, RelativeVar};
+use pion_core::optimize::optimize;
 use pion_core::prim::Prim;
 use pion_core::semantics::{Telescope, Type, Value};
-use pion_core::syntax::{Expr, FunArg, FunParam, LetBinding};
+use pion_core::syntax::{Expr, FunArg, FunParam, Item, LetBinding};
 use pion_printer::{docs, BumpDocAllocator, DocAllocator as _};
 use pion_surface::syntax::{self as surface, Located, Rec};
 use pion_util::location::Location;
 use text_size::TextRange;
 
 use super::Elaborator;
+use crate::command::EvalBackend;
 use crate::diagnostics;
+use crate::env::MetaEnv;
+use crate::lint::BinderKind;
+use crate::reduce::ReduceCommand;
+
+/// The result of elaborating a top-level item.
+#[derive(Debug, Clone, Default)]
+pub struct TopLevelItem<'core> {
+    /// The let-bindings introduced by the item.
+    pub bindings: Vec<LetBinding<Expr<'core>, Expr<'core>>>,
+    /// The expression whose value is displayed by the item, before it is
+    /// evaluated: the argument of an `#eval` command, or the result
+    /// expression.
+    pub eval: Option<Expr<'core>>,
+}
+
+impl<'core> TopLevelItem<'core> {
+    const fn bindings(bindings: Vec<LetBinding<Expr<'core>, Expr<'core>>>) -> Self {
+        Self {
+            bindings,
+            eval: None,
+        }
+    }
+
+    const fn eval(expr: Expr<'core>) -> Self {
+        Self {
+            bindings: Vec::new(),
+            eval: Some(expr),
+        }
+    }
+
+    /// The core items of the item, in source order.
+    pub fn core_items(&self) -> impl Iterator<Item = Item<'core>> + '_ {
+        (self.bindings.iter().copied().map(Item::Let)).chain(self.eval.map(Item::Eval))
+    }
+}
 
 impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
-    fn elab_command(&mut self, command: Located<surface::Command<'surface>>) {
+    /// Elaborate `command`, returning the elaborated argument of an `#eval`
+    /// command.
+    fn elab_command(
+        &mut self,
+        command: Located<surface::Command<'surface>>,
+    ) -> Option<Expr<'core>> {
         match command.data {
+            surface::Command::AssertEq(lhs, rhs) => self.elab_assert_eq(&lhs, &rhs),
+            surface::Command::AssertType(expr, r#type) => self.elab_assert_type(&expr, &r#type),
             surface::Command::Check(expr) => {
                 let (expr, r#type) = self.synth_expr(&expr);
                 let r#type = self.quote_env().quote(&r#type);
@@ -31,9 +75,15 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                 self.command_handler.display_to_user(pretty);
             }
             surface::Command::Eval(surface_expr) => {
-                let (core_expr, _) = self.synth_expr(&surface_expr);
-                let core_expr = self.eval_env().normalize(&core_expr);
-                let core_expr = self.zonk_env().zonk(&core_expr);
+                let (elab_expr, _) = self.synth_expr(&surface_expr);
+                if !self.eval_commands {
+                    return Some(elab_expr);
+                }
+                let core_expr = self.eval_expr(surface_expr.range, &elab_expr);
+                if self.fuel.exceeded().is_some() {
+                    self.report_eval_limit(surface_expr.range);
+                    return Some(elab_expr);
+                }
 
                 let alloc = BumpDocAllocator::new(self.bump);
                 let surface_printer = pion_surface::printer::Printer::new(alloc, self.text);
@@ -55,13 +105,43 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                 .group();
                 self.command_handler
                     .display_to_user(doc.pretty(80).to_string());
+                return Some(elab_expr);
+            }
+            surface::Command::Fail(code, block) => self.elab_fail(command.range, code, &block),
+            surface::Command::Print(option, expr) => {
+                if option.data.as_str() != "optimized" {
+                    let option_loc = Location::new(self.file_id, option.range);
+                    diagnostics::unknown_print_option(self, option.data, option_loc);
+                    return None;
+                }
+
+                let (expr, _) = self.synth_expr(&expr);
+                let expr = self.zonk_env().zonk(&expr);
+                let expr = optimize(self.bump, self.opt_level, &expr);
+
+                let alloc = BumpDocAllocator::new(self.bump);
+                let unelaborator = pion_core::unelab::Unelaborator::new(
+                    alloc,
+                    pion_core::unelab::Config::default(),
+                );
+
+                let doc = unelaborator.expr(&mut self.env.locals.names, &expr);
+                let pretty = doc.pretty(80).to_string();
+                self.command_handler.display_to_user(pretty);
+            }
+            surface::Command::Reduce(options, expr) => {
+                self.elab_reduce_command(ReduceCommand::Reduce, options, &expr);
+            }
+            surface::Command::Trace(options, expr) => {
+                self.elab_reduce_command(ReduceCommand::Trace, options, &expr);
             }
             surface::Command::Show(name) => {
                 let Some(var) = self.env.locals.lookup(name.data) else {
                     let var_loc = Location::new(self.file_id, name.range);
                     diagnostics::unbound_local_var(self, name.data, var_loc);
-                    return;
+                    return None;
                 };
+                self.env.locals.mark_used(var);
 
                 match self.env.locals.infos.get_relative(var).unwrap() {
                     crate::LocalInfo::Param => {
@@ -107,6 +187,52 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
                 }
             }
         }
+        None
+    }
+
+    /// Evaluate `expr` to a normal form, with the configured
+    /// [`EvalBackend`].
+    fn eval_expr(&mut self, range: TextRange, expr: &Expr<'core>) -> Expr<'core> {
+        if self.eval_backend == EvalBackend::Bytecode {
+            match self.eval_bytecode(expr) {
+                Ok(Some(expr)) => return expr,
+                Err(pion_vm::Error::LimitExceeded(limit)) => {
+                    self.fuel.set_exceeded(limit);
+                    return Expr::Error;
+                }
+                Err(error) if error.is_bug() => {
+                    let loc = Location::new(self.file_id, range);
+                    diagnostics::bytecode_eval_failed(self, error, loc);
+                }
+                Ok(None) | Err(_) => {}
+            }
+        }
+        let expr = self.eval_env().normalize(expr);
+        self.zonk_env().zonk(&expr)
+    }
+
+    /// Evaluate `expr` with the bytecode virtual machine. Returns `None` if
+    /// it is not closed, or does not evaluate to first-order data.
+    fn eval_bytecode(&mut self, expr: &Expr<'core>) -> Result<Option<Expr<'core>>, pion_vm::Error> {
+        // The top-level bindings are already compiled: only the bindings of
+        // enclosing blocks need to be added.
+        let len = self.bytecode_lets.len();
+        let Some(lets) =
+            (self.env.locals.exprs.iter().skip(len).copied()).collect::<Option<Vec<_>>>()
+        else {
+            return Ok(None);
+        };
+        for rhs in lets {
+            let rhs = self.zonk_env().zonk(&rhs);
+            let rhs = optimize(self.bump, self.opt_level, &rhs);
+            self.bytecode_lets.push(&rhs);
+        }
+
+        let expr = self.zonk_env().zonk(expr);
+        let expr = optimize(self.bump, self.opt_level, &expr);
+        let value = self.bytecode_lets.eval(&expr, self.fuel.limits());
+        self.bytecode_lets.truncate(len);
+        Ok(value?.to_expr(self.bump))
     }
 
     pub fn synth_block(&mut self, block: &surface::Block<'surface>) -> (Expr<'core>, Type<'core>) {
@@ -125,6 +251,7 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
             };
 
             match stmt.data {
+                surface::Stmt::Error => recur(this, stmts, expr),
                 surface::Stmt::Let(Rec::Nonrec, binding) => {
                     this.elab_let(&binding, |this| recur(this, stmts, expr))
                 }
@@ -139,6 +266,187 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
         }
     }
 
+    /// Elaborate a top-level statement, returning the let-bindings it
+    /// introduces, zonked with the metavariable solutions found so far. The
+    /// bindings are not added to the environment: use
+    /// [`Self::push_top_level_bindings`] to do so.
+    ///
+    /// As in a block, metavariables are shared between top-level items, so a
+    /// later item may solve metavariables left unsolved by an earlier one.
+    /// Once every item has been elaborated, call
+    /// [`Self::default_unsolved_metas`] to report those that are still
+    /// unsolved.
+    pub fn elab_top_level_stmt(
+        &mut self,
+        stmt: &'surface Located<surface::Stmt<'surface>>,
+    ) -> TopLevelItem<'core> {
+        self.elab_top_level_item(stmt.range, |this| match &stmt.data {
+            surface::Stmt::Error => TopLevelItem::default(),
+            surface::Stmt::Let(Rec::Nonrec, binding) => {
+                TopLevelItem::bindings(this.elab_let_bindings(binding))
+            }
+            surface::Stmt::Let(Rec::Rec, binding) => {
+                TopLevelItem::bindings(vec![this.elab_letrec_binding(binding)])
+            }
+            surface::Stmt::Command(command) => TopLevelItem {
+                bindings: Vec::new(),
+                eval: this.elab_command(*command),
+            },
+        })
+    }
+
+    /// Add the let-bindings returned by [`Self::elab_top_level_stmt`] for
+    /// `stmt` to the environment.
+    pub fn push_top_level_bindings(
+        &mut self,
+        stmt: &surface::Stmt<'surface>,
+        bindings: &[LetBinding<Expr<'core>, Expr<'core>>],
+    ) {
+        // The bindings were already evaluated by `elab_top_level_stmt`, so
+        // evaluating them again with the same fuel could run out of it.
+        self.fuel.reset(self.bump);
+        self.push_let_bindings(bindings);
+        if self.eval_backend == EvalBackend::Bytecode {
+            for binding in bindings {
+                let rhs = optimize(self.bump, self.opt_level, &binding.rhs);
+                self.bytecode_lets.push(&rhs);
+            }
+        }
+        if let surface::Stmt::Let(_, binding) = stmt {
+            if binding.instance {
+                self.env.locals.mark_instances(bindings.len());
+            }
+        }
+    }
+
+    /// Elaborate the result expression of a top-level block. See
+    /// [`Self::elab_top_level_stmt`].
+    pub fn elab_top_level_expr(
+        &mut self,
+        expr: &'surface Located<surface::Expr<'surface>>,
+    ) -> TopLevelItem<'core> {
+        self.elab_top_level_item(expr.range, |this| {
+            let (expr, _) = this.synth_expr(expr);
+            TopLevelItem::eval(expr)
+        })
+    }
+
+    /// Elaborate the result expression of a top-level block, and display its
+    /// value. See [`Self::elab_top_level_stmt`].
+    pub fn run_top_level_expr(
+        &mut self,
+        surface_expr: &'surface Located<surface::Expr<'surface>>,
+    ) -> TopLevelItem<'core> {
+        self.elab_top_level_item(surface_expr.range, |this| {
+            let (elab_expr, _) = this.synth_expr(surface_expr);
+            let expr = this.eval_expr(surface_expr.range, &elab_expr);
+            if this.fuel.exceeded().is_some() {
+                this.report_eval_limit(surface_expr.range);
+                return TopLevelItem::eval(elab_expr);
+            }
+
+            let alloc = BumpDocAllocator::new(this.bump);
+            let unelaborator =
+                pion_core::unelab::Unelaborator::new(alloc, pion_core::unelab::Config::default());
+            let doc = unelaborator.expr(&mut this.env.locals.names, &expr);
+            this.command_handler
+                .display_to_user(doc.pretty(80).to_string());
+            TopLevelItem::eval(elab_expr)
+        })
+    }
+
+    /// Whether every metavariable created so far has been solved, and there
+    /// are no postponed problems. If so, elaborating later items cannot
+    /// change the results of earlier ones.
+    pub fn is_settled(&self) -> bool {
+        self.env.metas.all_solved()
+            && self.env.constraints.is_empty()
+            && self.env.instance_goals.is_empty()
+    }
+
+    /// The number of metavariables created so far.
+    pub fn meta_len(&self) -> usize { usize::from(self.env.metas.len()) }
+
+    /// The metavariables created after the first `start`, so that they can be
+    /// restored with [`Self::push_metas`] when the results of elaborating
+    /// an item are reused instead of elaborating it again.
+    pub fn metas_since(&self, start: usize) -> MetaEnv<'core> {
+        self.env.metas.since(EnvLen::from(start))
+    }
+
+    /// Add the metavariables returned by [`Self::metas_since`].
+    pub fn push_metas(&mut self, metas: &MetaEnv<'core>) { self.env.metas.extend(metas); }
+
+    /// Zonk the items of a file, once all of them have been elaborated and
+    /// [`Self::default_unsolved_metas`] has been called, since later items
+    /// may have solved metavariables in earlier ones.
+    pub fn zonk_top_level_items(&mut self, items: &mut [Item<'core>]) {
+        let outer_locals = std::mem::take(&mut self.env.locals);
+        for item in items {
+            match item {
+                Item::Let(binding) => {
+                    binding.r#type = self.zonk_env().zonk(&binding.r#type);
+                    binding.rhs = self.zonk_env().zonk(&binding.rhs);
+                    self.push_let_bindings(std::slice::from_ref(binding));
+                }
+                Item::Eval(expr) => *expr = self.zonk_env().zonk(expr),
+            }
+        }
+        self.env.locals = outer_locals;
+    }
+
+    /// Elaborate the top-level item at `range` with `elab`, with a fresh
+    /// supply of [fuel][Self::set_eval_limits].
+    fn elab_top_level_item(
+        &mut self,
+        range: TextRange,
+        elab: impl FnOnce(&mut Self) -> TopLevelItem<'core>,
+    ) -> TopLevelItem<'core> {
+        self.fuel.reset(self.bump);
+        let meta_len = self.env.metas.len();
+        let mut item = elab(self);
+
+        if let Some(expr) = &mut item.eval {
+            *expr = self.zonk_env().zonk(expr);
+        }
+        let local_len = self.env.locals.len();
+        for binding in &mut item.bindings {
+            binding.r#type = self.zonk_env().zonk(&binding.r#type);
+            binding.rhs = self.zonk_env().zonk(&binding.rhs);
+            self.push_let_bindings(std::slice::from_ref(binding));
+        }
+        self.env.locals.truncate(local_len);
+        if self.fuel.exceeded().is_some() {
+            self.forget_unsolved_problems(range, meta_len);
+        }
+        self.report_eval_limit(range);
+        item
+    }
+
+    /// Once evaluation has run out of fuel, it returns errors, which leave
+    /// metavariables unsolved and problems stuck. Rather than reporting them,
+    /// solve the metavariables created since `meta_len` with `Value::Error`,
+    /// and forget the problems that arose in the item at `range`.
+    fn forget_unsolved_problems(&mut self, range: TextRange, meta_len: EnvLen) {
+        for value in (self.env.metas.values.iter_mut()).skip(usize::from(meta_len)) {
+            value.get_or_insert(Value::Error);
+        }
+        (self.env.constraints).retain(|constraint| !range.contains_range(constraint.range));
+        (self.env.instance_goals).retain(|goal| !range.contains_range(goal.range));
+    }
+
+    /// Report the instance arguments that cannot be found, the postponed
+    /// unification problems that are still stuck, and the metavariables that
+    /// have not been solved, and solve them with `Value::Error`.
+    pub fn default_unsolved_metas(&mut self) {
+        self.report_unsolved_instance_goals();
+        self.report_stuck_constraints();
+        self.report_unsolved_metas();
+        for value in self.env.metas.values.iter_mut() {
+            value.get_or_insert(Value::Error);
+        }
+    }
+
     pub(super) fn check_block(
         &mut self,
         range: TextRange,
@@ -164,6 +472,7 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
             };
 
             match stmt.data {
+                surface::Stmt::Error => recur(this, stmts, expr, expected),
                 surface::Stmt::Let(Rec::Nonrec, binding) => {
                     let (expr, ()) = this.elab_let(&binding, |this| {
                         let expr = recur(this, stmts, expr, expected);
@@ -192,20 +501,16 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
         surface_binding: &'surface surface::LetBinding<'surface>,
         mut elab_body: impl FnMut(&mut Self) -> (Expr<'core>, T),
     ) -> (Expr<'core>, T) {
-        let surface::LetBinding {
-            pat: surface_pat,
-            r#type: surface_type,
-            rhs: surface_rhs,
-        } = surface_binding;
-        let (pat, r#type) = self.synth_ann_pat(surface_pat, *surface_type);
-        let rhs_expr = self.check_expr(surface_rhs, &r#type);
-
-        let bindings = self.destruct_pat(&pat, &rhs_expr, &r#type, false);
+        let bindings = self.elab_let_bindings(surface_binding);
 
         let (body_expr, body_type) = {
             let local_len = self.env.locals.len();
             self.push_let_bindings(&bindings);
+            if surface_binding.instance {
+                self.env.locals.mark_instances(bindings.len());
+            }
             let (body_expr, body_type) = elab_body(self);
+            self.lint_unused(BinderKind::Let, surface_binding.pat, local_len);
             self.env.locals.truncate(local_len);
             (body_expr, body_type)
         };
@@ -214,13 +519,53 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
         (expr, body_type)
     }
 
+    fn elab_let_bindings(
+        &mut self,
+        surface_binding: &'surface surface::LetBinding<'surface>,
+    ) -> Vec<LetBinding<Expr<'core>, Expr<'core>>> {
+        let surface::LetBinding {
+            instance: _,
+            pat: surface_pat,
+            r#type: surface_type,
+            rhs: surface_rhs,
+        } = surface_binding;
+        let (pat, r#type) = self.synth_ann_pat(surface_pat, *surface_type);
+        let rhs_expr = self.check_expr(surface_rhs, &r#type);
+        self.destruct_pat(&pat, &rhs_expr, &r#type, false)
+    }
+
     // FIXME: check patterns for exhaustiveness
     fn elab_letrec<T>(
         &mut self,
         surface_binding: &'surface surface::LetBinding<'surface>,
         mut elab_body: impl FnMut(&mut Self) -> (Expr<'core>, T),
     ) -> (Expr<'core>, T) {
+        let binding = self.elab_letrec_binding(surface_binding);
+
+        let (body_expr, body_type) = {
+            let local_len = self.env.locals.len();
+            self.push_let_bindings(&[binding]);
+            if surface_binding.instance {
+                self.env.locals.mark_instances(1);
+            }
+            let (body_expr, body_type) = elab_body(self);
+            self.lint_unused(BinderKind::Let, surface_binding.pat, local_len);
+            self.env.locals.pop();
+            (body_expr, body_type)
+        };
+
+        let (r#type, rhs, body) = self.bump.alloc((binding.r#type, binding.rhs, body_expr));
+        let binding = LetBinding::new(binding.name, &*r#type, &*rhs);
+        let core_expr = Expr::Let { binding, body };
+        (core_expr, body_type)
+    }
+
+    fn elab_letrec_binding(
+        &mut self,
+        surface_binding: &'surface surface::LetBinding<'surface>,
+    ) -> LetBinding<Expr<'core>, Expr<'core>> {
         let surface::LetBinding {
+            instance: _,
             pat: surface_pat,
             r#type: surface_type,
             rhs: surface_rhs,
@@ -242,10 +587,22 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
             Expr::FunLit { .. } => {
                 r#type = self.elim_env().update_metas(&r#type);
                 let Value::FunType { param, body } = &r#type else {
-                    unreachable!()
+                    // A function literal only checks against a type that is
+                    // not a function type if the type is an error, which has
+                    // already been reported.
+                    return LetBinding::new(name, r#type_expr, Expr::Error);
                 };
 
                 let (param, output_type) = self.quote_env().quote_fun(*param, body.clone());
+                if output_type.references_local(RelativeVar::default()) {
+                    let loc = Location::new(self.file_id, surface_pat.range);
+                    diagnostics::recursive_let_dependent(self, loc);
+                    return LetBinding::new(name, r#type_expr, Expr::Error);
+                }
+                // The output type does not mention the parameter, so quote it
+                // again outside of the parameter's scope.
+                let output_type = self.elim_env().apply_closure(body.clone(), Value::Error);
+                let output_type = &*self.bump.alloc(self.quote_env().quote(&output_type));
 
                 let fix = &Expr::Prim(Prim::fix);
                 Expr::FunApp {
@@ -270,19 +627,6 @@ impl<'handler, 'core, 'text, 'surface> Elaborator<'handler, 'core, 'text> {
             }
         };
 
-        let (body_expr, body_type) = {
-            let rhs_value = self.eval_env().eval(&rhs_expr);
-            self.env
-                .locals
-                .push_let(name, rhs_expr, r#type.clone(), rhs_value);
-            let (body_expr, body_type) = elab_body(self);
-            self.env.locals.pop();
-            (body_expr, body_type)
-        };
-
-        let (r#type, rhs, body) = self.bump.alloc((r#type_expr, rhs_expr, body_expr));
-        let binding = LetBinding::new(name, &*r#type, &*rhs);
-        let core_expr = Expr::Let { binding, body };
-        (core_expr, body_type)
+        LetBinding::new(name, r#type_expr, rhs_expr)
     }
 }
@@ -1,7 +1,10 @@
+use std::fmt;
+
 use pion_core::env::{AbsoluteVar, EnvLen, RelativeVar, SharedEnv, SliceEnv, UniqueEnv};
-use pion_core::semantics::{self, Closure, Elim, EvalOpts, Head, MetaValues, Value};
+use pion_core::semantics::{self, Closure, Elim, EvalOpts, Fuel, Head, MetaValues, Value};
 use pion_core::syntax::{Expr, FunArg, FunParam, Lit, Plicity};
-use pion_diagnostic::{Diagnostic, Label};
+use pion_diagnostic::{codes, Diagnostic, Label};
+use pion_symbol::Symbol;
 use pion_util::slice_vec::SliceVec;
 use text_size::TextRange;
 
@@ -11,6 +14,25 @@ pub struct UnifyCtx<'core, 'env> {
     renaming: &'env mut PartialRenaming,
     local_env: EnvLen,
     meta_values: &'env mut MetaValues<'core>,
+    /// The resources that evaluation during unification may use.
+    fuel: &'env Fuel,
+    /// Problems that could not be solved yet.
+    postponed: Vec<Postponed<'core>>,
+    /// Whether any metavariable has been solved.
+    solved: bool,
+}
+
+/// A unification problem that was postponed, because a metavariable was
+/// applied to a spine that is not a pattern. It may be solvable once other
+/// metavariables have been solved.
+#[derive(Debug, Clone)]
+pub struct Postponed<'core> {
+    /// The length of the local environment `left` and `right` are in.
+    pub local_len: EnvLen,
+    pub left: Value<'core>,
+    pub right: Value<'core>,
+    /// Why the problem could not be solved.
+    pub error: SpineError,
 }
 
 /// A partial renaming from a source environment to a target environment.
@@ -95,25 +117,41 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
         renaming: &'env mut PartialRenaming,
         local_env: EnvLen,
         meta_values: &'env mut SliceEnv<Option<Value<'core>>>,
+        fuel: &'env Fuel,
     ) -> Self {
         Self {
             bump: arena,
             renaming,
             local_env,
             meta_values,
+            fuel,
+            postponed: Vec::new(),
+            solved: false,
         }
     }
 
+    /// The problems that were postponed, and whether any metavariable was
+    /// solved.
+    pub fn finish(self) -> (Vec<Postponed<'core>>, bool) { (self.postponed, self.solved) }
+
     pub fn quote_env(&self) -> semantics::QuoteEnv<'core, '_> {
         semantics::QuoteEnv::new(self.bump, self.local_env, self.meta_values)
     }
 
     pub fn elim_env(&self) -> semantics::ElimEnv<'core, '_> {
-        semantics::ElimEnv::new(self.bump, EvalOpts::default(), self.meta_values)
+        let opts = EvalOpts {
+            fuel: Some(self.fuel),
+            ..EvalOpts::default()
+        };
+        semantics::ElimEnv::new(self.bump, opts, self.meta_values)
     }
 
     /// Unify two values, updating the solution environment if necessary.
-    pub fn unify(&mut self, left: &Value<'core>, right: &Value<'core>) -> Result<(), UnifyError> {
+    pub fn unify(
+        &mut self,
+        left: &Value<'core>,
+        right: &Value<'core>,
+    ) -> Result<(), UnifyError<'core>> {
         if std::ptr::eq(left, right) {
             return Ok(());
         }
@@ -121,13 +159,13 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
         let left = self.elim_env().update_metas(left);
         let right = self.elim_env().update_metas(right);
 
-        match (left, right) {
+        match (left.clone(), right.clone()) {
             (Value::Lit(left), Value::Lit(right)) if left == right => Ok(()),
 
             (Value::Neutral(left_head, left_spine), Value::Neutral(right_head, right_spine))
                 if left_head == right_head =>
             {
-                self.unify_spines(&left_spine, &right_spine)
+                self.unify_spines((&left, &right), &left_spine, &right_spine)
             }
 
             (
@@ -149,7 +187,34 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
                     param: right_param,
                     body: right_body,
                 },
-            ) => self.unify_funs(left_param, left_body, right_param, right_body),
+            ) => {
+                if left_param.plicity != right_param.plicity {
+                    return Err(self.mismatch(&left, &right));
+                }
+                let body_segment = match left {
+                    Value::FunType { .. } => PathSegment::ReturnType,
+                    _ => PathSegment::FunBody,
+                };
+                self.unify_funs(left_param, left_body, right_param, right_body, body_segment)
+            }
+            // One of the values has a metavariable at its head, so we
+            // attempt to solve it using pattern unification.
+            (Value::Neutral(Head::MetaVar(var), spine), value)
+            | (value, Value::Neutral(Head::MetaVar(var), spine)) => {
+                match self.solve(var, &spine, &value) {
+                    Err(UnifyError::Spine(error)) if error.can_postpone() => {
+                        self.postponed.push(Postponed {
+                            local_len: self.local_env,
+                            left,
+                            right,
+                            error,
+                        });
+                        Ok(())
+                    }
+                    result => result,
+                }
+            }
+
             // Unify a function literal with a value, using eta-conversion:
             // `(fun x => f x) ?= f`
             (Value::FunLit { param, body, .. }, value)
@@ -166,7 +231,7 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
                 let result = self.unify(&left_value, &right_value);
                 self.local_env.pop();
 
-                result
+                result.map_err(|error| error.within(PathSegment::FunBody, [param.name]))
             }
 
             (Value::RecordType(mut left_telescope), Value::RecordType(mut right_telescope)) => {
@@ -174,13 +239,14 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
                     left_telescope.fields,
                     right_telescope.fields,
                 ) {
-                    return Err(UnifyError::Mismatch);
+                    return Err(self.mismatch(&left, &right));
                 }
 
                 let local_len = self.local_env;
+                let mut labels = Vec::new();
 
                 while let Some((
-                    (_, left_value, left_update_telescope),
+                    (label, left_value, left_update_telescope),
                     (_, right_value, right_update_telescope),
                 )) = Option::zip(
                     self.elim_env().split_telescope(&mut left_telescope),
@@ -188,8 +254,9 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
                 ) {
                     if let Err(error) = self.unify(&left_value, &right_value) {
                         self.local_env.truncate(local_len);
-                        return Err(error);
+                        return Err(error.within(PathSegment::Field(label), labels));
                     }
+                    labels.push(Some(label));
 
                     let left_var = Value::local_var(self.local_env.to_absolute());
                     let right_var = Value::local_var(self.local_env.to_absolute());
@@ -205,11 +272,14 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
 
             (Value::List(left_values), Value::List(right_values)) => {
                 if left_values.len() != right_values.len() {
-                    return Err(UnifyError::Mismatch);
+                    return Err(self.mismatch(&left, &right));
                 }
 
-                for (left, right) in left_values.iter().zip(right_values.iter()) {
-                    self.unify(left, right)?;
+                for (index, (left, right)) in
+                    left_values.iter().zip(right_values.iter()).enumerate()
+                {
+                    (self.unify(left, right))
+                        .map_err(|error| error.within(PathSegment::Elem(index), []))?;
                 }
 
                 Ok(())
@@ -217,11 +287,12 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
 
             (Value::RecordLit(left_fields), Value::RecordLit(right_fields)) => {
                 if !pion_core::syntax::record_keys_equal(left_fields, right_fields) {
-                    return Err(UnifyError::Mismatch);
+                    return Err(self.mismatch(&left, &right));
                 }
 
-                for ((_, left), (_, right)) in left_fields.iter().zip(right_fields.iter()) {
-                    self.unify(left, right)?;
+                for ((label, left), (_, right)) in left_fields.iter().zip(right_fields.iter()) {
+                    (self.unify(left, right))
+                        .map_err(|error| error.within(PathSegment::Field(*label), []))?;
                 }
 
                 Ok(())
@@ -238,33 +309,44 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
                 Ok(())
             }
 
-            // One of the values has a metavariable at its head, so we
-            // attempt to solve it using pattern unification.
-            (Value::Neutral(Head::MetaVar(var), spine), value)
-            | (value, Value::Neutral(Head::MetaVar(var), spine)) => self.solve(var, &spine, &value),
-
             (Value::Error, _) | (_, Value::Error) => Ok(()),
 
-            _ => Err(UnifyError::Mismatch),
+            _ => Err(self.mismatch(&left, &right)),
         }
     }
 
-    /// Unify two elimination spines.
+    /// A mismatch between `found` and `expected`, which are the smallest parts
+    /// of the values being unified that differ.
+    fn mismatch(&self, found: &Value<'core>, expected: &Value<'core>) -> UnifyError<'core> {
+        UnifyError::Mismatch(Box::new(Mismatch {
+            path: Vec::new(),
+            binders: Vec::new(),
+            found: self.quote_env().quote(found),
+            expected: self.quote_env().quote(expected),
+        }))
+    }
+
+    /// Unify two elimination spines, of the neutral values `left` and
+    /// `right`.
     fn unify_spines(
         &mut self,
+        (left, right): (&Value<'core>, &Value<'core>),
         left_spine: &[Elim<'core>],
         right_spine: &[Elim<'core>],
-    ) -> Result<(), UnifyError> {
+    ) -> Result<(), UnifyError<'core>> {
         if left_spine.len() != right_spine.len() {
-            return Err(UnifyError::Mismatch);
+            return Err(self.mismatch(left, right));
         }
 
+        let mut arg_index = 0;
         for (left_elim, right_elim) in Iterator::zip(left_spine.iter(), right_spine.iter()) {
             match (left_elim, right_elim) {
                 (Elim::FunApp(left_arg), Elim::FunApp(right_arg))
                     if left_arg.plicity == right_arg.plicity =>
                 {
-                    self.unify(&left_arg.expr, &right_arg.expr)?;
+                    (self.unify(&left_arg.expr, &right_arg.expr))
+                        .map_err(|error| error.within(PathSegment::Arg(arg_index), []))?;
+                    arg_index += 1;
                 }
                 (Elim::BoolCases(left_cases), Elim::BoolCases(right_cases)) => {
                     let left_then = self
@@ -285,7 +367,7 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
                 }
                 (Elim::RecordProj(left_field), Elim::RecordProj(right_field))
                     if left_field == right_field => {}
-                _ => return Err(UnifyError::Mismatch),
+                _ => return Err(self.mismatch(left, right)),
             }
         }
         Ok(())
@@ -298,12 +380,10 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
         left_body: Closure<'core>,
         right_param: FunParam<&Value<'core>>,
         right_body: Closure<'core>,
-    ) -> Result<(), UnifyError> {
-        if left_param.plicity != right_param.plicity {
-            return Err(UnifyError::Mismatch);
-        }
-
-        self.unify(left_param.r#type, right_param.r#type)?;
+        body_segment: PathSegment,
+    ) -> Result<(), UnifyError<'core>> {
+        (self.unify(left_param.r#type, right_param.r#type))
+            .map_err(|error| error.within(PathSegment::Param, []))?;
 
         let left_var = Value::local_var(self.local_env.to_absolute());
         let right_var = Value::local_var(self.local_env.to_absolute());
@@ -315,7 +395,7 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
         let result = self.unify(&left_value, &right_value);
         self.local_env.pop();
 
-        result
+        result.map_err(|error| error.within(body_segment, [right_param.name.or(left_param.name)]))
     }
 
     /// Solve a pattern unification problem that looks like:
@@ -335,7 +415,7 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
         meta_var: AbsoluteVar,
         spine: &[Elim<'core>],
         value: &Value<'core>,
-    ) -> Result<(), UnifyError> {
+    ) -> Result<(), UnifyError<'core>> {
         self.init_renaming(spine)?;
         let expr = self.rename(meta_var, value)?;
         let fun_expr = self.fun_intros(spine, expr);
@@ -348,6 +428,7 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
         )
         .eval(&fun_expr);
         self.meta_values.set_absolute(meta_var, Some(solution));
+        self.solved = true;
         Ok(())
     }
 
@@ -429,7 +510,7 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
                     Elim::BoolCases(cases) => {
                         let then = self
                             .elim_env()
-                            .apply_bool_cases(cases.clone(), Value::Lit(Lit::Bool(false)));
+                            .apply_bool_cases(cases.clone(), Value::Lit(Lit::Bool(true)));
                         let then = self.rename(meta_var, &then)?;
 
                         let r#else = self
@@ -542,45 +623,159 @@ impl<'core, 'env> UnifyCtx<'core, 'env> {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum UnifyError {
+#[derive(Debug, Clone)]
+pub enum UnifyError<'core> {
     /// A known part of one value failed to match with a known part of the other
     /// value that we are comparing against.
-    //
-    // TODO: Return some sort of type-diff
-    Mismatch,
+    Mismatch(Box<Mismatch<'core>>),
     /// An error that was found in the problem spine.
     Spine(SpineError),
     /// An error that occurred when renaming the solution.
     Rename(RenameError),
 }
 
-impl UnifyError {
+/// The smallest parts of two values that failed to unify, and where they were
+/// found.
+#[derive(Debug, Clone)]
+pub struct Mismatch<'core> {
+    /// The path from the outermost values to `found` and `expected`, outermost
+    /// segment first.
+    pub path: Vec<PathSegment>,
+    /// The names of the local variables bound along `path`, which are in scope
+    /// in `found` and `expected`.
+    pub binders: Vec<Option<Symbol>>,
+    pub found: Expr<'core>,
+    pub expected: Expr<'core>,
+}
+
+/// A step into a part of a value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// The type of a function's parameter.
+    Param,
+    /// The body of a function type.
+    ReturnType,
+    /// The body of a function literal.
+    FunBody,
+    /// The argument of the `n`th application in a spine, counting from zero.
+    Arg(usize),
+    /// The field of a record or record type.
+    Field(Symbol),
+    /// The `n`th element of a list, counting from zero.
+    Elem(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Param => write!(f, "the parameter type"),
+            Self::ReturnType => write!(f, "the return type"),
+            Self::FunBody => write!(f, "the function body"),
+            Self::Arg(index) => write!(f, "argument {}", index + 1),
+            Self::Field(label) => write!(f, "field `{label}`"),
+            Self::Elem(index) => write!(f, "element {}", index + 1),
+        }
+    }
+}
+
+impl<'core> UnifyError<'core> {
+    /// Record that the error occurred in `segment` of the values being
+    /// unified, under the local variables named `binders`.
+    fn within(
+        mut self,
+        segment: PathSegment,
+        binders: impl IntoIterator<Item = Option<Symbol>>,
+    ) -> Self {
+        if let Self::Mismatch(mismatch) = &mut self {
+            mismatch.path.insert(0, segment);
+            mismatch.binders.splice(0..0, binders);
+        }
+        self
+    }
+
+    /// Forget where a mismatch occurred, so that it is reported between the
+    /// whole values being unified.
+    pub fn without_path(self) -> Self {
+        match self {
+            Self::Mismatch(mut mismatch) if !mismatch.path.is_empty() => {
+                mismatch.path.clear();
+                mismatch.binders.clear();
+                Self::Mismatch(mismatch)
+            }
+            error => error,
+        }
+    }
+
+    /// `expected` and `found` are the whole values that were unified. For a
+    /// mismatch, `pretty` prints the mismatching parts of them, given the
+    /// names of the local variables bound along the path to them.
     pub fn to_diagnostic(
-        self,
+        &self,
         file_id: usize,
         range: TextRange,
         expected: &str,
         found: &str,
+        mut pretty: impl FnMut(&[Option<Symbol>], &Expr<'core>) -> String,
     ) -> Diagnostic<usize> {
+        let mut notes = Vec::new();
         let message = match self {
-            Self::Mismatch => format!("type mismatch: expected `{expected}`, found `{found}`"),
-            Self::Spine(_) => String::from("variable appeared more than once in problem spine"),
+            Self::Mismatch(mismatch) if mismatch.path.is_empty() => {
+                format!("type mismatch: expected `{expected}`, found `{found}`")
+            }
+            Self::Mismatch(mismatch) => {
+                // Innermost segment first, eg "field `z` of field `y`".
+                let path = mismatch.path.iter().rev().map(ToString::to_string);
+                let path = path.collect::<Vec<_>>().join(" of ");
+                let part_expected = pretty(&mismatch.binders, &mismatch.expected);
+                let part_found = pretty(&mismatch.binders, &mismatch.found);
+                notes.push(format!("expected type: `{expected}`"));
+                notes.push(format!("   found type: `{found}`"));
+                format!("type mismatch in {path}: expected `{part_expected}`, found `{part_found}`")
+            }
+            Self::Spine(SpineError::NonLinearSpine(_)) => {
+                String::from("variable appeared more than once in problem spine")
+            }
+            Self::Spine(error) => format!("could not solve postponed unification problem: {error}"),
             Self::Rename(_) => {
                 String::from("application in problem spine was not a local variable")
             }
         };
         Diagnostic::error()
             .with_message(message)
+            .with_code(codes::UNABLE_TO_UNIFY.code)
             .with_labels(vec![Label::primary(file_id, range)])
+            .with_notes(notes)
+    }
+}
+
+impl SpineError {
+    /// Whether a problem with this error could become solvable once other
+    /// metavariables are solved.
+    pub const fn can_postpone(self) -> bool { !matches!(self, Self::NonLinearSpine(_)) }
+}
+
+impl fmt::Display for SpineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NonLinearSpine(_) => write!(f, "variable appeared more than once in spine"),
+            Self::NonLocalFunApp => {
+                write!(
+                    f,
+                    "metavariable was applied to an argument that is not a variable"
+                )
+            }
+            Self::BoolCases => write!(f, "metavariable was the condition of an `if`"),
+            Self::IntCases => write!(f, "metavariable was the scrutinee of a `match`"),
+            Self::RecordProj => write!(f, "metavariable was projected from"),
+        }
     }
 }
 
-impl From<SpineError> for UnifyError {
+impl From<SpineError> for UnifyError<'_> {
     fn from(error: SpineError) -> Self { Self::Spine(error) }
 }
 
-impl From<RenameError> for UnifyError {
+impl From<RenameError> for UnifyError<'_> {
     fn from(error: RenameError) -> Self { Self::Rename(error) }
 }
 
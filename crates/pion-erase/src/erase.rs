@@ -0,0 +1,206 @@
+//! Erasing core expressions to the [erased IR](crate::syntax).
+
+use pion_core::env::{RelativeVar, UniqueEnv};
+use pion_core::prim::Prim;
+use pion_core::syntax::{Expr, Lit};
+
+use crate::syntax::{self, prim_params};
+use crate::{Error, Relevance};
+
+/// The relevance of binders of type `r#type`.
+///
+/// This is a syntactic approximation: a type that only reduces to `Type` or
+/// an `Eq` proposition is considered relevant. That is always safe, since
+/// relevant binders are simply kept.
+pub fn relevance(r#type: &Expr) -> Relevance {
+    match r#type {
+        Expr::Prim(Prim::Type) => Relevance::Irrelevant,
+        Expr::FunType { body, .. } => relevance(body),
+        Expr::FunApp { .. } => {
+            let mut head = r#type;
+            while let Expr::FunApp { fun, .. } = head {
+                head = fun;
+            }
+            match head {
+                Expr::Prim(Prim::Eq) => Relevance::Irrelevant,
+                _ => Relevance::Relevant,
+            }
+        }
+        _ => Relevance::Relevant,
+    }
+}
+
+/// Erases expressions in the scope of some local variables.
+#[derive(Debug, Default)]
+pub struct Eraser {
+    locals: UniqueEnv<Relevance>,
+}
+
+impl Eraser {
+    pub fn new() -> Self { Self::default() }
+
+    /// Bring a variable into scope (for example, a top-level binding that
+    /// later expressions refer to).
+    pub fn push_local(&mut self, relevance: Relevance) { self.locals.push(relevance); }
+
+    pub fn pop_local(&mut self) { self.locals.pop(); }
+
+    pub fn erase(&mut self, expr: &Expr) -> Result<syntax::Expr, Error> {
+        let expr = match expr {
+            Expr::Error => return Err(Error::IllTyped),
+            Expr::MetaVar(_) => return Err(Error::UnsolvedMeta),
+            Expr::Lit(Lit::Int(int)) => syntax::Expr::Int(*int),
+            Expr::Lit(Lit::Bool(bool)) => syntax::Expr::Bool(*bool),
+            Expr::FunType { .. } | Expr::RecordType(_) => syntax::Expr::Erased,
+            Expr::Prim(prim) => self.erase_prim_app(*prim, &[])?,
+            Expr::LocalVar(var) => match self.locals.get_relative(*var) {
+                None => panic!("Unbound local var: {var:?}"),
+                Some(Relevance::Relevant) => syntax::Expr::Var(*var),
+                Some(Relevance::Irrelevant) => syntax::Expr::Erased,
+            },
+            Expr::Let { binding, body } => {
+                let relevance = relevance(binding.r#type);
+                let rhs = match relevance {
+                    Relevance::Relevant => self.erase(binding.rhs)?,
+                    Relevance::Irrelevant => syntax::Expr::Erased,
+                };
+                let body = self.erase_binder(relevance, body)?;
+                syntax::Expr::Let(Box::new(rhs), Box::new(body))
+            }
+            Expr::FunLit { param, body } => {
+                let relevance = relevance(param.r#type);
+                syntax::Expr::Fun(relevance, Box::new(self.erase_binder(relevance, body)?))
+            }
+            Expr::FunApp { .. } => {
+                let mut args = Vec::new();
+                let mut head = expr;
+                while let Expr::FunApp { fun, arg } = head {
+                    args.push(arg.expr);
+                    head = fun;
+                }
+                args.reverse();
+
+                if let Expr::Prim(prim) = head {
+                    return self.erase_prim_app(*prim, &args);
+                }
+                let mut expr = self.erase(head)?;
+                if expr == syntax::Expr::Erased {
+                    // The application of a type family, or of a lemma.
+                    return Ok(expr);
+                }
+                for arg in args {
+                    let arg = self.erase(arg)?;
+                    expr = syntax::Expr::App(Box::new(expr), Box::new(arg));
+                }
+                expr
+            }
+            Expr::ListLit(elems) => syntax::Expr::List(
+                (elems.iter())
+                    .map(|elem| self.erase(elem))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::RecordLit(fields) => syntax::Expr::Record(
+                (fields.iter())
+                    .map(|(label, expr)| Ok((*label, self.erase(expr)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::RecordProj(scrut, label) => {
+                syntax::Expr::Proj(Box::new(self.erase_inspected(scrut)?), *label)
+            }
+            Expr::MatchBool { cond, then, r#else } => syntax::Expr::If(
+                Box::new(self.erase_inspected(cond)?),
+                Box::new(self.erase(then)?),
+                Box::new(self.erase(r#else)?),
+            ),
+            Expr::MatchInt {
+                scrut,
+                cases,
+                default,
+            } => syntax::Expr::Switch {
+                scrut: Box::new(self.erase_inspected(scrut)?),
+                cases: (cases.iter())
+                    .map(|(int, expr)| Ok((*int, self.erase(expr)?)))
+                    .collect::<Result<_, _>>()?,
+                default: Box::new(self.erase(default)?),
+            },
+        };
+        Ok(expr)
+    }
+
+    fn erase_binder(&mut self, relevance: Relevance, body: &Expr) -> Result<syntax::Expr, Error> {
+        self.push_local(relevance);
+        let body = self.erase(body);
+        self.pop_local();
+        body
+    }
+
+    /// Erase an expression whose value is taken apart at runtime, checking
+    /// that it is not a type or a proof.
+    fn erase_inspected(&mut self, expr: &Expr) -> Result<syntax::Expr, Error> {
+        match self.erase(expr)? {
+            syntax::Expr::Erased => Err(Error::IrrelevantUse),
+            expr => Ok(expr),
+        }
+    }
+
+    /// Erase `prim` applied to `args`, dropping its irrelevant arguments. If
+    /// it is applied to fewer arguments than it has parameters, it is
+    /// eta-expanded so that the remaining ones can be passed like to any
+    /// other function.
+    fn erase_prim_app(&mut self, prim: Prim, args: &[&Expr]) -> Result<syntax::Expr, Error> {
+        let Some(params) = prim_params(prim) else {
+            return Ok(syntax::Expr::Erased);
+        };
+        let (given, rest) = args.split_at(params.len().min(args.len()));
+
+        // Arguments that are only passed along (such as list elements, or the
+        // branches of `bool_rec`) may be types or proofs.
+        let inspected = match prim {
+            Prim::push | Prim::bool_rec => 1,
+            Prim::subst => 0,
+            _ => params.len(),
+        };
+        let mut prim_args = Vec::new();
+        for (arg, relevance) in given.iter().zip(params) {
+            if *relevance == Relevance::Relevant {
+                prim_args.push(match prim_args.len() < inspected {
+                    true => self.erase_inspected(arg)?,
+                    false => self.erase(arg)?,
+                });
+            }
+        }
+
+        let mut expr = match &params[given.len()..] {
+            [] => syntax::Expr::Prim(prim, prim_args),
+            missing => {
+                // Bind the given arguments, then take the missing ones.
+                let binders = prim_args.len() + missing.len();
+                let mut refs: Vec<_> = (0..prim_args.len())
+                    .map(|binder| syntax::Expr::Var(RelativeVar::new(binders - binder - 1)))
+                    .collect();
+                for (index, relevance) in missing.iter().enumerate() {
+                    if *relevance == Relevance::Relevant {
+                        let binder = prim_args.len() + index;
+                        refs.push(syntax::Expr::Var(RelativeVar::new(binders - binder - 1)));
+                    }
+                }
+
+                let mut expr = syntax::Expr::Prim(prim, refs);
+                for relevance in missing.iter().rev() {
+                    expr = syntax::Expr::Fun(*relevance, Box::new(expr));
+                }
+                // Each argument is bound under the binders of the ones before it.
+                for (index, mut arg) in prim_args.into_iter().enumerate().rev() {
+                    arg.shift(index);
+                    expr = syntax::Expr::Let(Box::new(arg), Box::new(expr));
+                }
+                expr
+            }
+        };
+        for arg in rest {
+            let arg = self.erase(arg)?;
+            expr = syntax::Expr::App(Box::new(expr), Box::new(arg));
+        }
+        Ok(expr)
+    }
+}
@@ -0,0 +1,55 @@
+//! Erasing the parts of elaborated core that have no runtime content.
+//!
+//! The elaborator keeps every type argument and every `Eq` proof, since the
+//! type checker needs them. At runtime they are never inspected: nothing in
+//! the language can branch on a type or take apart a proof. The
+//! [erasure pass](erase) determines which binders are computationally
+//! irrelevant, and produces an [erased IR](syntax) in which irrelevant terms
+//! are replaced by a placeholder, ready for the bytecode VM or a code
+//! generator.
+//!
+//! A binder is irrelevant if its type is `Type`, an `Eq` proposition, or a
+//! function returning either (a type family, or a proof of a lemma). Values
+//! of these types can only flow into other irrelevant positions, so every
+//! reference to an irrelevant variable is erased. Erasure checks that no
+//! erased value is taken apart at runtime (by a `match`, a projection or an
+//! arithmetic primitive). Irrelevant parameters are kept (and passed an
+//! erased argument), so that calling conventions do not depend on types.
+
+use std::fmt;
+
+pub mod erase;
+pub mod syntax;
+
+pub use self::erase::Eraser;
+pub use self::syntax::Expr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The expression contains an error.
+    IllTyped,
+    /// The expression contains an unsolved metavariable.
+    UnsolvedMeta,
+    /// A type or proof is taken apart at runtime, for example by matching on
+    /// it.
+    IrrelevantUse,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IllTyped => write!(f, "cannot erase an ill-typed expression"),
+            Self::UnsolvedMeta => write!(f, "cannot erase an expression with unsolved holes"),
+            Self::IrrelevantUse => write!(f, "cannot use a type or proof at runtime"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Whether the value of a binder is needed at runtime.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Relevance {
+    Relevant,
+    Irrelevant,
+}
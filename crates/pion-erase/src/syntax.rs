@@ -0,0 +1,197 @@
+//! The erased IR.
+//!
+//! Compared to core, types and proofs have been replaced by
+//! [`Expr::Erased`], and primitive functions are always applied to exactly
+//! their relevant arguments. Variables are still de Bruijn indices, and
+//! every binder of the original expression is kept.
+
+use std::collections::BTreeSet;
+
+use pion_core::env::RelativeVar;
+use pion_core::prim::Prim;
+use pion_symbol::Symbol;
+
+use crate::Relevance;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// A value with no runtime content: a type, or a proof.
+    Erased,
+    Int(u32),
+    Bool(bool),
+    Var(RelativeVar),
+
+    /// `let x = rhs; body`.
+    Let(Box<Self>, Box<Self>),
+
+    /// A function. If its parameter is irrelevant, the body never refers to
+    /// it.
+    Fun(Relevance, Box<Self>),
+    App(Box<Self>, Box<Self>),
+    /// A primitive function applied to its relevant arguments:
+    ///
+    /// * `len xs`, `push xs x`, `append xs ys`
+    /// * `add x y` etc, and the comparisons
+    /// * `fix f`, which is a function
+    /// * `bool_rec b t f`
+    /// * `subst px`, which is `px`: the proof is erased, but closed proofs
+    ///   evaluate to `refl`.
+    ///
+    /// Type formers and `refl` have no relevant arguments, and are erased.
+    Prim(Prim, Vec<Self>),
+
+    List(Vec<Self>),
+    Record(Vec<(Symbol, Self)>),
+    Proj(Box<Self>, Symbol),
+
+    If(Box<Self>, Box<Self>, Box<Self>),
+    Switch {
+        scrut: Box<Self>,
+        cases: Vec<(u32, Self)>,
+        default: Box<Self>,
+    },
+}
+
+/// The relevance of each parameter of `prim`, or `None` if applications of
+/// `prim` are erased.
+pub const fn prim_params(prim: Prim) -> Option<&'static [Relevance]> {
+    use Relevance::{Irrelevant as I, Relevant as R};
+
+    match prim {
+        Prim::Type | Prim::Int | Prim::Bool | Prim::List | Prim::Eq | Prim::refl => None,
+        Prim::len => Some(&[I, R]),
+        Prim::push | Prim::append => Some(&[I, R, R]),
+        Prim::add
+        | Prim::sub
+        | Prim::mul
+        | Prim::eq
+        | Prim::ne
+        | Prim::gt
+        | Prim::lt
+        | Prim::gte
+        | Prim::lte => Some(&[R, R]),
+        Prim::fix => Some(&[I, I, R]),
+        Prim::bool_rec => Some(&[I, R, R, R]),
+        Prim::subst => Some(&[I, I, I, I, I, R]),
+    }
+}
+
+/// The number of arguments of `prim` in [`Expr::Prim`].
+pub fn prim_arity(prim: Prim) -> usize {
+    prim_params(prim).map_or(0, |params| {
+        (params.iter())
+            .filter(|relevance| **relevance == Relevance::Relevant)
+            .count()
+    })
+}
+
+impl Expr {
+    /// The variables (as relative indices) that the expression refers to.
+    pub fn free_vars(&self) -> BTreeSet<usize> {
+        let mut vars = BTreeSet::new();
+        recur(self, 0, &mut vars);
+        return vars;
+
+        fn recur(expr: &Expr, binders: usize, vars: &mut BTreeSet<usize>) {
+            match expr {
+                Expr::Var(var) => {
+                    if let Some(var) = usize::from(*var).checked_sub(binders) {
+                        vars.insert(var);
+                    }
+                }
+                Expr::Erased | Expr::Int(_) | Expr::Bool(_) => {}
+                Expr::Let(rhs, body) => {
+                    recur(rhs, binders, vars);
+                    recur(body, binders + 1, vars);
+                }
+                Expr::Fun(_, body) => recur(body, binders + 1, vars),
+                Expr::App(fun, arg) => {
+                    recur(fun, binders, vars);
+                    recur(arg, binders, vars);
+                }
+                Expr::Prim(_, exprs) | Expr::List(exprs) => {
+                    for expr in exprs {
+                        recur(expr, binders, vars);
+                    }
+                }
+                Expr::Record(fields) => {
+                    for (_, expr) in fields {
+                        recur(expr, binders, vars);
+                    }
+                }
+                Expr::Proj(scrut, _) => recur(scrut, binders, vars),
+                Expr::If(cond, then, r#else) => {
+                    recur(cond, binders, vars);
+                    recur(then, binders, vars);
+                    recur(r#else, binders, vars);
+                }
+                Expr::Switch {
+                    scrut,
+                    cases,
+                    default,
+                } => {
+                    recur(scrut, binders, vars);
+                    for (_, expr) in cases {
+                        recur(expr, binders, vars);
+                    }
+                    recur(default, binders, vars);
+                }
+            }
+        }
+    }
+
+    /// Add `amount` to the free variables of the expression, so that it can
+    /// be moved under `amount` more binders.
+    pub fn shift(&mut self, amount: usize) {
+        if amount != 0 {
+            recur(self, 0, amount);
+        }
+
+        fn recur(expr: &mut Expr, binders: usize, amount: usize) {
+            match expr {
+                Expr::Var(var) => {
+                    if usize::from(*var) >= binders {
+                        *var = RelativeVar::new(usize::from(*var) + amount);
+                    }
+                }
+                Expr::Erased | Expr::Int(_) | Expr::Bool(_) => {}
+                Expr::Let(rhs, body) => {
+                    recur(rhs, binders, amount);
+                    recur(body, binders + 1, amount);
+                }
+                Expr::Fun(_, body) => recur(body, binders + 1, amount),
+                Expr::App(fun, arg) => {
+                    recur(fun, binders, amount);
+                    recur(arg, binders, amount);
+                }
+                Expr::Prim(_, exprs) | Expr::List(exprs) => {
+                    for expr in exprs {
+                        recur(expr, binders, amount);
+                    }
+                }
+                Expr::Record(fields) => {
+                    for (_, expr) in fields {
+                        recur(expr, binders, amount);
+                    }
+                }
+                Expr::Proj(scrut, _) => recur(scrut, binders, amount),
+                Expr::If(cond, then, r#else) => {
+                    recur(cond, binders, amount);
+                    recur(then, binders, amount);
+                    recur(r#else, binders, amount);
+                }
+                Expr::Switch {
+                    scrut,
+                    cases,
+                    default,
+                } => {
+                    recur(scrut, binders, amount);
+                    for (_, expr) in cases {
+                        recur(expr, binders, amount);
+                    }
+                    recur(default, binders, amount);
+                }
+            }
+        }
+    }
+}
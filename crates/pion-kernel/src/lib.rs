@@ -0,0 +1,590 @@
+//! An independent type checker for elaborated core.
+//!
+//! The elaborator is large: it inserts implicit arguments, solves
+//! metavariables by unification, and compiles pattern matches. A bug in any
+//! of these can silently produce ill-typed core. The kernel re-checks the
+//! elaborator's output with a much smaller set of rules, and is deliberately
+//! independent of `pion-elab`: it has no metavariables, never inserts
+//! anything, and only compares types up to definitional equality (with eta
+//! for functions and records).
+//!
+//! `#error` expressions are accepted at any type, as they are by the
+//! normalizer: the elaborator only produces them after reporting a
+//! diagnostic, or as the parameter type of a metavariable's solution.
+
+use std::fmt;
+
+use pion_core::env::{EnvLen, UniqueEnv};
+use pion_core::prim::Prim;
+use pion_core::semantics::{
+    Closure, Elim, ElimEnv, EvalEnv, EvalOpts, Head, LocalValues, MetaValues, QuoteEnv, Telescope,
+    Type, Value,
+};
+use pion_core::syntax::{record_keys_equal, Expr, FunArg, FunParam, Item, LetBinding, Lit};
+use pion_core::unelab::{self, NameEnv, Unelaborator};
+use pion_printer::BumpDocAllocator;
+use pion_symbol::Symbol;
+
+/// A term that the kernel rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// What is wrong with the term.
+    pub message: String,
+    /// The offending term, pretty-printed.
+    pub term: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n  in term `{}`", self.message, self.term)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Checks expressions in the scope of some local variables.
+pub struct Checker<'core> {
+    bump: &'core bumpalo::Bump,
+    local_names: NameEnv,
+    local_types: UniqueEnv<Type<'core>>,
+    local_values: LocalValues<'core>,
+}
+
+/// The kernel never sees metavariables.
+fn no_metas<'core>() -> &'static MetaValues<'core> { <&MetaValues>::from(&[][..]) }
+
+impl<'core> Checker<'core> {
+    pub fn new(bump: &'core bumpalo::Bump) -> Self {
+        Self {
+            bump,
+            local_names: NameEnv::default(),
+            local_types: UniqueEnv::default(),
+            local_values: LocalValues::default(),
+        }
+    }
+
+    /// Check a top-level item. The bindings of `Let` items are brought into
+    /// scope for later items.
+    pub fn check_item(&mut self, item: &Item<'core>) -> Result<(), Error> {
+        match item {
+            Item::Let(binding) => {
+                let r#type = self.check_let_binding(binding.r#type, binding.rhs)?;
+                let value = self.eval(&binding.rhs);
+                self.push_local(binding.name, r#type, value);
+                Ok(())
+            }
+            Item::Eval(expr) => self.synth(expr).map(|_| ()),
+        }
+    }
+
+    /// Check that `expr` is a type.
+    pub fn check_type(&mut self, expr: &Expr<'core>) -> Result<(), Error> {
+        self.check(expr, &Type::TYPE)
+    }
+
+    /// Synthesize the type of `expr`.
+    pub fn synth(&mut self, expr: &Expr<'core>) -> Result<Type<'core>, Error> {
+        match expr {
+            Expr::Error => Ok(Type::Error),
+            Expr::Lit(Lit::Bool(_)) => Ok(Type::BOOL),
+            Expr::Lit(Lit::Int(_)) => Ok(Type::INT),
+            Expr::Prim(prim) => Ok(prim.r#type()),
+            Expr::LocalVar(var) => match self.local_types.get_relative(*var) {
+                Some(r#type) => Ok(r#type.clone()),
+                // The unelaborator cannot print unbound variables.
+                None => Err(Error {
+                    message: String::from("unbound local variable"),
+                    term: format!("{var:?}"),
+                }),
+            },
+            Expr::MetaVar(_) => Err(self.error(expr, "unsolved metavariable".to_owned())),
+
+            Expr::Let { binding, body } => {
+                let LetBinding { name, r#type, rhs } = binding;
+                let r#type = self.check_let_binding(**r#type, **rhs)?;
+                let value = self.eval(rhs);
+                self.push_local(*name, r#type, value);
+                let body_type = self.synth(body);
+                self.pop_local();
+                body_type
+            }
+
+            Expr::FunType { param, body } => {
+                self.check_type(param.r#type)?;
+                let param_type = self.eval(param.r#type);
+                self.push_param(param.name, param_type);
+                let result = self.check_type(body);
+                self.pop_local();
+                result.map(|()| Type::TYPE)
+            }
+            Expr::FunLit { param, body } => {
+                self.check_type(param.r#type)?;
+                let param_type = self.eval(param.r#type);
+                let local_values = self.local_values.clone();
+
+                self.push_param(param.name, param_type.clone());
+                let body_type = self.synth(body).map(|body_type| self.quote(&body_type));
+                self.pop_local();
+
+                Ok(Type::FunType {
+                    param: FunParam::new(param.plicity, param.name, self.bump.alloc(param_type)),
+                    body: Closure::new(local_values, self.bump.alloc(body_type?)),
+                })
+            }
+            Expr::FunApp { fun, arg } => match self.synth(fun)? {
+                Type::Error => {
+                    self.synth(arg.expr)?;
+                    Ok(Type::Error)
+                }
+                Type::FunType { param, body } if param.plicity == arg.plicity => {
+                    self.check(arg.expr, param.r#type)?;
+                    let arg_value = self.eval(arg.expr);
+                    Ok(self.elim_env().apply_closure(body, arg_value))
+                }
+                Type::FunType { param, .. } => Err(self.error(
+                    expr,
+                    format!(
+                        "{} argument applied to {} function",
+                        plicity_name(arg.plicity),
+                        plicity_name(param.plicity),
+                    ),
+                )),
+                fun_type => {
+                    let fun_type = self.pretty_value(&fun_type);
+                    Err(self.error(expr, format!("applied a non-function of type `{fun_type}`")))
+                }
+            },
+
+            Expr::ListLit(elems) => {
+                let elem_type = match elems.split_first() {
+                    None => Type::Error,
+                    Some((first, rest)) => {
+                        let elem_type = self.synth(first)?;
+                        for elem in rest {
+                            self.check(elem, &elem_type)?;
+                        }
+                        elem_type
+                    }
+                };
+                let elim_env = self.elim_env();
+                Ok(elim_env.fun_app(Type::prim(Prim::List), FunArg::explicit(elem_type)))
+            }
+            Expr::RecordType(fields) => {
+                self.check_duplicate_fields(expr, fields)?;
+                let len = self.local_values.len();
+                let mut result = Ok(());
+                for (name, r#type) in *fields {
+                    result = self.check_type(r#type);
+                    if result.is_err() {
+                        break;
+                    }
+                    let r#type = self.eval(r#type);
+                    self.push_param(Some(*name), r#type);
+                }
+                self.truncate_locals(len);
+                result.map(|()| Type::TYPE)
+            }
+            Expr::RecordLit(fields) => {
+                self.check_duplicate_fields(expr, fields)?;
+                let mut types = Vec::with_capacity(fields.len());
+                for (index, (name, expr)) in fields.iter().enumerate() {
+                    let r#type = self.synth(expr)?;
+                    let r#type = self.quote(&r#type).shift(self.bump, EnvLen::from(index));
+                    types.push((*name, r#type));
+                }
+                let types = self.bump.alloc_slice_copy(&types);
+                Ok(Type::RecordType(Telescope::new(
+                    self.local_values.clone(),
+                    types,
+                )))
+            }
+            Expr::RecordProj(scrut, label) => {
+                let mut telescope = match self.synth(scrut)? {
+                    Type::Error => return Ok(Type::Error),
+                    Type::RecordType(telescope) => telescope,
+                    scrut_type => {
+                        let scrut_type = self.pretty_value(&scrut_type);
+                        return Err(self.error(
+                            expr,
+                            format!("projected from a non-record of type `{scrut_type}`"),
+                        ));
+                    }
+                };
+                let scrut = self.eval(scrut);
+                while let Some((name, r#type, update)) =
+                    self.elim_env().split_telescope(&mut telescope)
+                {
+                    if name == *label {
+                        return Ok(r#type);
+                    }
+                    update(self.elim_env().record_proj(scrut.clone(), name));
+                }
+                Err(self.error(expr, format!("record has no field `{label}`")))
+            }
+
+            Expr::MatchBool { cond, then, r#else } => {
+                self.check(cond, &Type::BOOL)?;
+                let r#type = self.synth(then)?;
+                self.check(r#else, &r#type)?;
+                Ok(r#type)
+            }
+            Expr::MatchInt {
+                scrut,
+                cases,
+                default,
+            } => {
+                self.check(scrut, &Type::INT)?;
+                let r#type = self.synth(default)?;
+                for (_, expr) in *cases {
+                    self.check(expr, &r#type)?;
+                }
+                Ok(r#type)
+            }
+        }
+    }
+
+    /// Check that `expr` has type `expected`.
+    pub fn check(&mut self, expr: &Expr<'core>, expected: &Type<'core>) -> Result<(), Error> {
+        match (expr, expected) {
+            (Expr::Let { binding, body }, _) => {
+                let LetBinding { name, r#type, rhs } = binding;
+                let r#type = self.check_let_binding(**r#type, **rhs)?;
+                let value = self.eval(rhs);
+                self.push_local(*name, r#type, value);
+                let result = self.check(body, expected);
+                self.pop_local();
+                result
+            }
+            (
+                Expr::FunLit { param, body },
+                Type::FunType {
+                    param: expected_param,
+                    body: expected_body,
+                },
+            ) if param.plicity == expected_param.plicity => {
+                self.check_type(param.r#type)?;
+                let param_type = self.eval(param.r#type);
+                self.convert(expr, expected_param.r#type, &param_type)?;
+
+                let var = Value::local_var(self.local_values.len().to_absolute());
+                let expected_body = self.elim_env().apply_closure(expected_body.clone(), var);
+                self.push_param(param.name, param_type);
+                let result = self.check(body, &expected_body);
+                self.pop_local();
+                result
+            }
+            (Expr::ListLit(elems), Type::Neutral(Head::Prim(Prim::List), args)) => {
+                let [Elim::FunApp(elem_type)] = args.as_slice() else {
+                    return self.synth_and_convert(expr, expected);
+                };
+                for elem in *elems {
+                    self.check(elem, &elem_type.expr)?;
+                }
+                Ok(())
+            }
+            (Expr::RecordLit(fields), Type::RecordType(telescope))
+                if record_keys_equal(fields, telescope.fields) =>
+            {
+                self.check_duplicate_fields(expr, fields)?;
+                let mut telescope = telescope.clone();
+                for (_, expr) in *fields {
+                    let (_, r#type, update) =
+                        self.elim_env().split_telescope(&mut telescope).unwrap();
+                    self.check(expr, &r#type)?;
+                    update(self.eval(expr));
+                }
+                Ok(())
+            }
+            (Expr::MatchBool { cond, then, r#else }, _) => {
+                self.check(cond, &Type::BOOL)?;
+                self.check(then, expected)?;
+                self.check(r#else, expected)
+            }
+            (
+                Expr::MatchInt {
+                    scrut,
+                    cases,
+                    default,
+                },
+                _,
+            ) => {
+                self.check(scrut, &Type::INT)?;
+                for (_, expr) in *cases {
+                    self.check(expr, expected)?;
+                }
+                self.check(default, expected)
+            }
+            _ => self.synth_and_convert(expr, expected),
+        }
+    }
+
+    fn synth_and_convert(
+        &mut self,
+        expr: &Expr<'core>,
+        expected: &Type<'core>,
+    ) -> Result<(), Error> {
+        let r#type = self.synth(expr)?;
+        self.convert(expr, expected, &r#type)
+    }
+
+    /// Check the type and right-hand side of a let binding, returning the
+    /// type.
+    fn check_let_binding(
+        &mut self,
+        r#type: Expr<'core>,
+        rhs: Expr<'core>,
+    ) -> Result<Type<'core>, Error> {
+        self.check_type(&r#type)?;
+        let r#type = self.eval(&r#type);
+        self.check(&rhs, &r#type)?;
+        Ok(r#type)
+    }
+
+    fn check_duplicate_fields<T>(
+        &mut self,
+        expr: &Expr<'core>,
+        fields: &[(Symbol, T)],
+    ) -> Result<(), Error> {
+        for (index, (name, _)) in fields.iter().enumerate() {
+            if fields[..index].iter().any(|(prev, _)| prev == name) {
+                return Err(self.error(expr, format!("duplicate record field `{name}`")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that `found` is definitionally equal to `expected`.
+    fn convert(
+        &mut self,
+        expr: &Expr<'core>,
+        expected: &Type<'core>,
+        found: &Type<'core>,
+    ) -> Result<(), Error> {
+        if self.is_convertible(self.local_values.len(), expected, found) {
+            return Ok(());
+        }
+        let expected = self.pretty_value(expected);
+        let found = self.pretty_value(found);
+        Err(self.error(
+            expr,
+            format!("type mismatch: expected `{expected}`, found `{found}`"),
+        ))
+    }
+
+    /// Whether two values (in a scope of `len` local variables) are
+    /// definitionally equal.
+    fn is_convertible(&self, len: EnvLen, left: &Value<'core>, right: &Value<'core>) -> bool {
+        let var = || Value::local_var(len.to_absolute());
+        let next = EnvLen::from(usize::from(len) + 1);
+        let elim_env = self.elim_env();
+
+        match (left, right) {
+            (Value::Error, _) | (_, Value::Error) => true,
+            (Value::Lit(left), Value::Lit(right)) => left == right,
+
+            (Value::Neutral(left_head, left_spine), Value::Neutral(right_head, right_spine)) => {
+                left_head == right_head
+                    && left_spine.len() == right_spine.len()
+                    && (left_spine.iter().zip(right_spine.iter()))
+                        .all(|(left, right)| self.is_elim_convertible(len, left, right))
+            }
+
+            (
+                Value::FunType {
+                    param: left_param,
+                    body: left_body,
+                },
+                Value::FunType {
+                    param: right_param,
+                    body: right_body,
+                },
+            )
+            | (
+                Value::FunLit {
+                    param: left_param,
+                    body: left_body,
+                },
+                Value::FunLit {
+                    param: right_param,
+                    body: right_body,
+                },
+            ) => {
+                left_param.plicity == right_param.plicity
+                    && self.is_convertible(len, left_param.r#type, right_param.r#type)
+                    && self.is_convertible(
+                        next,
+                        &elim_env.apply_closure(left_body.clone(), var()),
+                        &elim_env.apply_closure(right_body.clone(), var()),
+                    )
+            }
+            // Eta-conversion: `(fun x => f x) = f`
+            (Value::FunLit { param, body }, value) | (value, Value::FunLit { param, body }) => {
+                let body = elim_env.apply_closure(body.clone(), var());
+                let app = elim_env.fun_app(value.clone(), FunArg::new(param.plicity, var()));
+                self.is_convertible(next, &body, &app)
+            }
+
+            (Value::RecordType(left), Value::RecordType(right)) => {
+                if !record_keys_equal(left.fields, right.fields) {
+                    return false;
+                }
+                let (mut left, mut right) = (left.clone(), right.clone());
+                let mut len = len;
+                while let Some(((_, left_type, left_update), (_, right_type, right_update))) =
+                    Option::zip(
+                        elim_env.split_telescope(&mut left),
+                        elim_env.split_telescope(&mut right),
+                    )
+                {
+                    if !self.is_convertible(len, &left_type, &right_type) {
+                        return false;
+                    }
+                    let var = Value::local_var(len.to_absolute());
+                    left_update(var.clone());
+                    right_update(var);
+                    len = EnvLen::from(usize::from(len) + 1);
+                }
+                true
+            }
+
+            (Value::List(left), Value::List(right)) => {
+                left.len() == right.len()
+                    && (left.iter().zip(right.iter()))
+                        .all(|(left, right)| self.is_convertible(len, left, right))
+            }
+            (Value::RecordLit(left), Value::RecordLit(right)) => {
+                record_keys_equal(left, right)
+                    && (left.iter().zip(right.iter()))
+                        .all(|((_, left), (_, right))| self.is_convertible(len, left, right))
+            }
+            // Eta-conversion: `{ x = r.x, y = r.y } = r`
+            (Value::RecordLit(fields), value) | (value, Value::RecordLit(fields)) => {
+                fields.iter().all(|(name, field)| {
+                    let proj = elim_env.record_proj(value.clone(), *name);
+                    self.is_convertible(len, field, &proj)
+                })
+            }
+
+            _ => false,
+        }
+    }
+
+    fn is_elim_convertible(&self, len: EnvLen, left: &Elim<'core>, right: &Elim<'core>) -> bool {
+        let eval = |local_values: &LocalValues<'core>, expr: &Expr<'core>| {
+            let mut local_values = local_values.clone();
+            EvalEnv::new(
+                self.bump,
+                EvalOpts::default(),
+                &mut local_values,
+                no_metas(),
+            )
+            .eval(expr)
+        };
+
+        match (left, right) {
+            (Elim::FunApp(left), Elim::FunApp(right)) => {
+                left.plicity == right.plicity && self.is_convertible(len, &left.expr, &right.expr)
+            }
+            (Elim::RecordProj(left), Elim::RecordProj(right)) => left == right,
+            (Elim::BoolCases(left), Elim::BoolCases(right)) => {
+                self.is_convertible(
+                    len,
+                    &eval(&left.local_values, left.then),
+                    &eval(&right.local_values, right.then),
+                ) && self.is_convertible(
+                    len,
+                    &eval(&left.local_values, left.r#else),
+                    &eval(&right.local_values, right.r#else),
+                )
+            }
+            (Elim::IntCases(left), Elim::IntCases(right)) => {
+                left.len() == right.len()
+                    && (left.cases.iter().zip(right.cases))
+                        .all(|((left_int, _), (right_int, _))| left_int == right_int)
+                    && (left.cases.iter().zip(right.cases)).all(
+                        |((_, left_expr), (_, right_expr))| {
+                            self.is_convertible(
+                                len,
+                                &eval(&left.local_values, left_expr),
+                                &eval(&right.local_values, right_expr),
+                            )
+                        },
+                    )
+                    && self.is_convertible(
+                        len,
+                        &eval(&left.local_values, left.default),
+                        &eval(&right.local_values, right.default),
+                    )
+            }
+            _ => false,
+        }
+    }
+
+    fn push_local(&mut self, name: Option<Symbol>, r#type: Type<'core>, value: Value<'core>) {
+        self.local_names.push(name);
+        self.local_types.push(r#type);
+        self.local_values.push(value);
+    }
+
+    fn push_param(&mut self, name: Option<Symbol>, r#type: Type<'core>) {
+        let var = Value::local_var(self.local_values.len().to_absolute());
+        self.push_local(name, r#type, var);
+    }
+
+    fn pop_local(&mut self) {
+        self.local_names.pop();
+        self.local_types.pop();
+        self.local_values.pop();
+    }
+
+    fn truncate_locals(&mut self, len: EnvLen) {
+        self.local_names.truncate(len);
+        self.local_types.truncate(len);
+        self.local_values.truncate(len);
+    }
+
+    fn elim_env(&self) -> ElimEnv<'core, '_> {
+        ElimEnv::new(self.bump, EvalOpts::default(), no_metas())
+    }
+
+    fn eval(&mut self, expr: &Expr<'core>) -> Value<'core> {
+        EvalEnv::new(
+            self.bump,
+            EvalOpts::default(),
+            &mut self.local_values,
+            no_metas(),
+        )
+        .eval(expr)
+    }
+
+    fn quote(&self, value: &Value<'core>) -> Expr<'core> {
+        QuoteEnv::new(self.bump, self.local_values.len(), no_metas()).quote(value)
+    }
+
+    fn pretty(&mut self, expr: &Expr<'core>) -> String {
+        let alloc = BumpDocAllocator::new(self.bump);
+        let unelaborator = Unelaborator::new(alloc, unelab::Config::default());
+        let doc = unelaborator.expr(&mut self.local_names, expr).into_doc();
+        doc.pretty(usize::MAX).to_string()
+    }
+
+    fn pretty_value(&mut self, value: &Value<'core>) -> String {
+        let expr = self.quote(value);
+        self.pretty(&expr)
+    }
+
+    fn error(&mut self, expr: &Expr<'core>, message: String) -> Error {
+        Error {
+            message,
+            term: self.pretty(expr),
+        }
+    }
+}
+
+const fn plicity_name(plicity: pion_core::syntax::Plicity) -> &'static str {
+    match plicity {
+        pion_core::syntax::Plicity::Explicit => "an explicit",
+        pion_core::syntax::Plicity::Implicit => "an implicit",
+        pion_core::syntax::Plicity::Instance => "an instance",
+    }
+}
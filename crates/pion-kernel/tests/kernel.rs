@@ -0,0 +1,161 @@
+//! Check that the kernel accepts everything the elaborator produces for the
+//! test data, and rejects ill-typed core.
+
+use pion_core::env::{AbsoluteVar, RelativeVar};
+use pion_core::prim::Prim;
+use pion_core::syntax::{Expr, FunArg, FunParam, Item, LetBinding, Lit};
+use pion_db::{Database, Event};
+use pion_diagnostic::Severity;
+use pion_kernel::Checker;
+use pion_symbol::Symbol;
+
+#[test]
+fn elaborated_test_data_is_well_typed() {
+    let test_data = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data");
+    let mut count = 0;
+
+    for entry in walkdir::WalkDir::new(test_data) {
+        let entry = entry.unwrap();
+        if entry.path().extension() != Some("pion".as_ref()) {
+            continue;
+        }
+
+        let text = std::fs::read_to_string(entry.path()).unwrap();
+        let bump = bumpalo::Bump::new();
+        let mut db = Database::new(&bump, 0, text);
+        let has_errors = (db.check().iter()).any(|event| {
+            matches!(event, Event::Diagnostic(diagnostic, _)
+                if diagnostic.severity == Severity::Error)
+        });
+        if has_errors {
+            continue;
+        }
+
+        let mut checker = Checker::new(&bump);
+        for item in db.elaborated_items() {
+            if let Err(error) = checker.check_item(&item) {
+                panic!("{}: {error}", entry.path().display());
+            }
+        }
+        count += 1;
+    }
+
+    assert!(count > 0, "no test files found");
+}
+
+/// Check `items` in order, returning the message of the first error.
+fn check_items(items: &[Item]) -> Result<(), String> {
+    let bump = bumpalo::Bump::new();
+    let mut checker = Checker::new(&bump);
+    for item in items {
+        checker
+            .check_item(item)
+            .map_err(|error| error.to_string())?;
+    }
+    Ok(())
+}
+
+const fn app<'core>(fun: &'core Expr<'core>, arg: &'core Expr<'core>) -> Expr<'core> {
+    Expr::FunApp {
+        fun,
+        arg: FunArg::explicit(arg),
+    }
+}
+
+const VAR0: Expr = Expr::LocalVar(RelativeVar::new(0));
+const VAR1: Expr = Expr::LocalVar(RelativeVar::new(1));
+
+#[test]
+fn accepts_well_typed_core() {
+    // let id : forall (A : Type) -> A -> A = fun A x => x;
+    let id_type = Expr::FunType {
+        param: FunParam::explicit(None, &Expr::TYPE),
+        body: &Expr::FunType {
+            param: FunParam::explicit(None, &VAR0),
+            body: &VAR1,
+        },
+    };
+    let id = Expr::FunLit {
+        param: FunParam::explicit(Some(Symbol::intern("A")), &Expr::TYPE),
+        body: &Expr::FunLit {
+            param: FunParam::explicit(Some(Symbol::intern("x")), &VAR0),
+            body: &VAR0,
+        },
+    };
+    let id_int = app(&VAR0, &Expr::INT);
+    let id_int_1 = app(&id_int, &Expr::Lit(Lit::Int(1)));
+    let add_1 = app(&Expr::Prim(Prim::add), &id_int_1);
+    let sum = app(&add_1, &Expr::Lit(Lit::Int(2)));
+
+    let items = [
+        Item::Let(LetBinding::new(Some(Symbol::intern("id")), id_type, id)),
+        Item::Eval(sum),
+    ];
+    assert_eq!(check_items(&items), Ok(()));
+}
+
+#[test]
+fn rejects_argument_of_wrong_type() {
+    let add_true = app(&Expr::Prim(Prim::add), &Expr::TRUE);
+    let expr = app(&add_true, &Expr::Lit(Lit::Int(1)));
+    let error = check_items(&[Item::Eval(expr)]).unwrap_err();
+    assert_eq!(
+        error,
+        "type mismatch: expected `Int`, found `Bool`\n  in term `true`"
+    );
+}
+
+#[test]
+fn rejects_binding_of_wrong_type() {
+    let binding = LetBinding::new(
+        Some(Symbol::intern("x")),
+        Expr::BOOL,
+        Expr::Lit(Lit::Int(5)),
+    );
+    let error = check_items(&[Item::Let(binding)]).unwrap_err();
+    assert_eq!(
+        error,
+        "type mismatch: expected `Bool`, found `Int`\n  in term `5`"
+    );
+}
+
+#[test]
+fn rejects_application_of_non_function() {
+    let expr = app(&Expr::Lit(Lit::Int(1)), &Expr::Lit(Lit::Int(2)));
+    let error = check_items(&[Item::Eval(expr)]).unwrap_err();
+    assert_eq!(
+        error,
+        "applied a non-function of type `Int`\n  in term `1 2`"
+    );
+}
+
+#[test]
+fn rejects_plicity_mismatch() {
+    let expr = Expr::FunApp {
+        fun: &Expr::Prim(Prim::len),
+        arg: FunArg::explicit(&Expr::INT),
+    };
+    let error = check_items(&[Item::Eval(expr)]).unwrap_err();
+    assert_eq!(
+        error,
+        "an explicit argument applied to an implicit function\n  in term `len Int`"
+    );
+}
+
+#[test]
+fn rejects_unbound_and_meta_variables() {
+    let error = check_items(&[Item::Eval(VAR0)]).unwrap_err();
+    assert!(error.starts_with("unbound local variable"), "{error}");
+
+    let meta = Expr::MetaVar(AbsoluteVar::from(0));
+    let error = check_items(&[Item::Eval(meta)]).unwrap_err();
+    assert!(error.starts_with("unsolved metavariable"), "{error}");
+}
+
+#[test]
+fn rejects_missing_record_field() {
+    let record = Expr::RecordLit(&[(Symbol::intern("x"), Expr::Lit(Lit::Int(1)))]);
+    let expr = Expr::RecordProj(&record, Symbol::intern("y"));
+    let error = check_items(&[Item::Eval(expr)]).unwrap_err();
+    assert_eq!(error, "record has no field `y`\n  in term `{ x = 1 }.y`");
+}
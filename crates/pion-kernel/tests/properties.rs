@@ -0,0 +1,650 @@
+//! Property tests of the evaluator in `pion_core::semantics`, on randomly
+//! generated well-typed core terms.
+//!
+//! Terms are generated from their types, over integers, booleans, lists,
+//! records and functions, using `let`, the list and arithmetic primitives,
+//! `bool_rec`, `MatchBool` and `MatchInt`. Primitives are also applied to
+//! fewer arguments than they take, so that they are used as functions. No
+//! terms use `fix`, so every term normalizes, and can also be run by the
+//! bytecode virtual machine and the step-by-step reducer. Normal forms are
+//! compared in the textual core format.
+
+use pion_core::env::{AbsoluteVar, EnvLen, RelativeVar, SliceEnv};
+use pion_core::optimize::{optimize, OptLevel};
+use pion_core::prim::Prim;
+use pion_core::reduce::{ReduceOpts, Reducer};
+use pion_core::semantics::{
+    self, EvalEnv, EvalLimits, EvalOpts, LocalValues, MetaValues, Value, ZonkEnv,
+};
+use pion_core::syntax::{Expr, FunArg, FunParam, Item, LetBinding, Lit};
+use pion_core::text::print_expr;
+use pion_kernel::Checker;
+use pion_symbol::Symbol;
+
+/// The number of terms generated for each property.
+const CASES: u64 = 500;
+
+/// The maximum depth of generated terms.
+const DEPTH: usize = 4;
+
+/// A small deterministic pseudo-random number generator (xorshift64), so
+/// that failures are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        usize::try_from(self.next() % u64::try_from(n).unwrap()).unwrap()
+    }
+
+    fn chance(&mut self, n: usize) -> bool { self.below(n) == 0 }
+}
+
+/// The types of generated terms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Ty {
+    Int,
+    Bool,
+    List(Box<Self>),
+    Record(Vec<(Symbol, Self)>),
+    Fun(Box<Self>, Box<Self>),
+}
+
+impl Ty {
+    /// Whether values of the type are first-order data, which the virtual
+    /// machine can convert back to expressions.
+    fn is_data(&self) -> bool {
+        match self {
+            Self::Int | Self::Bool => true,
+            Self::List(elem) => elem.is_data(),
+            Self::Record(fields) => fields.iter().all(|(_, r#type)| r#type.is_data()),
+            Self::Fun(..) => false,
+        }
+    }
+
+    fn to_expr<'core>(&self, bump: &'core bumpalo::Bump) -> Expr<'core> {
+        match self {
+            Self::Int => Expr::INT,
+            Self::Bool => Expr::BOOL,
+            Self::List(elem) => Expr::FunApp {
+                fun: &Expr::Prim(Prim::List),
+                arg: FunArg::explicit(bump.alloc(elem.to_expr(bump))),
+            },
+            Self::Record(fields) => {
+                let fields = (fields.iter()).map(|(name, r#type)| (*name, r#type.to_expr(bump)));
+                Expr::RecordType(bump.alloc_slice_fill_iter(fields))
+            }
+            // The types are closed, so no variables need to be shifted.
+            Self::Fun(param, body) => Expr::FunType {
+                param: FunParam::explicit(None, bump.alloc(param.to_expr(bump))),
+                body: bump.alloc(body.to_expr(bump)),
+            },
+        }
+    }
+}
+
+/// Generates terms of a given type.
+struct Gen<'core> {
+    bump: &'core bumpalo::Bump,
+    rng: Rng,
+    /// The types of the local variables in scope, innermost last.
+    locals: Vec<Ty>,
+    /// The types of the metavariables that may be referred to.
+    metas: Vec<Ty>,
+}
+
+impl<'core> Gen<'core> {
+    fn new(bump: &'core bumpalo::Bump, seed: u64) -> Self {
+        Self {
+            bump,
+            // xorshift gets stuck at zero.
+            rng: Rng(0x2545_f491_4f6c_dd1d ^ seed),
+            locals: Vec::new(),
+            metas: Vec::new(),
+        }
+    }
+
+    fn alloc(&self, expr: Expr<'core>) -> &'core Expr<'core> { self.bump.alloc(expr) }
+
+    fn app(&self, fun: Expr<'core>, arg: FunArg<Expr<'core>>) -> Expr<'core> {
+        let fun = self.alloc(fun);
+        let arg = FunArg::new(arg.plicity, self.alloc(arg.expr));
+        Expr::FunApp { fun, arg }
+    }
+
+    /// `prim @type_args args`.
+    fn prim_app(&self, prim: Prim, type_args: &[Ty], args: &[Expr<'core>]) -> Expr<'core> {
+        let type_args = type_args
+            .iter()
+            .map(|r#type| FunArg::implicit(r#type.to_expr(self.bump)));
+        let args = args.iter().map(|arg| FunArg::explicit(*arg));
+        (type_args.chain(args)).fold(Expr::Prim(prim), |fun, arg| self.app(fun, arg))
+    }
+
+    /// `bool_rec @(fun _ => r#type) args`.
+    fn bool_rec_app(&self, r#type: &Ty, args: &[Expr<'core>]) -> Expr<'core> {
+        // The type is closed, so it does not need to be shifted under the binder.
+        let motive = Expr::FunLit {
+            param: FunParam::explicit(None, &Expr::BOOL),
+            body: self.alloc(r#type.to_expr(self.bump)),
+        };
+        let fun = self.app(Expr::Prim(Prim::bool_rec), FunArg::implicit(motive));
+        (args.iter()).fold(fun, |fun, arg| self.app(fun, FunArg::explicit(*arg)))
+    }
+
+    fn name(&mut self) -> Option<Symbol> {
+        let names = ["a", "b", "c", "_"];
+        match names[self.rng.below(names.len())] {
+            "_" => None,
+            name => Some(Symbol::intern(name)),
+        }
+    }
+
+    /// A random type, nested at most `depth` deep.
+    fn ty(&mut self, depth: usize) -> Ty {
+        let choices = if depth == 0 { 2 } else { 5 };
+        match self.rng.below(choices) {
+            0 => Ty::Int,
+            1 => Ty::Bool,
+            2 => Ty::List(Box::new(self.ty(depth - 1))),
+            3 => {
+                let len = self.rng.below(3);
+                let fields = (["x", "y", "z"].iter().take(len))
+                    .map(|name| (Symbol::intern(name), self.ty(depth - 1)))
+                    .collect();
+                Ty::Record(fields)
+            }
+            _ => Ty::Fun(Box::new(self.ty(depth - 1)), Box::new(self.ty(depth - 1))),
+        }
+    }
+
+    fn int_lit(&mut self) -> Expr<'core> {
+        // Include large literals, to exercise wrapping arithmetic.
+        let ints = [0, 1, 2, 3, 7, u32::MAX];
+        Expr::Lit(Lit::Int(ints[self.rng.below(ints.len())]))
+    }
+
+    /// A term of type `r#type`, nested at most `depth` deep.
+    fn expr(&mut self, r#type: &Ty, depth: usize) -> Expr<'core> {
+        if depth == 0 {
+            return self.leaf(r#type);
+        }
+        let depth = depth - 1;
+
+        match self.rng.below(10) {
+            0 => self.leaf(r#type),
+            1 => {
+                let rhs_type = self.ty(1);
+                let rhs = self.expr(&rhs_type, depth);
+                self.locals.push(rhs_type.clone());
+                let body = self.expr(r#type, depth);
+                self.locals.pop();
+                let rhs_type = self.alloc(rhs_type.to_expr(self.bump));
+                let binding = LetBinding::new(self.name(), rhs_type, self.alloc(rhs));
+                Expr::Let {
+                    binding,
+                    body: self.alloc(body),
+                }
+            }
+            2 => {
+                let param_type = self.ty(1);
+                let fun_type = Ty::Fun(Box::new(param_type.clone()), Box::new(r#type.clone()));
+                let fun = self.expr(&fun_type, depth);
+                let arg = self.expr(&param_type, depth);
+                self.app(fun, FunArg::explicit(arg))
+            }
+            3 => {
+                let cond = self.expr(&Ty::Bool, depth);
+                let then = self.expr(r#type, depth);
+                let r#else = self.expr(r#type, depth);
+                match self.rng.chance(2) {
+                    true => self.bool_rec_app(r#type, &[cond, then, r#else]),
+                    false => Expr::MatchBool {
+                        cond: self.alloc(cond),
+                        then: self.alloc(then),
+                        r#else: self.alloc(r#else),
+                    },
+                }
+            }
+            4 => {
+                let scrut = self.expr(&Ty::Int, depth);
+                let mut cases = Vec::new();
+                for value in 0..4 {
+                    if self.rng.chance(2) {
+                        cases.push((value, self.expr(r#type, depth)));
+                    }
+                }
+                let default = self.expr(r#type, depth);
+                Expr::MatchInt {
+                    scrut: self.alloc(scrut),
+                    cases: self.bump.alloc_slice_copy(&cases),
+                    default: self.alloc(default),
+                }
+            }
+            5 => {
+                let name = Symbol::intern(["x", "y", "z"][self.rng.below(3)]);
+                let other = Symbol::intern("w");
+                let mut fields = vec![(name, r#type.clone())];
+                if self.rng.chance(2) {
+                    fields.insert(self.rng.below(2), (other, self.ty(1)));
+                }
+                let record = self.expr(&Ty::Record(fields), depth);
+                Expr::RecordProj(self.alloc(record), name)
+            }
+            6 => {
+                // Bind a partially applied primitive before applying it to the
+                // rest of its arguments, so that erasure eta-expands it.
+                let param_type = match self.rng.chance(2) {
+                    true => r#type.clone(),
+                    false => self.ty(1),
+                };
+                let Some(fun) = self.partial_prim_app(&param_type, r#type, depth) else {
+                    return self.intro(r#type, depth);
+                };
+                let fun_type = Ty::Fun(Box::new(param_type.clone()), Box::new(r#type.clone()));
+                self.locals.push(fun_type.clone());
+                let arg = self.expr(&param_type, depth);
+                self.locals.pop();
+                let fun_type = self.alloc(fun_type.to_expr(self.bump));
+                let binding = LetBinding::new(self.name(), fun_type, self.alloc(fun));
+                let body = self.app(Expr::LocalVar(RelativeVar::new(0)), FunArg::explicit(arg));
+                Expr::Let {
+                    binding,
+                    body: self.alloc(body),
+                }
+            }
+            _ => self.intro(r#type, depth),
+        }
+    }
+
+    /// A term of type `r#type` built from the primitives or constructors of
+    /// that type.
+    fn intro(&mut self, r#type: &Ty, depth: usize) -> Expr<'core> {
+        match r#type {
+            Ty::Int => match self.rng.below(3) {
+                0 => {
+                    let prim = [Prim::add, Prim::sub, Prim::mul][self.rng.below(3)];
+                    let args = [self.expr(&Ty::Int, depth), self.expr(&Ty::Int, depth)];
+                    self.prim_app(prim, &[], &args)
+                }
+                1 => {
+                    let elem = self.ty(1);
+                    let list = self.expr(&Ty::List(Box::new(elem.clone())), depth);
+                    self.prim_app(Prim::len, &[elem], &[list])
+                }
+                _ => self.int_lit(),
+            },
+            Ty::Bool => {
+                let prims = [Prim::eq, Prim::ne, Prim::gt, Prim::lt, Prim::gte, Prim::lte];
+                let prim = prims[self.rng.below(prims.len())];
+                let args = [self.expr(&Ty::Int, depth), self.expr(&Ty::Int, depth)];
+                self.prim_app(prim, &[], &args)
+            }
+            Ty::List(elem) => match self.rng.below(3) {
+                0 => {
+                    let list = self.expr(r#type, depth);
+                    let value = self.expr(elem, depth);
+                    self.prim_app(Prim::push, &[(**elem).clone()], &[list, value])
+                }
+                1 => {
+                    let args = [self.expr(r#type, depth), self.expr(r#type, depth)];
+                    self.prim_app(Prim::append, &[(**elem).clone()], &args)
+                }
+                _ => {
+                    let elems: Vec<_> = (0..self.rng.below(3))
+                        .map(|_| self.expr(elem, depth))
+                        .collect();
+                    Expr::ListLit(self.bump.alloc_slice_copy(&elems))
+                }
+            },
+            Ty::Record(fields) => {
+                let fields: Vec<_> = (fields.iter())
+                    .map(|(name, r#type)| (*name, self.expr(r#type, depth)))
+                    .collect();
+                Expr::RecordLit(self.bump.alloc_slice_copy(&fields))
+            }
+            Ty::Fun(param, body) => {
+                if self.rng.chance(2) {
+                    if let Some(expr) = self.partial_prim_app(param, body, depth) {
+                        return expr;
+                    }
+                }
+                let name = self.name();
+                self.locals.push((**param).clone());
+                let body = self.expr(body, depth);
+                self.locals.pop();
+                let param = self.alloc(param.to_expr(self.bump));
+                Expr::FunLit {
+                    param: FunParam::explicit(name, param),
+                    body: self.alloc(body),
+                }
+            }
+        }
+    }
+
+    /// A primitive applied to fewer arguments than it takes, of type `param ->
+    /// body`, if there is one. Erasure eta-expands these, and they can be
+    /// passed around like any other function.
+    fn partial_prim_app(&mut self, param: &Ty, body: &Ty, depth: usize) -> Option<Expr<'core>> {
+        let fun = |param: &Ty, body: &Ty| Ty::Fun(Box::new(param.clone()), Box::new(body.clone()));
+        let r#type = fun(param, body);
+
+        // Each primitive of type `r#type` once applied to its implicit argument
+        // (if any) and to arguments of the given types.
+        let mut candidates: Vec<(Prim, Option<Ty>, Vec<Ty>)> = Vec::new();
+        let int_prims = [Prim::add, Prim::sub, Prim::mul];
+        let bool_prims = [Prim::eq, Prim::ne, Prim::gt, Prim::lt, Prim::gte, Prim::lte];
+        for (prims, result) in [(&int_prims[..], Ty::Int), (&bool_prims[..], Ty::Bool)] {
+            for prim in prims {
+                if r#type == fun(&Ty::Int, &fun(&Ty::Int, &result)) {
+                    candidates.push((*prim, None, vec![]));
+                }
+                if r#type == fun(&Ty::Int, &result) {
+                    candidates.push((*prim, None, vec![Ty::Int]));
+                }
+            }
+        }
+        if let Ty::List(elem) = param {
+            let type_arg = Some((**elem).clone());
+            if *body == Ty::Int {
+                candidates.push((Prim::len, type_arg.clone(), vec![]));
+            }
+            if *body == fun(param, param) {
+                candidates.push((Prim::append, type_arg.clone(), vec![]));
+            }
+            if body == param {
+                candidates.push((Prim::append, type_arg.clone(), vec![param.clone()]));
+            }
+            if *body == fun(elem, param) {
+                candidates.push((Prim::push, type_arg, vec![]));
+            }
+        }
+        if let Ty::List(elem) = body {
+            if **elem == *param {
+                candidates.push((Prim::push, Some(param.clone()), vec![body.clone()]));
+            }
+        }
+        if let Ty::Fun(then, r#else) = body {
+            if *param == Ty::Bool && **r#else == fun(then, then) {
+                candidates.push((Prim::bool_rec, Some((**then).clone()), vec![]));
+            }
+            if **then == *param && **r#else == *param {
+                candidates.push((Prim::bool_rec, Some(param.clone()), vec![Ty::Bool]));
+            }
+        }
+        if param == body {
+            let args = vec![Ty::Bool, param.clone()];
+            candidates.push((Prim::bool_rec, Some(param.clone()), args));
+        }
+
+        if candidates.is_empty() {
+            return None;
+        }
+        let (prim, implicit, arg_types) = candidates.swap_remove(self.rng.below(candidates.len()));
+        let args: Vec<_> = (arg_types.iter())
+            .map(|r#type| self.expr(r#type, depth))
+            .collect();
+        match prim {
+            Prim::bool_rec => Some(self.bool_rec_app(&implicit.unwrap(), &args)),
+            _ => Some(self.prim_app(prim, implicit.as_slice(), &args)),
+        }
+    }
+
+    /// A small term of type `r#type`: a variable or a literal.
+    fn leaf(&mut self, r#type: &Ty) -> Expr<'core> {
+        let locals = (self.locals.iter().rev().enumerate())
+            .filter(|(_, local)| *local == r#type)
+            .map(|(index, _)| Expr::LocalVar(index.into()));
+        let metas = (self.metas.iter().enumerate())
+            .filter(|(_, meta)| *meta == r#type)
+            .map(|(index, _)| Expr::MetaVar(AbsoluteVar::from(index)));
+        let vars: Vec<_> = locals.chain(metas).collect();
+        if !vars.is_empty() && self.rng.chance(2) {
+            return vars[self.rng.below(vars.len())];
+        }
+
+        match r#type {
+            Ty::Int => self.int_lit(),
+            Ty::Bool => Expr::Lit(Lit::Bool(self.rng.chance(2))),
+            // `intro` could recurse forever on lists, by pushing onto lists.
+            Ty::List(_) => Expr::ListLit(&[]),
+            Ty::Record(_) | Ty::Fun(..) => self.intro(r#type, 0),
+        }
+    }
+}
+
+/// A generated term and its type.
+struct Case<'core> {
+    r#type: Ty,
+    expr: Expr<'core>,
+}
+
+/// Generate a closed term for each seed, and check `property` for it.
+fn check_property(property: impl Fn(&bumpalo::Bump, &Case) -> Result<(), String>) {
+    for seed in 0..CASES {
+        let bump = bumpalo::Bump::new();
+        let mut r#gen = Gen::new(&bump, seed);
+        let r#type = r#gen.ty(2);
+        let expr = r#gen.expr(&r#type, DEPTH);
+        let case = Case { r#type, expr };
+        if let Err(error) = property(&bump, &case) {
+            panic!(
+                "seed {seed}: {error}\nterm: {}\ntype: {}",
+                print_expr(&bump, &case.expr),
+                print_expr(&bump, &case.r#type.to_expr(&bump)),
+            );
+        }
+    }
+}
+
+fn no_metas<'core>() -> &'static MetaValues<'core> { <&MetaValues>::from(&[][..]) }
+
+fn normalize<'core>(bump: &'core bumpalo::Bump, expr: &Expr<'core>) -> Expr<'core> {
+    semantics::normalize(bump, &mut LocalValues::default(), no_metas(), expr)
+}
+
+/// Normalize `expr` in an environment of `len` parameters.
+fn normalize_under<'core>(
+    bump: &'core bumpalo::Bump,
+    len: usize,
+    expr: &Expr<'core>,
+) -> Expr<'core> {
+    let mut local_values = LocalValues::default();
+    for level in 0..len {
+        local_values.push(Value::local_var(AbsoluteVar::from(level)));
+    }
+    semantics::normalize(bump, &mut local_values, no_metas(), expr)
+}
+
+/// Check `expr` against `r#type` with the kernel.
+fn check_type<'core>(
+    bump: &'core bumpalo::Bump,
+    expr: Expr<'core>,
+    r#type: &Ty,
+) -> Result<(), String> {
+    let binding = LetBinding::new(None, r#type.to_expr(bump), expr);
+    let mut checker = Checker::new(bump);
+    (checker.check_item(&Item::Let(binding))).map_err(|error| error.to_string())
+}
+
+#[track_caller]
+fn assert_same(
+    what: &str,
+    bump: &bumpalo::Bump,
+    expected: &Expr,
+    actual: &Expr,
+) -> Result<(), String> {
+    let (expected, actual) = (print_expr(bump, expected), print_expr(bump, actual));
+    match expected == actual {
+        true => Ok(()),
+        false => Err(format!("{what}:\nexpected: {expected}\nactual:   {actual}")),
+    }
+}
+
+#[test]
+fn generated_terms_are_well_typed() {
+    check_property(|bump, case| check_type(bump, case.expr, &case.r#type));
+}
+
+#[test]
+fn normalization_is_idempotent() {
+    check_property(|bump, case| {
+        let once = normalize(bump, &case.expr);
+        let twice = normalize(bump, &once);
+        assert_same("normalizing a normal form changed it", bump, &once, &twice)
+    });
+}
+
+#[test]
+fn normalization_preserves_types() {
+    check_property(|bump, case| {
+        let expr = normalize(bump, &case.expr);
+        check_type(bump, expr, &case.r#type)
+            .map_err(|error| format!("normal form is ill-typed: {error}"))
+    });
+}
+
+#[test]
+fn normalize_agrees_with_optimize() {
+    check_property(|bump, case| {
+        let expected = normalize(bump, &case.expr);
+        for level in [OptLevel::None, OptLevel::Simplify, OptLevel::Inline] {
+            let optimized = optimize(bump, level, &case.expr);
+            let actual = normalize(bump, &optimized);
+            assert_same(
+                &format!("optimizing at {level:?} changed the normal form"),
+                bump,
+                &expected,
+                &actual,
+            )?;
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn normalize_agrees_with_virtual_machine() {
+    check_property(|bump, case| {
+        if !case.r#type.is_data() {
+            return Ok(());
+        }
+        let expected = normalize(bump, &case.expr);
+        let value = pion_vm::eval(&[], &case.expr, EvalLimits::default())
+            .map_err(|error| format!("virtual machine failed: {error}"))?;
+        let actual = value
+            .to_expr(bump)
+            .ok_or("virtual machine did not return data")?;
+        assert_same(
+            "virtual machine disagrees with normalize",
+            bump,
+            &expected,
+            &actual,
+        )
+    });
+}
+
+#[test]
+fn normalize_agrees_with_reducer() {
+    check_property(|bump, case| {
+        if !case.r#type.is_data() {
+            return Ok(());
+        }
+        let expected = normalize(bump, &case.expr);
+        let defs = <&SliceEnv<Option<Expr>>>::from(&[][..]);
+        let reducer = Reducer::new(bump, ReduceOpts::default(), defs);
+        let mut actual = case.expr;
+        for _ in 0..100_000 {
+            match reducer.step(&actual) {
+                Some((_, next)) => actual = next,
+                None => {
+                    return assert_same(
+                        "reducer disagrees with normalize",
+                        bump,
+                        &expected,
+                        &actual,
+                    )
+                }
+            }
+        }
+        Err(String::from("reducer did not terminate"))
+    });
+}
+
+#[test]
+fn shift_commutes_with_normalization() {
+    for seed in 0..CASES {
+        let bump = bumpalo::Bump::new();
+        let mut r#gen = Gen::new(&bump, seed);
+
+        // A term in the scope of some parameters, with some more parameters
+        // added by shifting.
+        let locals = 1 + r#gen.rng.below(3);
+        let extra = 1 + r#gen.rng.below(3);
+        r#gen.locals = (0..locals).map(|_| r#gen.ty(1)).collect();
+        let r#type = r#gen.ty(2);
+        let expr = r#gen.expr(&r#type, DEPTH);
+
+        let amount = EnvLen::from(extra);
+        let expected = normalize_under(&bump, locals, &expr).shift(&bump, amount);
+        let actual = normalize_under(&bump, locals + extra, &expr.shift(&bump, amount));
+        if let Err(error) = assert_same(
+            "shifting and normalizing do not commute",
+            &bump,
+            &expected,
+            &actual,
+        ) {
+            panic!("seed {seed}: {error}\nterm: {}", print_expr(&bump, &expr));
+        }
+    }
+}
+
+#[test]
+fn zonking_is_stable() {
+    for seed in 0..CASES {
+        let bump = bumpalo::Bump::new();
+        let mut r#gen = Gen::new(&bump, seed);
+
+        // Solve some metavariables with closed terms, and leave one unsolved.
+        let mut meta_values = Vec::new();
+        for _ in 0..3 {
+            let r#type = r#gen.ty(1);
+            let solution = r#gen.expr(&r#type, 2);
+            let metas = <&MetaValues>::from(&meta_values[..]);
+            let opts = EvalOpts::default();
+            let value =
+                EvalEnv::new(&bump, opts, &mut LocalValues::default(), metas).eval(&solution);
+            meta_values.push(Some(value));
+            r#gen.metas.push(r#type);
+        }
+        meta_values.push(None);
+        r#gen.metas.push(Ty::Int);
+        let metas = <&MetaValues>::from(&meta_values[..]);
+
+        let r#type = r#gen.ty(2);
+        let expr = r#gen.expr(&r#type, DEPTH);
+        let zonk = |expr| ZonkEnv::new(&bump, &mut LocalValues::default(), metas).zonk(expr);
+        let normalize =
+            |expr| semantics::normalize(&bump, &mut LocalValues::default(), metas, expr);
+
+        let once = zonk(&expr);
+        let twice = zonk(&once);
+        let result = assert_same("zonking a zonked term changed it", &bump, &once, &twice)
+            .and_then(|()| {
+                assert_same(
+                    "zonking changed the normal form",
+                    &bump,
+                    &normalize(&expr),
+                    &normalize(&once),
+                )
+            });
+        if let Err(error) = result {
+            panic!("seed {seed}: {error}\nterm: {}", print_expr(&bump, &expr));
+        }
+    }
+}
@@ -39,6 +39,7 @@ pub fn next_token(text: &str) -> Option<(TokenKind, usize)> {
         b';' => (TokenKind::Semicolon, 1),
         b':' => (TokenKind::Colon, 1),
         b'.' => (TokenKind::Dot, 1),
+        b'@' if bytes.get(1) == Some(&b'@') => (TokenKind::DoubleAt, 2),
         b'@' => (TokenKind::At, 1),
         b'|' => (TokenKind::Pipe, 1),
 
@@ -154,15 +155,22 @@ const fn keyword_or_ident(bytes: &[u8]) -> TokenKind {
         b"forall" => TokenKind::KwForall,
         b"fun" => TokenKind::KwFun,
         b"if" => TokenKind::KwIf,
+        b"instance" => TokenKind::KwInstance,
         b"let" => TokenKind::KwLet,
         b"match" => TokenKind::KwMatch,
         b"rec" => TokenKind::KwRec,
         b"then" => TokenKind::KwThen,
         b"true" => TokenKind::KwTrue,
 
+        b"#assert_eq" => TokenKind::KwAssertEq,
+        b"#assert_type" => TokenKind::KwAssertType,
         b"#check" => TokenKind::KwCheck,
         b"#eval" => TokenKind::KwEval,
+        b"#fail" => TokenKind::KwFail,
+        b"#print" => TokenKind::KwPrint,
+        b"#reduce" => TokenKind::KwReduce,
         b"#show" => TokenKind::KwShow,
+        b"#trace" => TokenKind::KwTrace,
         _ => TokenKind::Ident,
     }
 }
@@ -125,7 +125,7 @@ mod tests {
     #[test]
     fn punctuation() {
         check(
-            ",;:==>->",
+            ",;:==>->@@@",
             expect![[r#"
                 0..1: Comma(",")
                 1..2: Semicolon(";")
@@ -133,6 +133,8 @@ mod tests {
                 3..4: Eq("=")
                 4..6: DoubleArrow("=>")
                 6..8: SingleArrow("->")
+                8..10: DoubleAt("@@")
+                10..11: At("@")
             "#]],
         );
     }
@@ -140,24 +142,26 @@ mod tests {
     #[test]
     fn keywords() {
         check(
-            "else false forall fun if let then true",
+            "else false forall fun if instance let then true",
             expect![[r#"
-        0..4: KwElse("else")
-        4..5: Whitespace(" ")
-        5..10: KwFalse("false")
-        10..11: Whitespace(" ")
-        11..17: KwForall("forall")
-        17..18: Whitespace(" ")
-        18..21: KwFun("fun")
-        21..22: Whitespace(" ")
-        22..24: KwIf("if")
-        24..25: Whitespace(" ")
-        25..28: KwLet("let")
-        28..29: Whitespace(" ")
-        29..33: KwThen("then")
-        33..34: Whitespace(" ")
-        34..38: KwTrue("true")
-    "#]],
+                0..4: KwElse("else")
+                4..5: Whitespace(" ")
+                5..10: KwFalse("false")
+                10..11: Whitespace(" ")
+                11..17: KwForall("forall")
+                17..18: Whitespace(" ")
+                18..21: KwFun("fun")
+                21..22: Whitespace(" ")
+                22..24: KwIf("if")
+                24..25: Whitespace(" ")
+                25..33: KwInstance("instance")
+                33..34: Whitespace(" ")
+                34..37: KwLet("let")
+                37..38: Whitespace(" ")
+                38..42: KwThen("then")
+                42..43: Whitespace(" ")
+                43..47: KwTrue("true")
+            "#]],
         );
     }
 
@@ -39,6 +39,8 @@ pub enum TokenKind {
     KwFun,
     /// The `if` keyword.
     KwIf,
+    /// The `instance` keyword.
+    KwInstance,
     /// The `let` keyword.
     KwLet,
     /// The `match` keyword.
@@ -50,12 +52,24 @@ pub enum TokenKind {
     /// The `true` keyword.
     KwTrue,
 
+    /// The `#assert_eq` keyword.
+    KwAssertEq,
+    /// The `#assert_type` keyword.
+    KwAssertType,
     /// The `#check` keyword.
     KwCheck,
     /// The `#eval` keyword.
     KwEval,
+    /// The `#fail` keyword.
+    KwFail,
+    /// The `#print` keyword.
+    KwPrint,
+    /// The `#reduce` keyword.
+    KwReduce,
     /// The `#show` keyword.
     KwShow,
+    /// The `#trace` keyword.
+    KwTrace,
 
     /// A left parenthesis `(`.
     LParen,
@@ -82,6 +96,8 @@ pub enum TokenKind {
     Dot,
     /// An at symbol `@`.
     At,
+    /// A double at symbol `@@`.
+    DoubleAt,
     /// An equals sign `=`.
     Eq,
     /// A pipe `|`.
@@ -123,14 +139,21 @@ impl TokenKind {
             Self::KwForall => "keyword `forall`",
             Self::KwFun => "keyword `fun`",
             Self::KwIf => "keyword `if`",
+            Self::KwInstance => "keyword `instance`",
             Self::KwLet => "keyword `let`",
             Self::KwMatch => "keyword `match`",
             Self::KwRec => "keyword `rec`",
             Self::KwThen => "keyword `then`",
             Self::KwTrue => "keyword `true`",
+            Self::KwAssertEq => "keyword `#assert_eq`",
+            Self::KwAssertType => "keyword `#assert_type`",
             Self::KwCheck => "keyword `#check`",
             Self::KwEval => "keyword `#eval`",
+            Self::KwFail => "keyword `#fail`",
+            Self::KwPrint => "keyword `#print`",
+            Self::KwReduce => "keyword `#reduce`",
             Self::KwShow => "keyword `#show`",
+            Self::KwTrace => "keyword `#trace`",
             Self::LParen => "`(`",
             Self::RParen => "`)`",
             Self::LCurly => "`{`",
@@ -143,6 +166,7 @@ impl TokenKind {
             Self::Colon => "`:`",
             Self::Dot => "`.`",
             Self::At => "`@`",
+            Self::DoubleAt => "`@@`",
             Self::Eq => "`=`",
             Self::Pipe => "`|`",
             Self::DoubleArrow => "`=>`",
@@ -0,0 +1,279 @@
+//! Lossless concrete syntax trees.
+//!
+//! Unlike the abstract syntax in [`pion_surface::syntax`], a concrete syntax
+//! tree contains every token of the source text: whitespace, comments,
+//! unknown characters and tokens skipped during error recovery are all
+//! attached to the innermost node that encloses them. Concatenating the text
+//! of every token in the tree reproduces the source text exactly.
+
+use pion_lexer::{Token, TokenKind};
+use text_size::TextRange;
+
+/// A node in the concrete syntax tree.
+#[derive(Debug, Copy, Clone)]
+pub struct Node<'cst> {
+    pub kind: NodeKind,
+    /// The range of source text covered by the node. Does not include any
+    /// trivia before the first token or after the last token of the node.
+    pub range: TextRange,
+    pub children: &'cst [Element<'cst>],
+}
+
+/// A child of a [`Node`].
+#[derive(Debug, Copy, Clone)]
+pub enum Element<'cst> {
+    Node(Node<'cst>),
+    Token(Token),
+}
+
+/// The different kinds of nodes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    /// Source file, `<block>`.
+    File,
+    /// A sequence of statements and an optional result expression.
+    Block,
+
+    /// A statement that could not be parsed.
+    ErrorStmt,
+    /// `let <rec>? <instance>? <pat> (: <type>)? = <rhs>;`
+    LetStmt,
+    /// `#assert_eq <lhs> <rhs>;`
+    AssertEqCommand,
+    /// `#assert_type <expr> <type>;`
+    AssertTypeCommand,
+    /// `#check <expr>;`
+    CheckCommand,
+    /// `#eval <expr>;`
+    EvalCommand,
+    /// `#fail <code>? { <block> };`
+    FailCommand,
+    /// `#print <option> <expr>;`
+    PrintCommand,
+    /// `#reduce <expr>, <options>;`
+    ReduceCommand,
+    /// `#show <name>;`
+    ShowCommand,
+    /// `#trace <expr>, <options>;`
+    TraceCommand,
+
+    /// An expression that could not be parsed.
+    ErrorExpr,
+    /// `true`, `false`, `42`, `0b101010` or `0x2a`.
+    LitExpr,
+    /// `x`
+    VarExpr,
+    /// `_`
+    HoleExpr,
+    /// `(<expr>)`
+    ParenExpr,
+    /// `<expr> : <type>`
+    AnnExpr,
+    /// `do { <block> }`
+    DoExpr,
+    /// `if <cond> then <then> else <else>`
+    IfExpr,
+    /// `match <scrut> { <cases> }`
+    MatchExpr,
+    /// `<plicity> <lhs> -> <rhs>`
+    FunArrowExpr,
+    /// `forall <params> -> <body>`
+    FunTypeExpr,
+    /// `fun <params> => <body>`
+    FunLitExpr,
+    /// `<fun> <args>`
+    FunAppExpr,
+    /// `[<exprs>]`
+    ListExpr,
+    /// `(<exprs>)`, or `{}`
+    TupleExpr,
+    /// `{ <type-fields> }`
+    RecordTypeExpr,
+    /// `{ <expr-fields> }`
+    RecordLitExpr,
+    /// `<scrut>.<name>`
+    RecordProjExpr,
+
+    /// `<plicity> <pat>` or `(<plicity> <pat> : <type>)`
+    FunParam,
+    /// `<plicity> <expr>`
+    FunArg,
+    /// `<pat> <guard>? => <expr>`
+    MatchCase,
+    /// `if <cond>`
+    MatchGuard,
+    /// `<name> : <type>`
+    TypeField,
+    /// `<name> = <expr>`
+    ExprField,
+    /// `<name>` or `<name> = <lit>`
+    ReduceOption,
+
+    /// A pattern that could not be parsed.
+    ErrorPat,
+    /// `_`
+    UnderscorePat,
+    /// `x`
+    VarPat,
+    /// `true`, `false`, `42`, `0b101010` or `0x2a`.
+    LitPat,
+    /// `(<pat>)`
+    ParenPat,
+    /// `(<pats>)`
+    TuplePat,
+    /// `{ <pat-fields> }`
+    RecordPat,
+    /// `<pat> | <pat>`
+    OrPat,
+    /// `<name> = <pat>`
+    PatField,
+}
+
+impl<'cst> Node<'cst> {
+    pub const fn new(kind: NodeKind, range: TextRange, children: &'cst [Element<'cst>]) -> Self {
+        Self {
+            kind,
+            range,
+            children,
+        }
+    }
+
+    /// The child nodes of this node.
+    pub fn nodes(&self) -> impl Iterator<Item = Node<'cst>> + '_ {
+        self.children.iter().filter_map(|child| match child {
+            Element::Node(node) => Some(*node),
+            Element::Token(_) => None,
+        })
+    }
+
+    /// The child tokens of this node, excluding trivia.
+    pub fn tokens(&self) -> impl Iterator<Item = Token> + '_ {
+        self.children.iter().filter_map(|child| match child {
+            Element::Token(token) if !token.kind.is_trivia() => Some(*token),
+            _ => None,
+        })
+    }
+
+    /// The first child token of this node of kind `kind`.
+    pub fn token(&self, kind: TokenKind) -> Option<Token> {
+        self.tokens().find(|token| token.kind == kind)
+    }
+
+    /// All tokens in this node and its descendants, including trivia, in
+    /// source order.
+    pub fn descendant_tokens(&self) -> Vec<Token> {
+        fn recur(node: &Node, tokens: &mut Vec<Token>) {
+            for child in node.children {
+                match child {
+                    Element::Node(node) => recur(node, tokens),
+                    Element::Token(token) => tokens.push(*token),
+                }
+            }
+        }
+
+        let mut tokens = Vec::new();
+        recur(self, &mut tokens);
+        tokens
+    }
+
+    /// Reconstruct the source text of this node and its descendants.
+    pub fn text(&self, text: &str) -> String {
+        (self.descendant_tokens().iter())
+            .map(|token| &text[token.range])
+            .collect()
+    }
+}
+
+/// Values that can be added as children of a [`Node`].
+pub trait IntoElements<'cst> {
+    fn push_to(self, elements: &mut Vec<Element<'cst>>);
+}
+
+impl<'cst> IntoElements<'cst> for Token {
+    fn push_to(self, elements: &mut Vec<Element<'cst>>) { elements.push(Element::Token(self)); }
+}
+
+impl<'cst> IntoElements<'cst> for Node<'cst> {
+    fn push_to(self, elements: &mut Vec<Element<'cst>>) { elements.push(Element::Node(self)); }
+}
+
+impl<'cst> IntoElements<'cst> for Element<'cst> {
+    fn push_to(self, elements: &mut Vec<Self>) { elements.push(self); }
+}
+
+impl<'cst, T: IntoElements<'cst>> IntoElements<'cst> for Option<T> {
+    fn push_to(self, elements: &mut Vec<Element<'cst>>) {
+        if let Some(elem) = self {
+            elem.push_to(elements);
+        }
+    }
+}
+
+impl<'cst, T: IntoElements<'cst>> IntoElements<'cst> for Vec<T> {
+    fn push_to(self, elements: &mut Vec<Element<'cst>>) {
+        for elem in self {
+            elem.push_to(elements);
+        }
+    }
+}
+
+/// Construct a [`Node`] of kind `NodeKind::$kind`, whose children are the
+/// concatenation of `$children`.
+macro_rules! node {
+    ($bump:expr, $kind:ident, $range:expr, [$($child:expr),* $(,)?]) => {{
+        #[allow(unused_mut)]
+        let mut children = Vec::new();
+        $($crate::cst::IntoElements::push_to($child, &mut children);)*
+        $crate::cst::Node::new(
+            $crate::cst::NodeKind::$kind,
+            $range,
+            $bump.alloc_slice_copy(&children),
+        )
+    }};
+}
+pub(crate) use node;
+
+/// Insert `tokens` (which must be sorted and must not overlap any tokens
+/// already in the tree) into `node`, attaching each token to the innermost
+/// node that encloses it.
+pub(crate) fn insert_tokens<'cst>(
+    bump: &'cst bumpalo::Bump,
+    node: Node<'cst>,
+    tokens: &[Token],
+) -> Node<'cst> {
+    if tokens.is_empty() {
+        return node;
+    }
+
+    let mut children = Vec::with_capacity(node.children.len() + tokens.len());
+    let mut tokens = tokens;
+    for child in node.children {
+        let child_range = match child {
+            Element::Node(node) => node.range,
+            Element::Token(token) => token.range,
+        };
+
+        let before = tokens
+            .iter()
+            .take_while(|token| token.range.end() <= child_range.start())
+            .count();
+        children.extend(tokens[..before].iter().copied().map(Element::Token));
+        tokens = &tokens[before..];
+
+        match child {
+            Element::Node(child) if !child.range.is_empty() => {
+                let inside = tokens
+                    .iter()
+                    .take_while(|token| child.range.contains_range(token.range))
+                    .count();
+                let child = insert_tokens(bump, *child, &tokens[..inside]);
+                tokens = &tokens[inside..];
+                children.push(Element::Node(child));
+            }
+            _ => children.push(*child),
+        }
+    }
+    children.extend(tokens.iter().copied().map(Element::Token));
+
+    Node::new(node.kind, node.range, bump.alloc_slice_copy(&children))
+}
@@ -1,11 +1,14 @@
 #![feature(iter_intersperse)]
 
 use lalrpop_util::lalrpop_mod;
-use pion_diagnostic::{Diagnostic, DiagnosticHandler, Label};
-use pion_lexer::TokenKind;
-use pion_surface::syntax::{Block, Expr, File, Located};
+use pion_diagnostic::{codes, Diagnostic, DiagnosticHandler, Label};
+use pion_lexer::{Token, TokenKind};
+use pion_surface::syntax::{Expr, File, Located};
 use text_size::{TextRange, TextSize};
 
+pub mod cst;
+mod lower;
+
 lalrpop_mod!(
     #[allow(
         clippy::all,
@@ -19,7 +22,7 @@ lalrpop_mod!(
     "/grammar.rs"
 );
 
-pub type LalrpopError = lalrpop_util::ParseError<TextSize, TokenKind, std::convert::Infallible>;
+pub type LalrpopError = lalrpop_util::ParseError<TextSize, Token, std::convert::Infallible>;
 
 const fn error_range(error: &LalrpopError) -> TextRange {
     match error {
@@ -53,9 +56,11 @@ fn error_to_diagnostic(file_id: usize, range: TextRange, error: LalrpopError) ->
     match error {
         lalrpop_util::ParseError::InvalidToken { .. } => Diagnostic::error()
             .with_message("Syntax error: invalid token")
+            .with_code(codes::INVALID_TOKEN.code)
             .with_labels(vec![Label::primary(file_id, range)]),
         lalrpop_util::ParseError::UnrecognizedEof { expected, .. } => Diagnostic::error()
             .with_message("Syntax error: unexpected end of file")
+            .with_code(codes::UNEXPECTED_EOF.code)
             .with_labels(vec![
                 Label::primary(file_id, range).with_message(format_expected(expected))
             ]),
@@ -63,43 +68,178 @@ fn error_to_diagnostic(file_id: usize, range: TextRange, error: LalrpopError) ->
             token: (_, token, _),
             expected,
             ..
-        } => Diagnostic::error()
-            .with_message(format!("Syntax error: unexpected {}", token.description()))
-            .with_labels(vec![Label::primary(file_id, range)])
-            .with_message(format_expected(expected)),
+        } => {
+            let label = match expected.is_empty() {
+                true => Label::primary(file_id, range),
+                false => Label::primary(file_id, range).with_message(format_expected(expected)),
+            };
+            // Unknown characters are not tokens of the language at all.
+            let code = match token.kind {
+                TokenKind::Unknown => codes::INVALID_TOKEN,
+                _ => codes::UNEXPECTED_TOKEN,
+            };
+            Diagnostic::error()
+                .with_message(format!(
+                    "Syntax error: unexpected {}",
+                    token.kind.description()
+                ))
+                .with_code(code.code)
+                .with_labels(vec![label])
+        }
         lalrpop_util::ParseError::ExtraToken {
             token: (_, token, _),
         } => Diagnostic::error()
-            .with_message(format!("Syntax error: unexpected {}", token.description()))
+            .with_message(format!(
+                "Syntax error: unexpected {}",
+                token.kind.description()
+            ))
+            .with_code(codes::UNEXPECTED_TOKEN.code)
             .with_labels(vec![Label::primary(file_id, range)]),
         lalrpop_util::ParseError::User { error } => match error {},
     }
 }
 
-pub fn parse_file<'surface>(
-    bump: &'surface bumpalo::Bump,
+/// Lex `text`, recovering from some common errors before the tokens reach the
+/// parser:
+/// - unknown characters are reported and skipped
+/// - missing `;`s between statements are reported and inserted (as zero-width
+///   tokens), so that a statement is not swallowed by error recovery of the
+///   previous statement
+///
+/// Returns the tokens to pass to the parser, and all tokens (including trivia
+/// and unknown characters) in the source text.
+fn tokens(
+    text: &str,
+    errors: &mut Vec<LalrpopError>,
+) -> (Vec<(TextSize, Token, TextSize)>, Vec<Token>) {
+    let all_tokens: Vec<Token> = pion_lexer::lex(text).collect();
+    let mut tokens: Vec<(TextSize, Token, TextSize)> = Vec::new();
+
+    for token in &all_tokens {
+        let (start, end) = (token.range.start(), token.range.end());
+        if token.kind == TokenKind::Unknown {
+            errors.push(LalrpopError::UnrecognizedToken {
+                token: (start, *token, end),
+                expected: Vec::new(),
+            });
+            continue;
+        }
+        if token.kind.is_trivia() {
+            continue;
+        }
+
+        let starts_stmt = matches!(
+            token.kind,
+            TokenKind::KwLet
+                | TokenKind::KwAssertEq
+                | TokenKind::KwAssertType
+                | TokenKind::KwCheck
+                | TokenKind::KwEval
+                | TokenKind::KwFail
+                | TokenKind::KwPrint
+                | TokenKind::KwReduce
+                | TokenKind::KwShow
+                | TokenKind::KwTrace
+        );
+        let ends_expr = |kind| {
+            matches!(
+                kind,
+                TokenKind::Ident
+                    | TokenKind::DecInt
+                    | TokenKind::BinInt
+                    | TokenKind::HexInt
+                    | TokenKind::KwTrue
+                    | TokenKind::KwFalse
+                    | TokenKind::Underscore
+                    | TokenKind::RParen
+                    | TokenKind::RCurly
+                    | TokenKind::RSquare
+            )
+        };
+        if let Some((_, prev, prev_end)) = tokens.last().copied() {
+            if starts_stmt && ends_expr(prev.kind) {
+                errors.push(LalrpopError::UnrecognizedToken {
+                    token: (start, *token, end),
+                    expected: vec![String::from("\";\"")],
+                });
+                let semi = Token::new(TokenKind::Semicolon, TextRange::empty(prev_end));
+                tokens.push((prev_end, semi, prev_end));
+            }
+        }
+
+        tokens.push((start, *token, end));
+    }
+
+    (tokens, all_tokens)
+}
+
+/// Attach all tokens in `all_tokens` that are not already in `node` (trivia,
+/// unknown characters and tokens skipped during error recovery) to `node`.
+fn attach_missing_tokens<'cst>(
+    bump: &'cst bumpalo::Bump,
+    node: cst::Node<'cst>,
+    all_tokens: &[Token],
+) -> cst::Node<'cst> {
+    let present = node.descendant_tokens();
+    let mut present = present
+        .iter()
+        .filter(|token| !token.range.is_empty())
+        .peekable();
+
+    let mut missing = Vec::new();
+    for token in all_tokens {
+        match present.peek() {
+            Some(next) if next.range == token.range => {
+                present.next();
+            }
+            _ => missing.push(*token),
+        }
+    }
+
+    cst::insert_tokens(bump, node, &missing)
+}
+
+/// Parse `text` to a lossless concrete syntax tree.
+pub fn parse_file_cst<'cst>(
+    bump: &'cst bumpalo::Bump,
     handler: &mut dyn DiagnosticHandler,
     file_id: usize,
     text: &str,
-) -> File<'surface> {
-    let tokens = pion_lexer::lex(text)
-        .filter(|token| !token.kind.is_trivia())
-        .map(|token| (token.range.start(), token.kind, token.range.end()));
+) -> cst::Node<'cst> {
     let mut errors = Vec::new();
-    let file = match grammar::FileParser::new().parse(bump, text, &mut errors, tokens) {
-        Ok(expr) => expr,
+    let (tokens, all_tokens) = tokens(text, &mut errors);
+    let node = match grammar::FileParser::new().parse(bump, &mut errors, tokens) {
+        Ok(node) => node,
         Err(error) => {
             errors.push(error);
-            File {
-                contents: Block::default(),
-            }
+            cst::Node::new(cst::NodeKind::File, TextRange::default(), &[])
         }
     };
-    for error in errors {
-        let range = error_range(&error);
-        handler.handle_diagnostic(error_to_diagnostic(file_id, range, error));
-    }
-    file
+    report_errors(handler, file_id, errors);
+
+    let range = TextRange::up_to(TextSize::of(text));
+    let node = cst::Node::new(node.kind, range, node.children);
+    attach_missing_tokens(bump, node, &all_tokens)
+}
+
+pub fn parse_file<'surface>(
+    bump: &'surface bumpalo::Bump,
+    handler: &mut dyn DiagnosticHandler,
+    file_id: usize,
+    text: &str,
+) -> File<'surface> {
+    let node = parse_file_cst(bump, handler, file_id, text);
+    lower_file(bump, text, node)
+}
+
+/// Lower a concrete syntax tree produced by [`parse_file_cst`] to the
+/// abstract syntax.
+pub fn lower_file<'surface>(
+    bump: &'surface bumpalo::Bump,
+    text: &str,
+    node: cst::Node,
+) -> File<'surface> {
+    lower::Lowerer::new(bump, text).file(node)
 }
 
 pub fn parse_expr<'surface>(
@@ -108,21 +248,23 @@ pub fn parse_expr<'surface>(
     file_id: usize,
     text: &str,
 ) -> Located<Expr<'surface>> {
-    let tokens = pion_lexer::lex(text)
-        .filter(|token| !token.kind.is_trivia())
-        .map(|token| (token.range.start(), token.kind, token.range.end()));
     let mut errors = Vec::new();
-    let expr = match grammar::ExprParser::new().parse(bump, text, &mut errors, tokens) {
-        Ok(expr) => expr,
+    let (tokens, _) = tokens(text, &mut errors);
+    let expr = match grammar::ExprParser::new().parse(bump, &mut errors, tokens) {
+        Ok(node) => lower::Lowerer::new(bump, text).expr(node),
         Err(error) => {
             let range = error_range(&error);
             errors.push(error);
             Located::new(range, Expr::Error)
         }
     };
+    report_errors(handler, file_id, errors);
+    expr
+}
+
+fn report_errors(handler: &mut dyn DiagnosticHandler, file_id: usize, errors: Vec<LalrpopError>) {
     for error in errors {
         let range = error_range(&error);
         handler.handle_diagnostic(error_to_diagnostic(file_id, range, error));
     }
-    expr
 }
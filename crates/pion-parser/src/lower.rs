@@ -0,0 +1,443 @@
+//! Lowering concrete syntax trees to abstract syntax.
+
+use pion_lexer::{Token, TokenKind};
+use pion_surface::syntax::{
+    Block, Command, Expr, ExprField, File, FunArg, FunParam, IntLit, LetBinding, Lit, Located,
+    MatchCase, MatchGuard, Pat, PatField, Plicity, Rec, ReduceOption, Stmt, TypeField,
+};
+use pion_symbol::Symbol;
+
+use crate::cst::{Node, NodeKind};
+
+pub struct Lowerer<'surface, 'text> {
+    bump: &'surface bumpalo::Bump,
+    text: &'text str,
+}
+
+impl<'surface, 'text> Lowerer<'surface, 'text> {
+    pub const fn new(bump: &'surface bumpalo::Bump, text: &'text str) -> Self {
+        Self { bump, text }
+    }
+
+    pub fn file(&self, node: Node) -> File<'surface> {
+        debug_assert_eq!(node.kind, NodeKind::File);
+        let contents = match node.nodes().next() {
+            Some(block) => self.block(block),
+            None => Block::default(),
+        };
+        File { contents }
+    }
+
+    fn block(&self, node: Node) -> Block<'surface> {
+        debug_assert_eq!(node.kind, NodeKind::Block);
+        let mut stmts = Vec::new();
+        let mut result_expr = None;
+        for child in node.nodes() {
+            match child.kind {
+                NodeKind::ErrorStmt => stmts.extend(self.error_stmts(child)),
+                NodeKind::LetStmt
+                | NodeKind::AssertEqCommand
+                | NodeKind::AssertTypeCommand
+                | NodeKind::CheckCommand
+                | NodeKind::EvalCommand
+                | NodeKind::FailCommand
+                | NodeKind::PrintCommand
+                | NodeKind::ReduceCommand
+                | NodeKind::ShowCommand
+                | NodeKind::TraceCommand => stmts.push(self.stmt(child)),
+                _ => result_expr = Some(&*self.bump.alloc(self.expr(child))),
+            }
+        }
+        Block {
+            stmts: self.bump.alloc_slice_copy(&stmts),
+            result_expr,
+        }
+    }
+
+    fn stmt(&self, node: Node) -> Located<Stmt<'surface>> {
+        let stmt = match node.kind {
+            NodeKind::LetStmt => {
+                let rec = match node.token(TokenKind::KwRec) {
+                    Some(_) => Rec::Rec,
+                    None => Rec::Nonrec,
+                };
+                let instance = node.token(TokenKind::KwInstance).is_some();
+                Stmt::Let(rec, self.let_binding(node, instance))
+            }
+            NodeKind::AssertEqCommand => {
+                let lhs = self.expr(nth_node(node, 0));
+                let rhs = self.expr(nth_node(node, 1));
+                Stmt::Command(Located::new(node.range, Command::AssertEq(lhs, rhs)))
+            }
+            NodeKind::AssertTypeCommand => {
+                let expr = self.expr(nth_node(node, 0));
+                let r#type = self.expr(nth_node(node, 1));
+                Stmt::Command(Located::new(node.range, Command::AssertType(expr, r#type)))
+            }
+            NodeKind::CheckCommand => {
+                let expr = self.expr(nth_node(node, 0));
+                Stmt::Command(Located::new(node.range, Command::Check(expr)))
+            }
+            NodeKind::EvalCommand => {
+                let expr = self.expr(nth_node(node, 0));
+                Stmt::Command(Located::new(node.range, Command::Eval(expr)))
+            }
+            NodeKind::FailCommand => {
+                let code = node.token(TokenKind::Ident).map(|token| self.symbol(token));
+                let block = self.block(nth_node(node, 0));
+                Stmt::Command(Located::new(node.range, Command::Fail(code, block)))
+            }
+            NodeKind::PrintCommand => {
+                let option = self.ident(node);
+                let expr = self.expr(nth_node(node, 0));
+                Stmt::Command(Located::new(node.range, Command::Print(option, expr)))
+            }
+            NodeKind::ReduceCommand => {
+                let (options, expr) = self.reduce_command(node);
+                Stmt::Command(Located::new(node.range, Command::Reduce(options, expr)))
+            }
+            NodeKind::ShowCommand => {
+                let name = self.ident(node);
+                Stmt::Command(Located::new(node.range, Command::Show(name)))
+            }
+            NodeKind::TraceCommand => {
+                let (options, expr) = self.reduce_command(node);
+                Stmt::Command(Located::new(node.range, Command::Trace(options, expr)))
+            }
+            kind => unreachable!("expected statement, found {kind:?}"),
+        };
+        Located::new(node.range, stmt)
+    }
+
+    fn reduce_command(&self, node: Node) -> (&'surface [ReduceOption], Located<Expr<'surface>>) {
+        let mut options = Vec::new();
+        let mut expr = None;
+        for child in node.nodes() {
+            match child.kind {
+                NodeKind::ReduceOption => options.push(ReduceOption {
+                    name: self.ident(child),
+                    value: child.tokens().find_map(lit_token),
+                }),
+                _ => expr = Some(self.expr(child)),
+            }
+        }
+        (self.bump.alloc_slice_copy(&options), expr.unwrap())
+    }
+
+    /// The statements to use in place of a statement that could not be
+    /// parsed. If the statement starts with `let <rec>? <instance>? <pat>`, we
+    /// still bind each variable of `<pat>` (to an error expression), so that
+    /// later references to them do not report spurious unbound variable
+    /// errors.
+    fn error_stmts(&self, node: Node) -> Vec<Located<Stmt<'surface>>> {
+        let mut tokens = node.descendant_tokens().into_iter();
+        let mut tokens =
+            std::iter::from_fn(|| tokens.find(|token| !token.kind.is_trivia())).peekable();
+
+        let error = vec![Located::new(node.range, Stmt::Error)];
+        if tokens.next().map(|token| token.kind) != Some(TokenKind::KwLet) {
+            return error;
+        }
+        let rec = match tokens.next_if(|token| token.kind == TokenKind::KwRec) {
+            Some(_) => Rec::Rec,
+            None => Rec::Nonrec,
+        };
+        let instance = (tokens.next_if(|token| token.kind == TokenKind::KwInstance)).is_some();
+
+        // The variables of the pattern are the identifiers before the `:` or `=`
+        // that ends it, other than the names of record fields (which are
+        // followed by `=`).
+        let mut names = Vec::new();
+        let mut depth = 0_u32;
+        while let Some(token) = tokens.next() {
+            match token.kind {
+                TokenKind::LParen | TokenKind::LCurly | TokenKind::LSquare => depth += 1,
+                TokenKind::RParen | TokenKind::RCurly | TokenKind::RSquare if depth > 0 => {
+                    depth -= 1;
+                }
+                TokenKind::Ident => {
+                    let next = tokens.peek().map(|token| token.kind);
+                    if depth == 0 || next != Some(TokenKind::Eq) {
+                        names.push(self.symbol(token));
+                    }
+                }
+                TokenKind::Eq | TokenKind::Colon if depth == 0 => break,
+                TokenKind::Eq
+                | TokenKind::Underscore
+                | TokenKind::Comma
+                | TokenKind::Pipe
+                | TokenKind::KwTrue
+                | TokenKind::KwFalse
+                | TokenKind::DecInt
+                | TokenKind::BinInt
+                | TokenKind::HexInt => {}
+                _ => break,
+            }
+        }
+        if names.is_empty() {
+            return error;
+        }
+
+        (names.into_iter())
+            .map(|name| {
+                let (pat, rhs) = self.bump.alloc((
+                    Located::new(name.range, Pat::Var(name)),
+                    Located::new(node.range, Expr::Error),
+                ));
+                let binding = LetBinding {
+                    instance,
+                    pat,
+                    r#type: None,
+                    rhs,
+                };
+                Located::new(node.range, Stmt::Let(rec, binding))
+            })
+            .collect()
+    }
+
+    fn let_binding(&self, node: Node, instance: bool) -> LetBinding<'surface> {
+        let mut nodes = node.nodes();
+        let pat = self.pat(nodes.next().unwrap());
+        let r#type = (node.token(TokenKind::Colon))
+            .map(|_| &*self.bump.alloc(self.expr(nodes.next().unwrap())));
+        let rhs = self.expr(nodes.next().unwrap());
+        let (pat, rhs) = self.bump.alloc((pat, rhs));
+        LetBinding {
+            instance,
+            pat,
+            r#type,
+            rhs,
+        }
+    }
+
+    pub fn expr(&self, node: Node) -> Located<Expr<'surface>> {
+        let expr = match node.kind {
+            NodeKind::ErrorExpr => Expr::Error,
+            NodeKind::LitExpr => Expr::Lit(lit(node)),
+            NodeKind::VarExpr => Expr::VarRef(self.ident(node)),
+            NodeKind::HoleExpr => Expr::Hole,
+            NodeKind::ParenExpr => Expr::Paren(self.alloc_expr(nth_node(node, 0))),
+            NodeKind::AnnExpr => {
+                let expr = self.expr(nth_node(node, 0));
+                let r#type = self.expr(nth_node(node, 1));
+                let (expr, r#type) = self.bump.alloc((expr, r#type));
+                Expr::Ann(expr, r#type)
+            }
+            NodeKind::DoExpr => Expr::Do(self.block(nth_node(node, 0))),
+            NodeKind::IfExpr => {
+                let cond = self.expr(nth_node(node, 0));
+                let then = self.expr(nth_node(node, 1));
+                let r#else = self.expr(nth_node(node, 2));
+                let (cond, then, r#else) = self.bump.alloc((cond, then, r#else));
+                Expr::If(cond, then, r#else)
+            }
+            NodeKind::MatchExpr => {
+                let mut nodes = node.nodes();
+                let scrut = self.alloc_expr(nodes.next().unwrap());
+                let cases: Vec<_> = nodes.map(|case| self.match_case(case)).collect();
+                Expr::Match(scrut, self.bump.alloc_slice_copy(&cases))
+            }
+            NodeKind::FunArrowExpr => {
+                let plicity = plicity(node);
+                let lhs = self.expr(nth_node(node, 0));
+                let rhs = self.expr(nth_node(node, 1));
+                let (lhs, rhs) = self.bump.alloc((lhs, rhs));
+                Expr::FunArrow(plicity, lhs, rhs)
+            }
+            NodeKind::FunTypeExpr | NodeKind::FunLitExpr => {
+                let params: Vec<_> = (node.nodes())
+                    .filter(|node| node.kind == NodeKind::FunParam)
+                    .map(|param| self.fun_param(param))
+                    .collect();
+                let params = self.bump.alloc_slice_copy(&params);
+                let body = self.alloc_expr(node.nodes().last().unwrap());
+                match node.kind {
+                    NodeKind::FunTypeExpr => Expr::FunType(params, body),
+                    _ => Expr::FunLit(params, body),
+                }
+            }
+            NodeKind::FunAppExpr => {
+                let mut nodes = node.nodes();
+                let fun = self.alloc_expr(nodes.next().unwrap());
+                let mut args = Vec::new();
+                for arg in nodes {
+                    self.fun_args(arg, &mut args);
+                }
+                Expr::FunApp(fun, self.bump.alloc_slice_copy(&args))
+            }
+            NodeKind::ListExpr => Expr::ListLit(self.exprs(node)),
+            NodeKind::TupleExpr => Expr::TupleLit(self.exprs(node)),
+            NodeKind::RecordTypeExpr => {
+                let fields: Vec<_> = (node.nodes())
+                    .map(|field| {
+                        let name = self.ident(field);
+                        let r#type = self.expr(nth_node(field, 0));
+                        Located::new(field.range, TypeField { name, r#type })
+                    })
+                    .collect();
+                Expr::RecordType(self.bump.alloc_slice_copy(&fields))
+            }
+            NodeKind::RecordLitExpr => {
+                let fields: Vec<_> = (node.nodes())
+                    .map(|field| {
+                        let name = self.ident(field);
+                        let expr = self.expr(nth_node(field, 0));
+                        Located::new(field.range, ExprField { name, expr })
+                    })
+                    .collect();
+                Expr::RecordLit(self.bump.alloc_slice_copy(&fields))
+            }
+            NodeKind::RecordProjExpr => {
+                let scrut = self.alloc_expr(nth_node(node, 0));
+                Expr::RecordProj(scrut, self.ident(node))
+            }
+            kind => unreachable!("expected expression, found {kind:?}"),
+        };
+        Located::new(node.range, expr)
+    }
+
+    fn alloc_expr(&self, node: Node) -> &'surface Located<Expr<'surface>> {
+        self.bump.alloc(self.expr(node))
+    }
+
+    fn exprs(&self, node: Node) -> &'surface [Located<Expr<'surface>>] {
+        let exprs: Vec<_> = node.nodes().map(|expr| self.expr(expr)).collect();
+        self.bump.alloc_slice_copy(&exprs)
+    }
+
+    fn match_case(&self, node: Node) -> MatchCase<'surface> {
+        debug_assert_eq!(node.kind, NodeKind::MatchCase);
+        let mut nodes = node.nodes();
+        let pat = self.pat(nodes.next().unwrap());
+        let mut next = nodes.next().unwrap();
+        let guard = match next.kind {
+            NodeKind::MatchGuard => {
+                let cond = self.expr(nth_node(next, 0));
+                next = nodes.next().unwrap();
+                Some(MatchGuard::If(cond))
+            }
+            _ => None,
+        };
+        let expr = self.expr(next);
+        MatchCase { pat, guard, expr }
+    }
+
+    fn fun_param(&self, node: Node) -> Located<FunParam<'surface>> {
+        debug_assert_eq!(node.kind, NodeKind::FunParam);
+        let plicity = plicity(node);
+        let mut nodes = node.nodes();
+        let pat = self.pat(nodes.next().unwrap());
+        let r#type = nodes.next().map(|r#type| self.expr(r#type));
+        Located::new(
+            node.range,
+            FunParam {
+                plicity,
+                pat,
+                r#type,
+            },
+        )
+    }
+
+    /// An implicit argument that is a record literal, `@{A = Int, B = Bool}`,
+    /// gives an argument for each named parameter.
+    fn fun_args(&self, node: Node, args: &mut Vec<Located<FunArg<'surface>>>) {
+        debug_assert_eq!(node.kind, NodeKind::FunArg);
+        let plicity = plicity(node);
+        let expr = nth_node(node, 0);
+        if plicity.is_implicit() && expr.kind == NodeKind::RecordLitExpr {
+            args.extend(expr.nodes().map(|field| {
+                let name = Some(self.ident(field));
+                let expr = self.alloc_expr(nth_node(field, 0));
+                Located::new(
+                    field.range,
+                    FunArg {
+                        plicity,
+                        name,
+                        expr,
+                    },
+                )
+            }));
+        } else {
+            let expr = self.alloc_expr(expr);
+            let name = None;
+            args.push(Located::new(
+                node.range,
+                FunArg {
+                    plicity,
+                    name,
+                    expr,
+                },
+            ));
+        }
+    }
+
+    pub fn pat(&self, node: Node) -> Located<Pat<'surface>> {
+        let pat = match node.kind {
+            NodeKind::ErrorPat => Pat::Error,
+            NodeKind::UnderscorePat => Pat::Underscore,
+            NodeKind::VarPat => Pat::Var(self.ident(node)),
+            NodeKind::LitPat => Pat::Lit(lit(node)),
+            NodeKind::ParenPat => Pat::Paren(self.bump.alloc(self.pat(nth_node(node, 0)))),
+            NodeKind::TuplePat => Pat::TupleLit(self.pats(node)),
+            NodeKind::OrPat => Pat::Or(self.pats(node)),
+            NodeKind::RecordPat => {
+                let fields: Vec<_> = (node.nodes())
+                    .map(|field| {
+                        let name = self.ident(field);
+                        let pat = self.pat(nth_node(field, 0));
+                        Located::new(field.range, PatField { name, pat })
+                    })
+                    .collect();
+                Pat::RecordLit(self.bump.alloc_slice_copy(&fields))
+            }
+            kind => unreachable!("expected pattern, found {kind:?}"),
+        };
+        Located::new(node.range, pat)
+    }
+
+    fn pats(&self, node: Node) -> &'surface [Located<Pat<'surface>>] {
+        let pats: Vec<_> = node.nodes().map(|pat| self.pat(pat)).collect();
+        self.bump.alloc_slice_copy(&pats)
+    }
+
+    /// The first identifier token in `node`.
+    fn ident(&self, node: Node) -> Located<Symbol> {
+        self.symbol(node.token(TokenKind::Ident).unwrap())
+    }
+
+    fn symbol(&self, token: Token) -> Located<Symbol> {
+        Located::new(token.range, Symbol::intern(&self.text[token.range]))
+    }
+}
+
+fn plicity(node: Node) -> Plicity {
+    if node.token(TokenKind::DoubleAt).is_some() {
+        return Plicity::Instance;
+    }
+    match node.token(TokenKind::At) {
+        Some(_) => Plicity::Implicit,
+        None => Plicity::Explicit,
+    }
+}
+
+fn lit(node: Node) -> Located<Lit> {
+    let token = node.tokens().next().unwrap();
+    match lit_token(token) {
+        Some(lit) => lit,
+        None => unreachable!("expected literal, found {:?}", token.kind),
+    }
+}
+
+fn lit_token(token: Token) -> Option<Located<Lit>> {
+    let lit = match token.kind {
+        TokenKind::KwTrue => Lit::Bool(true),
+        TokenKind::KwFalse => Lit::Bool(false),
+        TokenKind::DecInt => Lit::Int(IntLit::Dec),
+        TokenKind::BinInt => Lit::Int(IntLit::Bin),
+        TokenKind::HexInt => Lit::Int(IntLit::Hex),
+        _ => return None,
+    };
+    Some(Located::new(token.range, lit))
+}
+
+fn nth_node<'cst>(node: Node<'cst>, n: usize) -> Node<'cst> { node.nodes().nth(n).unwrap() }
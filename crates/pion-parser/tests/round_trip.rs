@@ -0,0 +1,55 @@
+//! Check that parsing every file in `test-data` to a concrete syntax tree and
+//! then printing it reproduces the input exactly.
+
+use pion_parser::cst::{Element, Node};
+
+fn check_ranges(node: &Node) {
+    for child in node.children {
+        if let Element::Node(child) = child {
+            assert!(
+                child.range.is_empty() || node.range.contains_range(child.range),
+                "{:?} at {:?} is not contained in {:?} at {:?}",
+                child.kind,
+                child.range,
+                node.kind,
+                node.range
+            );
+            check_ranges(child);
+        }
+    }
+}
+
+#[test]
+fn round_trip() {
+    let test_data = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data");
+    let mut count = 0;
+
+    for entry in walkdir::WalkDir::new(test_data) {
+        let entry = entry.unwrap();
+        if entry.path().extension() != Some("pion".as_ref()) {
+            continue;
+        }
+
+        let text = std::fs::read_to_string(entry.path()).unwrap();
+        let bump = bumpalo::Bump::new();
+        let mut handler = |_| {};
+        let node = pion_parser::parse_file_cst(&bump, &mut handler, 0, &text);
+
+        let tokens = node.descendant_tokens();
+        let mut pos = 0.into();
+        for token in tokens.iter().filter(|token| !token.range.is_empty()) {
+            assert_eq!(
+                token.range.start(),
+                pos,
+                "{}: tokens not contiguous",
+                entry.path().display()
+            );
+            pos = token.range.end();
+        }
+        assert_eq!(node.text(&text), text, "{}", entry.path().display());
+        check_ranges(&node);
+        count += 1;
+    }
+
+    assert!(count > 0, "no test files found");
+}
@@ -57,6 +57,7 @@ impl<'bump> BumpDocAllocator<'bump> {
     pub fn let_stmt(
         &'bump self,
         rec: bool,
+        instance: bool,
         pat: impl Pretty<'bump, Self>,
         r#type: Option<impl Pretty<'bump, Self>>,
         rhs: impl Pretty<'bump, Self>,
@@ -66,6 +67,11 @@ impl<'bump> BumpDocAllocator<'bump> {
         } else {
             self.nil()
         };
+        let instance = if instance {
+            docs![self, self.text("instance"), self.space()]
+        } else {
+            self.nil()
+        };
 
         let r#type = match r#type {
             None => self.nil(),
@@ -77,6 +83,7 @@ impl<'bump> BumpDocAllocator<'bump> {
             "let",
             self.space(),
             rec,
+            instance,
             pat,
             r#type,
             docs![self, self.line(), "= ", rhs, ";"]
@@ -216,6 +223,14 @@ impl<'bump> BumpDocAllocator<'bump> {
         docs![self, plicity, expr]
     }
 
+    pub fn named_fun_arg(
+        &'bump self,
+        name: impl Pretty<'bump, Self>,
+        expr: impl Pretty<'bump, Self>,
+    ) -> DocBuilder<'bump> {
+        docs![self, "@{", name, " = ", expr, "}"]
+    }
+
     pub fn fun_param(
         &'bump self,
         plicity: impl Pretty<'bump, Self>,
@@ -1,6 +1,8 @@
-use pion_printer::{docs, BumpDocAllocator, DocAllocator, DocBuilder};
+use pion_printer::{docs, BumpDocAllocator, DocAllocator, DocBuilder, INDENT};
 
-use crate::syntax::{Block, Command, Expr, FunParam, Lit, Located, MatchGuard, Pat, Plicity, Stmt};
+use crate::syntax::{
+    Block, Command, Expr, FunParam, Lit, Located, MatchGuard, Pat, Plicity, ReduceOption, Stmt,
+};
 
 pub struct Printer<'bump, 'text> {
     alloc: BumpDocAllocator<'bump>,
@@ -72,9 +74,17 @@ impl<'bump, 'text> Printer<'bump, 'text> {
             Expr::FunApp(fun, args) => {
                 let fun = self.expr(&fun.data);
                 let args = args.iter().map(|arg| {
-                    let arg_plicity = self.plicity(arg.data.plicity);
                     let arg_expr = self.expr(&arg.data.expr.data);
-                    self.alloc.fun_arg(arg_plicity, arg_expr)
+                    match arg.data.name {
+                        Some(name) => {
+                            let name = self.alloc.symbol(name.data);
+                            self.alloc.named_fun_arg(name, arg_expr)
+                        }
+                        None => {
+                            let arg_plicity = self.plicity(arg.data.plicity);
+                            self.alloc.fun_arg(arg_plicity, arg_expr)
+                        }
+                    }
                 });
                 self.alloc.fun_app_expr(fun, args)
             }
@@ -121,13 +131,39 @@ impl<'bump, 'text> Printer<'bump, 'text> {
 
     pub fn stmt(&'bump self, stmt: &Stmt) -> DocBuilder<'bump> {
         match stmt {
+            Stmt::Error => docs![&self.alloc, "#error", ";"],
             Stmt::Let(rec, binding) => {
                 let pat = self.pat(&binding.pat.data);
                 let r#type = binding.r#type.as_ref().map(|ty| self.expr(&ty.data));
                 let rhs = self.expr(&binding.rhs.data);
-                self.alloc.let_stmt(rec.is_rec(), pat, r#type, rhs)
+                self.alloc
+                    .let_stmt(rec.is_rec(), binding.instance, pat, r#type, rhs)
             }
             Stmt::Command(command) => match command.data {
+                Command::AssertEq(lhs, rhs) => {
+                    let lhs = self.expr(&lhs.data);
+                    let rhs = self.expr(&rhs.data);
+                    docs![
+                        &self.alloc,
+                        "#assert_eq",
+                        self.alloc.space(),
+                        lhs,
+                        self.alloc.space(),
+                        rhs
+                    ]
+                }
+                Command::AssertType(expr, r#type) => {
+                    let expr = self.expr(&expr.data);
+                    let r#type = self.expr(&r#type.data);
+                    docs![
+                        &self.alloc,
+                        "#assert_type",
+                        self.alloc.space(),
+                        expr,
+                        self.alloc.space(),
+                        r#type
+                    ]
+                }
                 Command::Check(expr) => {
                     let expr = self.expr(&expr.data);
                     docs![&self.alloc, "#check", self.alloc.space(), expr]
@@ -136,14 +172,55 @@ impl<'bump, 'text> Printer<'bump, 'text> {
                     let expr = self.expr(&expr.data);
                     docs![&self.alloc, "#eval", self.alloc.space(), expr]
                 }
+                Command::Fail(code, block) => {
+                    let code =
+                        code.map(|code| docs![&self.alloc, " ", self.alloc.symbol(code.data)]);
+                    let block = self.block(&block).nest(INDENT);
+                    docs![&self.alloc, "#fail", code, " {", block, "}"].group()
+                }
+                Command::Print(option, expr) => {
+                    let option = self.alloc.symbol(option.data);
+                    let expr = self.expr(&expr.data);
+                    docs![
+                        &self.alloc,
+                        "#print",
+                        self.alloc.space(),
+                        option,
+                        self.alloc.space(),
+                        expr
+                    ]
+                }
+                Command::Reduce(options, expr) => self.reduce_command("#reduce", options, &expr),
                 Command::Show(name) => {
                     let name = self.alloc.symbol(name.data);
                     docs![&self.alloc, "#show", self.alloc.space(), name]
                 }
+                Command::Trace(options, expr) => self.reduce_command("#trace", options, &expr),
             },
         }
     }
 
+    fn reduce_command(
+        &'bump self,
+        keyword: &'static str,
+        options: &[ReduceOption],
+        expr: &Located<Expr>,
+    ) -> DocBuilder<'bump> {
+        let expr = self.expr(&expr.data);
+        let options = options.iter().map(|option| {
+            let name = self.alloc.symbol(option.name.data);
+            let value = (option.value).map(|value| docs![&self.alloc, " = ", self.lit(value)]);
+            docs![&self.alloc, ",", self.alloc.space(), name, value]
+        });
+        docs![
+            &self.alloc,
+            keyword,
+            self.alloc.space(),
+            expr,
+            self.alloc.concat(options)
+        ]
+    }
+
     pub fn fun_param(&'bump self, param: &FunParam) -> DocBuilder<'bump> {
         let plicity = self.plicity(param.plicity);
         let pat = self.pat(&param.pat.data);
@@ -188,6 +265,7 @@ impl<'bump, 'text> Printer<'bump, 'text> {
         match plicity {
             Plicity::Explicit => self.alloc.nil(),
             Plicity::Implicit => self.alloc.text("@"),
+            Plicity::Instance => self.alloc.text("@@"),
         }
     }
 
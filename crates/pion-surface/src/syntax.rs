@@ -104,6 +104,8 @@ pub struct Block<'surface> {
 /// Statements.
 #[derive(Debug, Copy, Clone)]
 pub enum Stmt<'surface> {
+    /// A statement that could not be parsed.
+    Error,
     /// Let-statement, `let <rec> <binding>;`.
     Let(Rec, LetBinding<'surface>),
     /// An interactive command, `#<command>`.
@@ -112,12 +114,33 @@ pub enum Stmt<'surface> {
 
 #[derive(Debug, Copy, Clone)]
 pub enum Command<'surface> {
+    /// Assert command, `#assert_eq <lhs> <rhs>`.
+    AssertEq(Located<Expr<'surface>>, Located<Expr<'surface>>),
+    /// Assert command, `#assert_type <expr> <type>`.
+    AssertType(Located<Expr<'surface>>, Located<Expr<'surface>>),
     /// Check command, `#check <expr>`.
     Check(Located<Expr<'surface>>),
     /// Eval command, `#eval <expr>`.
     Eval(Located<Expr<'surface>>),
+    /// Expected failure command, `#fail <code>? { <block> }`.
+    Fail(Option<Located<Symbol>>, Block<'surface>),
+    /// Print command, `#print <option> <expr>`. The only option is
+    /// `optimized`.
+    Print(Located<Symbol>, Located<Expr<'surface>>),
+    /// Reduce command, `#reduce <expr>, <options>`.
+    Reduce(&'surface [ReduceOption], Located<Expr<'surface>>),
     /// Show command, `#show <name>`.
     Show(Located<Symbol>),
+    /// Trace command, `#trace <expr>, <options>`.
+    Trace(&'surface [ReduceOption], Located<Expr<'surface>>),
+}
+
+/// An option of a `#reduce` or `#trace` command, `<name>` or
+/// `<name> = <lit>`.
+#[derive(Debug, Copy, Clone)]
+pub struct ReduceOption {
+    pub name: Located<Symbol>,
+    pub value: Option<Located<Lit>>,
 }
 
 /// Recursive or non-recursive binding.
@@ -133,9 +156,12 @@ impl Rec {
     pub const fn is_nonrec(&self) -> bool { matches!(self, Self::Nonrec) }
 }
 
-/// Let-binding, `<pat> (: <type>)? = <rhs>`.
+/// Let-binding, `<instance>? <pat> (: <type>)? = <rhs>`.
 #[derive(Debug, Copy, Clone)]
 pub struct LetBinding<'surface> {
+    /// Whether the binding was declared with `instance`, making it available
+    /// to instance resolution.
+    pub instance: bool,
     pub pat: &'surface Located<Pat<'surface>>,
     pub r#type: Option<&'surface Located<Expr<'surface>>>,
     pub rhs: &'surface Located<Expr<'surface>>,
@@ -148,6 +174,8 @@ pub enum Plicity {
     Implicit,
     /// Arguments supplied by the user.
     Explicit,
+    /// Arguments to be filled in by instance resolution.
+    Instance,
 }
 
 impl Plicity {
@@ -157,12 +185,16 @@ impl Plicity {
     /// Returns `true` if the plicity is explicit.
     pub const fn is_explicit(self) -> bool { matches!(self, Self::Explicit) }
 
+    /// Returns `true` if the plicity is instance.
+    pub const fn is_instance(self) -> bool { matches!(self, Self::Instance) }
+
     /// Returns the description of the plicity, suitable for displaying in
     /// diagnostics.
     pub const fn description(self) -> &'static str {
         match self {
             Self::Implicit => "implicit",
             Self::Explicit => "explicit",
+            Self::Instance => "instance",
         }
     }
 }
@@ -204,10 +236,12 @@ pub struct FunParam<'surface> {
     pub r#type: Option<Located<Expr<'surface>>>,
 }
 
-/// Function argument, `<plicity> <expr>`.
+/// Function argument, `<plicity> <expr>`, or `@{<name> = <expr>}` for an
+/// implicit argument given by the name of its parameter.
 #[derive(Debug, Copy, Clone)]
 pub struct FunArg<'surface> {
     pub plicity: Plicity,
+    pub name: Option<Located<Symbol>>,
     pub expr: &'surface Located<Expr<'surface>>,
 }
 
@@ -0,0 +1,69 @@
+//! Finding similarly named items, for "did you mean" suggestions.
+
+/// The Levenshtein distance between `a` and `b`: the minimum number of
+/// single-character insertions, deletions or substitutions needed to
+/// transform `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev_row: Vec<usize> = (0..=b.len()).collect();
+    let mut row = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution_cost = usize::from(a_char != *b_char);
+            row[j + 1] = (prev_row[j] + substitution_cost)
+                .min(prev_row[j + 1] + 1)
+                .min(row[j] + 1);
+        }
+        std::mem::swap(&mut prev_row, &mut row);
+    }
+
+    prev_row[b.len()]
+}
+
+/// Find the candidate most similar to `name`, if any is similar enough to be
+/// a plausible typo. Ties are broken in favour of the earliest candidate.
+///
+/// A candidate is only considered similar if it can be reached by changing
+/// at most a third of the characters in `name` (but always at least one
+/// character, and never all of them).
+pub fn find_best_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let len = name.chars().count();
+    let max_distance = usize::min(usize::max(len / 3, 1), len.saturating_sub(1));
+
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_examples() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("flaw", "lawn"), 2);
+        assert_eq!(edit_distance("λx", "λy"), 1);
+    }
+
+    #[test]
+    fn best_match() {
+        assert_eq!(find_best_match("lenght", ["len", "length"]), Some("length"));
+        assert_eq!(find_best_match("xs", ["ys", "zs"]), Some("ys"));
+        assert_eq!(find_best_match("x", ["y", "z"]), None);
+        assert_eq!(find_best_match("foo", ["bar", "baz"]), None);
+        assert_eq!(find_best_match("foo", ["foo"]), None);
+    }
+}
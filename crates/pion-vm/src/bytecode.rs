@@ -0,0 +1,116 @@
+//! The bytecode executed by the [`Machine`](crate::machine::Machine).
+//!
+//! Each function runs in a frame: a window onto the machine's value stack
+//! whose first slots hold the function's captured variables, followed by its
+//! parameter, and then any values pushed by its code. Local variables are
+//! referred to by their slot in the current frame, and top-level bindings by
+//! their level.
+
+use pion_core::prim::Prim;
+use pion_symbol::Symbol;
+
+/// An index into [`Program::functions`].
+pub type FunctionId = u32;
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    /// The labels of record literals, indexed by [`Instr::Record`].
+    pub labels: Vec<Box<[Symbol]>>,
+}
+
+impl Program {
+    /// Implements `fix f x = f (fix f) x`. Called with the stack
+    /// `[x, f, fix f]`.
+    pub const FIX: FunctionId = 0;
+
+    fn fix() -> Function {
+        Function {
+            code: vec![Instr::Apply, Instr::Swap, Instr::TailApply],
+            switches: Vec::new(),
+        }
+    }
+}
+
+/// A program containing only [`Program::FIX`].
+impl Default for Program {
+    fn default() -> Self {
+        Self {
+            functions: vec![Self::fix()],
+            labels: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub code: Vec<Instr>,
+    /// The jump tables, indexed by [`Instr::Switch`].
+    pub switches: Vec<Switch>,
+}
+
+/// A jump table for matching on an integer.
+#[derive(Debug, Clone)]
+pub struct Switch {
+    /// The integers to match, and where to jump to if they are matched.
+    pub cases: Box<[(u32, u32)]>,
+    /// Where to jump to if no case is matched.
+    pub default: u32,
+}
+
+impl Switch {
+    pub fn target(&self, int: u32) -> u32 {
+        (self.cases.iter())
+            .find(|(case, _)| *case == int)
+            .map_or(self.default, |(_, target)| *target)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Instr {
+    /// Push an integer.
+    Int(u32),
+    /// Push a boolean.
+    Bool(bool),
+    /// Push a value with no runtime content: a type, or a proof.
+    Erased,
+    /// Push a copy of the value in a slot of the current frame.
+    Local(u32),
+    /// Push a copy of the value of the top-level binding at the given level.
+    Global(u32),
+    /// Pop `captures` values, and push a closure of `function` capturing them.
+    Closure { function: FunctionId, captures: u32 },
+
+    /// Pop an argument and a function, and push the result of applying the
+    /// function to the argument.
+    Apply,
+    /// Pop the relevant arguments of the primitive function, and push the
+    /// result of applying it to them.
+    CallPrim(Prim),
+    /// Pop an argument and a function, and return the result of applying the
+    /// function to the argument from the current frame.
+    TailApply,
+    /// Pop a value and return it from the current frame.
+    Return,
+
+    /// Pop a value, drop the given number of values beneath it, and push it
+    /// back.
+    Slide(u32),
+    /// Swap the top two values.
+    Swap,
+
+    /// Continue from the given instruction.
+    Jump(u32),
+    /// Pop a boolean, and continue from the given instruction if it is false.
+    JumpIfFalse(u32),
+    /// Pop an integer, and continue from the target given by a jump table.
+    Switch(u32),
+
+    /// Pop the given number of values, and push them as a list.
+    List(u32),
+    /// Pop one value for each of the labels in [`Program::labels`], and push
+    /// them as a record.
+    Record(u32),
+    /// Pop a record, and push one of its fields.
+    Proj(Symbol),
+}
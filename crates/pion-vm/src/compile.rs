@@ -0,0 +1,259 @@
+//! Compiling erased expressions to bytecode.
+//!
+//! Core expressions are first [erased](pion_erase), and functions are then
+//! closure converted: each closure captures only the variables its body refers
+//! to.
+
+use pion_erase::syntax::{self as erased, prim_arity};
+use pion_util::numeric_conversions::TruncateFrom;
+
+use crate::bytecode::{Function, FunctionId, Instr, Program, Switch};
+use crate::Error;
+
+/// Compile `expr` into a new function of `program`, which takes no arguments
+/// and returns the value of `expr`. `globals` is the number of top-level
+/// bindings in scope of `expr`.
+pub fn compile_function(
+    program: &mut Program,
+    globals: usize,
+    expr: &erased::Expr,
+) -> Result<FunctionId, Error> {
+    let mut compiler = Compiler { program };
+    let mut builder = FunctionBuilder::default();
+    let mut vars = (0..globals)
+        .map(|level| Some(Var::Global(u32::truncate_from(level))))
+        .collect();
+    compiler.compile(&mut builder, &mut vars, expr, true)?;
+    Ok(compiler.push_function(builder.finish()))
+}
+
+/// Where the value of a variable is found.
+#[derive(Debug, Copy, Clone)]
+enum Var {
+    /// A top-level binding, by level.
+    Global(u32),
+    /// A slot in the current frame.
+    Local(u32),
+}
+
+struct Compiler<'program> {
+    program: &'program mut Program,
+}
+
+/// The code of a function being compiled.
+#[derive(Default)]
+struct FunctionBuilder {
+    code: Vec<Instr>,
+    switches: Vec<Switch>,
+    /// The number of values in the function's frame.
+    depth: u32,
+}
+
+impl FunctionBuilder {
+    fn emit(&mut self, instr: Instr) {
+        self.depth = match instr {
+            Instr::Int(_) | Instr::Bool(_) | Instr::Erased | Instr::Local(_) | Instr::Global(_) => {
+                self.depth + 1
+            }
+            Instr::Closure { captures, .. } => self.depth + 1 - captures,
+            Instr::Apply | Instr::JumpIfFalse(_) | Instr::Switch(_) => self.depth - 1,
+            Instr::CallPrim(prim) => self.depth + 1 - u32::truncate_from(prim_arity(prim)),
+            Instr::Slide(n) => self.depth - n,
+            Instr::List(n) => self.depth + 1 - n,
+            Instr::Record(_) => unreachable!("use `emit_record` instead"),
+            Instr::TailApply | Instr::Return | Instr::Swap | Instr::Jump(_) | Instr::Proj(_) => {
+                self.depth
+            }
+        };
+        self.code.push(instr);
+    }
+
+    fn emit_record(&mut self, labels: u32, len: usize) {
+        self.depth = self.depth + 1 - u32::truncate_from(len);
+        self.code.push(Instr::Record(labels));
+    }
+
+    fn next_pc(&self) -> u32 { u32::truncate_from(self.code.len()) }
+
+    /// Point the jump at `pc` to the next instruction.
+    fn patch_jump(&mut self, pc: u32) {
+        let target = self.next_pc();
+        match &mut self.code[usize::try_from(pc).unwrap()] {
+            Instr::Jump(pc) | Instr::JumpIfFalse(pc) => *pc = target,
+            instr => unreachable!("not a jump: {instr:?}"),
+        }
+    }
+
+    fn finish(self) -> Function {
+        Function {
+            code: self.code,
+            switches: self.switches,
+        }
+    }
+}
+
+impl Compiler<'_> {
+    fn push_function(&mut self, function: Function) -> FunctionId {
+        let id = FunctionId::truncate_from(self.program.functions.len());
+        self.program.functions.push(function);
+        id
+    }
+
+    /// Compile code that pushes the value of `expr` (or returns it, if
+    /// `tail` is set). `vars` maps each variable in scope (by level) to where
+    /// its value is found.
+    fn compile(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        vars: &mut Vec<Option<Var>>,
+        expr: &erased::Expr,
+        tail: bool,
+    ) -> Result<(), Error> {
+        match expr {
+            erased::Expr::Erased => builder.emit(Instr::Erased),
+            erased::Expr::Int(int) => builder.emit(Instr::Int(*int)),
+            erased::Expr::Bool(bool) => builder.emit(Instr::Bool(*bool)),
+            erased::Expr::Var(var) => {
+                let level = vars.len() - usize::from(*var) - 1;
+                match vars[level].expect("uncaptured variables are not referred to") {
+                    Var::Global(level) => builder.emit(Instr::Global(level)),
+                    Var::Local(slot) => builder.emit(Instr::Local(slot)),
+                }
+            }
+            erased::Expr::Let(rhs, body) => {
+                self.compile(builder, vars, rhs, false)?;
+                vars.push(Some(Var::Local(builder.depth - 1)));
+                let result = self.compile(builder, vars, body, tail);
+                vars.pop();
+                result?;
+                if !tail {
+                    builder.emit(Instr::Slide(1));
+                }
+                return Ok(());
+            }
+            erased::Expr::Fun(_, body) => {
+                // Top-level bindings are not captured.
+                let mut inner_vars: Vec<_> = (vars.iter())
+                    .map(|var| var.filter(|var| matches!(var, Var::Global(_))))
+                    .collect();
+                let mut captures = 0;
+                for var in expr.free_vars() {
+                    let level = vars.len() - var - 1;
+                    if let Some(Var::Local(slot)) = vars[level] {
+                        builder.emit(Instr::Local(slot));
+                        inner_vars[level] = Some(Var::Local(captures));
+                        captures += 1;
+                    }
+                }
+                let mut inner = FunctionBuilder {
+                    depth: captures + 1,
+                    ..FunctionBuilder::default()
+                };
+                inner_vars.push(Some(Var::Local(inner.depth - 1)));
+                self.compile(&mut inner, &mut inner_vars, body, true)?;
+
+                let function = self.push_function(inner.finish());
+                builder.emit(Instr::Closure { function, captures });
+            }
+            erased::Expr::App(fun, arg) => {
+                self.compile(builder, vars, fun, false)?;
+                self.compile(builder, vars, arg, false)?;
+                match tail {
+                    false => builder.emit(Instr::Apply),
+                    true => {
+                        builder.emit(Instr::TailApply);
+                        return Ok(());
+                    }
+                }
+            }
+            erased::Expr::Prim(prim, args) => {
+                for arg in args {
+                    self.compile(builder, vars, arg, false)?;
+                }
+                builder.emit(Instr::CallPrim(*prim));
+            }
+            erased::Expr::List(elems) => {
+                for elem in elems {
+                    self.compile(builder, vars, elem, false)?;
+                }
+                builder.emit(Instr::List(u32::truncate_from(elems.len())));
+            }
+            erased::Expr::Record(fields) => {
+                for (_, expr) in fields {
+                    self.compile(builder, vars, expr, false)?;
+                }
+                let labels = u32::truncate_from(self.program.labels.len());
+                self.program
+                    .labels
+                    .push(fields.iter().map(|(label, _)| *label).collect());
+                builder.emit_record(labels, fields.len());
+            }
+            erased::Expr::Proj(scrut, label) => {
+                self.compile(builder, vars, scrut, false)?;
+                builder.emit(Instr::Proj(*label));
+            }
+            erased::Expr::If(cond, then, r#else) => {
+                self.compile(builder, vars, cond, false)?;
+                let depth = builder.depth - 1;
+                let jump_to_else = builder.next_pc();
+                builder.emit(Instr::JumpIfFalse(0));
+
+                self.compile(builder, vars, then, tail)?;
+                let jump_to_end = builder.next_pc();
+                if !tail {
+                    builder.emit(Instr::Jump(0));
+                }
+
+                builder.patch_jump(jump_to_else);
+                builder.depth = depth;
+                self.compile(builder, vars, r#else, tail)?;
+                if !tail {
+                    builder.patch_jump(jump_to_end);
+                }
+                return Ok(());
+            }
+            erased::Expr::Switch {
+                scrut,
+                cases,
+                default,
+            } => {
+                self.compile(builder, vars, scrut, false)?;
+                let switch = u32::truncate_from(builder.switches.len());
+                builder.switches.push(Switch {
+                    cases: Box::new([]),
+                    default: 0,
+                });
+                builder.emit(Instr::Switch(switch));
+                let depth = builder.depth;
+
+                let mut targets = Vec::with_capacity(cases.len());
+                let mut jumps_to_end = Vec::with_capacity(cases.len());
+                for (int, expr) in cases {
+                    targets.push((*int, builder.next_pc()));
+                    builder.depth = depth;
+                    self.compile(builder, vars, expr, tail)?;
+                    if !tail {
+                        jumps_to_end.push(builder.next_pc());
+                        builder.emit(Instr::Jump(0));
+                    }
+                }
+                let default_target = builder.next_pc();
+                builder.depth = depth;
+                self.compile(builder, vars, default, tail)?;
+                for jump in jumps_to_end {
+                    builder.patch_jump(jump);
+                }
+
+                let switch = &mut builder.switches[usize::try_from(switch).unwrap()];
+                switch.cases = targets.into_boxed_slice();
+                switch.default = default_target;
+                return Ok(());
+            }
+        }
+
+        if tail {
+            builder.emit(Instr::Return);
+        }
+        Ok(())
+    }
+}
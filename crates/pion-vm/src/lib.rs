@@ -0,0 +1,175 @@
+//! A bytecode compiler and virtual machine for evaluating closed core
+//! expressions.
+//!
+//! [`EvalEnv::normalize`](pion_core::semantics::EvalEnv::normalize) is
+//! designed for type checking: it evaluates under binders, and builds values
+//! that can be compared and quoted. This backend only evaluates closed,
+//! well-typed expressions to runtime values, and is much faster at doing so.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use pion_core::semantics::{EvalLimits, LimitExceeded};
+use pion_core::syntax::Expr;
+use pion_erase::{Eraser, Relevance};
+
+use self::bytecode::{FunctionId, Program};
+use self::machine::{Fuel, Machine};
+
+pub mod bytecode;
+pub mod compile;
+pub mod machine;
+
+pub use self::machine::Value;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The expression contains an error.
+    IllTyped,
+    /// The expression contains an unsolved metavariable.
+    UnsolvedMeta,
+    /// A type or proof is taken apart at runtime.
+    IrrelevantUse,
+    /// Evaluation reached a value of the wrong type.
+    WrongType,
+    /// Evaluation used more resources than allowed.
+    LimitExceeded(LimitExceeded),
+}
+
+impl Error {
+    /// Whether the error can only be caused by a bug, in the elaborator or in
+    /// this backend: well-typed expressions never reach it, even if they
+    /// contain errors or unsolved metavariables.
+    pub const fn is_bug(self) -> bool {
+        match self {
+            Self::IllTyped | Self::UnsolvedMeta | Self::LimitExceeded(_) => false,
+            Self::IrrelevantUse | Self::WrongType => true,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IllTyped => write!(f, "cannot evaluate an ill-typed expression"),
+            Self::UnsolvedMeta => write!(f, "cannot evaluate an expression with unsolved holes"),
+            Self::IrrelevantUse => write!(f, "cannot use a type or proof at runtime"),
+            Self::WrongType => write!(f, "evaluation reached a value of the wrong type"),
+            Self::LimitExceeded(_) => write!(f, "evaluation limit exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<pion_erase::Error> for Error {
+    fn from(error: pion_erase::Error) -> Self {
+        match error {
+            pion_erase::Error::IllTyped => Self::IllTyped,
+            pion_erase::Error::UnsolvedMeta => Self::UnsolvedMeta,
+            pion_erase::Error::IrrelevantUse => Self::IrrelevantUse,
+        }
+    }
+}
+
+/// Compile and run `expr`, in the scope of the top-level bindings `lets`
+/// (each in scope of the bindings before it).
+pub fn eval(lets: &[Expr], expr: &Expr, limits: EvalLimits) -> Result<Value, Error> {
+    let mut env = Lets::default();
+    for rhs in lets {
+        env.push(rhs);
+    }
+    env.eval(expr, limits)
+}
+
+/// Top-level bindings, in scope of which many expressions can be evaluated.
+///
+/// Each binding is compiled once, when it is added. Its value is computed
+/// the first time an evaluated expression depends on it, and then reused.
+#[derive(Debug, Default)]
+pub struct Lets {
+    eraser: Eraser,
+    program: Program,
+    bindings: Vec<Let>,
+    /// The values of the bindings that have been computed, by level.
+    values: Vec<Option<Value>>,
+}
+
+#[derive(Debug)]
+struct Let {
+    /// The function that computes the value of the binding.
+    function: Result<FunctionId, Error>,
+    /// The levels of the earlier bindings that the binding refers to.
+    deps: Vec<usize>,
+}
+
+impl Lets {
+    pub fn len(&self) -> usize { self.bindings.len() }
+
+    pub fn is_empty(&self) -> bool { self.bindings.is_empty() }
+
+    /// Add a binding, in scope of the bindings before it.
+    pub fn push(&mut self, rhs: &Expr) {
+        let len = self.len();
+        let r#let = match self.eraser.erase(rhs) {
+            Err(error) => Let {
+                function: Err(Error::from(error)),
+                deps: Vec::new(),
+            },
+            Ok(rhs) => Let {
+                function: compile::compile_function(&mut self.program, len, &rhs),
+                deps: (rhs.free_vars().into_iter())
+                    .map(|var| len - var - 1)
+                    .collect(),
+            },
+        };
+        self.eraser.push_local(Relevance::Relevant);
+        self.bindings.push(r#let);
+        self.values.push(None);
+    }
+
+    /// Remove the bindings after the first `len`.
+    pub fn truncate(&mut self, len: usize) {
+        while self.len() > len {
+            self.eraser.pop_local();
+            self.bindings.pop();
+            self.values.pop();
+        }
+    }
+
+    /// Compile and run `expr`, in the scope of the bindings. Computing the
+    /// values of the bindings it depends on counts towards `limits`.
+    pub fn eval(&mut self, expr: &Expr, limits: EvalLimits) -> Result<Value, Error> {
+        let len = self.len();
+        let expr = self.eraser.erase(expr)?;
+        let main = compile::compile_function(&mut self.program, len, &expr)?;
+        let mut fuel = Fuel::new(limits);
+        let levels = expr.free_vars().into_iter().map(|var| len - var - 1);
+        self.force(levels, &mut fuel)?;
+        Machine::new(&self.program, &self.values).run(main, &mut fuel)
+    }
+
+    /// Compute the values of the bindings at `levels`, and of the bindings
+    /// they depend on.
+    fn force(
+        &mut self,
+        levels: impl IntoIterator<Item = usize>,
+        fuel: &mut Fuel,
+    ) -> Result<(), Error> {
+        let mut pending: Vec<_> = levels.into_iter().collect();
+        let mut needed = BTreeSet::new();
+        while let Some(level) = pending.pop() {
+            if self.values[level].is_none() && needed.insert(level) {
+                pending.extend(&self.bindings[level].deps);
+            }
+        }
+
+        // Each binding only depends on bindings at lower levels.
+        for level in needed {
+            let function = self.bindings[level].function?;
+            let value = Machine::new(&self.program, &self.values).run(function, fuel)?;
+            self.values[level] = Some(value);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,373 @@
+//! A stack machine that executes bytecode.
+
+use std::rc::Rc;
+
+use ecow::EcoVec;
+use pion_core::prim::Prim;
+use pion_core::semantics::{EvalLimits, LimitExceeded};
+use pion_core::syntax::{Expr, Lit};
+use pion_erase::syntax::prim_arity;
+use pion_symbol::Symbol;
+use pion_util::collect_in::CollectIn;
+use pion_util::numeric_conversions::TruncateFrom;
+
+use crate::bytecode::{FunctionId, Instr, Program};
+use crate::Error;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(u32),
+    Bool(bool),
+    /// A value with no runtime content: a type, or a proof.
+    Erased,
+    List(EcoVec<Self>),
+    Record(Rc<[(Symbol, Self)]>),
+    /// A function, and the values of the variables it captures.
+    Closure(FunctionId, Rc<[Self]>),
+    /// `fix @A @B f`.
+    Fix(Rc<Self>),
+}
+
+impl Value {
+    /// Convert the value back to a core expression, if it is first-order data
+    /// (functions and types cannot be converted back).
+    pub fn to_expr<'core>(&self, bump: &'core bumpalo::Bump) -> Option<Expr<'core>> {
+        match self {
+            Self::Int(int) => Some(Expr::Lit(Lit::Int(*int))),
+            Self::Bool(bool) => Some(Expr::Lit(Lit::Bool(*bool))),
+            Self::List(elems) => {
+                let elems: Option<Vec<_>> = elems.iter().map(|elem| elem.to_expr(bump)).collect();
+                Some(Expr::ListLit(elems?.into_iter().collect_in(bump)))
+            }
+            Self::Record(fields) => {
+                let fields: Option<Vec<_>> = (fields.iter())
+                    .map(|(label, value)| Some((*label, value.to_expr(bump)?)))
+                    .collect();
+                Some(Expr::RecordLit(fields?.into_iter().collect_in(bump)))
+            }
+            Self::Erased | Self::Closure(..) | Self::Fix(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Frame {
+    function: FunctionId,
+    pc: usize,
+    /// The index of the frame's first slot in the value stack.
+    base: usize,
+}
+
+/// The resources used by runs of a [`Machine`], checked against
+/// [`EvalLimits`]. A step is a call to a function or to a primitive, and the
+/// arena is the memory allocated for values. Frames live on the heap rather
+/// than the native stack, so their depth is only bounded by the steps it takes
+/// to push them, not by [`EvalLimits::max_depth`].
+#[derive(Debug)]
+pub struct Fuel {
+    limits: EvalLimits,
+    steps: u64,
+    allocated_bytes: usize,
+}
+
+impl Fuel {
+    pub const fn new(limits: EvalLimits) -> Self {
+        Self {
+            limits,
+            steps: 0,
+            allocated_bytes: 0,
+        }
+    }
+
+    fn step(&mut self) -> Result<(), Error> {
+        self.steps += 1;
+        match self.steps > self.limits.max_steps {
+            true => Err(Error::LimitExceeded(LimitExceeded::Steps(
+                self.limits.max_steps,
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    /// Account for the allocation of `len` values.
+    fn alloc(&mut self, len: usize) -> Result<(), Error> {
+        let bytes = len.saturating_mul(size_of::<Value>());
+        self.allocated_bytes = self.allocated_bytes.saturating_add(bytes);
+        match self.allocated_bytes > self.limits.max_arena_bytes {
+            true => Err(Error::LimitExceeded(LimitExceeded::ArenaBytes(
+                self.limits.max_arena_bytes,
+            ))),
+            false => Ok(()),
+        }
+    }
+}
+
+pub struct Machine<'program> {
+    program: &'program Program,
+    /// The values of the top-level bindings, by level. Only those that the
+    /// code being run refers to need to be computed.
+    globals: &'program [Option<Value>],
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+impl<'program> Machine<'program> {
+    pub const fn new(program: &'program Program, globals: &'program [Option<Value>]) -> Self {
+        Self {
+            program,
+            globals,
+            stack: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Run `main`, a function with no arguments, to completion, or until it
+    /// runs out of `fuel`.
+    pub fn run(&mut self, main: FunctionId, fuel: &mut Fuel) -> Result<Value, Error> {
+        self.stack.clear();
+        self.frames.clear();
+        self.frames.push(Frame {
+            function: main,
+            pc: 0,
+            base: 0,
+        });
+
+        let program = self.program;
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let function = &program.functions[usize::try_from(frame.function).unwrap()];
+            let instr = function.code[frame.pc];
+            frame.pc += 1;
+            let base = frame.base;
+
+            match instr {
+                Instr::Int(int) => self.stack.push(Value::Int(int)),
+                Instr::Bool(bool) => self.stack.push(Value::Bool(bool)),
+                Instr::Erased => self.stack.push(Value::Erased),
+                Instr::Local(slot) => {
+                    let value = self.stack[base + usize::try_from(slot).unwrap()].clone();
+                    self.stack.push(value);
+                }
+                Instr::Global(level) => {
+                    let value = &self.globals[usize::try_from(level).unwrap()];
+                    let value = value.clone().expect("top-level binding not computed");
+                    self.stack.push(value);
+                }
+                Instr::Closure { function, captures } => {
+                    fuel.alloc(usize::try_from(captures).unwrap())?;
+                    let start = self.stack.len() - usize::try_from(captures).unwrap();
+                    let captures = self.stack.drain(start..).collect();
+                    self.stack.push(Value::Closure(function, captures));
+                }
+
+                Instr::Apply | Instr::TailApply => {
+                    fuel.step()?;
+                    let arg = self.pop();
+                    let fun = self.pop();
+                    if let Some(value) = self.apply(fun, arg, instr == Instr::TailApply)? {
+                        return Ok(value);
+                    }
+                }
+                Instr::CallPrim(Prim::fix) => {
+                    fuel.step()?;
+                    fuel.alloc(1)?;
+                    let f = self.pop();
+                    self.stack.push(Value::Fix(Rc::new(f)));
+                }
+                Instr::CallPrim(prim) => {
+                    fuel.step()?;
+                    let start = self.stack.len() - prim_arity(prim);
+                    if let Some(len) = prim_alloc_len(prim, &self.stack[start..]) {
+                        fuel.alloc(len)?;
+                    }
+                    let value = prim_app(prim, &self.stack[start..])?;
+                    self.stack.truncate(start);
+                    self.stack.push(value);
+                }
+                Instr::Return => {
+                    let value = self.pop();
+                    if let Some(value) = self.ret(value) {
+                        return Ok(value);
+                    }
+                }
+
+                Instr::Slide(n) => {
+                    let value = self.pop();
+                    self.stack
+                        .truncate(self.stack.len() - usize::try_from(n).unwrap());
+                    self.stack.push(value);
+                }
+                Instr::Swap => {
+                    let len = self.stack.len();
+                    self.stack.swap(len - 1, len - 2);
+                }
+
+                Instr::Jump(pc) => self.jump(pc),
+                Instr::JumpIfFalse(pc) => match self.stack.pop() {
+                    Some(Value::Bool(true)) => {}
+                    Some(Value::Bool(false)) => self.jump(pc),
+                    _ => return Err(Error::WrongType),
+                },
+                Instr::Switch(switch) => {
+                    let Some(Value::Int(int)) = self.stack.pop() else {
+                        return Err(Error::WrongType);
+                    };
+                    let switch = &function.switches[usize::try_from(switch).unwrap()];
+                    self.jump(switch.target(int));
+                }
+
+                Instr::List(len) => {
+                    fuel.alloc(usize::try_from(len).unwrap())?;
+                    let start = self.stack.len() - usize::try_from(len).unwrap();
+                    let elems = self.stack.drain(start..).collect();
+                    self.stack.push(Value::List(elems));
+                }
+                Instr::Record(labels) => {
+                    let labels = &program.labels[usize::try_from(labels).unwrap()];
+                    fuel.alloc(labels.len())?;
+                    let start = self.stack.len() - labels.len();
+                    let values = self.stack.drain(start..);
+                    let fields = labels.iter().copied().zip(values).collect();
+                    self.stack.push(Value::Record(fields));
+                }
+                Instr::Proj(label) => {
+                    let value = match self.pop() {
+                        Value::Record(fields) => (fields.iter())
+                            .find(|(field, _)| *field == label)
+                            .map(|(_, value)| value.clone())
+                            .ok_or(Error::WrongType)?,
+                        Value::Erased => Value::Erased,
+                        _ => return Err(Error::WrongType),
+                    };
+                    self.stack.push(value);
+                }
+            }
+        }
+    }
+
+    fn jump(&mut self, pc: u32) {
+        self.frames.last_mut().unwrap().pc = usize::try_from(pc).unwrap();
+    }
+
+    fn pop(&mut self) -> Value { self.stack.pop().expect("value stack underflow") }
+
+    /// Return `value` from the current frame. Returns the value if it was
+    /// returned from the main function.
+    fn ret(&mut self, value: Value) -> Option<Value> {
+        let frame = self.frames.pop().unwrap();
+        self.stack.truncate(frame.base);
+        match self.frames.is_empty() {
+            true => Some(value),
+            false => {
+                self.stack.push(value);
+                None
+            }
+        }
+    }
+
+    /// Push a frame that calls `function` with `args` as its first slots, or
+    /// replace the current frame if this is a `tail` call.
+    fn call(&mut self, function: FunctionId, args: impl IntoIterator<Item = Value>, tail: bool) {
+        if tail {
+            let frame = self.frames.last_mut().unwrap();
+            self.stack.truncate(frame.base);
+            self.stack.extend(args);
+            frame.function = function;
+            frame.pc = 0;
+        } else {
+            let base = self.stack.len();
+            self.stack.extend(args);
+            self.frames.push(Frame {
+                function,
+                pc: 0,
+                base,
+            });
+        }
+    }
+
+    /// Apply `fun` to `arg`. The result is pushed onto the stack, or returned
+    /// from the current frame if this is a `tail` call.
+    fn apply(&mut self, fun: Value, arg: Value, tail: bool) -> Result<Option<Value>, Error> {
+        let value = match fun {
+            Value::Closure(function, captures) => {
+                let captures = captures.iter().cloned();
+                self.call(function, captures.chain([arg]), tail);
+                return Ok(None);
+            }
+            Value::Fix(f) => {
+                self.call_fix(f, arg, tail);
+                return Ok(None);
+            }
+            // The application of a type family.
+            Value::Erased => Value::Erased,
+            Value::Int(_) | Value::Bool(_) | Value::List(_) | Value::Record(_) => {
+                return Err(Error::WrongType)
+            }
+        };
+
+        Ok(self.push_result(value, tail))
+    }
+
+    /// Call `fix f x`.
+    fn call_fix(&mut self, f: Rc<Value>, x: Value, tail: bool) {
+        let args = [x, Value::clone(&f), Value::Fix(f)];
+        self.call(Program::FIX, args, tail);
+    }
+
+    /// Push `value` onto the stack, or return it from the current frame if
+    /// `tail` is set.
+    fn push_result(&mut self, value: Value, tail: bool) -> Option<Value> {
+        match tail {
+            true => self.ret(value),
+            false => {
+                self.stack.push(value);
+                None
+            }
+        }
+    }
+}
+
+/// The number of values allocated by applying `prim` to `args`, if it
+/// allocates.
+fn prim_alloc_len(prim: Prim, args: &[Value]) -> Option<usize> {
+    match (prim, args) {
+        (Prim::push, [Value::List(list), _]) => Some(list.len() + 1),
+        (Prim::append, [Value::List(lhs), Value::List(rhs)]) => Some(lhs.len() + rhs.len()),
+        _ => None,
+    }
+}
+
+/// Compute the result of a primitive function applied to its relevant
+/// arguments.
+fn prim_app(prim: Prim, args: &[Value]) -> Result<Value, Error> {
+    let value = match (prim, args) {
+        (Prim::len, [Value::List(list)]) => Value::Int(u32::truncate_from(list.len())),
+        (Prim::push, [Value::List(list), elem]) => {
+            let mut list = list.clone();
+            list.push(elem.clone());
+            Value::List(list)
+        }
+        (Prim::append, [Value::List(lhs), Value::List(rhs)]) => {
+            let mut list = lhs.clone();
+            list.extend_from_slice(rhs);
+            Value::List(list)
+        }
+        (Prim::bool_rec, [Value::Bool(true), then, _]) => then.clone(),
+        (Prim::bool_rec, [Value::Bool(false), _, r#else]) => r#else.clone(),
+
+        (Prim::add, [Value::Int(lhs), Value::Int(rhs)]) => Value::Int(lhs.wrapping_add(*rhs)),
+        (Prim::sub, [Value::Int(lhs), Value::Int(rhs)]) => Value::Int(lhs.wrapping_sub(*rhs)),
+        (Prim::mul, [Value::Int(lhs), Value::Int(rhs)]) => Value::Int(lhs.wrapping_mul(*rhs)),
+        (Prim::eq, [Value::Int(lhs), Value::Int(rhs)]) => Value::Bool(lhs == rhs),
+        (Prim::ne, [Value::Int(lhs), Value::Int(rhs)]) => Value::Bool(lhs != rhs),
+        (Prim::gt, [Value::Int(lhs), Value::Int(rhs)]) => Value::Bool(lhs > rhs),
+        (Prim::lt, [Value::Int(lhs), Value::Int(rhs)]) => Value::Bool(lhs < rhs),
+        (Prim::gte, [Value::Int(lhs), Value::Int(rhs)]) => Value::Bool(lhs >= rhs),
+        (Prim::lte, [Value::Int(lhs), Value::Int(rhs)]) => Value::Bool(lhs <= rhs),
+
+        // The proof is erased, but closed proofs evaluate to `refl`.
+        (Prim::subst, [px]) => px.clone(),
+        _ => return Err(Error::WrongType),
+    };
+    Ok(value)
+}
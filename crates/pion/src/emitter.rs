@@ -0,0 +1,254 @@
+//! Reporting diagnostics to the user.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use codespan_reporting::diagnostic::LabelStyle;
+use codespan_reporting::files::{Files, SimpleFiles};
+use codespan_reporting::term::termcolor::StandardStream;
+use pion_diagnostic::lints::{self, LintLevel};
+use pion_diagnostic::{Diagnostic, DiagnosticHandler, Severity, Suggestion};
+
+/// How diagnostics are printed.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// Human readable messages, with source snippets.
+    #[default]
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Options controlling which diagnostics are reported, and at what severity.
+#[derive(Debug, Clone, Default)]
+pub struct EmitterOpts {
+    /// Report all warnings as errors, unless the lint was explicitly
+    /// configured.
+    pub deny_warnings: bool,
+    /// Maximum number of errors to print.
+    pub error_limit: Option<usize>,
+    /// Levels of lints explicitly configured by the user, keyed by diagnostic
+    /// code.
+    pub lint_levels: HashMap<&'static str, LintLevel>,
+    /// How diagnostics are printed.
+    pub message_format: MessageFormat,
+}
+
+/// A [`DiagnosticHandler`] that prints diagnostics to the terminal, counting
+/// them by severity.
+pub struct DiagnosticEmitter<'files> {
+    writer: StandardStream,
+    files: &'files SimpleFiles<String, String>,
+    opts: EmitterOpts,
+
+    error_count: usize,
+    warning_count: usize,
+    suppressed_error_count: usize,
+    suggestions: Vec<Suggestion>,
+}
+
+impl<'files> DiagnosticEmitter<'files> {
+    pub const fn new(
+        writer: StandardStream,
+        files: &'files SimpleFiles<String, String>,
+        opts: EmitterOpts,
+    ) -> Self {
+        Self {
+            writer,
+            files,
+            opts,
+
+            error_count: 0,
+            warning_count: 0,
+            suppressed_error_count: 0,
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub const fn error_count(&self) -> usize { self.error_count }
+
+    /// The suggested fixes attached to all reported diagnostics.
+    pub fn suggestions(&self) -> &[Suggestion] { &self.suggestions }
+
+    /// Apply the user-configured lint levels to `diagnostic`, returning `None`
+    /// if the diagnostic should not be reported.
+    fn apply_lint_level(&self, mut diagnostic: Diagnostic<usize>) -> Option<Diagnostic<usize>> {
+        let lint = (diagnostic.code.as_deref()).and_then(lints::Lint::from_code);
+
+        let level = match lint {
+            Some(lint) => match self.opts.lint_levels.get(lint.code) {
+                Some(level) => *level,
+                None if self.opts.deny_warnings && lint.default_level == LintLevel::Warn => {
+                    LintLevel::Deny
+                }
+                None => lint.default_level,
+            },
+            None if self.opts.deny_warnings && diagnostic.severity == Severity::Warning => {
+                LintLevel::Deny
+            }
+            None => return Some(diagnostic),
+        };
+
+        diagnostic.severity = match level {
+            LintLevel::Allow => return None,
+            LintLevel::Warn => Severity::Warning,
+            LintLevel::Deny => Severity::Error,
+        };
+        Some(diagnostic)
+    }
+
+    fn emit(&mut self, diagnostic: &Diagnostic<usize>, suggestions: &[Suggestion]) {
+        match self.opts.message_format {
+            MessageFormat::Human => {
+                let config = codespan_reporting::term::Config::default();
+                codespan_reporting::term::emit(&mut self.writer, &config, self.files, diagnostic)
+                    .expect("Could not print diagnostic");
+            }
+            MessageFormat::Json => {
+                let json = diagnostic_to_json(self.files, diagnostic, suggestions);
+                writeln!(self.writer, "{json}").expect("Could not print diagnostic");
+            }
+        }
+    }
+
+    /// Report how many errors and warnings were emitted, including any that
+    /// were not printed due to the error limit.
+    pub fn finish(&mut self, name: &str) {
+        if self.opts.message_format == MessageFormat::Json {
+            return;
+        }
+
+        if self.suppressed_error_count != 0 {
+            let diagnostic = Diagnostic::note().with_message(format!(
+                "{} more {} not shown due to `--error-limit`",
+                self.suppressed_error_count,
+                pluralize(self.suppressed_error_count, "error was", "errors were"),
+            ));
+            self.emit(&diagnostic, &[]);
+        }
+
+        let warnings = match self.warning_count {
+            0 => String::new(),
+            count => format!(
+                "; {count} {} emitted",
+                pluralize(count, "warning", "warnings")
+            ),
+        };
+        let diagnostic = match self.error_count {
+            0 if self.warning_count == 0 => return,
+            0 => Diagnostic::warning().with_message(format!(
+                "`{name}` generated {} {}",
+                self.warning_count,
+                pluralize(self.warning_count, "warning", "warnings")
+            )),
+            count => Diagnostic::error().with_message(format!(
+                "could not check `{name}` due to {count} previous {}{warnings}",
+                pluralize(count, "error", "errors"),
+            )),
+        };
+        self.emit(&diagnostic, &[]);
+    }
+}
+
+impl<'files> DiagnosticHandler for DiagnosticEmitter<'files> {
+    fn handle_diagnostic(&mut self, diagnostic: Diagnostic<usize>) {
+        self.handle_diagnostic_with_suggestions(diagnostic, Vec::new());
+    }
+
+    fn handle_diagnostic_with_suggestions(
+        &mut self,
+        diagnostic: Diagnostic<usize>,
+        suggestions: Vec<Suggestion>,
+    ) {
+        let Some(diagnostic) = self.apply_lint_level(diagnostic) else {
+            return;
+        };
+
+        match diagnostic.severity {
+            Severity::Bug | Severity::Error => {
+                self.error_count += 1;
+                if let Some(limit) = self.opts.error_limit {
+                    if self.error_count > limit {
+                        // The fixes of diagnostics that are not shown are
+                        // still applied by `--fix`.
+                        self.suppressed_error_count += 1;
+                        self.suggestions.extend(suggestions);
+                        return;
+                    }
+                }
+            }
+            Severity::Warning => self.warning_count += 1,
+            Severity::Note | Severity::Help => {}
+        }
+
+        self.emit(&diagnostic, &suggestions);
+        self.suggestions.extend(suggestions);
+    }
+}
+
+/// Convert `diagnostic` to a JSON object, with the file name and line/column
+/// numbers of each label resolved.
+fn diagnostic_to_json(
+    files: &SimpleFiles<String, String>,
+    diagnostic: &Diagnostic<usize>,
+    suggestions: &[Suggestion],
+) -> serde_json::Value {
+    let severity = match diagnostic.severity {
+        Severity::Bug => "bug",
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    };
+
+    let labels = (diagnostic.labels.iter())
+        .map(|label| {
+            let style = match label.style {
+                LabelStyle::Primary => "primary",
+                LabelStyle::Secondary => "secondary",
+            };
+            let file = files.name(label.file_id).ok();
+            let start = files.location(label.file_id, label.range.start).ok();
+            let end = files.location(label.file_id, label.range.end).ok();
+            serde_json::json!({
+                "style": style,
+                "file": file,
+                "start": label.range.start,
+                "end": label.range.end,
+                "line_start": start.map(|loc| loc.line_number),
+                "column_start": start.map(|loc| loc.column_number),
+                "line_end": end.map(|loc| loc.line_number),
+                "column_end": end.map(|loc| loc.column_number),
+                "message": label.message,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let suggestions = (suggestions.iter())
+        .map(|suggestion| {
+            serde_json::json!({
+                "file": files.name(suggestion.file_id).ok(),
+                "start": suggestion.range.start,
+                "end": suggestion.range.end,
+                "replacement": suggestion.replacement,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::json!({
+        "severity": severity,
+        "code": diagnostic.code,
+        "message": diagnostic.message,
+        "labels": labels,
+        "notes": diagnostic.notes,
+        "suggestions": suggestions,
+    })
+}
+
+const fn pluralize<'a>(count: usize, singular: &'a str, plural: &'a str) -> &'a str {
+    if count == 1 {
+        singular
+    } else {
+        plural
+    }
+}
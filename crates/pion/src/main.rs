@@ -1,12 +1,228 @@
-use std::io::{IsTerminal, Read};
+use std::io::{IsTerminal, Read, Write};
+use std::process::ExitCode;
 
 use camino::Utf8PathBuf;
 use clap::Parser;
+use emitter::{DiagnosticEmitter, EmitterOpts, MessageFormat};
+use pion_core::optimize::OptLevel;
+use pion_core::semantics::EvalLimits;
+use pion_core::syntax::Item;
+use pion_diagnostic::codes::Code;
+use pion_diagnostic::lints::{Lint, LintLevel};
+use pion_elab::command::{CommandHandler, EvalBackend};
 use pion_util::numeric_conversions::ZeroExtendFrom;
 
+mod emitter;
+mod watch;
+
 #[derive(Parser)]
 pub enum Cli {
-    Check { path: PathOrStdin },
+    /// Parse and elaborate a file, reporting any diagnostics.
+    Check(CheckArgs),
+    /// Check a file, and print the value of its result expression. `#eval`
+    /// commands and the result are evaluated with the bytecode virtual
+    /// machine.
+    Run(CheckArgs),
+    /// Check a file, and report how many of its assertions (`#assert_eq`,
+    /// `#assert_type` and `#fail` commands) passed and failed.
+    Test(TestArgs),
+    /// Check a file, and compile it to a program that prints the value of
+    /// each `#eval` command and of its result expression.
+    Compile(CompileArgs),
+    /// Print a detailed explanation of a diagnostic code.
+    Explain {
+        /// The diagnostic code, eg `E0003`.
+        #[arg(value_parser = parse_code)]
+        code: Code,
+    },
+}
+
+#[derive(clap::Args)]
+pub struct CheckArgs {
+    path: PathOrStdin,
+
+    #[command(flatten)]
+    diagnostics: DiagnosticArgs,
+
+    /// Apply suggested fixes to the file. When checking stdin, the fixed
+    /// text is printed instead of the output of commands.
+    #[arg(long)]
+    fix: bool,
+
+    /// Check the file again whenever it changes.
+    #[arg(long, conflicts_with = "fix")]
+    watch: bool,
+
+    /// Re-check the elaborated program with the core type checker, failing
+    /// if the elaborator produced an ill-typed term.
+    #[arg(long)]
+    verify_core: bool,
+
+    /// Print an intermediate representation of the checked file.
+    #[arg(long, value_enum, value_name = "IR", conflicts_with = "watch")]
+    emit: Option<Emit>,
+
+    /// How much to optimize programs before running or compiling them: `0`
+    /// (not at all), `1` (simplify) or `2` (simplify and inline).
+    #[arg(long, value_name = "LEVEL", value_parser = parse_opt_level, default_value = "2")]
+    opt_level: OptLevel,
+
+    #[command(flatten)]
+    eval_limits: EvalLimitArgs,
+
+    /// Save the results of checking files without diagnostics in `DIR`, and
+    /// reuse them instead of elaborating a file whose text has not changed.
+    #[arg(long, value_name = "DIR", conflicts_with = "watch")]
+    cache_dir: Option<Utf8PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct TestArgs {
+    path: PathOrStdin,
+
+    #[command(flatten)]
+    diagnostics: DiagnosticArgs,
+
+    #[command(flatten)]
+    eval_limits: EvalLimitArgs,
+}
+
+#[derive(clap::Args)]
+pub struct CompileArgs {
+    path: PathOrStdin,
+
+    #[command(flatten)]
+    diagnostics: DiagnosticArgs,
+
+    /// The language to compile to.
+    #[arg(long, value_enum)]
+    target: Target,
+
+    /// Write the compiled program to `PATH`, instead of printing it.
+    #[arg(short = 'o', long = "output", value_name = "PATH")]
+    output: Option<Utf8PathBuf>,
+
+    /// How much to optimize programs before running or compiling them: `0`
+    /// (not at all), `1` (simplify) or `2` (simplify and inline).
+    #[arg(long, value_name = "LEVEL", value_parser = parse_opt_level, default_value = "2")]
+    opt_level: OptLevel,
+
+    #[command(flatten)]
+    eval_limits: EvalLimitArgs,
+}
+
+/// Limits on evaluation during type checking and `#eval`, which apply to each
+/// top-level item separately.
+#[derive(clap::Args)]
+pub struct EvalLimitArgs {
+    /// Stop evaluating after `N` steps.
+    #[arg(long = "max-eval-steps", value_name = "N", default_value_t = EvalLimits::default().max_steps)]
+    steps: u64,
+
+    /// Stop evaluating when it nests more than `N` levels deep.
+    #[arg(long = "max-eval-depth", value_name = "N", default_value_t = EvalLimits::default().max_depth)]
+    depth: u32,
+
+    /// Stop evaluating after allocating `BYTES` bytes.
+    #[arg(long = "max-arena-size", value_name = "BYTES", default_value_t = EvalLimits::default().max_arena_bytes)]
+    arena_size: usize,
+}
+
+impl EvalLimitArgs {
+    const fn limits(&self) -> EvalLimits {
+        EvalLimits {
+            max_steps: self.steps,
+            max_depth: self.depth,
+            max_arena_bytes: self.arena_size,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum Emit {
+    /// The elaborated core of each binding and evaluated expression, after
+    /// optimization, in the textual format of `pion_core::text`.
+    Core,
+}
+
+#[derive(Copy, Clone, clap::ValueEnum)]
+pub enum Target {
+    /// A single C source file.
+    C,
+    /// A WebAssembly module, exporting the top-level functions whose
+    /// parameters and results are all `Int`s or `Bool`s.
+    Wasm,
+}
+
+#[derive(clap::Args)]
+pub struct DiagnosticArgs {
+    /// Report all warnings as errors.
+    #[arg(long)]
+    deny_warnings: bool,
+
+    /// Stop printing errors after `N` errors have been reported.
+    #[arg(long, value_name = "N")]
+    error_limit: Option<usize>,
+
+    /// Do not report the lint with the diagnostic code `CODE`.
+    #[arg(short = 'A', long = "allow", value_name = "CODE", value_parser = parse_lint)]
+    allow: Vec<Lint>,
+
+    /// Report the lint with the diagnostic code `CODE` as a warning.
+    #[arg(short = 'W', long = "warn", value_name = "CODE", value_parser = parse_lint)]
+    warn: Vec<Lint>,
+
+    /// Report the lint with the diagnostic code `CODE` as an error.
+    #[arg(short = 'D', long = "deny", value_name = "CODE", value_parser = parse_lint)]
+    deny: Vec<Lint>,
+
+    /// How diagnostics are printed.
+    #[arg(long, value_enum, default_value_t)]
+    message_format: MessageFormat,
+}
+
+impl DiagnosticArgs {
+    /// If a lint is configured more than once, `--deny` takes precedence over
+    /// `--warn`, which takes precedence over `--allow`.
+    fn emitter_opts(&self) -> EmitterOpts {
+        let mut lint_levels = std::collections::HashMap::new();
+        let levels = [
+            (&self.allow, LintLevel::Allow),
+            (&self.warn, LintLevel::Warn),
+            (&self.deny, LintLevel::Deny),
+        ];
+        for (lints, level) in levels {
+            for lint in lints {
+                lint_levels.insert(lint.code, level);
+            }
+        }
+
+        EmitterOpts {
+            deny_warnings: self.deny_warnings,
+            error_limit: self.error_limit,
+            lint_levels,
+            message_format: self.message_format,
+        }
+    }
+}
+
+fn parse_lint(code: &str) -> Result<Lint, String> {
+    Lint::from_code(code).ok_or_else(|| format!("unknown lint code `{code}`"))
+}
+
+fn parse_opt_level(level: &str) -> Result<OptLevel, String> {
+    match level {
+        "0" => Ok(OptLevel::None),
+        "1" => Ok(OptLevel::Simplify),
+        "2" => Ok(OptLevel::Inline),
+        _ => Err(format!(
+            "unknown optimization level `{level}`, expected 0, 1 or 2"
+        )),
+    }
+}
+
+fn parse_code(code: &str) -> Result<Code, String> {
+    Code::from_code(code).ok_or_else(|| format!("unknown diagnostic code `{code}`"))
 }
 
 #[derive(Clone, Debug)]
@@ -45,46 +261,228 @@ impl PathOrStdin {
     }
 }
 
-fn main() -> std::io::Result<()> {
+/// Check the text in `db`, printing diagnostics (and command output, if
+/// `print_output` is set).
+fn check(
+    args: &DiagnosticArgs,
+    name: &str,
+    db: &mut pion_db::Database,
+    print_output: bool,
+) -> CheckResult {
+    let color = match std::io::stderr().is_terminal() {
+        true => codespan_reporting::term::termcolor::ColorChoice::Auto,
+        false => codespan_reporting::term::termcolor::ColorChoice::Never,
+    };
+    let writer = codespan_reporting::term::termcolor::StandardStream::stderr(color);
+    let mut files = codespan_reporting::files::SimpleFiles::new();
+    let file_id = files.add(name.to_owned(), db.text().to_owned());
+    debug_assert_eq!(file_id, db.file_id());
+
+    let mut diagnostic_handler = DiagnosticEmitter::new(writer, &files, args.emitter_opts());
+    let mut command_handler = CommandPrinter {
+        print_output,
+        passed: 0,
+        failed: 0,
+    };
+
+    for event in db.check() {
+        event.report(&mut diagnostic_handler, &mut command_handler);
+    }
+    diagnostic_handler.finish(name);
+
+    CheckResult {
+        error_count: diagnostic_handler.error_count(),
+        suggestions: diagnostic_handler.suggestions().to_vec(),
+        passed: command_handler.passed,
+        failed: command_handler.failed,
+    }
+}
+
+/// Prints the output of commands, and counts the assertions that passed and
+/// failed.
+struct CommandPrinter {
+    print_output: bool,
+    passed: usize,
+    failed: usize,
+}
+
+impl CommandHandler for CommandPrinter {
+    fn display_to_user(&mut self, text: String) {
+        if self.print_output {
+            println!("{text}");
+        }
+    }
+
+    fn assertion_result(&mut self, passed: bool) {
+        match passed {
+            true => self.passed += 1,
+            false => self.failed += 1,
+        }
+    }
+}
+
+struct CheckResult {
+    error_count: usize,
+    suggestions: Vec<pion_diagnostic::Suggestion>,
+    /// The number of assertions that passed.
+    passed: usize,
+    /// The number of assertions that failed.
+    failed: usize,
+}
+
+fn read_text(path: &PathOrStdin) -> std::io::Result<String> {
+    let text = path.read()?;
+    if text.len() >= usize::zext_from(u32::MAX) {
+        return Err(std::io::Error::other("input too big"));
+    }
+    Ok(text)
+}
+
+fn main() -> std::io::Result<ExitCode> {
     let command = Cli::parse();
     match &command {
-        Cli::Check { path } => {
-            let color = match std::io::stderr().is_terminal() {
-                true => codespan_reporting::term::termcolor::ColorChoice::Auto,
-                false => codespan_reporting::term::termcolor::ColorChoice::Never,
-            };
-            let mut writer = codespan_reporting::term::termcolor::StandardStream::stderr(color);
-            let mut files = codespan_reporting::files::SimpleFiles::new();
+        Cli::Check(args) => check_command(args, false),
+        Cli::Run(args) => check_command(args, true),
+        Cli::Test(args) => test_command(args),
+        Cli::Compile(args) => compile_command(args),
+        Cli::Explain { code } => {
+            println!("{code}: {}\n", code.title);
+            print!("{}", code.explanation);
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
 
-            let bump = bumpalo::Bump::new();
-            let text = path.read()?;
-            if text.len() >= usize::zext_from(u32::MAX) {
-                return Err(std::io::Error::other("input too big"));
-            }
-            let file_id = files.add(path.name(), text.clone());
+/// Create a database for checking `text` (or running it, if `run` is set).
+fn new_database(
+    bump: &bumpalo::Bump,
+    text: String,
+    run: bool,
+    opt_level: OptLevel,
+    eval_limits: EvalLimits,
+) -> pion_db::Database {
+    let mut db = pion_db::Database::new(bump, 0, text);
+    db.set_opt_level(opt_level);
+    db.set_eval_limits(eval_limits);
+    if run {
+        db.set_eval_backend(EvalBackend::Bytecode);
+        db.set_run(true);
+    }
+    db
+}
 
-            let mut diagnostic_handler = |diagnostic| {
-                let config = codespan_reporting::term::Config::default();
-                codespan_reporting::term::emit(&mut writer, &config, &files, &diagnostic)
-                    .expect("Could not print diagnostic");
-            };
+fn check_command(args: &CheckArgs, run: bool) -> std::io::Result<ExitCode> {
+    let path = &args.path;
+    if args.watch {
+        return match path {
+            PathOrStdin::Path(path) => watch::watch(args, path, run),
+            PathOrStdin::Stdin => Err(std::io::Error::other("cannot watch stdin")),
+        };
+    }
+
+    let bump = bumpalo::Bump::new();
+    let text = read_text(path)?;
+    let limits = args.eval_limits.limits();
+    let mut db = new_database(&bump, text.clone(), run, args.opt_level, limits);
+    db.set_cache(args.cache_dir.as_ref().map(pion_db::Cache::new));
+    // The fixed text of stdin is printed to stdout, so nothing else can be.
+    let print_output = !(args.fix && matches!(path, PathOrStdin::Stdin));
+    let result = check(&args.diagnostics, path.name(), &mut db, print_output);
+    let verified = !args.verify_core || result.error_count != 0 || verify_core(&bump, &mut db);
+
+    if print_output && args.emit == Some(Emit::Core) && result.error_count == 0 {
+        print!("{}", pion_core::text::print_items(&bump, &db.core_items()));
+    }
+
+    if args.fix {
+        let suggestions = (result.suggestions.iter()).filter(|s| s.file_id == db.file_id());
+        let fixed = pion_diagnostic::apply_suggestions(&text, suggestions);
+        match path {
+            PathOrStdin::Path(path) if fixed != text => std::fs::write(path, fixed)?,
+            PathOrStdin::Path(_) => {}
+            PathOrStdin::Stdin => print!("{fixed}"),
+        }
+    }
 
-            let mut command_handler = |text| {
-                println!("{text}");
+    match result.error_count {
+        0 if verified => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::FAILURE),
+    }
+}
+
+fn test_command(args: &TestArgs) -> std::io::Result<ExitCode> {
+    let path = &args.path;
+    let bump = bumpalo::Bump::new();
+    let text = read_text(path)?;
+    let limits = args.eval_limits.limits();
+    let mut db = new_database(&bump, text, false, OptLevel::default(), limits);
+    let result = check(&args.diagnostics, path.name(), &mut db, false);
+
+    // Errors outside of assertions also fail the test, since they may hide
+    // assertions that were never run.
+    let outcome = match result.error_count {
+        0 => "ok",
+        _ => "FAILED",
+    };
+    println!(
+        "test result: {outcome}. {} passed; {} failed",
+        result.passed, result.failed
+    );
+    match result.error_count {
+        0 => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::FAILURE),
+    }
+}
+
+/// Check the elaborated items of `db` with the core type checker, reporting
+/// the first ill-typed one. Returns whether all items are well-typed.
+fn verify_core<'db>(bump: &'db bumpalo::Bump, db: &mut pion_db::Database<'db>) -> bool {
+    let mut checker = pion_kernel::Checker::new(bump);
+    for item in db.elaborated_items() {
+        if let Err(error) = checker.check_item(&item) {
+            let item = match item {
+                Item::Let(binding) => match binding.name {
+                    Some(name) => format!("`{name}`"),
+                    None => String::from("a `let _` binding"),
+                },
+                Item::Eval(_) => String::from("an evaluated expression"),
             };
+            eprintln!("error: the elaborator produced ill-typed core for {item}: {error}");
+            return false;
+        }
+    }
+    true
+}
 
-            let file = pion_parser::parse_file(&bump, &mut diagnostic_handler, file_id, &text);
-            let mut elaborator = pion_elab::Elaborator::new(
-                &bump,
-                &text,
-                file_id,
-                &mut diagnostic_handler,
-                &mut command_handler,
-            );
-            elaborator.synth_block(&file.contents);
-            elaborator.report_unsolved_metas();
-
-            Ok(())
+fn compile_command(args: &CompileArgs) -> std::io::Result<ExitCode> {
+    let path = &args.path;
+    let bump = bumpalo::Bump::new();
+    let text = read_text(path)?;
+    let limits = args.eval_limits.limits();
+    let mut db = new_database(&bump, text, false, args.opt_level, limits);
+    // The compiled program evaluates the `#eval` commands instead.
+    db.set_eval_commands(false);
+    let result = check(&args.diagnostics, path.name(), &mut db, false);
+    if result.error_count != 0 {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let items = db.core_items();
+    let program = match pion_codegen::lower::lower_program(&items) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("error: {error}");
+            return Ok(ExitCode::FAILURE);
         }
+    };
+    let output = match args.target {
+        Target::C => pion_codegen::c::emit_program(&program).into_bytes(),
+        Target::Wasm => pion_codegen::wasm::emit_module(&program),
+    };
+
+    match &args.output {
+        Some(output_path) => std::fs::write(output_path, output)?,
+        None => std::io::stdout().write_all(&output)?,
     }
+    Ok(ExitCode::SUCCESS)
 }
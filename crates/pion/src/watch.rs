@@ -0,0 +1,98 @@
+//! Checking a file again whenever it changes.
+
+use std::io::Write;
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use camino::Utf8Path;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::{check, new_database, read_text, verify_core, CheckArgs, PathOrStdin};
+
+/// How long to wait for a burst of writes to finish before checking again.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Results are never freed from the database's arena: once it grows past this
+/// size, start again with a fresh database.
+const MAX_ARENA_BYTES: usize = 256 * 1024 * 1024;
+
+pub fn watch(args: &CheckArgs, path: &Utf8Path, run: bool) -> std::io::Result<ExitCode> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    })
+    .map_err(std::io::Error::other)?;
+
+    // Watch the directory rather than the file itself: many editors save a file by
+    // replacing it, which would end a watch on the original file.
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_str().is_empty() => dir,
+        _ => Utf8Path::new("."),
+    };
+    watcher
+        .watch(dir.as_std_path(), RecursiveMode::NonRecursive)
+        .map_err(std::io::Error::other)?;
+
+    let path_or_stdin = PathOrStdin::Path(path.to_owned());
+    loop {
+        let bump = bumpalo::Bump::new();
+        let limits = args.eval_limits.limits();
+        let mut db = new_database(&bump, String::new(), run, args.opt_level, limits);
+
+        while bump.allocated_bytes() < MAX_ARENA_BYTES {
+            // Clear the terminal and move the cursor to the top left.
+            print!("\x1b[2J\x1b[H");
+            println!("[{}] Checking `{path}`", timestamp());
+            match read_text(&path_or_stdin) {
+                Ok(text) => {
+                    db.set_text(text);
+                    let result = check(&args.diagnostics, path.as_str(), &mut db, true);
+                    if args.verify_core && result.error_count == 0 {
+                        verify_core(&bump, &mut db);
+                    }
+                }
+                Err(error) => eprintln!("error: could not read `{path}`: {error}"),
+            }
+            std::io::stdout().flush()?;
+
+            wait_for_change(&receiver, path)?;
+        }
+    }
+}
+
+/// Block until `path` is created, modified or removed, and then until no
+/// more events have been received for [`DEBOUNCE`].
+fn wait_for_change(
+    receiver: &mpsc::Receiver<notify::Result<notify::Event>>,
+    path: &Utf8Path,
+) -> std::io::Result<()> {
+    loop {
+        let event = receiver.recv().map_err(std::io::Error::other)?;
+        let event = event.map_err(std::io::Error::other)?;
+
+        // Reading the file generates access events, which must be ignored.
+        let changed = matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        );
+        let affects_path = (event.paths.iter())
+            .any(|event_path| event_path.file_name() == path.file_name().map(AsRef::as_ref));
+        if changed && affects_path {
+            break;
+        }
+    }
+
+    while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+    Ok(())
+}
+
+/// The current time of day in UTC, as `HH:MM:SS`.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let (hours, mins, secs) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    format!("{hours:02}:{mins:02}:{secs:02} UTC")
+}
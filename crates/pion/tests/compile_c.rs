@@ -0,0 +1,155 @@
+//! Check that programs compiled with `pion compile --target c` print the same
+//! values as `#eval`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use pion_db::{Database, Event};
+use pion_diagnostic::codes;
+
+const WORKSPACE_DIR: &str = env!("CARGO_WORKSPACE_DIR");
+const PION_EXE: &str = env!("CARGO_BIN_EXE_pion");
+
+/// The values displayed by `#eval` with the normalizer, with whitespace
+/// normalized, or `None` for those that are too expensive to normalize.
+fn expected_values(text: &str) -> Vec<Option<String>> {
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, text.to_owned());
+    db.set_run(true);
+    (db.check().into_iter())
+        .map(|event| match event {
+            Event::Output(output) => {
+                let value = output.rsplit('⇝').next().unwrap();
+                Some(normalize_whitespace(value))
+            }
+            Event::Diagnostic(diagnostic, _)
+                if diagnostic.code.as_deref() == Some(codes::EVAL_LIMIT_EXCEEDED.code) =>
+            {
+                None
+            }
+            event @ (Event::Diagnostic(..) | Event::Assertion(_)) => {
+                panic!("unexpected event: {event:?}")
+            }
+        })
+        .collect()
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Compile the file at `path` to C, build it with the system C compiler, and
+/// return the lines that the program prints.
+fn compile_and_run(path: &Path, out_dir: &Path) -> Vec<String> {
+    let c_path = out_dir.join("main.c");
+    let exe_path = out_dir.join("main");
+
+    let output = Command::new(PION_EXE)
+        .args(["compile", "--target", "c", "-o"])
+        .arg(&c_path)
+        .arg(path)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "`pion compile` failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Tail calls are only guaranteed to run in constant space with
+    // optimizations enabled.
+    let output = Command::new("cc")
+        .args(["-std=c99", "-O2", "-Wall", "-Wextra", "-Werror", "-o"])
+        .arg(&exe_path)
+        .arg(&c_path)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "`cc` failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = Command::new(&exe_path).output().unwrap();
+    assert!(output.status.success(), "compiled program failed");
+    (String::from_utf8(output.stdout).unwrap().lines())
+        .map(normalize_whitespace)
+        .collect()
+}
+
+fn cc_available() -> bool {
+    Command::new("cc")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[test]
+fn compiled_programs_match_eval() {
+    if !cc_available() {
+        eprintln!("skipping: no C compiler found");
+        return;
+    }
+
+    let workspace_dir = PathBuf::from(WORKSPACE_DIR);
+    let out_dir = std::env::temp_dir().join(format!("pion-compile-c-{}", std::process::id()));
+    let mut count = 0;
+
+    for dir in ["test-data/run", "test-data/compile"] {
+        for entry in walkdir::WalkDir::new(workspace_dir.join(dir)) {
+            let entry = entry.unwrap();
+            if entry.path().extension() != Some("pion".as_ref()) {
+                continue;
+            }
+
+            let text = std::fs::read_to_string(entry.path()).unwrap();
+            let file_dir = out_dir.join(entry.path().file_stem().unwrap());
+            std::fs::create_dir_all(&file_dir).unwrap();
+
+            let expected = expected_values(&text);
+            let actual = compile_and_run(entry.path(), &file_dir);
+            assert_eq!(
+                actual.len(),
+                expected.len(),
+                "{}: wrong number of values printed",
+                entry.path().display()
+            );
+            for (actual, expected) in actual.iter().zip(&expected) {
+                // Functions and types cannot be printed by compiled programs.
+                if actual == "<function>" || actual == "<erased>" {
+                    continue;
+                }
+                if let Some(expected) = expected {
+                    assert_eq!(actual, expected, "{}", entry.path().display());
+                }
+            }
+            count += 1;
+        }
+    }
+
+    std::fs::remove_dir_all(&out_dir).unwrap();
+    assert!(count > 0, "no test files found");
+}
+
+#[test]
+fn compiling_does_not_eval() {
+    if !cc_available() {
+        eprintln!("skipping: no C compiler found");
+        return;
+    }
+
+    // Too deep for the normalizer, which must not be run when compiling.
+    let text = "
+let rec sum : Int -> Int -> Int = fun n acc => if eq n 0 then acc else sum (sub n 1) (add acc n);
+#eval sum 1000000 0;
+#eval sum 10 0;
+";
+    let out_dir = std::env::temp_dir().join(format!("pion-compile-eval-{}", std::process::id()));
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let path = out_dir.join("sum.pion");
+    std::fs::write(&path, text).unwrap();
+
+    assert_eq!(expected_values(text), [None, Some(String::from("55"))]);
+    assert_eq!(compile_and_run(&path, &out_dir), ["1784293664", "55"]);
+    std::fs::remove_dir_all(&out_dir).unwrap();
+}
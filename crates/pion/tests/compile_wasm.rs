@@ -0,0 +1,212 @@
+//! Check that functions exported by modules compiled with
+//! `pion compile --target wasm` return the same values as `#eval`.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use pion_db::{Database, Event};
+use pion_elab::command::EvalBackend;
+use wasmi::{Engine, Instance, Linker, Module, Store, Val};
+
+const WORKSPACE_DIR: &str = env!("CARGO_WORKSPACE_DIR");
+const PION_EXE: &str = env!("CARGO_BIN_EXE_pion");
+
+fn compile(text: &str) -> Vec<u8> {
+    let mut child = Command::new(PION_EXE)
+        .args(["compile", "--target", "wasm", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    (child.stdin.take().unwrap())
+        .write_all(text.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "`pion compile` failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+/// Validate `module`, and run its start function.
+fn instantiate(module: &[u8]) -> (Store<()>, Instance) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, module).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = (Linker::new(&engine).instantiate(&mut store, &module))
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    (store, instance)
+}
+
+/// The value of `expr` in the scope of `text`, as an `i32`.
+fn eval(text: &str, expr: &str) -> i32 {
+    let text = format!("{text}\n#eval {expr};\n");
+    let bump = bumpalo::Bump::new();
+    let mut db = Database::new(&bump, 0, text);
+    db.set_eval_backend(EvalBackend::Bytecode);
+    let events = db.check();
+    let Some(Event::Output(output)) = events.last() else {
+        panic!("unexpected events: {events:?}")
+    };
+    to_i32(output.rsplit('⇝').next().unwrap().trim())
+}
+
+/// Convert an `Int` or `Bool` literal to its WebAssembly representation.
+fn to_i32(value: &str) -> i32 {
+    match value {
+        "true" => 1,
+        "false" => 0,
+        int => i32::from_ne_bytes(int.parse::<u32>().unwrap().to_ne_bytes()),
+    }
+}
+
+const PROGRAM: &str = "
+let rec fact : Int -> Int = fun n => if eq n 0 then 1 else mul n (fact (sub n 1));
+
+let rec sum : Int -> Int -> Int = fun n acc => if eq n 0 then acc else sum (sub n 1) (add acc n);
+
+let rec range : Int -> List Int -> List Int
+    = fun n acc => if eq (len acc) n then acc else range n (push acc (len acc));
+
+let rec sum-list : List Int -> Int -> Int -> Int = fun xs i acc => match (lt i (len xs)) {
+    false => acc,
+    true => sum-list xs (add i 1) (add acc i),
+};
+
+let triangle : Int -> Int = fun n => sum-list (append (range n []) []) 0 0;
+
+let point : Int -> Int -> { x : Int, y : Int } = fun x y => { x = x, y = y };
+let manhattan : Int -> Int -> Int = fun x y => do {
+    let p = point x y;
+    let twice = fun (f : Int -> Int) (x : Int) => f (f x);
+    add (p.x) (twice (add (p.y)) 0)
+};
+
+let classify : Int -> Bool -> Int = fun n flag => match n {
+    0 => 100,
+    1 => bool_rec @(fun _ => Int) flag 10 20,
+    _ => if flag then n else sub 0 n,
+};
+
+let id : forall (@A : Type) -> A -> A = fun x => x;
+let not : Bool -> Bool = fun b => if b then false else true;
+let answer = id 42;
+
+let pick : Int -> Bool -> Int -> Int = fun a c => bool_rec @(fun _ => Int) c a;
+";
+
+#[test]
+fn exported_functions_match_eval() {
+    let (mut store, instance) = instantiate(&compile(PROGRAM));
+    let calls: &[(&str, &[&str])] = &[
+        ("fact", &["5"]),
+        ("fact", &["12"]),
+        ("sum", &["100", "0"]),
+        ("sum", &["100000", "0"]),
+        ("triangle", &["10"]),
+        // Large enough that memory has to grow.
+        ("triangle", &["3000"]),
+        ("manhattan", &["3", "4"]),
+        ("classify", &["0", "true"]),
+        ("classify", &["1", "false"]),
+        ("classify", &["1", "true"]),
+        ("classify", &["7", "true"]),
+        ("classify", &["7", "false"]),
+        ("not", &["true"]),
+        ("not", &["false"]),
+        ("answer", &[]),
+        ("pick", &["7", "true", "9"]),
+        ("pick", &["7", "false", "9"]),
+    ];
+
+    for (name, args) in calls {
+        let func = instance.get_func(&store, name).unwrap();
+        let params: Vec<_> = args.iter().map(|arg| Val::I32(to_i32(arg))).collect();
+        let mut results = [Val::I32(0)];
+        func.call(&mut store, &params, &mut results).unwrap();
+
+        let expr = std::iter::once(*name).chain(args.iter().copied());
+        let expr = expr.collect::<Vec<_>>().join(" ");
+        let Val::I32(actual) = results[0] else {
+            panic!("wrong result type")
+        };
+        assert_eq!(actual, eval(PROGRAM, &expr), "{expr}");
+    }
+}
+
+#[test]
+fn only_scalar_functions_are_exported() {
+    let (store, instance) = instantiate(&compile(PROGRAM));
+    let mut exports: Vec<_> = instance
+        .exports(&store)
+        .map(|export| export.name().to_owned())
+        .collect();
+    exports.sort();
+    assert_eq!(
+        exports,
+        [
+            "answer",
+            "classify",
+            "fact",
+            "manhattan",
+            "not",
+            "pick",
+            "sum",
+            "triangle"
+        ]
+    );
+}
+
+#[test]
+fn test_data_modules_validate() {
+    let workspace_dir = PathBuf::from(WORKSPACE_DIR);
+    let mut count = 0;
+    for dir in ["test-data/run", "test-data/compile"] {
+        for entry in walkdir::WalkDir::new(workspace_dir.join(dir)) {
+            let entry = entry.unwrap();
+            if entry.path().extension() != Some("pion".as_ref()) {
+                continue;
+            }
+            let text = std::fs::read_to_string(entry.path()).unwrap();
+            instantiate(&compile(&text));
+            count += 1;
+        }
+    }
+    assert!(count > 0, "no test files found");
+}
+
+#[test]
+fn irrelevant_parameters_are_not_exported() {
+    // `#eval` leaves `subst` stuck, so these are checked against known values.
+    let program = "
+let cast : forall (n : Int) (m : Int) -> Eq @Int n m -> Int -> Int
+    = fun n m eq x => subst @Int @(fun _ => Int) n m eq x;
+let scale : forall (@factor : Int) -> Int -> Int = fun x => mul factor x;
+let succ : forall (@A : Type) -> Int -> Int = fun x => add x 1;
+let five = cast 2 2 (refl 2) 5;
+";
+    let (mut store, instance) = instantiate(&compile(program));
+    let calls: &[(&str, &[i32], i32)] = &[
+        ("cast", &[4, 4, 9], 9),
+        ("scale", &[3, 14], 42),
+        ("succ", &[41], 42),
+        ("five", &[], 5),
+    ];
+
+    for (name, args, expected) in calls {
+        let func = instance.get_func(&store, name).unwrap();
+        let params: Vec<_> = args.iter().map(|arg| Val::I32(*arg)).collect();
+        let mut results = [Val::I32(0)];
+        func.call(&mut store, &params, &mut results).unwrap();
+        let Val::I32(actual) = results[0] else {
+            panic!("wrong result type")
+        };
+        assert_eq!(actual, *expected, "{name} {args:?}");
+    }
+}
@@ -0,0 +1,88 @@
+//! Check that the examples in the explanation of every diagnostic code are
+//! correct: the first example should report the diagnostic, and any
+//! subsequent examples (showing how to fix it) should not.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use pion_diagnostic::codes::ALL_CODES;
+
+/// Extract the contents of all ```` ```pion ```` code blocks in `markdown`.
+fn pion_code_blocks(markdown: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<String> = None;
+    for line in markdown.lines() {
+        match (&mut current, line.trim_end()) {
+            (None, "```pion") => current = Some(String::new()),
+            (Some(_), "```") => blocks.extend(current.take()),
+            (Some(block), line) => {
+                block.push_str(line);
+                block.push('\n');
+            }
+            (None, _) => {}
+        }
+    }
+    assert!(current.is_none(), "unterminated code block");
+    blocks
+}
+
+/// Run `pion check` on `text`, returning the diagnostic codes reported.
+fn reported_codes(text: &str) -> Vec<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pion"))
+        .args(["check", "--message-format", "json", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(text.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let json: serde_json::Value = serde_json::from_str(line).unwrap();
+            json["code"].as_str().map(String::from)
+        })
+        .collect()
+}
+
+#[test]
+fn explanation_examples() {
+    let mut failures = Vec::new();
+
+    for code in ALL_CODES {
+        let blocks = pion_code_blocks(code.explanation);
+        let Some((example, fixes)) = blocks.split_first() else {
+            failures.push(format!("{code}: explanation has no example"));
+            continue;
+        };
+
+        let reported = reported_codes(example);
+        if !reported.iter().any(|c| c == code.code) {
+            failures.push(format!("{code}: example reported {reported:?}"));
+        }
+
+        for fix in fixes {
+            let reported = reported_codes(fix);
+            if !reported.is_empty() {
+                failures.push(format!("{code}: fixed example reported {reported:?}"));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn codes_are_unique_and_sorted() {
+    for pair in ALL_CODES.windows(2) {
+        assert!(pair[0].code < pair[1].code, "{} >= {}", pair[0], pair[1]);
+    }
+}
@@ -0,0 +1,53 @@
+//! Check that `pion check --fix` applies suggested fixes.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn fix(text: &str) -> String { fix_with_args(&[], text) }
+
+fn fix_with_args(args: &[&str], text: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pion"))
+        .args(["check", "--fix"])
+        .args(args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(text.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn fix_unbound_names() {
+    let text = "let length = 5;\nlet x = { foo = lenght }.fo;\n";
+    let expected = "let length = 5;\nlet x = { foo = length }.foo;\n";
+    assert_eq!(fix(text), expected);
+}
+
+#[test]
+fn fix_nothing() {
+    let text = "let x = qwerty;\n";
+    assert_eq!(fix(text), text);
+}
+
+#[test]
+fn fix_stdin_without_command_output() {
+    let text = "let length = 5;\n#check lenght;\n#eval lenght;\n";
+    let expected = "let length = 5;\n#check length;\n#eval length;\n";
+    assert_eq!(fix(text), expected);
+}
+
+#[test]
+fn fix_past_error_limit() {
+    let text = "let length = 5;\n#check lenght;\n#check lenght;\n#check lenght;\n";
+    let expected = "let length = 5;\n#check length;\n#check length;\n#check length;\n";
+    assert_eq!(fix_with_args(&["--error-limit", "1"], text), expected);
+}
@@ -15,22 +15,40 @@ fn main() {
 
     let update_snapshots = std::env::var_os("UPDATE_EXPECT").is_some();
 
-    let tests = (find_source_files(format!("{TESTS_DIR}/elab"))
-        .map(|path| elab_test(&path, update_snapshots)))
+    let elab_tests = find_source_files(format!("{TESTS_DIR}/elab"))
+        .map(|path| snapshot_test(&path, "check", update_snapshots));
+    let run_tests = find_source_files(format!("{TESTS_DIR}/run"))
+        .map(|path| snapshot_test(&path, "run", update_snapshots));
+    let test_tests = find_source_files(format!("{TESTS_DIR}/test"))
+        .map(|path| snapshot_test(&path, "test", update_snapshots));
+    let ui_tests = find_source_files(format!("{TESTS_DIR}/ui")).map(|path| ui_test(&path));
+    let tests = (elab_tests
+        .chain(run_tests)
+        .chain(test_tests)
+        .chain(ui_tests))
     .collect();
 
     libtest_mimic::run(&args, tests).exit()
 }
 
-fn elab_test(input_path: &Path, update: bool) -> libtest_mimic::Trial {
+/// Run `pion <subcommand>` on the file at `input_path`, and compare its output
+/// against the snapshot next to it.
+fn snapshot_test(
+    input_path: &Path,
+    subcommand: &'static str,
+    update: bool,
+) -> libtest_mimic::Trial {
     let test_name = input_path.strip_prefix(TESTS_DIR).unwrap();
     let test_name = test_name.display().to_string().replace('/', "::");
     let expected_path = input_path.with_extension("snapshot");
 
     let input_path = input_path.to_owned();
     libtest_mimic::Trial::test(test_name, move || {
+        let input = std::fs::read_to_string(&input_path)?;
         let mut command = std::process::Command::new(PION_EXE);
-        command.args(["check", &input_path.display().to_string()]);
+        command.arg(subcommand);
+        command.args(extra_args(&input));
+        command.arg(input_path.display().to_string());
         let output = command.output()?;
         let exit_status = output.status;
 
@@ -72,6 +90,154 @@ stderr = """
     })
 }
 
+/// Run `pion check` on the file at `input_path`, and compare the diagnostics
+/// it reports against the annotations in the file (see [`Annotation`]). Every
+/// error and warning must be matched by exactly one annotation on the line of
+/// its primary label, and every annotation must match a diagnostic.
+fn ui_test(input_path: &Path) -> libtest_mimic::Trial {
+    let test_name = input_path.strip_prefix(TESTS_DIR).unwrap();
+    let test_name = test_name.display().to_string().replace('/', "::");
+
+    let input_path = input_path.to_owned();
+    libtest_mimic::Trial::test(test_name, move || {
+        let input = std::fs::read_to_string(&input_path)?;
+        let mut expected = parse_annotations(&input)?;
+
+        let mut command = std::process::Command::new(PION_EXE);
+        command.args(["check", "--message-format", "json"]);
+        command.args(extra_args(&input));
+        command.arg(input_path.display().to_string());
+        let output = command.output()?;
+
+        let mut errors = Vec::new();
+        let expect_failure = expected.iter().any(|annotation| annotation.kind == "ERROR");
+        if output.status.success() == expect_failure {
+            errors.push(format!("unexpected exit status: {}", output.status));
+        }
+
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        for line in stderr.lines() {
+            let Ok(diagnostic) = serde_json::from_str::<serde_json::Value>(line) else {
+                errors.push(format!("unexpected output: {line}"));
+                continue;
+            };
+            let Some(actual) = Annotation::from_json(&diagnostic) else {
+                continue;
+            };
+            match expected
+                .iter()
+                .position(|annotation| annotation.matches(&actual))
+            {
+                Some(index) => {
+                    expected.swap_remove(index);
+                }
+                None => errors.push(format!("unexpected diagnostic: {actual}")),
+            }
+        }
+        expected.sort_by_key(|annotation| annotation.line);
+        errors.extend(
+            expected
+                .iter()
+                .map(|annotation| format!("missing diagnostic: {annotation}")),
+        );
+
+        match errors.as_slice() {
+            [] => Ok(()),
+            _ => Err(Failed::from(errors.join("\n"))),
+        }
+    })
+}
+
+/// An expected diagnostic, written in a comment of the form `//~ <KIND>
+/// <message>` on the line that it is reported on, or `//~^ <KIND> <message>`
+/// on the line below it (with one `^` for each line). `//~| <KIND> <message>`
+/// expects a diagnostic on the same line as the previous annotation.
+///
+/// The kind is `ERROR` or `WARN`, and the diagnostic matches if its code or
+/// a part of its message is `<message>`.
+#[derive(Debug)]
+struct Annotation {
+    line: usize,
+    kind: String,
+    message: String,
+}
+
+impl Annotation {
+    /// The diagnostic reported as `json` by `--message-format json`, or `None`
+    /// if it is not an error or a warning.
+    fn from_json(json: &serde_json::Value) -> Option<Self> {
+        let kind = match json["severity"].as_str()? {
+            "bug" | "error" => "ERROR",
+            "warning" => "WARN",
+            _ => return None,
+        };
+        let labels = json["labels"].as_array()?;
+        let primary = labels.iter().find(|label| label["style"] == "primary");
+        let line = primary.and_then(|label| label["line_start"].as_u64());
+        let code = json["code"].as_str().unwrap_or_default();
+        Some(Self {
+            line: line.map_or(0, |line| usize::try_from(line).unwrap()),
+            kind: kind.to_owned(),
+            message: format!("[{code}] {}", json["message"].as_str()?),
+        })
+    }
+
+    fn matches(&self, actual: &Self) -> bool {
+        self.line == actual.line
+            && self.kind == actual.kind
+            && actual.message.contains(&self.message)
+    }
+}
+
+impl std::fmt::Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {} {}", self.line, self.kind, self.message)
+    }
+}
+
+/// The annotations in `input`.
+fn parse_annotations(input: &str) -> Result<Vec<Annotation>, String> {
+    let mut annotations = Vec::new();
+    let mut previous_line = None;
+    for (index, line) in input.lines().enumerate() {
+        let Some((_, annotation)) = line.split_once("//~") else {
+            continue;
+        };
+        let line = index + 1;
+        let invalid = || format!("line {line}: invalid annotation: `//~{annotation}`");
+
+        let (line, annotation) = match annotation.strip_prefix('|') {
+            Some(annotation) => (previous_line.ok_or_else(invalid)?, annotation),
+            None => {
+                let above = annotation.chars().take_while(|c| *c == '^').count();
+                let target = line.checked_sub(above).filter(|line| *line > 0);
+                (target.ok_or_else(invalid)?, &annotation[above..])
+            }
+        };
+        let (kind, message) = annotation.trim().split_once(' ').ok_or_else(invalid)?;
+        if !matches!(kind, "ERROR" | "WARN") {
+            return Err(invalid());
+        }
+
+        previous_line = Some(line);
+        annotations.push(Annotation {
+            line,
+            kind: kind.to_owned(),
+            message: message.trim().to_owned(),
+        });
+    }
+    Ok(annotations)
+}
+
+/// Extra command line arguments, specified by lines of the form `//@ args:
+/// <args>` in the test file.
+fn extra_args(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .lines()
+        .filter_map(|line| line.strip_prefix("//@ args:"))
+        .flat_map(str::split_whitespace)
+}
+
 /// Recursively walk over test files under a file path.
 fn find_source_files(root: impl AsRef<Path>) -> impl Iterator<Item = PathBuf> {
     WalkDir::new(root)
@@ -0,0 +1,85 @@
+//! Check that `pion check --watch` checks the file again when it changes.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for output that should not appear. Longer than the
+/// debounce period of `--watch`.
+const QUIET: Duration = Duration::from_secs(1);
+
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) { let _ = self.0.kill(); }
+}
+
+/// The next line of output, which should contain `needle`.
+#[track_caller]
+fn expect_line(lines: &mpsc::Receiver<String>, needle: &str) {
+    let line = (lines.recv_timeout(TIMEOUT))
+        .unwrap_or_else(|_| panic!("timed out waiting for output containing {needle:?}"));
+    assert!(
+        line.contains(needle),
+        "expected output containing {needle:?}, found {line:?}"
+    );
+}
+
+/// The file is checked once, with the output `output`, and not checked
+/// again.
+#[track_caller]
+fn expect_check(lines: &mpsc::Receiver<String>, output: &str) {
+    expect_line(lines, "Checking");
+    expect_line(lines, output);
+    if let Ok(line) = lines.recv_timeout(QUIET) {
+        panic!("expected no more output, found {line:?}");
+    }
+}
+
+#[test]
+fn watch() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("watch");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("test.pion");
+    std::fs::write(&path, "#check 1;\n").unwrap();
+
+    let mut child = KillOnDrop(
+        Command::new(env!("CARGO_BIN_EXE_pion"))
+            .args(["check", "--watch"])
+            .arg(&path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    let (sender, lines) = mpsc::channel();
+    let stdout = BufReader::new(child.0.stdout.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stdout.lines() {
+            let Ok(line) = line else { return };
+            if sender.send(line).is_err() {
+                return;
+            }
+        }
+    });
+
+    expect_check(&lines, "1 : Int");
+
+    // A burst of writes is checked once, with the final contents.
+    for n in 3..10 {
+        std::fs::write(&path, format!("#check {n};\n")).unwrap();
+    }
+    std::fs::write(&path, "#check true;\n").unwrap();
+    expect_check(&lines, "true : Bool");
+
+    // Replace the file, as many editors do when saving.
+    let tmp_path = dir.join("test.pion.tmp");
+    std::fs::write(&tmp_path, "#check 2;\n").unwrap();
+    std::fs::rename(&tmp_path, &path).unwrap();
+    expect_check(&lines, "2 : Int");
+}